@@ -0,0 +1,151 @@
+## Settings window
+
+settings-title = Einstellungen
+settings-tab-world = Welt
+settings-tab-graphics = Grafik
+settings-tab-player = Spieler
+settings-tab-debug = Debug
+
+## Settings every build has
+
+settings-quality = Qualität
+quality-low = Niedrig
+quality-medium = Mittel
+quality-high = Hoch
+quality-ultra = Ultra
+settings-msaa = Kantenglättung (nach Neustart)
+settings-msaa-off = Aus
+settings-wireframe = Drahtgitter erlauben (nach Neustart)
+settings-ui-scale = UI-Skalierung
+settings-high-contrast = Hoher Kontrast
+settings-gamepad-navigation = Menüsteuerung per Gamepad
+
+## Settings panels, looked up from the name each one is added with
+
+settings-panel-accessibility = Barrierefreiheit
+settings-panel-ambient-particles = Umgebungspartikel
+settings-panel-birds = Vögel
+settings-panel-build-mode = Baumodus
+settings-panel-campfires = Lagerfeuer
+settings-panel-clear-colour = Hintergrundfarbe
+settings-panel-editor = Editor
+settings-panel-collectibles = Sammelobjekte
+settings-panel-footprints = Fußspuren
+settings-panel-glider = Gleiter
+settings-panel-grapple = Enterhaken
+settings-panel-graphics = Grafik
+settings-panel-harvesting = Ernten
+settings-panel-landing = Landung
+settings-panel-language = Sprache
+settings-panel-lightning = Blitze
+settings-panel-metrics-export = Metrik-Export
+settings-panel-movement = Bewegung
+settings-panel-post-processing = Nachbearbeitung
+settings-panel-precipitation = Niederschlag
+settings-panel-scatter = Verteilung
+settings-panel-sky = Himmel
+settings-panel-snow = Schnee
+settings-panel-stats = Statistiken
+settings-panel-terrain = Gelände
+settings-panel-terrain-debug = Gelände-Debug
+settings-panel-terraform = Geländeformung
+settings-panel-timescale = Zeitraffer
+settings-panel-torch = Fackel
+settings-panel-wanderers = Wanderer
+settings-panel-water = Wasser
+settings-panel-water-life = Wasserleben
+settings-panel-wetness = Nässe
+settings-panel-wind = Wind
+
+## HUD
+
+hud-orbs-collected = Gesammelte Kugeln: { $count }
+hud-inventory = Holz: { $wood }  Stein: { $stone }
+hud-building = Baue: { $piece }  (R zum Wechseln, Linksklick zum Platzieren, Rechtsklick zum Entfernen, K zum Kopieren, V zum Stempeln)
+build-piece-cube = Würfel
+build-piece-tree = Baum
+build-piece-rock = Fels
+hud-editor = Editor, Simulation { $simulation }  (F2 zurück zum Spiel, P anhalten oder fortsetzen, Bewegungstasten zum Fliegen, Sprinten für schneller, Esc gibt den Mauszeiger frei)
+editor-simulation-paused = angehalten
+editor-simulation-running = läuft
+hud-terraforming = Geländeformung: { $operation }, { $shape }, { $falloff }  Radius { $radius } m  Stärke { $strength } m/s  (1-7 Werkzeug, H Form, J Abfall, , und . Radius, - und = Stärke, K Stempelecken erfassen, V stempeln, R Stempel drehen)
+
+## Terraform brush
+
+brush-title = Pinsel
+brush-operation = Werkzeug
+brush-operation-raise = Anheben
+brush-operation-lower = Absenken
+brush-operation-flatten = Einebnen
+brush-operation-smooth = Glätten
+brush-operation-noise = Rauschen
+brush-operation-set-height = Höhe setzen
+brush-operation-paint = Bemalen
+brush-shape = Form
+brush-shape-circle = Kreis
+brush-shape-square = Quadrat
+brush-shape-jittered = Unregelmäßig
+brush-falloff = Abfall
+brush-falloff-constant = Konstant
+brush-falloff-linear = Linear
+brush-falloff-smooth = Weich
+brush-falloff-spherical = Kugelförmig
+brush-radius = Radius
+brush-strength = Stärke
+brush-target-height = Zielhöhe
+brush-terrain-type = Geländeart
+brush-terrain-type-none = Unbemalt
+terrain-type-water = Wasser
+terrain-type-sand = Sand
+terrain-type-lowland = Tiefland
+terrain-type-forest = Wald
+terrain-type-rock = Fels
+terrain-type-snow = Schnee
+
+## Höhenstempel
+
+stamps-title = Höhenstempel
+stamps-capturing = K über der gegenüberliegenden Ecke drücken, um zu erfassen
+stamps-clipboard = Zwischenablage: { $width } x { $depth } m
+stamps-clipboard-empty = Zwischenablage: leer, K über zwei Ecken drücken, um zu erfassen
+stamps-rotation = Drehung
+stamps-scale = Größe
+stamps-height-scale = Höhenfaktor
+stamps-save = Speichern
+stamps-refresh = Aktualisieren
+stamps-load = { $name } laden
+
+## Errors
+
+errors-dismiss = Schließen
+
+## Prefabs
+
+prefabs-title = Vorlagen
+prefabs-clipboard = Zwischenablage: { $pieces ->
+    [one] 1 Teil
+   *[other] { $pieces } Teile
+}
+prefabs-clipboard-empty = Zwischenablage: leer
+prefabs-save = Speichern
+prefabs-refresh = Aktualisieren
+prefabs-load = { $name } laden
+
+## Terrain
+
+terrain-failures-title = Fehler bei der Chunk-Erzeugung
+terrain-failure = { $x }, { $y }: { $attempts } Mal fehlgeschlagen, { $status }
+terrain-failure-gave-up = aufgegeben
+terrain-failure-retrying = neuer Versuch
+terrain-problems-title = Probleme in der Geländekonfiguration
+terrain-heights-title = Chunk-Höhen
+terrain-heights-no-target = Schau einen Chunk an, um seine Höhen zu sehen
+terrain-heights-chunk = Chunk { $x }, { $y }
+terrain-heights-generating = Wird noch erzeugt
+terrain-heights-stats = Min { $min }  Max { $max }  Mittel { $mean }
+terrain-diff-title = Chunk-Konfigurationsvergleich
+terrain-diff-snapshot = Konfiguration festhalten
+terrain-diff-compare = Anvisierten Chunk vergleichen
+terrain-diff-no-baseline = Halte die Geländekonfiguration fest, ändere sie und vergleiche dann den Chunk, den du ansiehst
+terrain-diff-summary = Chunk { $x }, { $y }: Höhen im Mittel um { $mean } verschoben, höchstens um { $max }
+terrain-diff-legend = Festgehalten, aktuell und der Unterschied: rot wo es höher, blau wo es tiefer wurde
@@ -0,0 +1,151 @@
+## Settings window
+
+settings-title = Settings
+settings-tab-world = World
+settings-tab-graphics = Graphics
+settings-tab-player = Player
+settings-tab-debug = Debug
+
+## Settings every build has
+
+settings-quality = Quality
+quality-low = Low
+quality-medium = Medium
+quality-high = High
+quality-ultra = Ultra
+settings-msaa = Anti-aliasing (on restart)
+settings-msaa-off = Off
+settings-wireframe = Allow wireframes (on restart)
+settings-ui-scale = UI scale
+settings-high-contrast = High contrast
+settings-gamepad-navigation = Gamepad menu navigation
+
+## Settings panels, looked up from the name each one is added with
+
+settings-panel-accessibility = Accessibility
+settings-panel-ambient-particles = Ambient particles
+settings-panel-birds = Birds
+settings-panel-build-mode = Build mode
+settings-panel-campfires = Campfires
+settings-panel-clear-colour = Clear colour
+settings-panel-editor = Editor
+settings-panel-collectibles = Collectibles
+settings-panel-footprints = Footprints
+settings-panel-glider = Glider
+settings-panel-grapple = Grapple
+settings-panel-graphics = Graphics
+settings-panel-harvesting = Harvesting
+settings-panel-landing = Landing
+settings-panel-language = Language
+settings-panel-lightning = Lightning
+settings-panel-metrics-export = Metrics export
+settings-panel-movement = Movement
+settings-panel-post-processing = Post processing
+settings-panel-precipitation = Precipitation
+settings-panel-scatter = Scatter
+settings-panel-sky = Sky
+settings-panel-snow = Snow
+settings-panel-stats = Stats
+settings-panel-terrain = Terrain
+settings-panel-terrain-debug = Terrain debug
+settings-panel-terraform = Terraform
+settings-panel-timescale = Timescale
+settings-panel-torch = Torch
+settings-panel-wanderers = Wanderers
+settings-panel-water = Water
+settings-panel-water-life = Water life
+settings-panel-wetness = Wetness
+settings-panel-wind = Wind
+
+## HUD
+
+hud-orbs-collected = Orbs collected: { $count }
+hud-inventory = Wood: { $wood }  Stone: { $stone }
+hud-building = Building: { $piece }  (R to change, left click to place, right click to remove, K to copy, V to stamp)
+build-piece-cube = Cube
+build-piece-tree = Tree
+build-piece-rock = Rock
+hud-editor = Editor, simulation { $simulation }  (F2 back to play, P pause or run, movement keys to fly, sprint to fly faster, Esc frees the cursor)
+editor-simulation-paused = paused
+editor-simulation-running = running
+hud-terraforming = Terraforming: { $operation }, { $shape }, { $falloff }  Radius { $radius } m  Strength { $strength } m/s  (1-7 operation, H shape, J falloff, , and . radius, - and = strength, K capture stamp corners, V stamp, R turn stamp)
+
+## Terraform brush
+
+brush-title = Brush
+brush-operation = Operation
+brush-operation-raise = Raise
+brush-operation-lower = Lower
+brush-operation-flatten = Flatten
+brush-operation-smooth = Smooth
+brush-operation-noise = Noise
+brush-operation-set-height = Set height
+brush-operation-paint = Paint
+brush-shape = Shape
+brush-shape-circle = Circle
+brush-shape-square = Square
+brush-shape-jittered = Jittered
+brush-falloff = Falloff
+brush-falloff-constant = Constant
+brush-falloff-linear = Linear
+brush-falloff-smooth = Smooth
+brush-falloff-spherical = Spherical
+brush-radius = Radius
+brush-strength = Strength
+brush-target-height = Target height
+brush-terrain-type = Terrain type
+brush-terrain-type-none = Unpainted
+terrain-type-water = Water
+terrain-type-sand = Sand
+terrain-type-lowland = Lowland
+terrain-type-forest = Forest
+terrain-type-rock = Rock
+terrain-type-snow = Snow
+
+## Height stamps
+
+stamps-title = Height stamps
+stamps-capturing = Press K again over the opposite corner to capture
+stamps-clipboard = Clipboard: { $width } x { $depth } m
+stamps-clipboard-empty = Clipboard: empty, press K over two corners to capture
+stamps-rotation = Rotation
+stamps-scale = Scale
+stamps-height-scale = Height scale
+stamps-save = Save
+stamps-refresh = Refresh
+stamps-load = Load { $name }
+
+## Errors
+
+errors-dismiss = Dismiss
+
+## Prefabs
+
+prefabs-title = Prefabs
+prefabs-clipboard = Clipboard: { $pieces ->
+    [one] 1 piece
+   *[other] { $pieces } pieces
+}
+prefabs-clipboard-empty = Clipboard: empty
+prefabs-save = Save
+prefabs-refresh = Refresh
+prefabs-load = Load { $name }
+
+## Terrain
+
+terrain-failures-title = Chunk generation failures
+terrain-failure = { $x }, { $y }: failed { $attempts } times, { $status }
+terrain-failure-gave-up = gave up
+terrain-failure-retrying = retrying
+terrain-problems-title = Terrain config problems
+terrain-heights-title = Chunk heights
+terrain-heights-no-target = Look at a chunk to see its heights
+terrain-heights-chunk = Chunk { $x }, { $y }
+terrain-heights-generating = Still generating
+terrain-heights-stats = Min { $min }  Max { $max }  Mean { $mean }
+terrain-diff-title = Chunk config diff
+terrain-diff-snapshot = Snapshot config
+terrain-diff-compare = Compare targeted chunk
+terrain-diff-no-baseline = Snapshot the terrain config, change it, then compare the chunk you're looking at
+terrain-diff-summary = Chunk { $x }, { $y }: heights moved { $mean } on average, { $max } at most
+terrain-diff-legend = Snapshot, current, and the difference: red where it rose, blue where it fell
@@ -0,0 +1,51 @@
+#version 450
+
+layout(location=0)in vec3 v_Direction;
+layout(location=0)out vec4 o_Target;
+
+layout(set=2,binding=0)uniform Aurora_intensity{
+  float intensity;
+};
+layout(set=2,binding=1)uniform Aurora_phase{
+  float phase;
+};
+layout(set=3,binding=0)uniform TimeUniform_value{
+  float time;
+};
+
+const float PI=3.14159265;
+const vec3 LOW_COLOR=vec3(.1,1.,.45);
+const vec3 HIGH_COLOR=vec3(.55,.2,.9);
+
+float hash(float x){
+  return fract(sin(x*127.1)*43758.5453);
+}
+
+float noise(float x){
+  float i=floor(x);
+  float f=fract(x);
+  return mix(hash(i),hash(i+1.),f*f*(3.-2.*f));
+}
+
+void main(){
+  vec3 direction=normalize(v_Direction);
+  // the band hangs over the northern (-z) half of the sky
+  float azimuth=atan(direction.x,-direction.z);
+  float elevation=asin(direction.y);
+  float north=smoothstep(PI*.6,PI*.2,abs(azimuth));
+
+  // the bottom edge of the band snakes slowly back and forth
+  float t=time+phase;
+  float bottom=.35+.08*sin(azimuth*3.+t*.07)+.04*sin(azimuth*7.-t*.13);
+  float above=elevation-bottom;
+  // sharp lower edge, long fade upwards like hanging curtains
+  float band=smoothstep(-.02,.02,above)*exp(-max(above,0.)*6.);
+
+  // thin bright folds scrolling sideways along the ribbon
+  float folds=noise(azimuth*40.+t*.6)*.6+noise(azimuth*90.-t*1.1)*.4;
+  float ribbons=pow(folds,3.);
+
+  float strength=band*ribbons*north*intensity;
+  vec3 color=mix(LOW_COLOR,HIGH_COLOR,clamp(above*4.,0.,1.));
+  o_Target=vec4(color,strength);
+}
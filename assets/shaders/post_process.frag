@@ -0,0 +1,91 @@
+#version 450
+
+layout(location=0)in vec2 v_Uv;
+layout(location=0)out vec4 o_Target;
+
+layout(set=0,binding=0)uniform texture2D PostProcess_scene;
+layout(set=0,binding=1)uniform sampler PostProcess_scene_sampler;
+
+layout(set=1,binding=0)uniform PostProcessUniform{
+  float exposure;
+  float tonemapper;
+  float lut_strength;
+  float godray_intensity;
+  vec2 sun_screen_position;
+  float godray_density;
+  float godray_decay;
+  float godray_threshold;
+};
+
+layout(set=2,binding=0)uniform texture2D ColorGrading_lut;
+layout(set=2,binding=1)uniform sampler ColorGrading_lut_sampler;
+
+// the lookup table is 16 slices of 16x16 laid side by side along the blue axis
+const float LUT_SIZE=16.;
+const int GODRAY_SAMPLES=48;
+
+// Blurs the bright parts of the scene out along lines from the sun, so anything in front of
+// it leaves shafts of shadow between the rays
+vec3 godrays(vec2 uv){
+  vec2 step_size=(uv-sun_screen_position)*godray_density/float(GODRAY_SAMPLES);
+  vec3 total=vec3(0.);
+  float weight=1.;
+  for(int i=0;i<GODRAY_SAMPLES;i++){
+    uv-=step_size;
+    vec3 color=texture(sampler2D(PostProcess_scene,PostProcess_scene_sampler),uv).rgb;
+    float luminance=dot(color,vec3(.2126,.7152,.0722));
+    total+=color*smoothstep(godray_threshold,1.,luminance)*weight;
+    weight*=godray_decay;
+  }
+  return total*godray_intensity/float(GODRAY_SAMPLES);
+}
+
+vec3 reinhard(vec3 color){
+  return color/(1.+color);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 color){
+  const float a=2.51;
+  const float b=.03;
+  const float c=2.43;
+  const float d=.59;
+  const float e=.14;
+  return clamp((color*(a*color+b))/(color*(c*color+d)+e),0.,1.);
+}
+
+vec3 tonemap(vec3 color){
+  if(tonemapper>1.5){
+    return aces(color);
+  }else if(tonemapper>.5){
+    return reinhard(color);
+  }
+  return clamp(color,0.,1.);
+}
+
+vec3 linear_to_srgb(vec3 color){
+  return mix(color*12.92,1.055*pow(color,vec3(1./2.4))-.055,step(.0031308,color));
+}
+
+vec3 grade(vec3 color){
+  // the table is indexed by srgb encoded colors, its texture decodes back to linear
+  vec3 cell=linear_to_srgb(color)*(LUT_SIZE-1.);
+  float slice=floor(cell.b);
+  float next_slice=min(slice+1.,LUT_SIZE-1.);
+  vec2 texel=vec2(cell.r+.5,cell.g+.5)/vec2(LUT_SIZE*LUT_SIZE,LUT_SIZE);
+  vec2 slice_width=vec2(1./LUT_SIZE,0.);
+
+  vec3 low=texture(sampler2D(ColorGrading_lut,ColorGrading_lut_sampler),texel+slice*slice_width).rgb;
+  vec3 high=texture(sampler2D(ColorGrading_lut,ColorGrading_lut_sampler),texel+next_slice*slice_width).rgb;
+  return mix(low,high,cell.b-slice);
+}
+
+void main(){
+  vec3 color=texture(sampler2D(PostProcess_scene,PostProcess_scene_sampler),v_Uv).rgb;
+  if(godray_intensity>0.){
+    color+=godrays(v_Uv);
+  }
+  color=tonemap(color*exposure);
+  color=mix(color,grade(color),lut_strength);
+  o_Target=vec4(color,1.);
+}
@@ -0,0 +1,11 @@
+#version 450
+
+layout(location=0)in vec3 Vertex_Position;
+layout(location=1)in vec2 Vertex_Uv;
+layout(location=0)out vec2 v_Uv;
+
+void main(){
+  // the quad is already in clip space, covering the whole screen
+  v_Uv=Vertex_Uv;
+  gl_Position=vec4(Vertex_Position.xy,0.,1.);
+}
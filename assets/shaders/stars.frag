@@ -0,0 +1,35 @@
+#version 450
+
+layout(location=0)in vec3 v_Direction;
+layout(location=0)out vec4 o_Target;
+
+layout(set=2,binding=0)uniform StarDome_brightness{
+  float brightness;
+};
+
+// cells per radian the sky is split into, each holding at most one star
+const float DENSITY=60.;
+// fraction of cells left empty
+const float EMPTY=.96;
+
+float hash(vec2 cell){
+  return fract(sin(dot(cell,vec2(127.1,311.7)))*43758.5453);
+}
+
+void main(){
+  vec3 direction=normalize(v_Direction);
+  vec2 angles=vec2(atan(direction.z,direction.x),asin(direction.y))*DENSITY;
+  vec2 cell=floor(angles);
+
+  float seed=hash(cell);
+  if(seed<EMPTY){
+    discard;
+  }
+
+  // put the star somewhere random in its cell, with a random brightness
+  vec2 star=cell+vec2(hash(cell+17.),hash(cell+59.))*.6+.2;
+  float size=mix(.05,.15,hash(cell+31.));
+  float glow=1.-smoothstep(0.,size,length(angles-star));
+  float twinkle=(seed-EMPTY)/(1.-EMPTY);
+  o_Target=vec4(vec3(.9,.95,1.),glow*mix(.3,1.,twinkle)*brightness);
+}
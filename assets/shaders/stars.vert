@@ -0,0 +1,18 @@
+#version 450
+
+layout(location=0)in vec3 Vertex_Position;
+layout(location=0)out vec3 v_Direction;
+
+layout(set=0,binding=0)uniform CameraViewProj{
+  mat4 ViewProj;
+};
+
+layout(set=1,binding=0)uniform Transform{
+  mat4 Model;
+};
+
+void main(){
+  // the stars stay fixed to the dome as it turns, so pass on its untransformed direction
+  v_Direction=Vertex_Position;
+  gl_Position=ViewProj*Model*vec4(Vertex_Position,1.);
+}
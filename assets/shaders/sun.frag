@@ -1,8 +1,22 @@
 #version 450
 
-layout(location=0)in float height;
+layout(location=0)in vec2 v_Uv;
 layout(location=0)out vec4 o_Target;
 
+layout(set=2,binding=0)uniform SunSprite_color{
+  vec4 color;
+};
+layout(set=2,binding=1)uniform SunSprite_core{
+  float core;
+};
+layout(set=2,binding=2)uniform SunSprite_glow{
+  float glow;
+};
+
 void main(){
-  o_Target=vec4(vec3(1.0, 0.9098, 0.4078),1.);
+  float distance=length(v_Uv*2.-1.);
+  // a solid disc out to the core, surrounded by a soft glow fading to the edge of the quad
+  float disc=core>0.?1.-smoothstep(core*.9,core,distance):0.;
+  float bloom=pow(clamp(1.-distance,0.,1.),3.)*glow;
+  o_Target=vec4(color.rgb,color.a*max(disc,bloom));
 }
@@ -1,21 +1,50 @@
 #version 450
 
-layout(location=0)in float height;
+layout(location=0)in vec2 v_SplatUv;
+layout(location=1)in vec2 v_WorldXz;
 layout(location=0)out vec4 o_Target;
 
-float rand(vec3 coords){
-  return fract(sin(dot(coords,vec3(12.9898,78.233, 54.02323)))*43758.5453);
-}
-
-vec3 baseTerrainColor(float height){
-  if(height<1.){
-    return vec3(.8,.7059,.1725) + ((rand(gl_FragCoord.xyz) - 0.5 ) / 5.0);
-  } else {
-    return vec3(.2275,.8118,.2588);
-  }
-}
+layout(set=2,binding=0)uniform SplatMaterial_tint{
+  vec4 tint;
+};
+layout(set=2,binding=1)uniform SplatMaterial_tile_size{
+  float tile_size;
+};
+layout(set=2,binding=2)uniform SplatMaterial_grass_color{
+  vec4 grass_color;
+};
+layout(set=2,binding=3)uniform SplatMaterial_rock_color{
+  vec4 rock_color;
+};
+layout(set=2,binding=4)uniform SplatMaterial_sand_color{
+  vec4 sand_color;
+};
+layout(set=2,binding=5)uniform SplatMaterial_snow_color{
+  vec4 snow_color;
+};
+layout(set=2,binding=6)uniform texture2D SplatMaterial_splat;
+layout(set=2,binding=7)uniform sampler SplatMaterial_splat_sampler;
+layout(set=2,binding=8)uniform texture2D SplatMaterial_grass;
+layout(set=2,binding=9)uniform sampler SplatMaterial_grass_sampler;
+layout(set=2,binding=10)uniform texture2D SplatMaterial_rock;
+layout(set=2,binding=11)uniform sampler SplatMaterial_rock_sampler;
+layout(set=2,binding=12)uniform texture2D SplatMaterial_sand;
+layout(set=2,binding=13)uniform sampler SplatMaterial_sand_sampler;
+layout(set=2,binding=14)uniform texture2D SplatMaterial_snow;
+layout(set=2,binding=15)uniform sampler SplatMaterial_snow_sampler;
 
 void main(){
-  vec3 color = baseTerrainColor(height);
-  o_Target=vec4(color,1.);
+  // grass, rock, sand and snow, which only add up to one before they're filtered
+  vec4 weights=texture(sampler2D(SplatMaterial_splat,SplatMaterial_splat_sampler),v_SplatUv);
+  weights/=max(dot(weights,vec4(1.)),.0001);
+
+  // tiled in world space so the textures run on across the chunk borders
+  vec2 tiled=v_WorldXz/tile_size;
+  vec3 grass=texture(sampler2D(SplatMaterial_grass,SplatMaterial_grass_sampler),tiled).rgb*grass_color.rgb;
+  vec3 rock=texture(sampler2D(SplatMaterial_rock,SplatMaterial_rock_sampler),tiled).rgb*rock_color.rgb;
+  vec3 sand=texture(sampler2D(SplatMaterial_sand,SplatMaterial_sand_sampler),tiled).rgb*sand_color.rgb;
+  vec3 snow=texture(sampler2D(SplatMaterial_snow,SplatMaterial_snow_sampler),tiled).rgb*snow_color.rgb;
+
+  vec3 color=grass*weights.r+rock*weights.g+sand*weights.b+snow*weights.a;
+  o_Target=vec4(color*tint.rgb,1.);
 }
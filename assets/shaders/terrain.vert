@@ -1,7 +1,8 @@
 #version 450
 
 layout(location=0)in vec3 Vertex_Position;
-layout(location=0)out float height;
+layout(location=0)out vec2 v_SplatUv;
+layout(location=1)out vec2 v_WorldXz;
 
 layout(set=0,binding=0)uniform CameraViewProj{
   mat4 ViewProj;
@@ -11,7 +12,13 @@ layout(set=1,binding=0)uniform Transform{
   mat4 Model;
 };
 
+// the splat map has a texel for each of the chunk's 241 x 241 cells, which the mesh's
+// vertices sit on before the chunk is stretched over its span
+const float CELLS=241.;
+
 void main(){
-  gl_Position=ViewProj*Model*vec4(Vertex_Position,1.);
-  height=Vertex_Position.y;
+  vec4 world=Model*vec4(Vertex_Position,1.);
+  gl_Position=ViewProj*world;
+  v_SplatUv=(Vertex_Position.xz+.5)/CELLS;
+  v_WorldXz=world.xz;
 }
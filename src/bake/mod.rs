@@ -0,0 +1,324 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    convert::TryInto,
+    env,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use bevy::{prelude::*, tasks::TaskPool};
+use color_eyre::{eyre::eyre, Report};
+
+use crate::{
+    error_log::ErrorLog,
+    scatter::{self, Candidate, PropKind, ScatterConfig},
+    terrain::{self, ChunkCoords, Config, GenerationPipeline, HeightMap},
+};
+
+const BAKE_DIR: &str = "baked";
+// marks a file as a baked archive in this layout
+const MAGIC: &[u8; 8] = b"JWBAKE01";
+// the magic, seed, both fingerprints and the chunk count
+const HEADER_SIZE: usize = 8 + 4 + 8 + 8 + 4;
+// each chunk's coordinates, and where its data starts in the file and how long it is
+const INDEX_ENTRY_SIZE: usize = 4 + 4 + 8 + 4;
+
+/// Streams chunks from the archive baked for the terrain's seed, when there is one, in place
+/// of generating them
+pub struct BakePlugin;
+
+impl Plugin for BakePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<BakedWorld>()
+            .add_system_to_stage(CoreStage::PreUpdate, open_for_seed.system());
+    }
+}
+
+/// The archive baked for the terrain's current seed, if there is one
+#[derive(Default)]
+pub struct BakedWorld(pub Option<Arc<BakedArchive>>);
+
+/// How many chunks out from the spawn to bake, when launched with `--bake <radius>`
+pub fn bake_radius() -> Option<i32> {
+    let mut args = env::args().skip_while(|arg| arg != "--bake");
+    args.next()?;
+    match args.next().and_then(|radius| radius.parse().ok()) {
+        Some(radius) if radius >= 0 => Some(radius),
+        _ => {
+            eprintln!("--bake needs how many chunks out from the spawn to bake, e.g. --bake 20");
+            None
+        }
+    }
+}
+
+/// Where the archive baked for a seed is kept
+pub fn archive_path(seed: u32) -> PathBuf {
+    Path::new(BAKE_DIR).join(format!("seed-{}.bake", seed))
+}
+
+/// Generates every chunk in the square `radius` chunks out from the spawn on every core, and
+/// writes their height maps and the spots their props could stand on into one archive. The
+/// colliders are heightfields of the same heights, so they're built from them when loaded.
+pub fn bake(radius: i32) -> Result<(), Report> {
+    let started = Instant::now();
+    let mut config = Config::saved_or_default();
+    config.set_seed(terrain::seed_argument());
+    let pipeline = GenerationPipeline::default();
+    let scatter_config = ScatterConfig::default();
+
+    let chunks: Vec<ChunkCoords> = (-radius..=radius)
+        .flat_map(|y| (-radius..=radius).map(move |x| ChunkCoords { x, y }))
+        .collect();
+    println!(
+        "Baking {} chunks of seed {}...",
+        chunks.len(),
+        config.seed()
+    );
+    let blobs = TaskPool::new().scope(|scope| {
+        for &coords in chunks.iter() {
+            let (config, pipeline, scatter_config) = (&config, &pipeline, &scatter_config);
+            scope.spawn(async move { encode_chunk(config, pipeline, scatter_config, coords) });
+        }
+    });
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&config.seed().to_le_bytes());
+    bytes.extend_from_slice(&config.generation_fingerprint().to_le_bytes());
+    bytes.extend_from_slice(&scatter_fingerprint(&scatter_config).to_le_bytes());
+    bytes.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    let mut offset = (HEADER_SIZE + INDEX_ENTRY_SIZE * chunks.len()) as u64;
+    for (coords, blob) in chunks.iter().zip(blobs.iter()) {
+        bytes.extend_from_slice(&coords.x.to_le_bytes());
+        bytes.extend_from_slice(&coords.y.to_le_bytes());
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+        offset += blob.len() as u64;
+    }
+    for blob in blobs {
+        bytes.extend_from_slice(&blob);
+    }
+
+    let path = archive_path(config.seed());
+    fs::create_dir_all(BAKE_DIR)?;
+    fs::write(&path, &bytes)?;
+    println!(
+        "Wrote {:.1}MB to {:?} in {:.1}s",
+        bytes.len() as f64 / 1_000_000.0,
+        path,
+        started.elapsed().as_secs_f32()
+    );
+    Ok(())
+}
+
+// The height map, cell by cell along each row, then the prop candidates
+fn encode_chunk(
+    config: &Config,
+    pipeline: &GenerationPipeline,
+    scatter_config: &ScatterConfig,
+    coords: ChunkCoords,
+) -> Vec<u8> {
+    let height_map = pipeline.run(config, coords);
+    let candidates = scatter::candidates(scatter_config, config, coords);
+
+    let mut bytes = Vec::with_capacity(8 + height_map.size * height_map.size * 4);
+    bytes.extend_from_slice(&(height_map.size as u32).to_le_bytes());
+    for height in height_map.data.iter().flatten() {
+        bytes.extend_from_slice(&height.to_le_bytes());
+    }
+    bytes.extend_from_slice(&(candidates.len() as u32).to_le_bytes());
+    for candidate in candidates {
+        bytes.push(kind_byte(candidate.kind));
+        bytes.extend_from_slice(&candidate.index.to_le_bytes());
+        for value in [
+            candidate.point.x,
+            candidate.point.y,
+            candidate.point.z,
+            candidate.size,
+        ]
+        .iter()
+        {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn kind_byte(kind: PropKind) -> u8 {
+    match kind {
+        PropKind::Tree => 0,
+        PropKind::Rock => 1,
+    }
+}
+
+fn byte_kind(byte: u8) -> Option<PropKind> {
+    match byte {
+        0 => Some(PropKind::Tree),
+        1 => Some(PropKind::Rock),
+        _ => None,
+    }
+}
+
+// Identifies the scatter settings that decide where props can go, as the quality presets
+// change how many there are
+fn scatter_fingerprint(config: &ScatterConfig) -> u64 {
+    let placing = |kind| {
+        let prop = config.prop(kind);
+        (prop.per_chunk, prop.water_margin, prop.max_slope_degrees)
+    };
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", (placing(PropKind::Tree), placing(PropKind::Rock))).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// An archive written by `--bake`, read a chunk at a time through its index. Chunks are only
+/// handed out while the settings match the ones they were baked with.
+pub struct BakedArchive {
+    terrain_fingerprint: u64,
+    scatter_fingerprint: u64,
+    // shared by the chunk workers, each seeking to the chunk it's after
+    file: Mutex<File>,
+    index: HashMap<ChunkCoords, (u64, u32)>,
+}
+
+impl BakedArchive {
+    pub fn open(path: &Path) -> Result<BakedArchive, Report> {
+        let mut file = File::open(path)?;
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header)?;
+        if &header[..8] != MAGIC {
+            return Err(eyre!("{:?} isn't an archive baked by this version", path));
+        }
+        let truncated = || eyre!("{:?} is cut short", path);
+        let terrain_fingerprint = u64_at(&header, 12).ok_or_else(truncated)?;
+        let scatter_fingerprint = u64_at(&header, 20).ok_or_else(truncated)?;
+        let count = u32_at(&header, 28).ok_or_else(truncated)? as usize;
+
+        let mut entries = vec![0; INDEX_ENTRY_SIZE * count];
+        file.read_exact(&mut entries)?;
+        let index = entries
+            .chunks_exact(INDEX_ENTRY_SIZE)
+            .map(|entry| {
+                let coords = ChunkCoords {
+                    x: u32_at(entry, 0)? as i32,
+                    y: u32_at(entry, 4)? as i32,
+                };
+                Some((coords, (u64_at(entry, 8)?, u32_at(entry, 16)?)))
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(truncated)?;
+
+        Ok(BakedArchive {
+            terrain_fingerprint,
+            scatter_fingerprint,
+            file: Mutex::new(file),
+            index,
+        })
+    }
+
+    /// The chunk's height map as it came out of the generation pipeline
+    pub fn height_map(&self, config: &Config, coords: ChunkCoords) -> Option<HeightMap> {
+        if config.generation_fingerprint() != self.terrain_fingerprint {
+            return None;
+        }
+        let bytes = self.read(coords)?;
+        let size = u32_at(&bytes, 0)? as usize;
+        let cells = bytes.get(4..4 + size * size * 4)?;
+        let data = cells
+            .chunks_exact(size * 4)
+            .map(|row| {
+                row.chunks_exact(4)
+                    .map(|cell| f32::from_le_bytes(cell.try_into().unwrap()))
+                    .collect()
+            })
+            .collect();
+        Some(HeightMap { data, size })
+    }
+
+    /// The spots in the chunk props could stand on
+    pub fn candidates(
+        &self,
+        config: &ScatterConfig,
+        terrain_config: &Config,
+        coords: ChunkCoords,
+    ) -> Option<Vec<Candidate>> {
+        if terrain_config.generation_fingerprint() != self.terrain_fingerprint
+            || scatter_fingerprint(config) != self.scatter_fingerprint
+        {
+            return None;
+        }
+        let bytes = self.read(coords)?;
+        let size = u32_at(&bytes, 0)? as usize;
+        let start = 4 + size * size * 4;
+        let count = u32_at(&bytes, start)? as usize;
+        // the kind, index, point and size of each
+        let entry_size = 1 + 4 + 4 * 4;
+        bytes
+            .get(start + 4..start + 4 + count * entry_size)?
+            .chunks_exact(entry_size)
+            .map(|entry| {
+                let float = |at| u32_at(entry, at).map(f32::from_bits);
+                Some(Candidate {
+                    kind: byte_kind(entry[0])?,
+                    index: u32_at(entry, 1)?,
+                    point: Vec3::new(float(5)?, float(9)?, float(13)?),
+                    size: float(17)?,
+                })
+            })
+            .collect()
+    }
+
+    fn read(&self, coords: ChunkCoords) -> Option<Vec<u8>> {
+        let &(offset, length) = self.index.get(&coords)?;
+        let mut file = self.file.lock().ok()?;
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut bytes = vec![0; length as usize];
+        file.read_exact(&mut bytes).ok()?;
+        Some(bytes)
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+// Opens the archive for the terrain's seed whenever the seed changes
+fn open_for_seed(
+    config: Res<Config>,
+    mut baked: ResMut<BakedWorld>,
+    mut errors: ResMut<ErrorLog>,
+    mut opened_for: Local<Option<u32>>,
+) {
+    let seed = config.seed();
+    if *opened_for == Some(seed) {
+        return;
+    }
+    *opened_for = Some(seed);
+    let path = archive_path(seed);
+    if !path.exists() {
+        baked.0 = None;
+        return;
+    }
+    baked.0 = match BakedArchive::open(&path) {
+        Ok(archive) => {
+            info!(
+                "Streaming {} baked chunks from {:?}",
+                archive.index.len(),
+                path
+            );
+            Some(Arc::new(archive))
+        }
+        Err(error) => {
+            errors.report(format!("Failed to open baked archive: {}", error));
+            None
+        }
+    };
+}
@@ -0,0 +1,226 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use rand::Rng;
+
+use crate::{
+    settings::{AddSettings, SettingsTab},
+    terrain::{self, query},
+    weather::Wind,
+    Player,
+};
+
+pub struct BirdsPlugin;
+
+impl Plugin for BirdsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<FlockConfig>(SettingsTab::World, "Birds")
+            .add_startup_system(setup.system())
+            .add_system(populate_flock.system())
+            .add_system(flock.system());
+    }
+}
+
+pub struct Bird {
+    velocity: Vec3,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct FlockConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(max = 200))]
+    pub size: usize,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub speed: f32,
+    // how far around the player the flock circles
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub circle_radius: f32,
+    // height the birds try to keep above the ground below them
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub ground_clearance: f32,
+    // how far ahead the birds look for rising terrain
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub look_ahead: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub neighbour_radius: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub separation_radius: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub separation_weight: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub alignment_weight: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub cohesion_weight: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub circling_weight: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub altitude_weight: f32,
+    // fraction of the wind velocity the birds drift with
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub wind_influence: f32,
+}
+
+impl Default for FlockConfig {
+    fn default() -> Self {
+        Self {
+            size: 30,
+            speed: 14.0,
+            circle_radius: 80.0,
+            ground_clearance: 40.0,
+            look_ahead: 30.0,
+            neighbour_radius: 15.0,
+            separation_radius: 4.0,
+            separation_weight: 1.5,
+            alignment_weight: 0.6,
+            cohesion_weight: 0.4,
+            circling_weight: 0.8,
+            altitude_weight: 1.0,
+            wind_influence: 0.3,
+        }
+    }
+}
+
+struct BirdAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(BirdAssets {
+        mesh: meshes.add(Mesh::from(shape::Box::new(1.2, 0.1, 0.4))),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.1, 0.1, 0.12),
+            unlit: true,
+            ..Default::default()
+        }),
+    });
+}
+
+// Keeps the flock at its configured size, releasing new birds above the player
+fn populate_flock(
+    mut commands: Commands,
+    config: Res<FlockConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<BirdAssets>,
+    player_query: Query<&Transform, With<Player>>,
+    birds_query: Query<Entity, With<Bird>>,
+) {
+    let player_position = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    let count = birds_query.iter().count();
+    if count > config.size {
+        for entity in birds_query.iter().skip(config.size) {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    let mut rng = rand::thread_rng();
+    for _ in count..config.size {
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let point = player_position.xz()
+            + Vec2::new(angle.cos(), angle.sin()) * rng.gen_range(0.0..config.circle_radius);
+        let height = cruising_height(&terrain_config, &config, point);
+        let direction = Vec3::new(-angle.sin(), 0.0, angle.cos());
+
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: assets.mesh.clone(),
+                material: assets.material.clone(),
+                transform: Transform::from_xyz(point.x, height, point.y),
+                ..Default::default()
+            })
+            .insert(Bird {
+                velocity: direction * config.speed,
+            });
+    }
+}
+
+// Classic boids steering, plus circling around the player and following the terrain below
+fn flock(
+    time: Res<Time>,
+    config: Res<FlockConfig>,
+    terrain_config: Res<terrain::Config>,
+    wind: Res<Wind>,
+    player_query: Query<&Transform, (With<Player>, Without<Bird>)>,
+    mut birds_query: Query<(&mut Bird, &mut Transform)>,
+) {
+    let player_position = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+    let delta = time.delta_seconds();
+
+    let flock: Vec<(Vec3, Vec3)> = birds_query
+        .iter_mut()
+        .map(|(bird, transform)| (transform.translation, bird.velocity))
+        .collect();
+
+    for (mut bird, mut transform) in birds_query.iter_mut() {
+        let position = transform.translation;
+
+        let mut separation = Vec3::ZERO;
+        let mut average_velocity = Vec3::ZERO;
+        let mut centre = Vec3::ZERO;
+        let mut neighbours = 0;
+        for (other_position, other_velocity) in flock.iter() {
+            let offset = position - *other_position;
+            let distance = offset.length();
+            if distance <= f32::EPSILON || distance > config.neighbour_radius {
+                continue;
+            }
+
+            if distance < config.separation_radius {
+                separation += offset / (distance * distance);
+            }
+            average_velocity += *other_velocity;
+            centre += *other_position;
+            neighbours += 1;
+        }
+
+        let mut steering = separation * config.separation_weight;
+        if neighbours > 0 {
+            let neighbours = neighbours as f32;
+            steering += (average_velocity / neighbours - bird.velocity).normalize_or_zero()
+                * config.alignment_weight;
+            steering +=
+                (centre / neighbours - position).normalize_or_zero() * config.cohesion_weight;
+        }
+
+        // orbit the player, pulling back in when straying outside the circle
+        let to_player = (player_position - position).xz();
+        let tangent = Vec2::new(-to_player.y, to_player.x).normalize_or_zero();
+        let pull = (to_player.length() / config.circle_radius - 1.0).max(0.0);
+        let circling = tangent + to_player.normalize_or_zero() * pull;
+        steering += Vec3::new(circling.x, 0.0, circling.y) * config.circling_weight;
+
+        // hold altitude over the highest of the ground below and just ahead, so they climb
+        // over mountains before reaching them
+        let ahead = position.xz() + bird.velocity.xz().normalize_or_zero() * config.look_ahead;
+        let target_height = cruising_height(&terrain_config, &config, position.xz())
+            .max(cruising_height(&terrain_config, &config, ahead));
+        let climb =
+            ((target_height - position.y) / config.ground_clearance.max(1.0)).clamp(-1.0, 1.0);
+        steering += Vec3::Y * climb * config.altitude_weight;
+
+        bird.velocity =
+            (bird.velocity + steering * config.speed * delta).normalize_or_zero() * config.speed;
+
+        let velocity = bird.velocity + wind.velocity * config.wind_influence;
+        transform.translation += velocity * delta;
+        if velocity.length_squared() > f32::EPSILON {
+            transform.look_at(transform.translation + velocity, Vec3::Y);
+        }
+    }
+}
+
+fn cruising_height(terrain_config: &terrain::Config, config: &FlockConfig, point: Vec2) -> f32 {
+    query::height_at(terrain_config, point).max(terrain_config.sea_level())
+        + config.ground_clearance
+}
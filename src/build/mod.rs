@@ -0,0 +1,500 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_egui::{egui, EguiContext};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    na::{Point3, Vector3},
+    physics::{
+        ColliderBundle, IntoEntity, QueryPipelineColliderComponentsQuery,
+        QueryPipelineColliderComponentsSet,
+    },
+    prelude::{ColliderHandle, ColliderShape, InteractionGroups, QueryPipeline, Ray},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    editor::AppMode,
+    first_person::{MovementConfig, PlayerEyes},
+    locale::Locale,
+    save::WorldSave,
+    scatter::{PropAssets, PropKind, TREE_DEPTH, TREE_RADIUS},
+    settings::{AddSettings, SettingsTab},
+    terrain::{ChunkCoords, ChunkSpawnedEvent, SeenChunks},
+    Player,
+};
+
+mod prefab;
+
+const PIECE_KEY: KeyCode = KeyCode::R;
+const PLACE_BUTTON: MouseButton = MouseButton::Left;
+const REMOVE_BUTTON: MouseButton = MouseButton::Right;
+
+pub struct BuildPlugin;
+
+impl Plugin for BuildPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<BuildConfig>(SettingsTab::Player, "Build mode")
+            .init_resource::<BuildMode>()
+            .init_resource::<BuildTarget>()
+            .init_resource::<prefab::Clipboard>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system().label("build::toggle"))
+            .add_system(aim.system().label("build::aim").after("build::toggle"))
+            .add_system(preview.system().after("build::aim"))
+            .add_system(place.system().after("build::aim"))
+            .add_system(remove.system().after("build::aim"))
+            .add_system(prefab::copy.system().after("build::aim"))
+            .add_system(prefab::stamp.system().after("build::aim"))
+            .add_system(prefab::panel.system())
+            .add_system(load_in_new_chunks.system())
+            .add_system(despawn_unloaded.system())
+            .add_system(hud.system());
+    }
+}
+
+/// The things that can be built, each of which is saved as it was placed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Piece {
+    Cube,
+    Tree,
+    Rock,
+}
+
+impl Piece {
+    fn next(self) -> Piece {
+        match self {
+            Piece::Cube => Piece::Tree,
+            Piece::Tree => Piece::Rock,
+            Piece::Rock => Piece::Cube,
+        }
+    }
+}
+
+/// A piece placed in the world, as kept in the save
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PlacedPiece {
+    pub piece: Piece,
+    pub position: [f32; 3],
+    // the edge length of a cube, or how much a prop is scaled up
+    pub size: f32,
+}
+
+/// Marks a piece the player built, on the chunk it was placed in
+pub struct Built {
+    chunk: ChunkCoords,
+    placed: PlacedPiece,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct BuildConfig {
+    // how far away pieces can be placed and removed
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub reach: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    pub block_size: f32,
+    // lines cubes up on a grid of the block size, and props on whole block positions
+    pub snap_to_grid: bool,
+    // built pieces within this distance of where the player is looking are copied together
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub copy_radius: f32,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            reach: 20.0,
+            block_size: 1.0,
+            snap_to_grid: true,
+            copy_radius: 8.0,
+        }
+    }
+}
+
+/// Whether the player is building, and with which piece
+pub struct BuildMode {
+    pub active: bool,
+    pub piece: Piece,
+}
+
+impl Default for BuildMode {
+    fn default() -> Self {
+        Self {
+            active: false,
+            piece: Piece::Cube,
+        }
+    }
+}
+
+// Where the piece would go if placed now, and the built piece being looked at if any
+#[derive(Default)]
+struct BuildTarget {
+    placement: Option<PlacedPiece>,
+    looking_at: Option<Entity>,
+    // the grid corner nearest the hit, which prefabs are copied around and stamped at
+    anchor: Option<Vec3>,
+}
+
+struct BuildAssets {
+    cube_mesh: Handle<Mesh>,
+    cube_material: Handle<StandardMaterial>,
+    ghost_material: Handle<StandardMaterial>,
+}
+
+// The see-through copy of the piece showing where it'll be placed
+struct Ghost;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let assets = BuildAssets {
+        cube_mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+        cube_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.7, 0.55, 0.35),
+            ..Default::default()
+        }),
+        ghost_material: materials.add(StandardMaterial {
+            base_color: Color::rgba(0.6, 0.85, 1.0, 0.4),
+            unlit: true,
+            ..Default::default()
+        }),
+    };
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: assets.cube_mesh.clone(),
+            material: assets.ghost_material.clone(),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(Ghost);
+    commands.insert_resource(assets);
+}
+
+fn toggle(
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<MovementConfig>,
+    app_mode: Res<State<AppMode>>,
+    mut mode: ResMut<BuildMode>,
+) {
+    // building is part of playing, and put away while the editor's open
+    if *app_mode.current() != AppMode::Play {
+        mode.active = false;
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() {
+        return;
+    }
+    if config.map.build.iter().any(|&k| keys.just_pressed(k)) {
+        mode.active = !mode.active;
+    }
+    if mode.active && keys.just_pressed(PIECE_KEY) {
+        mode.piece = mode.piece.next();
+    }
+}
+
+// Casts the interaction ray from the eyes, placing cubes against whatever face it hits and
+// standing props on the ground
+fn aim(
+    config: Res<BuildConfig>,
+    mode: Res<BuildMode>,
+    mut target: ResMut<BuildTarget>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    player_query: Query<Entity, With<Player>>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    built_query: Query<(), With<Built>>,
+) {
+    *target = BuildTarget::default();
+    if !mode.active {
+        return;
+    }
+    let (player, eyes) = match (player_query.iter().next(), eyes_query.iter().next()) {
+        (Some(player), Some(eyes)) => (player, eyes),
+        _ => return,
+    };
+    let origin = eyes.translation;
+    let direction = eyes.rotation * -Vec3::Z;
+
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    let ray = Ray::new(
+        Point3::new(origin.x, origin.y, origin.z),
+        Vector3::new(direction.x, direction.y, direction.z),
+    );
+    let filter = |handle: ColliderHandle| handle.entity() != player;
+    let (handle, intersection) = match query_pipeline.cast_ray_and_get_normal(
+        &collider_set,
+        &ray,
+        config.reach,
+        true,
+        InteractionGroups::all(),
+        Some(&filter),
+    ) {
+        Some(hit) => hit,
+        None => return,
+    };
+
+    let hit = origin + direction * intersection.toi;
+    let normal = Vec3::new(
+        intersection.normal.x,
+        intersection.normal.y,
+        intersection.normal.z,
+    );
+    let entity = handle.entity();
+    if built_query.get(entity).is_ok() {
+        target.looking_at = Some(entity);
+    }
+
+    let size = config.block_size;
+    // cubes fill the grid's cells, while props stand on its corners
+    let cell = |value: f32| {
+        if config.snap_to_grid {
+            (value / size).floor() * size + size / 2.0
+        } else {
+            value
+        }
+    };
+    let corner = |value: f32| {
+        if config.snap_to_grid {
+            (value / size).round() * size
+        } else {
+            value
+        }
+    };
+    target.anchor = Some(Vec3::new(corner(hit.x), hit.y, corner(hit.z)));
+    let position = match mode.piece {
+        Piece::Cube => {
+            // step half a block out of the face, so the cube sits against it
+            let centre = hit + normal * size / 2.0;
+            Vec3::new(cell(centre.x), cell(centre.y), cell(centre.z))
+        }
+        // props only stand on fairly flat ground
+        Piece::Tree | Piece::Rock if normal.y < 0.5 => return,
+        Piece::Tree | Piece::Rock => Vec3::new(corner(hit.x), hit.y, corner(hit.z)),
+    };
+    target.placement = Some(PlacedPiece {
+        piece: mode.piece,
+        position: position.into(),
+        size: if mode.piece == Piece::Cube { size } else { 1.0 },
+    });
+}
+
+fn preview(
+    target: Res<BuildTarget>,
+    assets: Res<BuildAssets>,
+    prop_assets: Res<PropAssets>,
+    mut ghost_query: Query<(&mut Handle<Mesh>, &mut Transform, &mut Visible), With<Ghost>>,
+) {
+    for (mut mesh, mut transform, mut visible) in ghost_query.iter_mut() {
+        let placed = match target.placement {
+            Some(placed) => placed,
+            None => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+        visible.is_visible = true;
+        let (piece_mesh, _, piece_transform) = piece_parts(&placed, &assets, &prop_assets);
+        *mesh = piece_mesh;
+        *transform = piece_transform;
+    }
+}
+
+fn place(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    target: Res<BuildTarget>,
+    assets: Res<BuildAssets>,
+    prop_assets: Res<PropAssets>,
+    mut save: ResMut<WorldSave>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() || !buttons.just_pressed(PLACE_BUTTON) {
+        return;
+    }
+    let placed = match target.placement {
+        Some(placed) => placed,
+        None => return,
+    };
+
+    build_piece(&mut commands, &assets, &prop_assets, &mut save, placed);
+}
+
+// Places a piece in the world and remembers it in the save
+fn build_piece(
+    commands: &mut Commands,
+    assets: &BuildAssets,
+    prop_assets: &PropAssets,
+    save: &mut WorldSave,
+    placed: PlacedPiece,
+) {
+    let chunk = ChunkCoords::containing(Vec3::from(placed.position).xz());
+    save.built
+        .entry((chunk.x, chunk.y))
+        .or_default()
+        .push(placed);
+    spawn_piece(commands, assets, prop_assets, chunk, placed);
+}
+
+fn remove(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    target: Res<BuildTarget>,
+    mut save: ResMut<WorldSave>,
+    built_query: Query<&Built>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() || !buttons.just_pressed(REMOVE_BUTTON) {
+        return;
+    }
+    let (entity, built) = match target
+        .looking_at
+        .and_then(|entity| Some((entity, built_query.get(entity).ok()?)))
+    {
+        Some(target) => target,
+        None => return,
+    };
+
+    commands.entity(entity).despawn();
+    if let Some(pieces) = save.built.get_mut(&(built.chunk.x, built.chunk.y)) {
+        if let Some(index) = pieces.iter().position(|&placed| placed == built.placed) {
+            pieces.remove(index);
+        }
+    }
+}
+
+// Brings back the pieces built in each chunk as it loads
+fn load_in_new_chunks(
+    mut commands: Commands,
+    assets: Res<BuildAssets>,
+    prop_assets: Res<PropAssets>,
+    save: Res<WorldSave>,
+    mut events: EventReader<ChunkSpawnedEvent>,
+) {
+    for event in events.iter() {
+        if let Some(pieces) = save.built.get(&(event.coords.x, event.coords.y)) {
+            for &placed in pieces {
+                spawn_piece(&mut commands, &assets, &prop_assets, event.coords, placed);
+            }
+        }
+    }
+}
+
+fn despawn_unloaded(
+    mut commands: Commands,
+    seen_chunks: Res<SeenChunks>,
+    built_query: Query<(Entity, &Built)>,
+) {
+    for (entity, built) in built_query.iter() {
+        if !seen_chunks.contains_key(&built.chunk) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn hud(egui_context: Res<EguiContext>, locale: Res<Locale>, mode: Res<BuildMode>) {
+    if !mode.active {
+        return;
+    }
+    egui::Area::new("build_mode")
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+        .show(egui_context.ctx(), |ui| {
+            let piece = locale.text(match mode.piece {
+                Piece::Cube => "build-piece-cube",
+                Piece::Tree => "build-piece-tree",
+                Piece::Rock => "build-piece-rock",
+            });
+            ui.label(locale.format("hud-building", &[("piece", piece.into())]));
+        });
+}
+
+// The mesh, material and transform a placed piece is drawn with
+fn piece_parts(
+    placed: &PlacedPiece,
+    assets: &BuildAssets,
+    prop_assets: &PropAssets,
+) -> (Handle<Mesh>, Handle<StandardMaterial>, Transform) {
+    let position = Vec3::from(placed.position);
+    let prop = match placed.piece {
+        Piece::Cube => {
+            return (
+                assets.cube_mesh.clone(),
+                assets.cube_material.clone(),
+                Transform {
+                    translation: position,
+                    scale: Vec3::splat(placed.size),
+                    ..Default::default()
+                },
+            );
+        }
+        Piece::Tree => PropKind::Tree,
+        Piece::Rock => PropKind::Rock,
+    };
+    let (mesh, material) = prop_assets.get(prop);
+    let (translation, scale) = prop.standing_on(position, placed.size);
+    (
+        mesh,
+        material,
+        Transform {
+            translation,
+            scale,
+            ..Default::default()
+        },
+    )
+}
+
+fn spawn_piece(
+    commands: &mut Commands,
+    assets: &BuildAssets,
+    prop_assets: &PropAssets,
+    chunk: ChunkCoords,
+    placed: PlacedPiece,
+) {
+    let (mesh, material, transform) = piece_parts(&placed, assets, prop_assets);
+    let shape = match placed.piece {
+        Piece::Cube => ColliderShape::cuboid(
+            transform.scale.x / 2.0,
+            transform.scale.y / 2.0,
+            transform.scale.z / 2.0,
+        ),
+        Piece::Tree => {
+            let half_depth = TREE_DEPTH / 2.0 * transform.scale.y;
+            ColliderShape::capsule(
+                Point3::new(0.0, -half_depth, 0.0),
+                Point3::new(0.0, half_depth, 0.0),
+                TREE_RADIUS * transform.scale.x,
+            )
+        }
+        Piece::Rock => ColliderShape::ball(transform.scale.max_element()),
+    };
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh,
+            material,
+            transform,
+            ..Default::default()
+        })
+        .insert_bundle(ColliderBundle {
+            position: transform.translation.into(),
+            shape,
+            ..ColliderBundle::default()
+        })
+        .insert(Built { chunk, placed });
+}
@@ -0,0 +1,244 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_egui::{egui, EguiContext};
+use color_eyre::Report;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use super::{build_piece, BuildAssets, BuildConfig, BuildMode, BuildTarget, Built, PlacedPiece};
+use crate::{
+    error_log::ErrorLog,
+    locale::Locale,
+    save::WorldSave,
+    scatter::PropAssets,
+    terrain::ChunkCoords,
+    triggers::{self, Trigger, TriggerVolume},
+};
+
+const PREFAB_DIR: &str = "prefabs";
+const COPY_KEY: KeyCode = KeyCode::K;
+const STAMP_KEY: KeyCode = KeyCode::V;
+
+/// A group of built pieces, positioned relative to the point they were copied around,
+/// which can be stamped down anywhere and shared between worlds as a RON file
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Prefab {
+    pub pieces: Vec<PlacedPiece>,
+    // trigger volumes that go along with the structure, positioned the same way
+    #[serde(default)]
+    pub triggers: Vec<TriggerVolume>,
+}
+
+impl Prefab {
+    pub fn load(path: impl AsRef<Path>) -> Result<Prefab, Report> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    /// The pieces moved so the prefab's origin sits at a point in the world
+    pub fn placed_at(&self, origin: Vec3) -> impl Iterator<Item = PlacedPiece> + '_ {
+        self.pieces.iter().map(move |piece| PlacedPiece {
+            position: (Vec3::from(piece.position) + origin).into(),
+            ..*piece
+        })
+    }
+
+    /// The trigger volumes moved along with the pieces
+    pub fn triggers_at(&self, origin: Vec3) -> impl Iterator<Item = TriggerVolume> + '_ {
+        self.triggers.iter().map(move |volume| TriggerVolume {
+            position: (Vec3::from(volume.position) + origin).into(),
+            ..volume.clone()
+        })
+    }
+}
+
+// The prefab last copied or loaded, ready to be stamped
+#[derive(Default)]
+pub(super) struct Clipboard {
+    prefab: Option<Prefab>,
+    // what the prefab will be saved as from the panel
+    name: String,
+    // the prefabs in the folder, looked up again whenever this is cleared
+    files: Option<Vec<PathBuf>>,
+}
+
+pub(super) fn copy(
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<BuildConfig>,
+    target: Res<BuildTarget>,
+    mut clipboard: ResMut<Clipboard>,
+    built_query: Query<&Built>,
+    triggers_query: Query<&Trigger>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() || !keys.just_pressed(COPY_KEY) {
+        return;
+    }
+    let anchor = match target.anchor {
+        Some(anchor) => anchor,
+        None => return,
+    };
+
+    let pieces: Vec<PlacedPiece> = built_query
+        .iter()
+        .filter(|built| Vec3::from(built.placed.position).distance(anchor) <= config.copy_radius)
+        .map(|built| PlacedPiece {
+            position: (Vec3::from(built.placed.position) - anchor).into(),
+            ..built.placed
+        })
+        .collect();
+    let triggers: Vec<TriggerVolume> = triggers_query
+        .iter()
+        .filter(|trigger| {
+            Vec3::from(trigger.volume.position).distance(anchor) <= config.copy_radius
+        })
+        .map(|trigger| TriggerVolume {
+            position: (Vec3::from(trigger.volume.position) - anchor).into(),
+            ..trigger.volume.clone()
+        })
+        .collect();
+    info!(
+        "Copied {} built pieces and {} triggers",
+        pieces.len(),
+        triggers.len()
+    );
+    clipboard.prefab = if pieces.is_empty() && triggers.is_empty() {
+        None
+    } else {
+        Some(Prefab { pieces, triggers })
+    };
+}
+
+pub(super) fn stamp(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    target: Res<BuildTarget>,
+    clipboard: Res<Clipboard>,
+    assets: Res<BuildAssets>,
+    prop_assets: Res<PropAssets>,
+    mut save: ResMut<WorldSave>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() || !keys.just_pressed(STAMP_KEY) {
+        return;
+    }
+    let (prefab, anchor) = match (&clipboard.prefab, target.anchor) {
+        (Some(prefab), Some(anchor)) => (prefab, anchor),
+        _ => return,
+    };
+
+    for placed in prefab.placed_at(anchor) {
+        build_piece(&mut commands, &assets, &prop_assets, &mut save, placed);
+    }
+    for volume in prefab.triggers_at(anchor) {
+        let chunk = ChunkCoords::containing(Vec3::from(volume.position).xz());
+        save.triggers
+            .entry((chunk.x, chunk.y))
+            .or_default()
+            .push(volume.clone());
+        triggers::spawn_trigger(&mut commands, chunk, volume);
+    }
+}
+
+// Saves the clipboard to the prefabs folder, and loads any prefab in it back
+pub(super) fn panel(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    mode: Res<BuildMode>,
+    mut clipboard: ResMut<Clipboard>,
+    mut errors: ResMut<ErrorLog>,
+) {
+    if !mode.active {
+        return;
+    }
+    let clipboard = &mut *clipboard;
+
+    egui::Window::new(locale.text("prefabs-title"))
+        .id(egui::Id::new("prefabs"))
+        .show(egui_context.ctx(), |ui| {
+            match &clipboard.prefab {
+                Some(prefab) => ui.label(locale.format(
+                    "prefabs-clipboard",
+                    &[("pieces", prefab.pieces.len().into())],
+                )),
+                None => ui.label(locale.text("prefabs-clipboard-empty")),
+            };
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut clipboard.name);
+                let name = clipboard.name.trim().to_string();
+                if ui.button(locale.text("prefabs-save")).clicked() && !name.is_empty() {
+                    if let Some(prefab) = &clipboard.prefab {
+                        let path = Path::new(PREFAB_DIR).join(format!("{}.ron", name));
+                        if let Err(error) = prefab.save(&path) {
+                            errors.report(format!("Failed to save prefab {:?}: {}", path, error));
+                        }
+                        clipboard.files = None;
+                    }
+                }
+            });
+
+            ui.separator();
+            if ui.button(locale.text("prefabs-refresh")).clicked() {
+                clipboard.files = None;
+            }
+            let paths = clipboard.files.get_or_insert_with(prefab_files).clone();
+            for path in paths {
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if ui
+                    .button(locale.format("prefabs-load", &[("name", name.as_str().into())]))
+                    .clicked()
+                {
+                    match Prefab::load(&path) {
+                        Ok(prefab) => {
+                            clipboard.prefab = Some(prefab);
+                            clipboard.name = name;
+                        }
+                        Err(error) => {
+                            errors.report(format!("Failed to load prefab {:?}: {}", path, error))
+                        }
+                    }
+                }
+            }
+        });
+}
+
+fn prefab_files() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(PREFAB_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().map_or(false, |ext| ext == "ron"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
@@ -0,0 +1,250 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    first_person::{MovementConfig, PlayerEyes},
+    particles::{GroundContact, ParticleBurstEvent},
+    save::WorldSave,
+    scatter::HarvestTarget,
+    settings::{AddSettings, SettingsTab},
+    terrain::{self, query, ChunkCoords, ChunkSpawnedEvent, SeenChunks},
+};
+
+// height of the light above the logs
+const LIGHT_HEIGHT: f32 = 1.0;
+// radius of ground the fire rests on, for tilting it to the slope
+const FOOTPRINT: f32 = 1.0;
+
+pub struct CampfirePlugin;
+
+impl Plugin for CampfirePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<CampfireConfig>(SettingsTab::World, "Campfires")
+            .add_startup_system(setup.system())
+            .add_system(place.system().after("scatter::target"))
+            .add_system(load_in_new_chunks.system())
+            .add_system(despawn_unloaded.system())
+            .add_system(flicker.system())
+            .add_system(emit_flames.system());
+    }
+}
+
+pub struct Campfire {
+    chunk: ChunkCoords,
+    // offsets the flicker so neighbouring fires don't pulse in sync
+    phase: f32,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct CampfireConfig {
+    // how far in front of the player new campfires are placed
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.5))]
+    pub place_distance: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub light_intensity: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub light_range: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub flicker: f32,
+    // seconds between each puff of flame particles
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.01))]
+    pub flame_interval: f32,
+}
+
+impl Default for CampfireConfig {
+    fn default() -> Self {
+        Self {
+            place_distance: 3.0,
+            light_intensity: 400.0,
+            light_range: 30.0,
+            flicker: 0.25,
+            flame_interval: 0.1,
+        }
+    }
+}
+
+struct CampfireAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(CampfireAssets {
+        mesh: meshes.add(Mesh::from(shape::Box::new(1.2, 0.3, 1.2))),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.3, 0.18, 0.1),
+            emissive: Color::rgb(0.4, 0.1, 0.0),
+            ..Default::default()
+        }),
+    });
+}
+
+// Drops a campfire on the ground in front of the player and remembers it in the save,
+// unless the interact key is chopping or mining a prop instead
+fn place(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<MovementConfig>,
+    campfire_config: Res<CampfireConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<CampfireAssets>,
+    harvest_target: Res<HarvestTarget>,
+    mut save: ResMut<WorldSave>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked()
+        || !config.map.interact.iter().any(|&k| keys.just_pressed(k))
+        || harvest_target.0.is_some()
+    {
+        return;
+    }
+
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes,
+        None => return,
+    };
+    let forward = (eyes.rotation * -Vec3::Z).xz().normalize_or_zero();
+    let point = eyes.translation.xz() + forward * campfire_config.place_distance;
+    let transform = query::place_on_surface(&terrain_config, point, FOOTPRINT);
+    if transform.translation.y < terrain_config.sea_level() {
+        return;
+    }
+
+    let chunk = ChunkCoords::containing(point);
+    save.campfires
+        .entry((chunk.x, chunk.y))
+        .or_default()
+        .push(transform.translation.into());
+    spawn_campfire(&mut commands, &assets, &campfire_config, chunk, transform);
+}
+
+// Brings back the saved campfires of each chunk as it loads
+fn load_in_new_chunks(
+    mut commands: Commands,
+    config: Res<CampfireConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<CampfireAssets>,
+    save: Res<WorldSave>,
+    mut events: EventReader<ChunkSpawnedEvent>,
+) {
+    for event in events.iter() {
+        let positions = match save.campfires.get(&(event.coords.x, event.coords.y)) {
+            Some(positions) => positions,
+            None => continue,
+        };
+
+        for &position in positions {
+            let position = Vec3::from(position);
+            let transform = Transform {
+                translation: position,
+                ..query::place_on_surface(&terrain_config, position.xz(), FOOTPRINT)
+            };
+            spawn_campfire(&mut commands, &assets, &config, event.coords, transform);
+        }
+    }
+}
+
+fn despawn_unloaded(
+    mut commands: Commands,
+    seen_chunks: Res<SeenChunks>,
+    campfires_query: Query<(Entity, &Campfire)>,
+) {
+    for (entity, campfire) in campfires_query.iter() {
+        if !seen_chunks.contains_key(&campfire.chunk) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn flicker(
+    time: Res<Time>,
+    config: Res<CampfireConfig>,
+    campfires_query: Query<(&Campfire, &Children)>,
+    mut lights_query: Query<&mut Light>,
+) {
+    let t = time.seconds_since_startup() as f32;
+
+    for (campfire, children) in campfires_query.iter() {
+        let t = t + campfire.phase;
+        // a couple of out of step waves look less regular than a single one
+        let wave = ((t * 13.0).sin() + (t * 7.3).sin() * 0.5) / 1.5;
+        for &child in children.iter() {
+            if let Ok(mut light) = lights_query.get_mut(child) {
+                light.intensity = config.light_intensity * (1.0 + wave * config.flicker);
+                light.range = config.light_range;
+            }
+        }
+    }
+}
+
+fn emit_flames(
+    time: Res<Time>,
+    config: Res<CampfireConfig>,
+    mut since_last: Local<f32>,
+    mut bursts: EventWriter<ParticleBurstEvent>,
+    campfires_query: Query<&Transform, With<Campfire>>,
+) {
+    *since_last += time.delta_seconds();
+    if *since_last < config.flame_interval {
+        return;
+    }
+    *since_last = 0.0;
+
+    for transform in campfires_query.iter() {
+        bursts.send(ParticleBurstEvent {
+            position: transform.translation + Vec3::Y * 0.3,
+            color: Color::rgb(1.0, 0.45, 0.1),
+            count: 2,
+            speed: 0.6,
+            size: 0.25,
+            lifetime: 0.7,
+            // negative gravity so the flames rise
+            gravity: -3.0,
+            ground: GroundContact::PassThrough,
+            wind: 0.0,
+        });
+    }
+}
+
+fn spawn_campfire(
+    commands: &mut Commands,
+    assets: &CampfireAssets,
+    config: &CampfireConfig,
+    chunk: ChunkCoords,
+    transform: Transform,
+) {
+    let position = transform.translation;
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: assets.mesh.clone(),
+            material: assets.material.clone(),
+            transform,
+            ..Default::default()
+        })
+        .insert(Campfire {
+            chunk,
+            phase: position.x + position.z,
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(LightBundle {
+                light: Light {
+                    color: Color::rgb(1.0, 0.6, 0.3),
+                    intensity: config.light_intensity,
+                    range: config.light_range,
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, LIGHT_HEIGHT, 0.0),
+                ..Default::default()
+            });
+        });
+}
@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error_log::ErrorLog,
+    locale::Locale,
+    save::WorldSave,
+    settings::{AddSettings, SettingsTab},
+    terrain::{self, scatter, ChunkCoords, ChunkSpawnedEvent},
+    Player,
+};
+
+// keeps collectibles from landing on the same spots as other scattered objects
+const SCATTER_SALT: u64 = 1;
+
+pub struct CollectiblesPlugin;
+
+impl Plugin for CollectiblesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<CollectiblesConfig>(SettingsTab::World, "Collectibles")
+            .init_resource::<ScatteredChunks>()
+            .add_startup_system(setup.system())
+            .add_system(scatter_in_new_chunks.system())
+            .add_system(reset_on_terrain_change.system())
+            .add_system(animate.system())
+            .add_system(pickup.system())
+            .add_system(counter_hud.system());
+    }
+}
+
+/// Identifies a collectible by the chunk it was scattered in and its index within that chunk
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CollectibleId {
+    pub chunk_x: i32,
+    pub chunk_y: i32,
+    pub index: u32,
+}
+
+pub struct Collectible {
+    id: CollectibleId,
+    resting_height: f32,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct CollectiblesConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(max = 50))]
+    pub per_chunk: usize,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    pub pickup_radius: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub hover_height: f32,
+}
+
+impl Default for CollectiblesConfig {
+    fn default() -> Self {
+        Self {
+            per_chunk: 3,
+            pickup_radius: 3.0,
+            hover_height: 1.5,
+        }
+    }
+}
+
+struct CollectibleAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    pickup_sound: Handle<AudioSource>,
+}
+
+// Chunks that have already had their collectibles placed
+#[derive(Default)]
+struct ScatteredChunks(HashSet<ChunkCoords>);
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(CollectibleAssets {
+        mesh: meshes.add(Mesh::from(shape::Icosphere {
+            radius: 0.6,
+            subdivisions: 2,
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(1.0, 0.85, 0.3),
+            emissive: Color::rgb(1.0, 0.7, 0.2),
+            unlit: true,
+            ..Default::default()
+        }),
+        pickup_sound: errors.load(&asset_server, "sounds/pickup.wav"),
+    });
+}
+
+// Places the orbs for each newly loaded chunk, skipping any the player already picked up
+fn scatter_in_new_chunks(
+    mut commands: Commands,
+    config: Res<CollectiblesConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<CollectibleAssets>,
+    save: Res<WorldSave>,
+    mut scattered_chunks: ResMut<ScatteredChunks>,
+    mut events: EventReader<ChunkSpawnedEvent>,
+) {
+    for event in events.iter() {
+        if !scattered_chunks.0.insert(event.coords) {
+            continue;
+        }
+
+        let points = scatter::scatter_points(
+            &terrain_config,
+            event.coords,
+            SCATTER_SALT,
+            config.per_chunk,
+        );
+        for (index, point) in points.into_iter().enumerate() {
+            let id = CollectibleId {
+                chunk_x: event.coords.x,
+                chunk_y: event.coords.y,
+                index: index as u32,
+            };
+            if point.y < terrain_config.sea_level() || save.collected.contains(&id) {
+                continue;
+            }
+
+            let resting_height = point.y + config.hover_height;
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: assets.mesh.clone(),
+                    material: assets.material.clone(),
+                    transform: Transform::from_xyz(point.x, resting_height, point.z),
+                    ..Default::default()
+                })
+                .insert(Collectible { id, resting_height });
+        }
+    }
+}
+
+// The terrain moves under the orbs when its config changes, so scatter them again
+fn reset_on_terrain_change(
+    mut commands: Commands,
+    terrain_config: Res<terrain::Config>,
+    mut scattered_chunks: ResMut<ScatteredChunks>,
+    collectibles_query: Query<Entity, With<Collectible>>,
+) {
+    if !terrain_config.is_changed() || terrain_config.is_added() {
+        return;
+    }
+
+    for entity in collectibles_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    scattered_chunks.0.clear();
+}
+
+// Bob and spin the orbs so they catch the eye
+fn animate(time: Res<Time>, mut collectibles_query: Query<(&Collectible, &mut Transform)>) {
+    let t = time.seconds_since_startup() as f32;
+
+    for (collectible, mut transform) in collectibles_query.iter_mut() {
+        let phase = collectible.id.index as f32;
+        transform.translation.y = collectible.resting_height + (t * 2.0 + phase).sin() * 0.3;
+        transform.rotation = Quat::from_rotation_y(t + phase);
+    }
+}
+
+fn pickup(
+    mut commands: Commands,
+    config: Res<CollectiblesConfig>,
+    assets: Res<CollectibleAssets>,
+    audio: Res<Audio>,
+    mut save: ResMut<WorldSave>,
+    player_query: Query<&Transform, With<Player>>,
+    collectibles_query: Query<(Entity, &Collectible, &Transform), Without<Player>>,
+) {
+    let player_position = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    for (entity, collectible, transform) in collectibles_query.iter() {
+        if transform.translation.distance(player_position) < config.pickup_radius {
+            commands.entity(entity).despawn();
+            save.collected.insert(collectible.id);
+            audio.play(assets.pickup_sound.clone());
+        }
+    }
+}
+
+fn counter_hud(egui_context: Res<EguiContext>, locale: Res<Locale>, save: Res<WorldSave>) {
+    egui::Area::new("collectibles_counter")
+        .anchor(egui::Align2::RIGHT_TOP, [-20.0, 20.0])
+        .show(egui_context.ctx(), |ui| {
+            ui.label(locale.format(
+                "hud-orbs-collected",
+                &[("count", save.collected.len().into())],
+            ));
+        });
+}
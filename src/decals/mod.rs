@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+
+pub struct DecalsPlugin;
+
+impl Plugin for DecalsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<SpawnDecalEvent>()
+            .add_startup_system(setup.system())
+            .add_system(spawn.system().label("decals::spawn"))
+            .add_system(fade.system());
+    }
+}
+
+/// Requests a mark laid flat on the ground, like a scorch or a footprint
+#[derive(Clone, Copy, Debug)]
+pub struct SpawnDecalEvent {
+    // the decal is a unit disc in the xz plane, so scale it to the mark's size
+    pub transform: Transform,
+    pub color: Color,
+    // seconds before the decal is removed
+    pub lifetime: f32,
+    // seconds at the end of its life spent fading out, none at 0
+    pub fade: f32,
+}
+
+pub struct Decal {
+    age: f32,
+    lifetime: f32,
+    fade: f32,
+    color: Color,
+}
+
+// All decals are the same flattened disc
+struct DecalMesh(Handle<Mesh>);
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(DecalMesh(meshes.add(Mesh::from(shape::Icosphere {
+        radius: 1.0,
+        subdivisions: 2,
+    }))));
+}
+
+fn spawn(
+    mut commands: Commands,
+    mut events: EventReader<SpawnDecalEvent>,
+    decal_mesh: Res<DecalMesh>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for event in events.iter() {
+        // each decal fades on its own, so it needs its own material
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: decal_mesh.0.clone(),
+                material: materials.add(StandardMaterial {
+                    base_color: event.color,
+                    unlit: true,
+                    ..Default::default()
+                }),
+                transform: event.transform,
+                visible: Visible {
+                    is_visible: true,
+                    is_transparent: event.fade > 0.0 || event.color.a() < 1.0,
+                },
+                ..Default::default()
+            })
+            .insert(Decal {
+                age: 0.0,
+                lifetime: event.lifetime,
+                fade: event.fade,
+                color: event.color,
+            });
+    }
+}
+
+fn fade(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut decals_query: Query<(Entity, &mut Decal, &Handle<StandardMaterial>)>,
+) {
+    for (entity, mut decal, material) in decals_query.iter_mut() {
+        decal.age += time.delta_seconds();
+        if decal.age > decal.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let remaining = decal.lifetime - decal.age;
+        if remaining < decal.fade {
+            if let Some(material) = materials.get_mut(material) {
+                let mut color = decal.color;
+                color.set_a(decal.color.a() * remaining / decal.fade);
+                material.base_color = color;
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use bevy::{
+    input::mouse::MouseMotion,
+    math::Vec3Swizzles,
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, PerspectiveProjection},
+        mesh::Indices,
+        pipeline::PrimitiveTopology,
+        render_graph::base::camera::CAMERA_3D,
+    },
+};
+use bevy_egui::{egui, EguiContext};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::physics::RapierConfiguration;
+
+use crate::{
+    first_person::{MovementConfig, PlayerEyes},
+    locale::Locale,
+    settings::{AddSettings, SettingsTab},
+    terrain::{Chunk, ChunkCoords, SeenChunks},
+    timescale::Timescale,
+    Player,
+};
+
+const EDITOR_KEY: KeyCode = KeyCode::F2;
+const PAUSE_KEY: KeyCode = KeyCode::P;
+// how far each arm of the marker left where the player stands reaches
+const MARKER_SIZE: f32 = 3.0;
+
+/// Whether the world is being played, or edited from a free camera with the terrain tools
+/// out and the simulation paused or running at will
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppMode {
+    Play,
+    Editor,
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<EditorConfig>(SettingsTab::Debug, "Editor")
+            .add_state(AppMode::Play)
+            .init_resource::<EditorState>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system())
+            .add_system(simulate.system().before("timescale::clock"))
+            .add_system_set(SystemSet::on_enter(AppMode::Editor).with_system(enter.system()))
+            .add_system_set(SystemSet::on_exit(AppMode::Editor).with_system(exit.system()))
+            .add_system_set(
+                SystemSet::on_update(AppMode::Editor)
+                    .with_system(fly.system())
+                    .with_system(toggle_pause.system())
+                    .with_system(draw_gizmos.system())
+                    .with_system(hud.system()),
+            );
+    }
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct EditorConfig {
+    // metres a second the free camera flies, and how many times faster with sprint held
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub camera_speed: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub fast_multiplier: f32,
+    // stop the simulation on opening the editor, rather than leaving it running underneath
+    pub pause_on_enter: bool,
+    pub gizmos: bool,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            camera_speed: 80.0,
+            fast_multiplier: 4.0,
+            pause_on_enter: true,
+            gizmos: true,
+        }
+    }
+}
+
+/// The editor's free camera, and whether it has the simulation paused
+#[derive(Default)]
+pub struct EditorState {
+    pub paused: bool,
+    camera: Option<Entity>,
+    pitch: f32,
+    yaw: f32,
+}
+
+/// The camera the world is seen through while editing
+pub struct EditorCamera;
+
+// The lines drawn around the chunk under the camera and where the player stands
+struct Gizmos;
+
+fn setup(
+    mut commands: Commands,
+    mut editor: ResMut<EditorState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // left without a name so it's only handed the main pass while editing
+    let mut camera = PerspectiveCameraBundle {
+        perspective_projection: PerspectiveProjection {
+            far: 10000.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    camera.camera.name = None;
+    editor.camera = Some(commands.spawn_bundle(camera).insert(EditorCamera).id());
+
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::new(PrimitiveTopology::LineList)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgb(0.3, 1.0, 0.6),
+                unlit: true,
+                ..Default::default()
+            }),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(Gizmos);
+}
+
+// Opens and closes the editor, leaving the player where they stood
+fn toggle(keys: Res<Input<KeyCode>>, mut app_mode: ResMut<State<AppMode>>) {
+    if !keys.just_pressed(EDITOR_KEY) {
+        return;
+    }
+    let next = match app_mode.current() {
+        AppMode::Play => AppMode::Editor,
+        AppMode::Editor => AppMode::Play,
+    };
+    // only fails if a change is already queued this frame, which can go ahead instead
+    if app_mode.set(next).is_ok() {
+        info!("App mode: {:?}", next);
+    }
+}
+
+// Takes the view over from the player's eyes, starting from where they were looking
+fn enter(
+    config: Res<EditorConfig>,
+    mut editor: ResMut<EditorState>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    mut camera_query: Query<&mut Transform, With<EditorCamera>>,
+) {
+    editor.paused = config.pause_on_enter;
+    if let (Some(eyes), Some(mut transform)) =
+        (eyes_query.iter().next(), camera_query.iter_mut().next())
+    {
+        let forward = eyes.rotation * -Vec3::Z;
+        editor.yaw = (-forward.x).atan2(-forward.z);
+        editor.pitch = forward.y.clamp(-1.0, 1.0).asin();
+        transform.translation = eyes.translation;
+        transform.rotation = look_rotation(editor.yaw, editor.pitch);
+    }
+    if let Some(active_camera) = active_cameras.get_mut(CAMERA_3D) {
+        active_camera.entity = editor.camera;
+    }
+}
+
+// Hands the view back to the player's eyes, and lets the simulation run again
+fn exit(
+    mut editor: ResMut<EditorState>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    eyes_query: Query<Entity, With<PlayerEyes>>,
+    mut gizmos_query: Query<&mut Visible, With<Gizmos>>,
+) {
+    editor.paused = false;
+    if let Some(active_camera) = active_cameras.get_mut(CAMERA_3D) {
+        active_camera.entity = eyes_query.iter().next();
+    }
+    for mut visible in gizmos_query.iter_mut() {
+        visible.is_visible = false;
+    }
+}
+
+// Stops the physics and the world clock while the editor has the simulation paused
+fn simulate(
+    app_mode: Res<State<AppMode>>,
+    editor: Res<EditorState>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut timescale: ResMut<Timescale>,
+) {
+    let paused = *app_mode.current() == AppMode::Editor && editor.paused;
+    if rapier_config.physics_pipeline_active == paused {
+        rapier_config.physics_pipeline_active = !paused;
+    }
+    if timescale.paused != paused {
+        timescale.paused = paused;
+    }
+}
+
+fn toggle_pause(keys: Res<Input<KeyCode>>, mut editor: ResMut<EditorState>) {
+    if keys.just_pressed(PAUSE_KEY) {
+        editor.paused = !editor.paused;
+        info!(
+            "Simulation: {}",
+            if editor.paused { "paused" } else { "running" }
+        );
+    }
+}
+
+// Flies the camera the way it's facing with the movement keys, turning it with the mouse
+// while the cursor's locked. Goes by real time, so it still flies with the simulation paused.
+fn fly(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<EditorConfig>,
+    movement_config: Res<MovementConfig>,
+    mut motion: EventReader<MouseMotion>,
+    mut editor: ResMut<EditorState>,
+    mut camera_query: Query<&mut Transform, With<EditorCamera>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() {
+        // the cursor's over the panels, so the mouse moving it shouldn't turn the camera
+        for _ in motion.iter() {}
+        return;
+    }
+    // the same feel as looking around with the player's eyes
+    let sensitivity = movement_config.sensitivity / 10000.0;
+    for event in motion.iter() {
+        editor.pitch -= (sensitivity * event.delta.y * window.height()).to_radians();
+        editor.yaw -= (sensitivity * event.delta.x * window.width()).to_radians();
+    }
+    editor.pitch = editor.pitch.clamp(-1.54, 1.54);
+
+    let map = &movement_config.map;
+    let held = |codes: &[KeyCode]| codes.iter().any(|&k| keys.pressed(k));
+    for mut transform in camera_query.iter_mut() {
+        transform.rotation = look_rotation(editor.yaw, editor.pitch);
+        let forward = transform.rotation * -Vec3::Z;
+        let right = transform.rotation * Vec3::X;
+
+        let mut direction = Vec3::ZERO;
+        if held(map.forward) {
+            direction += forward;
+        }
+        if held(map.backward) {
+            direction -= forward;
+        }
+        if held(map.right) {
+            direction += right;
+        }
+        if held(map.left) {
+            direction -= right;
+        }
+        if held(map.up) {
+            direction += Vec3::Y;
+        }
+        if held(map.down) {
+            direction -= Vec3::Y;
+        }
+        if direction.length_squared() < 1E-6 {
+            continue;
+        }
+
+        let speed = if held(map.sprint) {
+            config.camera_speed * config.fast_multiplier
+        } else {
+            config.camera_speed
+        };
+        transform.translation += direction.normalize() * speed * time.delta_seconds();
+    }
+}
+
+fn look_rotation(yaw: f32, pitch: f32) -> Quat {
+    Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch)
+}
+
+// Outlines the chunk under the camera from its lowest to its highest point, and marks where
+// the player will carry on from
+fn draw_gizmos(
+    config: Res<EditorConfig>,
+    seen_chunks: Res<SeenChunks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    camera_query: Query<&Transform, With<EditorCamera>>,
+    player_query: Query<&Transform, With<Player>>,
+    chunks_query: Query<&Chunk>,
+    mut gizmos_query: Query<(&Handle<Mesh>, &mut Visible), With<Gizmos>>,
+) {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+
+    let aabb = camera_query
+        .iter()
+        .next()
+        .and_then(|camera| seen_chunks.get(&ChunkCoords::containing(camera.translation.xz())))
+        .and_then(|&(_, entity)| chunks_query.get(entity).ok())
+        .and_then(Chunk::aabb);
+    if let Some((min, max)) = aabb {
+        let corner = |x: bool, y: bool, z: bool| {
+            Vec3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        };
+        // each edge runs from a corner to the one across from it along a single axis
+        for &(x, y, z) in [
+            (false, false, false),
+            (true, false, false),
+            (false, true, false),
+            (true, true, false),
+            (false, false, true),
+            (true, false, true),
+            (false, true, true),
+            (true, true, true),
+        ]
+        .iter()
+        {
+            let from = corner(x, y, z);
+            for &(next_x, next_y, next_z) in [(true, y, z), (x, true, z), (x, y, true)].iter() {
+                if (next_x, next_y, next_z) != (x, y, z) {
+                    positions.push(from.into());
+                    positions.push(corner(next_x, next_y, next_z).into());
+                }
+            }
+        }
+    }
+    for player in player_query.iter() {
+        for &axis in [Vec3::X, Vec3::Y, Vec3::Z].iter() {
+            positions.push((player.translation - axis * MARKER_SIZE).into());
+            positions.push((player.translation + axis * MARKER_SIZE).into());
+        }
+    }
+
+    for (handle, mut visible) in gizmos_query.iter_mut() {
+        visible.is_visible = config.gizmos && !positions.is_empty();
+        if !visible.is_visible {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(handle) {
+            let indices = (0..positions.len() as u32).collect();
+            mesh.set_attribute(
+                Mesh::ATTRIBUTE_NORMAL,
+                vec![[0.0, 1.0, 0.0]; positions.len()],
+            );
+            mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+            mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+            mesh.set_indices(Some(Indices::U32(indices)));
+        }
+    }
+}
+
+fn hud(egui_context: Res<EguiContext>, locale: Res<Locale>, editor: Res<EditorState>) {
+    let simulation = if editor.paused {
+        "editor-simulation-paused"
+    } else {
+        "editor-simulation-running"
+    };
+    egui::Area::new("editor_mode")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .show(egui_context.ctx(), |ui| {
+            ui.label(locale.format(
+                "hud-editor",
+                &[("simulation", locale.text(simulation).into())],
+            ));
+        });
+}
@@ -0,0 +1,107 @@
+use bevy::{
+    asset::{Asset, LoadState},
+    log::error,
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContext};
+
+use crate::locale::Locale;
+
+/// Shows the errors the app recovered from as toasts that stay until they're dismissed,
+/// rather than stopping on them
+pub struct ErrorLogPlugin;
+
+impl Plugin for ErrorLogPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        // plugins built before this one may already have reported something
+        app.world_mut()
+            .get_resource_or_insert_with(ErrorLog::default);
+        app.add_system(check_assets.system())
+            .add_system(toasts.system());
+    }
+}
+
+struct Toast {
+    message: String,
+    // how many times the same error has come up since it was last dismissed
+    count: u32,
+}
+
+#[derive(Default)]
+pub struct ErrorLog {
+    toasts: Vec<Toast>,
+    // assets still loading, with the paths to report them by if they fail
+    watched: Vec<(HandleUntyped, String)>,
+}
+
+impl ErrorLog {
+    pub fn report(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        error!("{}", message);
+        match self
+            .toasts
+            .iter_mut()
+            .find(|toast| toast.message == message)
+        {
+            Some(toast) => toast.count += 1,
+            None => self.toasts.push(Toast { message, count: 1 }),
+        }
+    }
+
+    /// Loads an asset, reporting it if the file is missing or can't be read
+    pub fn load<T: Asset>(&mut self, asset_server: &AssetServer, path: &str) -> Handle<T> {
+        let handle = asset_server.load(path);
+        self.watched
+            .push((handle.clone_untyped(), path.to_string()));
+        handle
+    }
+}
+
+fn check_assets(asset_server: Res<AssetServer>, mut errors: ResMut<ErrorLog>) {
+    if errors.watched.is_empty() {
+        return;
+    }
+
+    let mut failed = Vec::new();
+    errors.watched.retain(
+        |(handle, path)| match asset_server.get_load_state(handle.id) {
+            LoadState::Failed => {
+                failed.push(path.clone());
+                false
+            }
+            LoadState::Loaded => false,
+            _ => true,
+        },
+    );
+    for path in failed {
+        errors.report(format!("Failed to load {}", path));
+    }
+}
+
+fn toasts(egui_context: Res<EguiContext>, locale: Res<Locale>, mut errors: ResMut<ErrorLog>) {
+    if errors.toasts.is_empty() {
+        return;
+    }
+
+    let mut dismissed = None;
+    egui::Area::new("error_toasts")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-20.0, -20.0])
+        .show(egui_context.ctx(), |ui| {
+            for (index, toast) in errors.toasts.iter().enumerate() {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(egui::Color32::LIGHT_RED, &toast.message);
+                        if toast.count > 1 {
+                            ui.label(format!("x{}", toast.count));
+                        }
+                        if ui.button(locale.text("errors-dismiss")).clicked() {
+                            dismissed = Some(index);
+                        }
+                    });
+                });
+            }
+        });
+    if let Some(index) = dismissed {
+        errors.toasts.remove(index);
+    }
+}
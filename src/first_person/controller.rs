@@ -0,0 +1,255 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    na::{Isometry3, Point3, Vector3},
+    physics::{
+        IntoEntity, QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet,
+    },
+    prelude::{
+        ColliderHandle, ColliderShape, InteractionGroups, QueryPipeline, RigidBodyPosition,
+        RigidBodyType, RigidBodyVelocity,
+    },
+};
+
+use crate::Player;
+
+use super::{
+    grapple::Grapple, Gliding, MovementConfig, MovementState, BODY_HALF_HEIGHT, MOVEMENT_TIMESTEP,
+};
+
+// half the width of the player's body
+const BODY_RADIUS: f32 = 0.5;
+// the gap kept between the capsule and whatever it's moved up against, so the next sweep
+// doesn't start out already touching it
+const SKIN: f32 = 0.02;
+// how many surfaces a move can slide along before giving up on the rest of it
+const MAX_SLIDES: usize = 4;
+
+/// How the player's body is moved about
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ControllerMode {
+    // a box pushed around by impulses, left to the physics
+    Dynamic,
+    // a capsule swept through the world by hand, walking up slopes and steps
+    Kinematic,
+}
+
+impl Default for ControllerMode {
+    fn default() -> Self {
+        ControllerMode::Dynamic
+    }
+}
+
+pub(super) fn body_type(mode: ControllerMode) -> RigidBodyType {
+    match mode {
+        ControllerMode::Dynamic => RigidBodyType::Dynamic,
+        ControllerMode::Kinematic => RigidBodyType::KinematicPositionBased,
+    }
+}
+
+pub(super) fn collider_shape(mode: ControllerMode) -> ColliderShape {
+    match mode {
+        ControllerMode::Dynamic => {
+            ColliderShape::cuboid(BODY_RADIUS, BODY_HALF_HEIGHT, BODY_RADIUS)
+        }
+        ControllerMode::Kinematic => {
+            // the same height as the box, the rounded ends included
+            let half_segment = BODY_HALF_HEIGHT - BODY_RADIUS;
+            ColliderShape::capsule(
+                Point3::new(0.0, -half_segment, 0.0),
+                Point3::new(0.0, half_segment, 0.0),
+                BODY_RADIUS,
+            )
+        }
+    }
+}
+
+/// Moves the capsule by the movement keys once per movement step when the controller is
+/// kinematic, sweeping it through the world and sliding it along whatever it runs into
+pub(super) fn move_kinematic(
+    config: Res<MovementConfig>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    mut query: Query<
+        (
+            Entity,
+            &mut RigidBodyPosition,
+            &mut RigidBodyVelocity,
+            &ColliderShape,
+            &mut MovementState,
+            Option<&Grapple>,
+            Option<&Gliding>,
+        ),
+        With<Player>,
+    >,
+) {
+    if config.controller != ControllerMode::Kinematic {
+        return;
+    }
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    let delta_seconds = MOVEMENT_TIMESTEP as f32;
+    let min_ground_normal = config.max_slope_degrees.to_radians().cos();
+
+    for (player, mut position, mut velocity, shape, mut movement_state, grapple, gliding) in
+        query.iter_mut()
+    {
+        let filter = |handle: ColliderHandle| handle.entity() != player;
+        // how far along a motion the capsule gets before touching something, and that
+        // something's surface normal
+        let cast = |from: Vec3, motion: Vec3| {
+            query_pipeline
+                .cast_shape(
+                    &collider_set,
+                    &Isometry3::translation(from.x, from.y, from.z),
+                    &Vector3::new(motion.x, motion.y, motion.z),
+                    &**shape,
+                    1.0,
+                    InteractionGroups::all(),
+                    Some(&filter),
+                )
+                .map(|(_, toi)| {
+                    (
+                        toi.toi,
+                        Vec3::new(toi.normal1.x, toi.normal1.y, toi.normal1.z),
+                    )
+                })
+        };
+
+        let start: Vec3 = position.next_position.translation.vector.into();
+        let mut linvel: Vec3 = velocity.linvel.into();
+        let grounded = snap_down(&cast, start, SKIN * 4.0, min_ground_normal).is_some();
+        let jump = movement_state.take_jump(&config, grounded);
+
+        if gliding.is_none() {
+            let ground_velocity = linvel * Vec3::new(1.0, 0.0, 1.0);
+            let desired =
+                movement_state.desired_velocity(&config, grapple.is_some(), ground_velocity);
+            linvel.x = desired.x;
+            linvel.z = desired.z;
+            if !config.gravity {
+                linvel.y = desired.y;
+            }
+        }
+        if config.gravity {
+            if !grounded {
+                linvel.y += config.gravity_strength * delta_seconds;
+            } else if linvel.y < 0.0 {
+                linvel.y = 0.0;
+            }
+        }
+        if jump {
+            linvel.y = config.jump_speed();
+        }
+
+        let horizontal = Vec3::new(linvel.x, 0.0, linvel.z) * delta_seconds;
+        let walked = move_across(
+            &cast,
+            start,
+            horizontal,
+            grounded && !jump,
+            config.step_offset,
+            min_ground_normal,
+        );
+        let (mut moved, normal) = slide(&cast, walked, Vec3::Y * linvel.y * delta_seconds, None);
+        match normal {
+            // landed on something it can stand on
+            Some(normal) if linvel.y < 0.0 && normal.y >= min_ground_normal => linvel.y = 0.0,
+            // bumped its head
+            Some(normal) if linvel.y > 0.0 && normal.y < 0.0 => linvel.y = 0.0,
+            _ => {}
+        }
+        // keep to the ground going down slopes and over bumps, rather than skipping off them
+        if grounded && config.gravity && linvel.y <= 0.0 {
+            if let Some(snapped) = snap_down(&cast, moved, config.snap_distance, min_ground_normal)
+            {
+                moved = snapped;
+            }
+        }
+
+        position.next_position.translation.vector = moved.into();
+        velocity.linvel = linvel.into();
+    }
+}
+
+// Sweeps the capsule along a motion, sliding along whatever it hits on the way, and returns
+// where it ends up along with the last surface it touched. With `walls`, slopes steeper than
+// it are treated as upright walls so they can't be walked up.
+fn slide(
+    cast: &impl Fn(Vec3, Vec3) -> Option<(f32, Vec3)>,
+    mut position: Vec3,
+    mut motion: Vec3,
+    walls: Option<f32>,
+) -> (Vec3, Option<Vec3>) {
+    let mut touched = None;
+    for _ in 0..MAX_SLIDES {
+        if motion.length_squared() < 1E-8 {
+            break;
+        }
+        let (toi, mut normal) = match cast(position, motion) {
+            Some(hit) => hit,
+            None => {
+                position += motion;
+                break;
+            }
+        };
+        position += motion * toi + normal * SKIN;
+        if let Some(min_ground_normal) = walls {
+            let flat = Vec3::new(normal.x, 0.0, normal.z);
+            if normal.y < min_ground_normal && flat.length_squared() > 1E-6 {
+                normal = flat.normalize();
+            }
+        }
+        let remaining = motion * (1.0 - toi);
+        motion = remaining - normal * remaining.dot(normal);
+        touched = Some(normal);
+    }
+    (position, touched)
+}
+
+// Moves along the ground, stepping up onto ledges no higher than the step offset when walking
+// into them
+fn move_across(
+    cast: &impl Fn(Vec3, Vec3) -> Option<(f32, Vec3)>,
+    position: Vec3,
+    motion: Vec3,
+    grounded: bool,
+    step_offset: f32,
+    min_ground_normal: f32,
+) -> Vec3 {
+    let (moved, normal) = slide(cast, position, motion, Some(min_ground_normal));
+    let blocked = normal.map_or(false, |normal| normal.y < min_ground_normal);
+    if !grounded || !blocked || step_offset <= 0.0 {
+        return moved;
+    }
+
+    // try again from the top of the step, then come back down onto it
+    let (raised, _) = slide(cast, position, Vec3::Y * step_offset, None);
+    let (across, _) = slide(cast, raised, motion, Some(min_ground_normal));
+    let drop = raised.y - position.y + SKIN * 2.0;
+    match snap_down(cast, across, drop, min_ground_normal) {
+        Some(stepped)
+            if (stepped - position).xz().length() > (moved - position).xz().length() + 1E-3 =>
+        {
+            stepped
+        }
+        _ => moved,
+    }
+}
+
+// Where the capsule comes to rest dropping up to a distance straight down, if it lands on
+// something it can stand on
+fn snap_down(
+    cast: &impl Fn(Vec3, Vec3) -> Option<(f32, Vec3)>,
+    position: Vec3,
+    distance: f32,
+    min_ground_normal: f32,
+) -> Option<Vec3> {
+    let motion = Vec3::Y * -distance;
+    let (toi, normal) = cast(position, motion)?;
+    if normal.y < min_ground_normal {
+        return None;
+    }
+    Some(position + motion * toi + normal * SKIN)
+}
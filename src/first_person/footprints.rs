@@ -0,0 +1,119 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    physics::{QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet},
+    prelude::{QueryPipeline, RigidBodyVelocity},
+};
+
+use super::{ground::ground_distance, Gliding};
+use crate::{
+    decals::SpawnDecalEvent,
+    terrain::{self, query, Biome},
+    Player,
+};
+
+// how far either side of the direction of travel each foot lands
+const FOOT_SPACING: f32 = 0.2;
+// lifted off the ground a touch so the terrain doesn't poke through the print
+const FOOTPRINT_LIFT: f32 = 0.03;
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct FootprintConfig {
+    pub enabled: bool,
+    // distance walked between one footprint and the next
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    pub stride: f32,
+    // seconds a footprint lasts, including its fade
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub lifetime: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub fade: f32,
+    // the player counts as walking while the ground is at most this far below their centre
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub ground_check_distance: f32,
+}
+
+impl Default for FootprintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stride: 0.9,
+            lifetime: 30.0,
+            fade: 10.0,
+            ground_check_distance: 1.6,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Strides {
+    walked: f32,
+    left_foot: bool,
+}
+
+// Presses a print into the sand or snow every stride the player walks across it
+pub fn leave(
+    config: Res<FootprintConfig>,
+    terrain_config: Res<terrain::Config>,
+    time: Res<Time>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    mut strides: Local<Strides>,
+    mut decals: EventWriter<SpawnDecalEvent>,
+    player_query: Query<(Entity, &Transform, &RigidBodyVelocity), (With<Player>, Without<Gliding>)>,
+) {
+    if !config.enabled {
+        return;
+    }
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+
+    for (player, transform, velocity) in player_query.iter() {
+        let velocity: Vec3 = velocity.linvel.into();
+        let heading = velocity.xz();
+        strides.walked += heading.length() * time.delta_seconds();
+        if strides.walked < config.stride {
+            continue;
+        }
+        strides.walked = 0.0;
+
+        let distance = match ground_distance(
+            &query_pipeline,
+            &collider_set,
+            player,
+            transform.translation,
+            config.ground_check_distance,
+        ) {
+            Some(distance) => distance,
+            None => continue,
+        };
+        let color = match query::biome_at(&terrain_config, transform.translation.xz()) {
+            Biome::Sand => Color::rgba(0.62, 0.52, 0.3, 0.8),
+            Biome::Snow => Color::rgba(0.72, 0.76, 0.85, 0.8),
+            _ => continue,
+        };
+
+        let forward = heading.normalize_or_zero();
+        let side = Vec2::new(-forward.y, forward.x)
+            * if strides.left_foot {
+                -FOOT_SPACING
+            } else {
+                FOOT_SPACING
+            };
+        strides.left_foot = !strides.left_foot;
+
+        let contact = transform.translation - Vec3::Y * distance + Vec3::new(side.x, 0.0, side.y);
+        let normal = query::normal_at(&terrain_config, contact.xz());
+        let yaw = Quat::from_rotation_y(forward.x.atan2(forward.y));
+        decals.send(SpawnDecalEvent {
+            transform: Transform {
+                translation: contact + normal * FOOTPRINT_LIFT,
+                rotation: Quat::from_rotation_arc(Vec3::Y, normal) * yaw,
+                scale: Vec3::new(0.12, 0.02, 0.28),
+            },
+            color,
+            lifetime: config.lifetime,
+            fade: config.fade,
+        });
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    physics::{QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet},
+    prelude::{QueryPipeline, RigidBodyMassProps, RigidBodyVelocity},
+};
+
+use super::{ground::ground_distance, EyesEntity, MovementConfig, PlayerEyes};
+use crate::{editor::AppMode, stats::Stamina, weather::Wind, Player};
+
+/// Attached to the player while the glider is open
+pub struct Gliding;
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct GliderConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub lift: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub drag: f32,
+    // the glider can't fall faster than this, no matter the pitch
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub max_sink_speed: f32,
+    // the glider can only be opened, and closes again, this far above the ground
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub ground_clearance: f32,
+}
+
+impl Default for GliderConfig {
+    fn default() -> Self {
+        Self {
+            lift: 0.08,
+            drag: 0.01,
+            max_sink_speed: 8.0,
+            ground_clearance: 4.0,
+        }
+    }
+}
+
+/// Opens the glider in mid-air, or closes it if already gliding
+pub fn toggle(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<MovementConfig>,
+    app_mode: Res<State<AppMode>>,
+    glider_config: Res<GliderConfig>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    player_query: Query<(Entity, &Transform, Option<&Gliding>, Option<&Stamina>), With<Player>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked()
+        || *app_mode.current() != AppMode::Play
+        || !config.map.glide.iter().any(|&k| keys.just_pressed(k))
+    {
+        return;
+    }
+
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    for (player, transform, gliding, stamina) in player_query.iter() {
+        if gliding.is_some() {
+            commands.entity(player).remove::<Gliding>();
+        } else if stamina.map_or(true, |stamina| !stamina.exhausted())
+            && ground_distance(
+                &query_pipeline,
+                &collider_set,
+                player,
+                transform.translation,
+                glider_config.ground_clearance,
+            )
+            .is_none()
+        {
+            commands.entity(player).insert(Gliding);
+        }
+    }
+}
+
+/// Closes the glider once the player gets close to the ground or runs out of stamina
+pub fn land(
+    mut commands: Commands,
+    glider_config: Res<GliderConfig>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    player_query: Query<(Entity, &Transform, Option<&Stamina>), (With<Player>, With<Gliding>)>,
+) {
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    for (player, transform, stamina) in player_query.iter() {
+        if stamina.map_or(false, |stamina| stamina.exhausted())
+            || ground_distance(
+                &query_pipeline,
+                &collider_set,
+                player,
+                transform.translation,
+                glider_config.ground_clearance,
+            )
+            .is_some()
+        {
+            commands.entity(player).remove::<Gliding>();
+        }
+    }
+}
+
+/// Applies lift and drag based on the pitch of the player's view and the airflow over the glider
+pub fn glide(
+    time: Res<Time>,
+    wind: Res<Wind>,
+    glider_config: Res<GliderConfig>,
+    mut player_query: Query<
+        (&EyesEntity, &mut RigidBodyVelocity, &RigidBodyMassProps),
+        (With<Player>, With<Gliding>),
+    >,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    for (eyes_entity, mut velocity, mass_props) in player_query.iter_mut() {
+        let eyes = match eyes_query.get(eyes_entity.0) {
+            Ok(eyes) => eyes,
+            Err(_) => continue,
+        };
+        let wing_up = eyes.rotation * Vec3::Y;
+
+        let current_velocity: Vec3 = velocity.linvel.into();
+        let air_velocity = current_velocity - wind.velocity;
+        let airspeed_squared = air_velocity.length_squared();
+        if airspeed_squared < 1E-6 {
+            continue;
+        }
+        let airflow = air_velocity.normalize();
+
+        // The more air hits the underside of the wing the more lift we get, pitching
+        // down trades lift for speed. Lift acts perpendicular to the airflow.
+        let angle_of_attack = (-airflow.dot(wing_up)).clamp(-1.0, 1.0);
+        let lift_direction = (wing_up - airflow * wing_up.dot(airflow)).normalize_or_zero();
+        let lift = lift_direction * glider_config.lift * airspeed_squared * angle_of_attack;
+        let drag = -airflow
+            * glider_config.drag
+            * airspeed_squared
+            * (1.0 + angle_of_attack * angle_of_attack);
+
+        let mut delta_velocity = (lift + drag) * time.delta_seconds();
+
+        let sink_speed = -(current_velocity.y + delta_velocity.y);
+        if sink_speed > glider_config.max_sink_speed {
+            delta_velocity.y += sink_speed - glider_config.max_sink_speed;
+        }
+
+        let impulse = delta_velocity * mass_props.mass();
+        if impulse.length_squared() > 1E-6 {
+            velocity.apply_impulse(mass_props, impulse.into());
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    na::{Point3, Vector3},
+    physics::{
+        IntoEntity, QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet,
+    },
+    prelude::{
+        ColliderHandle, InteractionGroups, QueryPipeline, Ray, RigidBodyMassProps,
+        RigidBodyVelocity,
+    },
+};
+
+use super::{EyesEntity, MovementConfig, PlayerEyes};
+use crate::{editor::AppMode, Player};
+
+/// Attached to the player while the grapple is hooked into the terrain
+pub struct Grapple {
+    pub anchor: Vec3,
+    pub rope_length: f32,
+}
+
+/// Marks the entity used to draw the rope between the player and the anchor
+pub struct GrappleRope;
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct GrappleConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub range: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub reel_speed: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub min_rope_length: f32,
+    // how hard the rope pulls the player back when stretched past its length
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub stiffness: f32,
+}
+
+impl Default for GrappleConfig {
+    fn default() -> Self {
+        Self {
+            range: 400.0,
+            reel_speed: 30.0,
+            min_rope_length: 3.0,
+            stiffness: 20.0,
+        }
+    }
+}
+
+pub fn setup_rope(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgb_u8(92, 64, 51),
+                unlit: true,
+                ..Default::default()
+            }),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: false,
+            },
+            ..Default::default()
+        })
+        .insert(GrappleRope);
+}
+
+/// Fires the grapple along the view direction, or releases it if already attached
+pub fn fire(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<MovementConfig>,
+    app_mode: Res<State<AppMode>>,
+    grapple_config: Res<GrappleConfig>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    player_query: Query<(Entity, &Transform, &EyesEntity, Option<&Grapple>), With<Player>>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked()
+        || *app_mode.current() != AppMode::Play
+        || !config.map.grapple.iter().any(|&k| keys.just_pressed(k))
+    {
+        return;
+    }
+
+    for (player, transform, eyes_entity, grapple) in player_query.iter() {
+        if grapple.is_some() {
+            commands.entity(player).remove::<Grapple>();
+            continue;
+        }
+
+        let eyes = match eyes_query.get(eyes_entity.0) {
+            Ok(eyes) => eyes,
+            Err(_) => continue,
+        };
+        let origin = eyes.translation;
+        let direction = eyes.rotation * -Vec3::Z;
+
+        let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+        let ray = Ray::new(
+            Point3::new(origin.x, origin.y, origin.z),
+            Vector3::new(direction.x, direction.y, direction.z),
+        );
+        // Don't let the ray hit the player's own collider
+        let filter = |handle: ColliderHandle| handle.entity() != player;
+
+        if let Some((_, toi)) = query_pipeline.cast_ray(
+            &collider_set,
+            &ray,
+            grapple_config.range,
+            true,
+            InteractionGroups::all(),
+            Some(&filter),
+        ) {
+            let anchor = origin + direction * toi;
+            commands.entity(player).insert(Grapple {
+                anchor,
+                rope_length: anchor.distance(transform.translation),
+            });
+        }
+    }
+}
+
+/// Keeps the player within rope length of the anchor, reeling in while the key is held.
+/// Rapier's joints at this version all hold their bodies at a fixed distance or along a fixed
+/// axis, so the slack rope is enforced here by hand rather than with a joint.
+pub fn swing(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    config: Res<MovementConfig>,
+    grapple_config: Res<GrappleConfig>,
+    mut player_query: Query<
+        (
+            &Transform,
+            &mut Grapple,
+            &mut RigidBodyVelocity,
+            &RigidBodyMassProps,
+        ),
+        With<Player>,
+    >,
+) {
+    for (transform, mut grapple, mut velocity, mass_props) in player_query.iter_mut() {
+        if config.map.reel_in.iter().any(|&k| keys.pressed(k)) {
+            grapple.rope_length = (grapple.rope_length
+                - grapple_config.reel_speed * time.delta_seconds())
+            .max(grapple_config.min_rope_length);
+        }
+
+        let offset = transform.translation - grapple.anchor;
+        let distance = offset.length();
+        if distance <= grapple.rope_length || distance < f32::EPSILON {
+            continue;
+        }
+
+        // The rope can only pull, so cancel any velocity moving away from the anchor
+        // and nudge the player back towards the end of the rope
+        let direction = offset / distance;
+        let current_velocity: Vec3 = velocity.linvel.into();
+        let outward_speed = current_velocity.dot(direction).max(0.0);
+        let stretch = distance - grapple.rope_length;
+        let delta_velocity = -direction
+            * (outward_speed + stretch * grapple_config.stiffness * time.delta_seconds());
+
+        let impulse = delta_velocity * mass_props.mass();
+        velocity.apply_impulse(mass_props, impulse.into());
+    }
+}
+
+/// Stretches the rope mesh between the player and the anchor
+pub fn draw_rope(
+    player_query: Query<(&Transform, Option<&Grapple>), With<Player>>,
+    mut rope_query: Query<(&mut Transform, &mut Visible), (With<GrappleRope>, Without<Player>)>,
+) {
+    let (player_transform, grapple) = match player_query.iter().next() {
+        Some(player) => player,
+        None => return,
+    };
+
+    for (mut transform, mut visible) in rope_query.iter_mut() {
+        visible.is_visible = grapple.is_some();
+
+        if let Some(grapple) = grapple {
+            let start = player_transform.translation;
+            let span = grapple.anchor - start;
+            let length = span.length();
+
+            transform.translation = start + span / 2.0;
+            transform.rotation = Quat::from_rotation_arc(Vec3::Y, span / length.max(f32::EPSILON));
+            transform.scale = Vec3::new(0.1, length, 0.1);
+        }
+    }
+}
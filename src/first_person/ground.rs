@@ -0,0 +1,33 @@
+use bevy::prelude::*;
+use bevy_rapier3d::{
+    na::{Point3, Vector3},
+    physics::{IntoEntity, QueryPipelineColliderComponentsSet},
+    prelude::{ColliderHandle, InteractionGroups, QueryPipeline, Ray},
+};
+
+/// Casts a ray straight down from the player, ignoring the player's own collider,
+/// and returns the distance to the ground if it is within `max_distance`
+pub fn ground_distance(
+    query_pipeline: &QueryPipeline,
+    collider_set: &QueryPipelineColliderComponentsSet,
+    player: Entity,
+    position: Vec3,
+    max_distance: f32,
+) -> Option<f32> {
+    let ray = Ray::new(
+        Point3::new(position.x, position.y, position.z),
+        Vector3::new(0.0, -1.0, 0.0),
+    );
+    let filter = |handle: ColliderHandle| handle.entity() != player;
+
+    query_pipeline
+        .cast_ray(
+            collider_set,
+            &ray,
+            max_distance,
+            true,
+            InteractionGroups::all(),
+            Some(&filter),
+        )
+        .map(|(_, distance)| distance)
+}
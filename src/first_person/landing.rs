@@ -0,0 +1,183 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    physics::{QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet},
+    prelude::{QueryPipeline, RigidBodyVelocity},
+};
+use rand::Rng;
+
+use super::{ground::ground_distance, PlayerEyes, EYES_OFFSET};
+use crate::{
+    particles::{GroundContact, ParticleBurstEvent},
+    Player,
+};
+
+/// Sent when the player hits the ground after a fall
+#[derive(Clone, Copy, Debug)]
+pub struct LandingEvent {
+    pub contact_point: Vec3,
+    pub impact_speed: f32,
+}
+
+/// Sent when a landing was hard enough to hurt
+#[derive(Clone, Copy, Debug)]
+pub struct FallDamageEvent {
+    pub damage: f32,
+}
+
+/// Shakes the camera it's attached to, decaying over time
+pub struct CameraShake {
+    pub trauma: f32,
+    // where the camera sits when it isn't shaking
+    pub rest: Vec3,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            trauma: 0.0,
+            rest: EYES_OFFSET,
+        }
+    }
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct LandingConfig {
+    // landings slower than this are ignored entirely
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub min_impact_speed: f32,
+    // landings faster than this start to do damage
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub safe_impact_speed: f32,
+    // landings at or above this speed do max_damage
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub lethal_impact_speed: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub max_damage: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub ground_check_distance: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub max_shake_offset: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub shake_decay: f32,
+}
+
+impl Default for LandingConfig {
+    fn default() -> Self {
+        Self {
+            min_impact_speed: 15.0,
+            safe_impact_speed: 40.0,
+            lethal_impact_speed: 120.0,
+            max_damage: 100.0,
+            ground_check_distance: 3.0,
+            max_shake_offset: 0.4,
+            shake_decay: 1.5,
+        }
+    }
+}
+
+/// Watches for the player's fall being stopped abruptly by the ground
+pub fn detect(
+    config: Res<LandingConfig>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    mut last_vertical_speed: Local<f32>,
+    mut events: EventWriter<LandingEvent>,
+    player_query: Query<(Entity, &Transform, &RigidBodyVelocity), With<Player>>,
+) {
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+
+    for (player, transform, velocity) in player_query.iter() {
+        let vertical_speed = velocity.linvel.y;
+        let falling_speed = -*last_vertical_speed;
+        *last_vertical_speed = vertical_speed;
+
+        // We were falling fast last frame and most of that speed has vanished
+        if falling_speed < config.min_impact_speed || -vertical_speed > falling_speed * 0.5 {
+            continue;
+        }
+
+        // Make sure it was the ground that stopped us and not the grapple or glider
+        if let Some(distance) = ground_distance(
+            &query_pipeline,
+            &collider_set,
+            player,
+            transform.translation,
+            config.ground_check_distance,
+        ) {
+            events.send(LandingEvent {
+                contact_point: transform.translation - Vec3::Y * distance,
+                impact_speed: falling_speed,
+            });
+        }
+    }
+}
+
+/// Turns landings into damage, camera shake and a puff of dust
+pub fn apply_effects(
+    config: Res<LandingConfig>,
+    mut landing_events: EventReader<LandingEvent>,
+    mut damage_events: EventWriter<FallDamageEvent>,
+    mut particle_events: EventWriter<ParticleBurstEvent>,
+    mut shake_query: Query<&mut CameraShake, With<PlayerEyes>>,
+) {
+    for landing in landing_events.iter() {
+        let severity = ((landing.impact_speed - config.safe_impact_speed)
+            / (config.lethal_impact_speed - config.safe_impact_speed).max(f32::EPSILON))
+        .clamp(0.0, 1.0);
+
+        if severity > 0.0 {
+            damage_events.send(FallDamageEvent {
+                damage: severity * config.max_damage,
+            });
+        }
+
+        let intensity = (landing.impact_speed / config.lethal_impact_speed).min(1.0);
+        for mut shake in shake_query.iter_mut() {
+            shake.trauma = (shake.trauma + intensity).min(1.0);
+        }
+
+        particle_events.send(ParticleBurstEvent {
+            position: landing.contact_point,
+            color: Color::rgb_u8(150, 130, 100),
+            count: 6 + (intensity * 20.0) as usize,
+            speed: 2.0 + intensity * 8.0,
+            size: 0.15,
+            lifetime: 0.8,
+            gravity: 9.8,
+            ground: GroundContact::Bounce,
+            wind: 0.0,
+        });
+    }
+}
+
+/// Jitters the eyes around their resting position while there is trauma left
+pub fn shake_camera(
+    time: Res<Time>,
+    config: Res<LandingConfig>,
+    mut shake_query: Query<(&mut CameraShake, &mut Transform), With<PlayerEyes>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (mut shake, mut transform) in shake_query.iter_mut() {
+        if shake.trauma <= 0.0 {
+            continue;
+        }
+
+        // Squaring the trauma makes small bumps subtle and big landings violent
+        let amount = shake.trauma * shake.trauma * config.max_shake_offset;
+        let offset = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        ) * amount;
+
+        shake.trauma = (shake.trauma - config.shake_decay * time.delta_seconds()).max(0.0);
+        transform.translation = if shake.trauma > 0.0 {
+            shake.rest + offset
+        } else {
+            shake.rest
+        };
+    }
+}
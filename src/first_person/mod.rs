@@ -1,29 +1,95 @@
 use bevy::{
     app::{Events, ManualEventReader},
+    core::FixedTimestep,
     input::mouse::MouseMotion,
+    math::Vec3Swizzles,
     prelude::*,
     render::camera::PerspectiveProjection,
 };
-use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
 use bevy_rapier3d::{
-    na::Vector,
+    na::{Isometry3, UnitQuaternion, Vector},
     physics::{ColliderBundle, RapierConfiguration, RigidBodyBundle, RigidBodyPositionSync},
+    physics::{QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet},
     prelude::{
-        ColliderMassProps, ColliderShape, PhysicsPipeline, RigidBodyActivation, RigidBodyDamping,
-        RigidBodyForces, RigidBodyMassProps, RigidBodyMassPropsFlags, RigidBodyType,
-        RigidBodyVelocity,
+        ColliderMassProps, ColliderShape, PhysicsPipeline, QueryPipeline, RigidBodyActivation,
+        RigidBodyDamping, RigidBodyForces, RigidBodyMassProps, RigidBodyMassPropsFlags,
+        RigidBodyPosition, RigidBodyType, RigidBodyVelocity,
     },
     render::RapierRenderPlugin,
 };
 
-use crate::Player;
+use crate::{
+    editor::AppMode,
+    settings::{AddSettings, SettingsTab},
+    stats::{DamageCause, PlayerDiedEvent, Stamina},
+    terrain, Player,
+};
+
+use self::{
+    footprints::FootprintConfig,
+    glider::GliderConfig,
+    grapple::{Grapple, GrappleConfig},
+    ground::ground_distance,
+    landing::{CameraShake, LandingConfig},
+    modal::{ActionMode, ModalKey},
+    torch::TorchConfig,
+};
+
+pub use self::{
+    controller::ControllerMode,
+    glider::Gliding,
+    landing::{FallDamageEvent, LandingEvent},
+    torch::Torch,
+};
 
+mod controller;
+mod footprints;
+mod glider;
+mod grapple;
+mod ground;
+mod landing;
+mod modal;
 mod mouse;
+mod torch;
 
-struct PlayerEyes;
+// Where the eyes sit relative to the centre of the player's body
+const EYES_OFFSET: Vec3 = Vec3::Y;
+// half the height of the player's collider, from its centre down to its feet
+pub const BODY_HALF_HEIGHT: f32 = 2.0;
+// how far below the feet the ground still counts as underfoot, for bumpy ground
+const GROUND_CHECK_MARGIN: f32 = 0.3;
+const SPAWN_HEIGHT: f32 = 200.0;
+// how far to look for dry land when the player drowns
+const SHORE_SEARCH_RADIUS: f32 = 500.0;
+// movement is pushed into the rigid body at this rate, however fast frames are being drawn
+const MOVEMENT_TIMESTEP: f64 = 1.0 / 60.0;
+
+pub struct PlayerEyes;
 struct EyesEntity(Entity);
 pub struct PlayerPlugin;
 
+/// What the player is currently doing with their movement keys
+#[derive(Default)]
+pub struct MovementState {
+    pub sprinting: bool,
+    pub crouching: bool,
+    pub zooming: bool,
+    // where the keys held down this frame ask to move, applied on the next movement step
+    pub direction: Vec3,
+    // jump was pressed since the last movement step
+    jump_queued: bool,
+    // seconds since the ground was last underfoot
+    airborne_time: f32,
+    sprint_key: ModalKey,
+    crouch_key: ModalKey,
+    zoom_key: ModalKey,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+struct MovementStage;
+
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<MouseState>()
@@ -31,20 +97,57 @@ impl Plugin for PlayerPlugin {
                 gravity: Vector::y() * -50.0,
                 ..Default::default()
             })
-            .add_plugin(InspectorPlugin::<MovementConfig>::new())
+            .add_settings::<MovementConfig>(SettingsTab::Player, "Movement")
+            .add_settings::<GrappleConfig>(SettingsTab::Player, "Grapple")
+            .add_settings::<GliderConfig>(SettingsTab::Player, "Glider")
+            .add_settings::<LandingConfig>(SettingsTab::Player, "Landing")
+            .add_settings::<TorchConfig>(SettingsTab::Player, "Torch")
+            .add_settings::<FootprintConfig>(SettingsTab::Player, "Footprints")
+            .add_event::<LandingEvent>()
+            .add_event::<FallDamageEvent>()
             .add_plugin(RapierRenderPlugin)
             .add_startup_system(setup_player.system())
             .add_startup_system(mouse::initial_grab.system())
-            .add_system(player_move.system())
-            .add_system(player_look.system())
+            .add_startup_system(grapple::setup_rope.system())
+            .add_system(read_movement_keys.system())
+            .add_system(player_look.system().label("player::look"))
             .add_system(mouse::grab.system())
+            .add_system(grapple::fire.system())
+            .add_system(grapple::swing.system())
+            .add_system(grapple::draw_rope.system())
+            .add_system(glider::toggle.system())
+            .add_system(glider::land.system())
+            .add_system(glider::glide.system())
+            .add_system(landing::detect.system())
+            .add_system(landing::apply_effects.system())
+            .add_system(modal::crouch.system().label("player::crouch"))
+            .add_system(landing::shake_camera.system().after("player::crouch"))
+            .add_system(modal::zoom.system())
+            .add_system(footprints::leave.system())
+            .add_system(torch::attach.system())
+            .add_system(torch::toggle.system().label("player::torch"))
+            .add_system(
+                torch::flicker
+                    .system()
+                    .label("player::torch_flicker")
+                    .after("player::torch"),
+            )
             .add_system(config_change.system())
-            .add_startup_system(enable_physics_profiling.system());
+            .add_system(respawn.system())
+            .add_startup_system(enable_physics_profiling.system())
+            .add_stage_after(
+                CoreStage::Update,
+                MovementStage,
+                SystemStage::parallel()
+                    .with_run_criteria(FixedTimestep::step(MOVEMENT_TIMESTEP))
+                    .with_system(apply_movement.system())
+                    .with_system(controller::move_kinematic.system()),
+            );
     }
 }
 
-fn setup_player(mut commands: Commands) {
-    let start_height = 200.0;
+fn setup_player(mut commands: Commands, config: Res<MovementConfig>) {
+    let start_height = SPAWN_HEIGHT;
     let transform = Transform::from_xyz(20.0, start_height, 20.0).looking_at(Vec3::ZERO, Vec3::Y);
 
     let rigid_body = RigidBodyBundle {
@@ -62,13 +165,13 @@ fn setup_player(mut commands: Commands) {
             sleeping: false,
             ..Default::default()
         },
-        body_type: RigidBodyType::Dynamic,
+        body_type: controller::body_type(config.controller),
         ..RigidBodyBundle::default()
     };
 
     let collider = ColliderBundle {
         mass_properties: ColliderMassProps::Density(100.0),
-        shape: ColliderShape::cuboid(0.5, 2.0, 0.5),
+        shape: controller::collider_shape(config.controller),
         ..ColliderBundle::default()
     };
 
@@ -79,21 +182,23 @@ fn setup_player(mut commands: Commands) {
         .insert(RigidBodyPositionSync::Interpolated { prev_pos: None })
         .insert(transform)
         .insert(Player)
+        .insert(MovementState::default())
         .id();
 
     let eyes = commands
         .spawn_bundle(PerspectiveCameraBundle {
             perspective_projection: PerspectiveProjection {
-                far: 5000.0,
+                far: 10000.0,
                 ..Default::default()
             },
             transform: Transform {
-                translation: Vec3::new(0.0, 1.0, 0.0),
+                translation: EYES_OFFSET,
                 ..Default::default()
             },
             ..Default::default()
         })
         .insert(PlayerEyes)
+        .insert(CameraShake::default())
         .id();
 
     commands
@@ -102,75 +207,143 @@ fn setup_player(mut commands: Commands) {
         .push_children(&[eyes]);
 }
 
-/// Handles keyboard input and movement
-fn player_move(
-    time: Res<Time>,
+/// Reads which way the movement keys are asking to go, every frame
+fn read_movement_keys(
     keys: Res<Input<KeyCode>>,
     windows: Res<Windows>,
-    mut config: ResMut<MovementConfig>,
-    mut query: Query<(
-        &Player,
-        &mut RigidBodyVelocity,
-        &RigidBodyMassProps,
-        &EyesEntity,
-    )>,
-    player_eyes_query: Query<(&PlayerEyes, &Transform)>,
+    app_mode: Res<State<AppMode>>,
+    config: Res<MovementConfig>,
+    mut query: Query<
+        (
+            &Transform,
+            &mut MovementState,
+            Option<&Stamina>,
+            Option<&Gliding>,
+        ),
+        With<Player>,
+    >,
 ) {
-    let window = windows.get_primary().unwrap();
-    for (_player, mut velocity, mass_props, eyes_entity) in query.iter_mut() {
-        config.sim_to_render += time.delta_seconds();
-
-        let looking = player_eyes_query
-            .get_component::<Transform>(eyes_entity.0)
-            .expect("Failed to get Transform from Eyes");
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    for (facing, mut movement_state, stamina, gliding) in query.iter_mut() {
+        // The glider is steered by looking around, so ignore the movement keys, as does the
+        // player while the editor's camera has them
+        if gliding.is_some() || !window.cursor_locked() || *app_mode.current() != AppMode::Play {
+            movement_state.direction = Vec3::ZERO;
+            movement_state.sprinting = false;
+            movement_state.crouching = false;
+            continue;
+        }
 
+        // The body only ever turns about the vertical, so its facing is always level
         let mut desired_direction = Vec3::ZERO;
-        let local_z = looking.local_z();
+        let local_z = facing.local_z();
         let forward = -Vec3::new(local_z.x, 0., local_z.z);
         let right = Vec3::new(local_z.z, 0., -local_z.x);
 
         for key in keys.get_pressed() {
-            if window.cursor_locked() {
-                if validate_key(config.map.forward, key) {
-                    desired_direction += forward
-                }
-                if validate_key(config.map.backward, key) {
-                    desired_direction -= forward
-                }
-                if validate_key(config.map.left, key) {
-                    desired_direction -= right
-                }
-                if validate_key(config.map.right, key) {
-                    desired_direction += right
-                }
+            if validate_key(config.map.forward, key) {
+                desired_direction += forward
+            }
+            if validate_key(config.map.backward, key) {
+                desired_direction -= forward
+            }
+            if validate_key(config.map.left, key) {
+                desired_direction -= right
+            }
+            if validate_key(config.map.right, key) {
+                desired_direction += right
+            }
 
-                if !config.gravity {
-                    if validate_key(config.map.up, key) {
-                        desired_direction += Vec3::Y
-                    }
-                    if validate_key(config.map.down, key) {
-                        desired_direction -= Vec3::Y
-                    }
+            if !config.gravity {
+                if validate_key(config.map.up, key) {
+                    desired_direction += Vec3::Y
+                }
+                if validate_key(config.map.down, key) {
+                    desired_direction -= Vec3::Y
                 }
             }
         }
 
-        if config.sim_to_render < config.dt {
+        let sprint = movement_state
+            .sprint_key
+            .update(config.sprint_mode, &keys, config.map.sprint);
+        movement_state.crouching =
+            movement_state
+                .crouch_key
+                .update(config.crouch_mode, &keys, config.map.crouch);
+        movement_state.zooming =
+            movement_state
+                .zoom_key
+                .update(config.zoom_mode, &keys, config.map.zoom);
+
+        // Held until the next movement step, which might not come this frame
+        if config.gravity && config.map.jump.iter().any(|&k| keys.just_pressed(k)) {
+            movement_state.jump_queued = true;
+        }
+
+        movement_state.sprinting = desired_direction.length_squared() > 1E-6
+            && sprint
+            && !movement_state.crouching
+            && !stamina.map_or(false, |stamina| stamina.exhausted());
+        movement_state.direction = desired_direction;
+    }
+}
+
+/// Pushes the player towards the speed the movement keys ask for, once per movement step,
+/// and jumps if they're on the ground or only just stepped off it
+fn apply_movement(
+    config: Res<MovementConfig>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    mut query: Query<
+        (
+            Entity,
+            &RigidBodyPosition,
+            &mut RigidBodyVelocity,
+            &RigidBodyMassProps,
+            &mut MovementState,
+            Option<&Grapple>,
+            Option<&Gliding>,
+        ),
+        With<Player>,
+    >,
+) {
+    // the kinematic controller moves the body itself
+    if config.controller != ControllerMode::Dynamic {
+        return;
+    }
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    for (player, position, mut velocity, mass_props, mut movement_state, grapple, gliding) in
+        query.iter_mut()
+    {
+        let centre: Vec3 = position.position.translation.vector.into();
+        let grounded = ground_distance(
+            &query_pipeline,
+            &collider_set,
+            player,
+            centre,
+            BODY_HALF_HEIGHT + GROUND_CHECK_MARGIN,
+        )
+        .is_some();
+        let jump = movement_state.take_jump(&config, grounded);
+        if gliding.is_some() {
             continue;
         }
-        // Calculate the remaining simulation to render time after all
-        // simulation steps were taken
-        config.sim_to_render %= config.dt;
+
+        if jump {
+            let rise = config.jump_speed() - velocity.linvel.y.max(0.0);
+            if rise > 0.0 {
+                velocity.apply_impulse(mass_props, (Vec3::Y * rise * mass_props.mass()).into());
+            }
+        }
 
         let current_velocity: Vec3 = velocity.linvel.into();
         let current_ground_velocity = current_velocity * Vec3::new(1.0, 0.0, 1.0);
-
-        let desired_velocity = if desired_direction.length_squared() > 1E-6 {
-            desired_direction.normalize() * config.speed
-        } else {
-            // No input, damp the velocity so we dont keep gliding off into the distance
-            current_ground_velocity * 0.5
-        };
+        let desired_velocity =
+            movement_state.desired_velocity(&config, grapple.is_some(), current_ground_velocity);
 
         // Calculate impulse - the desired momentum change for the time period
         let delta_velocity = desired_velocity - current_ground_velocity;
@@ -181,28 +354,84 @@ fn player_move(
     }
 }
 
-/// Handles looking around if cursor is locked
+impl MovementState {
+    // Keeps count of how long the player's been off the ground, and takes the jump queued since
+    // the last movement step if they're on it or only just stepped off it
+    fn take_jump(&mut self, config: &MovementConfig, grounded: bool) -> bool {
+        let jump = std::mem::take(&mut self.jump_queued);
+        if grounded {
+            self.airborne_time = 0.0;
+        } else {
+            self.airborne_time += MOVEMENT_TIMESTEP as f32;
+        }
+        if jump && self.airborne_time <= config.coyote_time {
+            // No second jump off the same bit of ground before leaving it
+            self.airborne_time = f32::INFINITY;
+            true
+        } else {
+            false
+        }
+    }
+
+    // The velocity the movement keys ask for, going on from the current one along the ground
+    fn desired_velocity(
+        &self,
+        config: &MovementConfig,
+        grappling: bool,
+        current_ground_velocity: Vec3,
+    ) -> Vec3 {
+        if self.direction.length_squared() > 1E-6 {
+            let speed = if self.sprinting {
+                config.speed * config.sprint_multiplier
+            } else if self.crouching {
+                config.speed * config.crouch_multiplier
+            } else {
+                config.speed
+            };
+            self.direction.normalize() * speed
+        } else if grappling {
+            // Keep our momentum while swinging on the grapple
+            current_ground_velocity
+        } else {
+            // No input, damp the velocity so we dont keep gliding off into the distance
+            current_ground_velocity * 0.5
+        }
+    }
+}
+
+/// Looks around while the cursor is locked, turning the whole player body to face left and
+/// right and tilting only the eyes up and down
 fn player_look(
     config: Res<MovementConfig>,
     windows: Res<Windows>,
+    app_mode: Res<State<AppMode>>,
     mut state: ResMut<MouseState>,
     motion: Res<Events<MouseMotion>>,
-    mut query: Query<(&PlayerEyes, &mut Transform)>,
+    mut player_query: Query<(&EyesEntity, &mut RigidBodyPosition), With<Player>>,
+    mut eyes_query: Query<&mut Transform, With<PlayerEyes>>,
 ) {
-    let window = windows.get_primary().unwrap();
-    for (_camera, mut transform) in query.iter_mut() {
-        for ev in state.reader_motion.iter(&motion) {
-            let sensitivity = config.sensitivity / 10000.0; // to keep config in reasonable range
-            if window.cursor_locked() {
-                state.pitch -= (sensitivity * ev.delta.y * window.height()).to_radians();
-                state.yaw -= (sensitivity * ev.delta.x * window.width()).to_radians();
-            }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    for ev in state.reader_motion.iter(&motion) {
+        let sensitivity = config.sensitivity / 10000.0; // to keep config in reasonable range
+        if window.cursor_locked() && *app_mode.current() == AppMode::Play {
+            state.pitch -= (sensitivity * ev.delta.y * window.height()).to_radians();
+            state.yaw -= (sensitivity * ev.delta.x * window.width()).to_radians();
+        }
+    }
+    state.pitch = state.pitch.clamp(-1.54, 1.54);
 
-            state.pitch = state.pitch.clamp(-1.54, 1.54);
+    for (eyes_entity, mut position) in player_query.iter_mut() {
+        // The body can't be turned by collisions, so it's safe to set its facing directly.
+        // Set every frame so a teleport can't leave it facing the wrong way
+        let facing = UnitQuaternion::from_axis_angle(&Vector::y_axis(), state.yaw);
+        position.position.rotation = facing;
+        position.next_position.rotation = facing;
 
-            // Order is important to prevent unintended roll
-            transform.rotation = Quat::from_axis_angle(Vec3::Y, state.yaw)
-                * Quat::from_axis_angle(Vec3::X, state.pitch);
+        if let Ok(mut transform) = eyes_query.get_mut(eyes_entity.0) {
+            transform.rotation = Quat::from_axis_angle(Vec3::X, state.pitch);
         }
     }
 }
@@ -210,16 +439,71 @@ fn player_look(
 fn config_change(
     config: Res<MovementConfig>,
     mut rapier_config: ResMut<RapierConfiguration>,
-    mut player_query: Query<&mut RigidBodyForces, With<Player>>,
+    mut player_query: Query<
+        (&mut RigidBodyForces, &mut RigidBodyType, &mut ColliderShape),
+        With<Player>,
+    >,
 ) {
     if config.is_changed() {
-        let mut forces = player_query.iter_mut().next().unwrap();
-        forces.gravity_scale = if config.gravity { 1.0 } else { 0.0 };
+        for (mut forces, mut body_type, mut shape) in player_query.iter_mut() {
+            forces.gravity_scale = if config.gravity { 1.0 } else { 0.0 };
+            // only swapped when the controller itself changes, not with every other setting
+            let wanted = controller::body_type(config.controller);
+            if *body_type != wanted {
+                *body_type = wanted;
+                *shape = controller::collider_shape(config.controller);
+            }
+        }
 
         rapier_config.gravity = Vector::y() * config.gravity_strength;
     }
 }
 
+// Puts the player back at the spawn point after they die, or on the nearest shore if they drowned
+fn respawn(
+    mut events: EventReader<PlayerDiedEvent>,
+    terrain_config: Res<terrain::Config>,
+    mut player_query: Query<
+        (&Transform, &mut RigidBodyPosition, &mut RigidBodyVelocity),
+        With<Player>,
+    >,
+) {
+    let death = match events.iter().last() {
+        Some(death) => *death,
+        None => return,
+    };
+
+    for (transform, mut position, mut velocity) in player_query.iter_mut() {
+        let shore = if death.cause == DamageCause::Drowning {
+            terrain::query::nearest_land(
+                &terrain_config,
+                transform.translation.xz(),
+                SHORE_SEARCH_RADIUS,
+            )
+        } else {
+            None
+        };
+
+        let respawn_point = match shore {
+            Some(shore) => Vec3::new(
+                shore.x,
+                terrain::query::height_at(&terrain_config, shore) + 3.0,
+                shore.y,
+            ),
+            None => Vec3::Y * SPAWN_HEIGHT,
+        };
+        teleport(&mut position, &mut velocity, respawn_point);
+    }
+}
+
+/// Moves the player's rigid body to a new position, stopping it dead
+pub fn teleport(position: &mut RigidBodyPosition, velocity: &mut RigidBodyVelocity, to: Vec3) {
+    position.position = Isometry3::translation(to.x, to.y, to.z);
+    position.next_position = position.position;
+    velocity.linvel = Vector::zeros();
+    velocity.angvel = Vector::zeros();
+}
+
 fn enable_physics_profiling(mut pipeline: ResMut<PhysicsPipeline>) {
     pipeline.counters.enable()
 }
@@ -231,36 +515,81 @@ where
     codes.iter().any(|m| m == key)
 }
 
+/// Where the player is looking, built up from the mouse movement
 #[derive(Default)]
-struct MouseState {
+pub struct MouseState {
     reader_motion: ManualEventReader<MouseMotion>,
-    pitch: f32,
-    yaw: f32,
+    pub pitch: f32,
+    pub yaw: f32,
 }
 
-#[derive(Inspectable)]
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
 pub struct MovementConfig {
-    #[inspectable(min = 0.1, max = 10.0)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1, max = 10.0))]
     pub sensitivity: f32,
     pub speed: f32,
-    dt: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub sprint_multiplier: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub crouch_multiplier: f32,
+    // how many times narrower the view gets when zoomed in
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub zoom_factor: f32,
+    // how high a jump lifts the player, in metres
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 10.0))]
+    pub jump_height: f32,
+    // how long after walking off an edge the player can still jump, in seconds
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub coyote_time: f32,
+    // a box pushed around by the physics, or a capsule swept through the world by hand
+    pub controller: ControllerMode,
+    // the steepest slope, in degrees, the kinematic controller walks up and stands on
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 89.0))]
+    pub max_slope_degrees: f32,
+    // the highest ledge it steps straight up onto, rather than stopping against
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 2.0))]
+    pub step_offset: f32,
+    // how far down it reaches to stay on the ground going over bumps and down slopes
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 2.0))]
+    pub snap_distance: f32,
+    // whether sprint, crouch and zoom need their key held down or toggle with each press
+    pub sprint_mode: ActionMode,
+    pub crouch_mode: ActionMode,
+    pub zoom_mode: ActionMode,
     gravity: bool,
     gravity_strength: f32,
-    #[inspectable(ignore)]
-    sim_to_render: f32,
-    #[inspectable(ignore)]
+    #[cfg_attr(feature = "dev-tools", inspectable(ignore))]
     pub map: CamKeyMap,
 }
 
+impl MovementConfig {
+    // The speed that gravity slows to a stop after rising the jump height
+    fn jump_speed(&self) -> f32 {
+        (2.0 * -self.gravity_strength * self.jump_height)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
 impl Default for MovementConfig {
     fn default() -> Self {
         Self {
             sensitivity: 1.2,
             speed: 60.,
-            dt: 1.0 / 60.0,
+            sprint_multiplier: 1.8,
+            crouch_multiplier: 0.5,
+            zoom_factor: 3.0,
+            jump_height: 2.5,
+            coyote_time: 0.15,
+            controller: ControllerMode::Dynamic,
+            max_slope_degrees: 45.0,
+            step_offset: 0.5,
+            snap_distance: 0.5,
+            sprint_mode: ActionMode::Hold,
+            crouch_mode: ActionMode::Hold,
+            zoom_mode: ActionMode::Hold,
             gravity: true,
             gravity_strength: -50.0,
-            sim_to_render: 0.0,
             map: CamKeyMap::default(),
         }
     }
@@ -272,6 +601,15 @@ pub struct CamKeyMap {
     pub left: &'static [KeyCode],
     pub right: &'static [KeyCode],
     pub jump: &'static [KeyCode],
+    pub grapple: &'static [KeyCode],
+    pub reel_in: &'static [KeyCode],
+    pub glide: &'static [KeyCode],
+    pub sprint: &'static [KeyCode],
+    pub crouch: &'static [KeyCode],
+    pub zoom: &'static [KeyCode],
+    pub interact: &'static [KeyCode],
+    pub torch: &'static [KeyCode],
+    pub build: &'static [KeyCode],
     pub up: &'static [KeyCode],
     pub down: &'static [KeyCode],
 }
@@ -284,6 +622,15 @@ impl Default for CamKeyMap {
             left: &[KeyCode::A],
             right: &[KeyCode::D],
             jump: &[KeyCode::Space],
+            grapple: &[KeyCode::E],
+            reel_in: &[KeyCode::Q],
+            glide: &[KeyCode::G],
+            sprint: &[KeyCode::LControl],
+            crouch: &[KeyCode::C],
+            zoom: &[KeyCode::Z],
+            interact: &[KeyCode::F],
+            torch: &[KeyCode::T],
+            build: &[KeyCode::B],
             up: &[KeyCode::Space],
             down: &[KeyCode::LShift],
         }
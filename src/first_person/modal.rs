@@ -0,0 +1,100 @@
+use bevy::{
+    prelude::*,
+    render::camera::{Camera, CameraProjection, PerspectiveProjection},
+};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+
+use super::{landing::CameraShake, EyesEntity, MovementConfig, MovementState, EYES_OFFSET};
+
+// how far the eyes drop while crouching
+const CROUCH_DROP: f32 = 0.8;
+// how quickly the eyes and field of view ease into crouching and zooming
+const EASE_RATE: f32 = 10.0;
+
+/// Whether an action is on only while its key is held, or flips on and off with each press
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ActionMode {
+    Hold,
+    Toggle,
+}
+
+impl Default for ActionMode {
+    fn default() -> Self {
+        ActionMode::Hold
+    }
+}
+
+/// Whether a modal action like sprinting is switched on
+#[derive(Default)]
+pub struct ModalKey {
+    toggled: bool,
+}
+
+impl ModalKey {
+    pub fn update(&mut self, mode: ActionMode, keys: &Input<KeyCode>, codes: &[KeyCode]) -> bool {
+        match mode {
+            ActionMode::Hold => {
+                self.toggled = false;
+                codes.iter().any(|&k| keys.pressed(k))
+            }
+            ActionMode::Toggle => {
+                if codes.iter().any(|&k| keys.just_pressed(k)) {
+                    self.toggled = !self.toggled;
+                }
+                self.toggled
+            }
+        }
+    }
+}
+
+// Lowers the eyes while crouching, leaving the camera shake to jitter around them
+pub fn crouch(
+    time: Res<Time>,
+    player_query: Query<(&EyesEntity, &MovementState)>,
+    mut eyes_query: Query<(&mut CameraShake, &mut Transform)>,
+) {
+    for (eyes_entity, movement_state) in player_query.iter() {
+        if let Ok((mut shake, mut transform)) = eyes_query.get_mut(eyes_entity.0) {
+            let target = if movement_state.crouching {
+                EYES_OFFSET - Vec3::Y * CROUCH_DROP
+            } else {
+                EYES_OFFSET
+            };
+            let ease = (EASE_RATE * time.delta_seconds()).min(1.0);
+            shake.rest = shake.rest + (target - shake.rest) * ease;
+            if shake.trauma <= 0.0 {
+                transform.translation = shake.rest;
+            }
+        }
+    }
+}
+
+// Narrows the field of view while zoomed in
+pub fn zoom(
+    time: Res<Time>,
+    config: Res<MovementConfig>,
+    player_query: Query<(&EyesEntity, &MovementState)>,
+    mut eyes_query: Query<(&mut Camera, &mut PerspectiveProjection)>,
+    mut base_fov: Local<Option<f32>>,
+) {
+    for (eyes_entity, movement_state) in player_query.iter() {
+        if let Ok((mut camera, mut projection)) = eyes_query.get_mut(eyes_entity.0) {
+            let base = *base_fov.get_or_insert(projection.fov);
+            let target = if movement_state.zooming {
+                base / config.zoom_factor
+            } else {
+                base
+            };
+            if (projection.fov - target).abs() < 1E-4 {
+                continue;
+            }
+
+            let ease = (EASE_RATE * time.delta_seconds()).min(1.0);
+            projection.fov += (target - projection.fov) * ease;
+            // the camera only rebuilds its projection when the window changes, so do it here
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+    }
+}
@@ -2,11 +2,16 @@ use bevy::prelude::*;
 
 /// Grabs the cursor when game first starts
 pub fn initial_grab(mut windows: ResMut<Windows>) {
-    toggle_grab(windows.get_primary_mut().unwrap());
+    if let Some(window) = windows.get_primary_mut() {
+        toggle_grab(window);
+    }
 }
 
 pub fn grab(keys: Res<Input<KeyCode>>, mut windows: ResMut<Windows>) {
-    let window = windows.get_primary_mut().unwrap();
+    let window = match windows.get_primary_mut() {
+        Some(window) => window,
+        None => return,
+    };
     if keys.just_pressed(KeyCode::Escape) {
         toggle_grab(window);
     }
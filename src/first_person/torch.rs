@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+
+use super::{MovementConfig, PlayerEyes};
+
+// held a little ahead of and below the eyes, so it doesn't light the camera itself
+const TORCH_OFFSET: Vec3 = Vec3::new(0.3, -0.3, -0.5);
+
+/// The light carried by the player, a child of their eyes
+pub struct Torch {
+    pub lit: bool,
+    // how much the torch brightens the unlit terrain this frame, black when it's out
+    pub glow: Color,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct TorchConfig {
+    pub color: Color,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub intensity: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub range: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub flicker: f32,
+    // the terrain isn't lit by lights, so it's tinted by this much of the torch's colour instead
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub terrain_glow: f32,
+}
+
+impl Default for TorchConfig {
+    fn default() -> Self {
+        Self {
+            color: Color::rgb(1.0, 0.75, 0.45),
+            intensity: 300.0,
+            range: 25.0,
+            flicker: 0.15,
+            terrain_glow: 0.15,
+        }
+    }
+}
+
+// Gives the eyes a torch whenever they're spawned, starting out unlit
+pub fn attach(mut commands: Commands, eyes_query: Query<Entity, Added<PlayerEyes>>) {
+    for eyes in eyes_query.iter() {
+        commands.entity(eyes).with_children(|parent| {
+            parent
+                .spawn_bundle(LightBundle {
+                    light: Light {
+                        intensity: 0.0,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(TORCH_OFFSET),
+                    ..Default::default()
+                })
+                .insert(Torch {
+                    lit: false,
+                    glow: Color::BLACK,
+                });
+        });
+    }
+}
+
+pub fn toggle(
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<MovementConfig>,
+    mut torch_query: Query<&mut Torch>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() || !config.map.torch.iter().any(|&k| keys.just_pressed(k)) {
+        return;
+    }
+
+    for mut torch in torch_query.iter_mut() {
+        torch.lit = !torch.lit;
+    }
+}
+
+pub fn flicker(
+    time: Res<Time>,
+    config: Res<TorchConfig>,
+    mut torch_query: Query<(&mut Torch, &mut Light)>,
+) {
+    let t = time.seconds_since_startup() as f32;
+    // quicker and more uneven than a campfire, as it's swung about
+    let wave = ((t * 17.0).sin() + (t * 9.1).sin() * 0.6 + (t * 4.3).sin() * 0.3) / 1.9;
+    let strength = 1.0 + wave * config.flicker;
+
+    for (mut torch, mut light) in torch_query.iter_mut() {
+        light.color = config.color;
+        light.range = config.range;
+        if torch.lit {
+            light.intensity = config.intensity * strength;
+            torch.glow = config.color * (config.terrain_glow * strength);
+        } else {
+            light.intensity = 0.0;
+            torch.glow = Color::BLACK;
+        }
+    }
+}
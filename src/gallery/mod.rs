@@ -0,0 +1,210 @@
+use std::{env, fs, path::Path};
+
+use bevy::{
+    app::AppExit,
+    log::{info, warn},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::{RigidBodyPosition, RigidBodyVelocity};
+use color_eyre::Report;
+use image::{imageops, RgbaImage};
+
+use crate::{
+    error_log::ErrorLog,
+    first_person::{self, MouseState},
+    post_process::Screenshot,
+    terrain::{self, Chunk, SeenChunks},
+    Player,
+};
+
+const GALLERY_DIR: &str = "gallery";
+// every seed is pictured from the same spot above the spawn point, looking north and down
+const VIEW_HEIGHT: f32 = 150.0;
+const VIEW_PITCH: f32 = -0.45;
+// time for props, textures and the exposure to settle once every chunk has its mesh
+const SETTLE_TIME: f32 = 3.0;
+// chunks that fail to generate never get a mesh, so a seed is pictured anyway after this long
+const MAX_WAIT: f32 = 60.0;
+const THUMBNAIL_WIDTH: u32 = 480;
+
+/// The seeds to picture when launched with `--gallery <seeds>`, given as a comma separated
+/// list where each entry is a seed or an inclusive range, e.g. `--gallery 1-8,42`
+pub fn gallery_seeds() -> Option<Vec<u32>> {
+    let mut args = env::args().skip_while(|arg| arg != "--gallery");
+    args.next()?;
+    match args.next().and_then(|list| terrain::parse_seeds(&list)) {
+        Some(seeds) => Some(seeds),
+        None => {
+            eprintln!("--gallery needs a list of seeds to picture, e.g. --gallery 1-8,42");
+            None
+        }
+    }
+}
+
+/// Generates the spawn area of each seed in turn and screenshots it from a standard angle,
+/// then lays every screenshot out on a contact sheet for browsing what the seeds look like.
+/// Quits once the sheet is written.
+pub struct GalleryPlugin {
+    pub seeds: Vec<u32>,
+}
+
+impl Plugin for GalleryPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Gallery {
+            seeds: self.seeds.clone(),
+            ..Default::default()
+        })
+        .add_system(hold_view.system().before("player::look"))
+        .add_system(capture.system().before("post_process::screenshot"));
+    }
+}
+
+#[derive(Default)]
+struct Gallery {
+    seeds: Vec<u32>,
+    // index of the seed being pictured
+    current: usize,
+    // since the seed was switched to, and since its chunks were all generated
+    waited: f32,
+    settled: f32,
+    capture_requested: bool,
+    thumbnails: Vec<RgbaImage>,
+}
+
+// Keeps the player hovering over the spawn point, looking the same way for every seed
+fn hold_view(
+    config: Res<terrain::Config>,
+    mut mouse: ResMut<MouseState>,
+    mut player_query: Query<(&mut RigidBodyPosition, &mut RigidBodyVelocity), With<Player>>,
+) {
+    let ground = terrain::query::height_at(&config, Vec2::ZERO).max(config.sea_level());
+    for (mut position, mut velocity) in player_query.iter_mut() {
+        first_person::teleport(
+            &mut position,
+            &mut velocity,
+            Vec3::new(0.0, ground + VIEW_HEIGHT, 0.0),
+        );
+    }
+    mouse.yaw = 0.0;
+    mouse.pitch = VIEW_PITCH;
+}
+
+fn capture(
+    time: Res<Time>,
+    mut config: ResMut<terrain::Config>,
+    seen_chunks: Res<SeenChunks>,
+    mut gallery: ResMut<Gallery>,
+    mut screenshot: ResMut<Screenshot>,
+    mut errors: ResMut<ErrorLog>,
+    mut app_exit_events: EventWriter<AppExit>,
+    chunks_query: Query<&Chunk>,
+) {
+    let seed = match gallery.seeds.get(gallery.current) {
+        Some(&seed) => seed,
+        None => return,
+    };
+    // changing the seed rebuilds the terrain
+    if config.seed() != seed {
+        config.set_seed(seed);
+        gallery.waited = 0.0;
+        gallery.settled = 0.0;
+        return;
+    }
+
+    let delta = time.delta_seconds();
+    gallery.waited += delta;
+    let generated =
+        !seen_chunks.is_empty() && chunks_query.iter().all(|chunk| chunk.mesh().is_some());
+    if generated {
+        gallery.settled += delta;
+    }
+    if gallery.settled < SETTLE_TIME && gallery.waited < MAX_WAIT {
+        return;
+    }
+    if gallery.settled < SETTLE_TIME {
+        warn!(
+            "Picturing seed {} before all of its chunks were generated",
+            seed
+        );
+    }
+
+    if !gallery.capture_requested {
+        screenshot.request();
+        gallery.capture_requested = true;
+        return;
+    }
+    let image = match screenshot.take() {
+        Some(image) => image,
+        None => return,
+    };
+
+    let path = Path::new(GALLERY_DIR).join(format!("seed-{}.png", seed));
+    if let Err(error) = save_image(&image, &path) {
+        errors.report(format!("Failed to write {:?}: {}", path, error));
+    }
+    info!(
+        "Pictured seed {} ({}/{})",
+        seed,
+        gallery.current + 1,
+        gallery.seeds.len()
+    );
+    let thumbnail_height = image.height() * THUMBNAIL_WIDTH / image.width().max(1);
+    gallery.thumbnails.push(imageops::resize(
+        &image,
+        THUMBNAIL_WIDTH,
+        thumbnail_height,
+        imageops::FilterType::Triangle,
+    ));
+    gallery.current += 1;
+    gallery.capture_requested = false;
+
+    if gallery.current == gallery.seeds.len() {
+        if let Err(error) = write_contact_sheet(&gallery.seeds, &gallery.thumbnails) {
+            errors.report(format!("Failed to write the contact sheet: {}", error));
+        }
+        app_exit_events.send(AppExit);
+    }
+}
+
+// Lays the thumbnails out in a square grid in the order the seeds were given, with an index
+// alongside saying which seed is where
+fn write_contact_sheet(seeds: &[u32], thumbnails: &[RgbaImage]) -> Result<(), Report> {
+    let (width, height) = match thumbnails.first() {
+        Some(thumbnail) => thumbnail.dimensions(),
+        None => return Ok(()),
+    };
+    let columns = (thumbnails.len() as f32).sqrt().ceil() as u32;
+    let rows = (thumbnails.len() as u32 + columns - 1) / columns;
+
+    let mut sheet =
+        RgbaImage::from_pixel(columns * width, rows * height, image::Rgba([0, 0, 0, 255]));
+    let mut index = String::new();
+    for (i, (seed, thumbnail)) in seeds.iter().zip(thumbnails).enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        imageops::overlay(&mut sheet, thumbnail, column * width, row * height);
+        index.push_str(&format!(
+            "row {}, column {}: seed {}\n",
+            row + 1,
+            column + 1,
+            seed
+        ));
+    }
+
+    let path = Path::new(GALLERY_DIR).join("contact_sheet.png");
+    save_image(&sheet, &path)?;
+    fs::write(Path::new(GALLERY_DIR).join("contact_sheet.txt"), index)?;
+    info!(
+        "Wrote the contact sheet of {} seeds to {:?}",
+        seeds.len(),
+        path
+    );
+    Ok(())
+}
+
+fn save_image(image: &RgbaImage, path: &Path) -> Result<(), Report> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    image.save(path)?;
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::{fs, path::Path};
+
+use bevy::{
+    log::{info, warn},
+    prelude::*,
+};
+use color_eyre::Report;
+use futures_lite::future;
+
+use super::{GraphicsSettings, MsaaLevel};
+
+pub const GPU_REPORT_PATH: &str = "saves/gpu_report.txt";
+
+/// What the GPU bevy will pick can do, found before the renderer starts, and which of the
+/// graphics settings were turned down to suit it
+pub struct GpuReport {
+    pub adapter: Option<wgpu::AdapterInfo>,
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    pub decisions: Vec<String>,
+}
+
+impl GpuReport {
+    /// Asks the adapter for its features and limits, and turns off whatever in the settings
+    /// it couldn't manage rather than letting the renderer fail on it
+    pub fn detect(settings: &mut GraphicsSettings) -> GpuReport {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        let adapter = future::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: None,
+        }));
+        let mut report = match &adapter {
+            Some(adapter) => GpuReport {
+                adapter: Some(adapter.get_info()),
+                features: adapter.features(),
+                limits: adapter.limits(),
+                decisions: Vec::new(),
+            },
+            None => GpuReport {
+                adapter: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+                decisions: vec!["No adapter found, so nothing optional is supported".into()],
+            },
+        };
+        report.fall_back(settings);
+        report
+    }
+
+    fn fall_back(&mut self, settings: &mut GraphicsSettings) {
+        if settings.wireframe
+            && !self
+                .features
+                .contains(wgpu::Features::NON_FILL_POLYGON_MODE)
+        {
+            settings.wireframe = false;
+            self.decisions
+                .push("Wireframes turned off: no NonFillPolygonMode support".into());
+        }
+
+        let software = self
+            .adapter
+            .as_ref()
+            .map_or(false, |info| info.device_type == wgpu::DeviceType::Cpu);
+        // only one and four samples are guaranteed to work with every texture format
+        let msaa = match settings.msaa {
+            _ if software => MsaaLevel::Off,
+            MsaaLevel::X2 | MsaaLevel::X8 => MsaaLevel::X4,
+            msaa => msaa,
+        };
+        if msaa != settings.msaa {
+            self.decisions.push(format!(
+                "MSAA lowered from {:?} to {:?}{}",
+                settings.msaa,
+                msaa,
+                if software {
+                    ": software renderer"
+                } else {
+                    ": only 4 samples are guaranteed"
+                }
+            ));
+            settings.msaa = msaa;
+        }
+    }
+
+    /// Writes the report out next to the saves, for attaching to bug reports
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+}
+
+// The report is made before bevy's logging is set up, so it's logged once the app starts
+pub fn log_report(report: Res<GpuReport>) {
+    for line in report.to_string().lines() {
+        info!("{}", line);
+    }
+    if let Err(error) = report.save(GPU_REPORT_PATH) {
+        warn!(
+            "Failed to write GPU report {:?}: {}",
+            GPU_REPORT_PATH, error
+        );
+    }
+}
+
+impl std::fmt::Display for GpuReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.adapter {
+            Some(info) => writeln!(
+                f,
+                "GPU: {} ({:?}, {:?}, vendor {:#x}, device {:#x})",
+                info.name, info.device_type, info.backend, info.vendor, info.device
+            )?,
+            None => writeln!(f, "GPU: none found")?,
+        }
+        writeln!(f, "Features: {:?}", self.features)?;
+        writeln!(f, "Limits: {:?}", self.limits)?;
+        if self.decisions.is_empty() {
+            writeln!(f, "Everything asked for is supported")?;
+        }
+        for decision in self.decisions.iter() {
+            writeln!(f, "{}", decision)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use std::{fs, path::Path};
+
+use bevy::{
+    log::warn,
+    prelude::*,
+    wgpu::{WgpuFeature, WgpuFeatures},
+};
+use bevy_egui::egui;
+use color_eyre::Report;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error_log::ErrorLog,
+    locale::Locale,
+    settings::{AddSettings, PlayerSettings, SettingsTab},
+};
+
+pub use self::caps::GpuReport;
+pub use self::quality::QualityPreset;
+
+mod caps;
+mod quality;
+
+pub const GRAPHICS_PATH: &str = "saves/graphics.ron";
+
+/// Saves the graphics settings when they're changed in the settings window, ready for the
+/// next launch, and applies the quality preset straight away
+pub struct GraphicsPlugin;
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_player_settings::<GraphicsSettings>(SettingsTab::Graphics, "Graphics")
+            .add_system(quality::apply_preset.system())
+            .add_startup_system(caps::log_report.system())
+            .add_system_to_stage(CoreStage::Last, write_on_change.system());
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum MsaaLevel {
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+impl MsaaLevel {
+    const ALL: [MsaaLevel; 4] = [MsaaLevel::Off, MsaaLevel::X2, MsaaLevel::X4, MsaaLevel::X8];
+
+    pub fn samples(&self) -> u32 {
+        match self {
+            MsaaLevel::Off => 1,
+            MsaaLevel::X2 => 2,
+            MsaaLevel::X4 => 4,
+            MsaaLevel::X8 => 8,
+        }
+    }
+}
+
+/// Graphics options kept between launches. MSAA and wireframes can only be applied when the
+/// window and GPU are first set up; the render graph is built around the sample count, so
+/// changes to them wait for a restart.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GraphicsSettings {
+    // applied live, setting the MSAA level for the next launch along with everything else
+    #[serde(default)]
+    pub quality: QualityPreset,
+    pub msaa: MsaaLevel,
+    // wireframe rendering needs the NonFillPolygonMode feature, which not every GPU has
+    pub wireframe: bool,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            quality: QualityPreset::High,
+            msaa: MsaaLevel::X4,
+            wireframe: true,
+        }
+    }
+}
+
+impl PlayerSettings for GraphicsSettings {
+    fn ui(&mut self, ui: &mut egui::Ui, locale: &Locale) {
+        ui.label(locale.text("settings-quality"));
+        ui.horizontal(|ui| {
+            for &preset in QualityPreset::ALL.iter() {
+                ui.selectable_value(&mut self.quality, preset, locale.text(preset.message_id()));
+            }
+        });
+
+        ui.label(locale.text("settings-msaa"));
+        ui.horizontal(|ui| {
+            for &msaa in MsaaLevel::ALL.iter() {
+                let label = match msaa {
+                    MsaaLevel::Off => locale.text("settings-msaa-off"),
+                    _ => format!("{}x", msaa.samples()),
+                };
+                ui.selectable_value(&mut self.msaa, msaa, label);
+            }
+        });
+        ui.checkbox(&mut self.wireframe, locale.text("settings-wireframe"));
+    }
+}
+
+impl GraphicsSettings {
+    pub fn load(path: impl AsRef<Path>) -> Result<GraphicsSettings, Report> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    pub fn load_or_default(path: impl AsRef<Path>) -> GraphicsSettings {
+        let path = path.as_ref();
+        if !path.exists() {
+            return GraphicsSettings::default();
+        }
+
+        GraphicsSettings::load(path).unwrap_or_else(|error| {
+            warn!("Failed to load graphics settings {:?}: {}", path, error);
+            GraphicsSettings::default()
+        })
+    }
+
+    /// The optional wgpu features asked for, which the GPU report has already turned off
+    /// if the GPU can't provide them
+    pub fn features(&self) -> WgpuFeatures {
+        let mut features = Vec::new();
+        if self.wireframe {
+            features.push(WgpuFeature::NonFillPolygonMode);
+        }
+        WgpuFeatures { features }
+    }
+
+    pub fn wireframe_supported(&self, features: &WgpuFeatures) -> bool {
+        features
+            .features
+            .iter()
+            .any(|feature| matches!(feature, WgpuFeature::NonFillPolygonMode))
+    }
+}
+
+fn write_on_change(settings: Res<GraphicsSettings>, mut errors: ResMut<ErrorLog>) {
+    if settings.is_changed() && !settings.is_added() {
+        if let Err(error) = settings.save(GRAPHICS_PATH) {
+            errors.report(format!("Failed to write graphics settings: {}", error));
+        }
+    }
+}
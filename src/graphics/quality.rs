@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{GraphicsSettings, MsaaLevel};
+use crate::{
+    particles::{AmbientParticlesConfig, WaterLifeConfig},
+    scatter::ScatterConfig,
+    terrain,
+};
+
+/// One selector for everything that trades looks for frame rate, picked from the settings
+/// window and applied to the other plugins' configs as soon as it changes
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl Default for QualityPreset {
+    fn default() -> Self {
+        QualityPreset::High
+    }
+}
+
+impl QualityPreset {
+    pub const ALL: [QualityPreset; 4] = [
+        QualityPreset::Low,
+        QualityPreset::Medium,
+        QualityPreset::High,
+        QualityPreset::Ultra,
+    ];
+
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            QualityPreset::Low => "quality-low",
+            QualityPreset::Medium => "quality-medium",
+            QualityPreset::High => "quality-high",
+            QualityPreset::Ultra => "quality-ultra",
+        }
+    }
+}
+
+struct QualityLevels {
+    view_distance: f32,
+    // how many of its own widths away a terrain node splits into smaller ones
+    lod_split_distance: f32,
+    bake_shadows: bool,
+    shadow_bake_distance: f32,
+    msaa: MsaaLevel,
+    // trees and rocks per chunk, relative to their defaults
+    prop_density: f32,
+    prop_view_distance: f32,
+    // fish, bubbles and ambient particles, relative to their defaults
+    water_life: f32,
+    ambient_particles: f32,
+}
+
+impl QualityPreset {
+    fn levels(&self) -> QualityLevels {
+        match self {
+            QualityPreset::Low => QualityLevels {
+                view_distance: 3000.0,
+                lod_split_distance: 1.5,
+                bake_shadows: false,
+                shadow_bake_distance: 700.0,
+                msaa: MsaaLevel::Off,
+                prop_density: 0.4,
+                prop_view_distance: 0.5,
+                water_life: 0.0,
+                ambient_particles: 0.3,
+            },
+            QualityPreset::Medium => QualityLevels {
+                view_distance: 4500.0,
+                lod_split_distance: 1.75,
+                bake_shadows: true,
+                shadow_bake_distance: 500.0,
+                msaa: MsaaLevel::X2,
+                prop_density: 0.7,
+                prop_view_distance: 0.75,
+                water_life: 0.5,
+                ambient_particles: 0.6,
+            },
+            // the plugins' own defaults
+            QualityPreset::High => QualityLevels {
+                view_distance: 6000.0,
+                lod_split_distance: 2.0,
+                bake_shadows: true,
+                shadow_bake_distance: 700.0,
+                msaa: MsaaLevel::X4,
+                prop_density: 1.0,
+                prop_view_distance: 1.0,
+                water_life: 1.0,
+                ambient_particles: 1.0,
+            },
+            QualityPreset::Ultra => QualityLevels {
+                view_distance: 9000.0,
+                lod_split_distance: 2.5,
+                bake_shadows: true,
+                shadow_bake_distance: 1000.0,
+                // the most samples every GPU is guaranteed to support
+                msaa: MsaaLevel::X4,
+                prop_density: 1.5,
+                prop_view_distance: 1.5,
+                water_life: 1.5,
+                ambient_particles: 1.5,
+            },
+        }
+    }
+}
+
+// Pushes the chosen preset into the configs it covers. Changing the terrain config rebuilds
+// the chunks, and the props and water life with them, while MSAA waits for a restart.
+pub fn apply_preset(
+    mut applied: Local<Option<QualityPreset>>,
+    mut settings: ResMut<GraphicsSettings>,
+    mut terrain_config: ResMut<terrain::Config>,
+    mut scatter_config: ResMut<ScatterConfig>,
+    mut water_life_config: ResMut<WaterLifeConfig>,
+    mut ambient_config: ResMut<AmbientParticlesConfig>,
+) {
+    let preset = settings.quality;
+    if *applied == Some(preset) {
+        return;
+    }
+    let levels = preset.levels();
+
+    terrain_config.set_quality(
+        levels.view_distance,
+        levels.lod_split_distance,
+        levels.bake_shadows,
+        levels.shadow_bake_distance,
+    );
+
+    let scatter_defaults = ScatterConfig::default();
+    let scatter_config = &mut *scatter_config;
+    for (prop, default) in [
+        (&mut scatter_config.trees, scatter_defaults.trees),
+        (&mut scatter_config.rocks, scatter_defaults.rocks),
+    ]
+    .iter_mut()
+    {
+        prop.per_chunk = (default.per_chunk as f32 * levels.prop_density).round() as usize;
+        prop.view_distance = default.view_distance * levels.prop_view_distance;
+    }
+
+    let water_life_defaults = WaterLifeConfig::default();
+    water_life_config.max_fish =
+        (water_life_defaults.max_fish as f32 * levels.water_life).round() as usize;
+    water_life_config.max_bubbles =
+        (water_life_defaults.max_bubbles as f32 * levels.water_life).round() as usize;
+    ambient_config.rate = AmbientParticlesConfig::default().rate * levels.ambient_particles;
+
+    // the sample count saved from the last launch stands until the preset is changed
+    if applied.is_some() {
+        settings.msaa = levels.msaa;
+    }
+    info!("Applied the {:?} graphics quality preset", preset);
+    *applied = Some(preset);
+}
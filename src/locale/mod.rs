@@ -0,0 +1,144 @@
+use std::{fs, path::Path};
+
+use bevy::{log::warn, prelude::*};
+use bevy_egui::egui;
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+use crate::settings::{AddSettings, PlayerSettings, SettingsTab};
+
+const LOCALE_DIR: &str = "assets/locales";
+
+/// Loads the user-facing strings for the language chosen in the settings window, so UI code
+/// asks for a message id rather than writing out English
+pub struct LocalePlugin;
+
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Locale::load(Language::English))
+            .add_player_settings::<LocaleConfig>(SettingsTab::Graphics, "Language")
+            .add_system(switch_language.system());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Language {
+    English,
+    German,
+}
+
+impl Language {
+    const ALL: [Language; 2] = [Language::English, Language::German];
+
+    // each language is listed in its own words, for finding it without reading the current one
+    fn native_name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+
+    fn id(&self) -> &'static str {
+        match self {
+            Language::English => "en-US",
+            Language::German => "de",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct LocaleConfig {
+    pub language: Language,
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        Self {
+            language: Language::English,
+        }
+    }
+}
+
+impl PlayerSettings for LocaleConfig {
+    fn ui(&mut self, ui: &mut egui::Ui, _: &Locale) {
+        for &language in Language::ALL.iter() {
+            ui.radio_value(&mut self.language, language, language.native_name());
+        }
+    }
+}
+
+pub struct Locale {
+    language: Language,
+    bundle: FluentBundle<FluentResource>,
+    // English, for whatever the chosen language hasn't translated yet
+    fallback: FluentBundle<FluentResource>,
+}
+
+impl Locale {
+    fn load(language: Language) -> Locale {
+        Locale {
+            language,
+            bundle: load_bundle(language),
+            fallback: load_bundle(Language::English),
+        }
+    }
+
+    /// The message with the given id, if either the chosen language or English has it
+    pub fn get(&self, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+        [&self.bundle, &self.fallback].iter().find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+            for error in errors {
+                warn!("Problem formatting message {}: {:?}", id, error);
+            }
+            Some(text.into_owned())
+        })
+    }
+
+    /// A message without arguments, or its id when no language has it
+    pub fn text(&self, id: &str) -> String {
+        self.get(id, None).unwrap_or_else(|| id.to_string())
+    }
+
+    /// A message filled in with named arguments, or its id when no language has it
+    pub fn format(&self, id: &str, args: &[(&str, FluentValue)]) -> String {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args.iter() {
+            fluent_args.set(*name, value.clone());
+        }
+        self.get(id, Some(&fluent_args))
+            .unwrap_or_else(|| id.to_string())
+    }
+}
+
+fn load_bundle(language: Language) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = language.id().parse().unwrap();
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // egui's font draws the unicode isolation marks around arguments as boxes
+    bundle.set_use_isolating(false);
+
+    let path = Path::new(LOCALE_DIR).join(format!("{}.ftl", language.id()));
+    let source = match fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(error) => {
+            warn!("Failed to read strings {:?}: {}", path, error);
+            return bundle;
+        }
+    };
+    // a resource with syntax errors still has every message that parsed
+    let resource = FluentResource::try_new(source).unwrap_or_else(|(resource, errors)| {
+        warn!("Problems parsing strings {:?}: {:?}", path, errors);
+        resource
+    });
+    if let Err(errors) = bundle.add_resource(resource) {
+        warn!("Problems adding strings {:?}: {:?}", path, errors);
+    }
+    bundle
+}
+
+fn switch_language(config: Res<LocaleConfig>, mut locale: ResMut<Locale>) {
+    if locale.language != config.language {
+        *locale = Locale::load(config.language);
+    }
+}
@@ -5,9 +5,8 @@ use bevy::{
     prelude::*,
     reflect::TypeUuid,
     render::{renderer::RenderResources, wireframe::WireframePlugin},
-    wgpu::{WgpuFeature, WgpuFeatures, WgpuOptions},
+    wgpu::WgpuOptions,
 };
-use bevy_inspector_egui::{widgets::ResourceInspector, Inspectable, InspectorPlugin};
 use bevy_rapier3d::{
     physics::{
         ColliderBundle, ColliderPositionSync, NoUserData, RapierPhysicsPlugin, RigidBodyBundle,
@@ -17,53 +16,170 @@ use bevy_rapier3d::{
 };
 use color_eyre::Report;
 
+use crate::bake::BakePlugin;
+use crate::birds::BirdsPlugin;
+use crate::build::BuildPlugin;
+use crate::campfire::CampfirePlugin;
+use crate::collectibles::CollectiblesPlugin;
+use crate::decals::DecalsPlugin;
+use crate::editor::EditorPlugin;
+use crate::error_log::ErrorLogPlugin;
 use crate::first_person::PlayerPlugin;
+use crate::gallery::GalleryPlugin;
+use crate::graphics::{GpuReport, GraphicsPlugin, GraphicsSettings, GRAPHICS_PATH};
+use crate::locale::LocalePlugin;
+use crate::map_window::MapWindowPlugin;
+use crate::metrics::MetricsPlugin;
+use crate::npc::NpcPlugin;
+use crate::particles::ParticlesPlugin;
+use crate::post_process::PostProcessPlugin;
+use crate::replay::ReplayPlugin;
+use crate::save::SavePlugin;
+use crate::scatter::ScatterPlugin;
+use crate::settings::{AddSettings, SettingsPlugin, SettingsTab};
+use crate::sky::SkyPlugin;
+use crate::soak::SoakPlugin;
+use crate::stats::StatsPlugin;
+use crate::terraform::TerraformPlugin;
 use crate::terrain::Terrain;
-
+use crate::timescale::{Timescale, TimescalePlugin};
+use crate::triggers::TriggersPlugin;
+use crate::weather::WeatherPlugin;
+
+mod bake;
+mod birds;
+mod build;
+mod campfire;
+mod collectibles;
+mod decals;
+mod editor;
+mod error_log;
 mod first_person;
+mod gallery;
+mod graphics;
+mod locale;
+mod map_window;
+mod metrics;
+mod npc;
+mod particles;
+mod post_process;
+mod profiling;
+mod replay;
+mod save;
+mod scatter;
+mod settings;
+mod sky;
+mod soak;
+mod stats;
+mod terraform;
 mod terrain;
+mod timescale;
+mod triggers;
+mod weather;
 
 fn main() -> Result<(), Report> {
     init()?;
 
-    App::build()
-        .insert_resource(WindowDescriptor {
-            title: "Josh's World".to_string(),
-            width: 2000.,
-            height: 1200.,
-            vsync: false,
-            ..Default::default()
-        })
-        .insert_resource(Msaa { samples: 4 })
-        .insert_resource(WgpuOptions {
-            features: WgpuFeatures {
-                features: vec![WgpuFeature::NonFillPolygonMode], // Wireframe rendering for debugging requires NonFillPolygonMode feature
-            },
-            ..Default::default()
-        })
-        // .add_plugin(NoCameraPlayerPlugin)
-        .add_plugins(DefaultPlugins)
-        .add_plugin(InspectorPlugin::<Config>::new())
-        .add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .add_plugin(EntityCountDiagnosticsPlugin::default())
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        // .add_plugin(WgpuResourceDiagnosticsPlugin::default())
-        .add_plugin(LogDiagnosticsPlugin::default())
-        .add_plugin(Terrain)
-        .add_plugin(PlayerPlugin)
-        .add_plugin(WireframePlugin)
-        .add_startup_system(setup.system())
-        .add_system(increase_shaders_time.system())
-        .add_stage_after(
-            CoreStage::Update,
-            SlowUpdateStage,
-            SystemStage::parallel()
-                .with_run_criteria(FixedTimestep::step(2.0))
-                .with_system(debug_player_position.system()),
-        )
-        .add_plugin(RapierRenderPlugin)
-        // .add_startup_system(test.system())
-        .run();
+    // the report only samples the noise, so it's written without opening a window
+    if let Some(seeds) = terrain::analysis::analysis_seeds() {
+        return terrain::analysis::write_reports(&seeds);
+    }
+    if let Some(export) = terrain::export::HeightmapExport::requested() {
+        return export.write();
+    }
+    if let Some(radius) = bake::bake_radius() {
+        return bake::bake(radius);
+    }
+
+    let mut graphics = GraphicsSettings::load_or_default(GRAPHICS_PATH);
+    // turn off whatever the GPU can't do before bevy asks it for them
+    let gpu = GpuReport::detect(&mut graphics);
+    let features = graphics.features();
+    let wireframe = graphics.wireframe_supported(&features);
+    let trace = profiling::trace_path();
+    let soak = soak::soak_minutes();
+    let gallery = gallery::gallery_seeds();
+    let map_window = map_window::map_window_requested();
+
+    let mut app = App::build();
+    app.insert_resource(WindowDescriptor {
+        title: "Josh's World".to_string(),
+        width: 2000.,
+        height: 1200.,
+        vsync: false,
+        ..Default::default()
+    })
+    .insert_resource(Msaa {
+        samples: graphics.msaa.samples(),
+    })
+    .insert_resource(WgpuOptions {
+        features,
+        ..Default::default()
+    })
+    .insert_resource(graphics)
+    .insert_resource(gpu)
+    // .add_plugin(NoCameraPlayerPlugin)
+    .add_plugins_with(DefaultPlugins, |group| {
+        profiling::trace_to_file(post_process::reroute_main_pass(group), trace)
+    })
+    .add_plugin(SettingsPlugin)
+    .add_plugin(LocalePlugin)
+    .add_plugin(ErrorLogPlugin)
+    .add_plugin(GraphicsPlugin)
+    .add_settings::<ClearColor>(SettingsTab::Graphics, "Clear colour")
+    .add_plugin(FrameTimeDiagnosticsPlugin::default())
+    .add_plugin(EntityCountDiagnosticsPlugin::default())
+    .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+    // .add_plugin(WgpuResourceDiagnosticsPlugin::default())
+    .add_plugin(LogDiagnosticsPlugin::default())
+    .add_plugin(BakePlugin)
+    .add_plugin(Terrain)
+    .add_plugin(PlayerPlugin)
+    .add_plugin(WeatherPlugin)
+    .add_plugin(ParticlesPlugin)
+    .add_plugin(DecalsPlugin)
+    .add_plugin(StatsPlugin)
+    .add_plugin(MetricsPlugin)
+    .add_plugin(SavePlugin)
+    .add_plugin(ReplayPlugin)
+    .add_plugin(CollectiblesPlugin)
+    .add_plugin(ScatterPlugin)
+    .add_plugin(NpcPlugin)
+    .add_plugin(BirdsPlugin)
+    .add_plugin(CampfirePlugin)
+    .add_plugin(BuildPlugin)
+    .add_plugin(TerraformPlugin)
+    .add_plugin(EditorPlugin)
+    .add_plugin(TriggersPlugin)
+    .add_plugin(TimescalePlugin)
+    .add_plugin(SkyPlugin)
+    .add_plugin(PostProcessPlugin)
+    .add_startup_system(setup.system())
+    .add_system(increase_shaders_time.system().after("timescale::clock"))
+    .add_stage_after(
+        CoreStage::Update,
+        SlowUpdateStage,
+        SystemStage::parallel()
+            .with_run_criteria(FixedTimestep::step(2.0))
+            .with_system(debug_player_position.system()),
+    )
+    .add_plugin(RapierRenderPlugin);
+    // .add_startup_system(test.system())
+
+    // Wireframe rendering for debugging requires the NonFillPolygonMode feature
+    if wireframe {
+        app.add_plugin(WireframePlugin);
+    }
+    if let Some(minutes) = soak {
+        app.add_plugin(SoakPlugin { minutes });
+    }
+    if let Some(seeds) = gallery {
+        app.add_plugin(GalleryPlugin { seeds });
+    }
+    if map_window {
+        app.add_plugin(MapWindowPlugin);
+    }
+    app.run();
     Ok(())
 }
 
@@ -76,21 +192,16 @@ fn init() -> Result<(), Report> {
     Ok(())
 }
 
-#[derive(Inspectable, Default)]
-struct Config {
-    clear_color: ResourceInspector<ClearColor>,
-}
-
 fn setup(mut commands: Commands) {
     commands.insert_resource(ClearColor(Color::rgb_u8(190, 246, 255)));
 }
 
-/// In this system we query for the `TimeComponent` and global `Time` resource, and set
-/// `time.seconds_since_startup()` as the `value` of the `TimeComponent`. This value will be
+/// In this system we query for the `TimeComponent` and the `Timescale` resource, and set
+/// `timescale.seconds_since_startup()` as the `value` of the `TimeComponent`. This value will be
 /// accessed by the fragment shader and used to animate the shader.
-fn increase_shaders_time(time: Res<Time>, mut query: Query<&mut TimeUniform>) {
+fn increase_shaders_time(timescale: Res<Timescale>, mut query: Query<&mut TimeUniform>) {
     for mut time_uniform in query.iter_mut() {
-        time_uniform.value = time.seconds_since_startup() as f32;
+        time_uniform.value = timescale.seconds_since_startup() as f32;
     }
 }
 
@@ -133,8 +244,8 @@ fn physics_test(mut commands: Commands) {
 
 #[derive(RenderResources, Default, TypeUuid)]
 #[uuid = "463e4b8a-d555-4fc2-ba9f-4c880063ba92"]
-struct TimeUniform {
-    value: f32,
+pub struct TimeUniform {
+    pub value: f32,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
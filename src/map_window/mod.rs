@@ -0,0 +1,215 @@
+use std::{env, f32::consts::FRAC_PI_2};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, Camera, OrthographicProjection},
+        pass::{
+            LoadOp, Operations, PassDescriptor, RenderPassDepthStencilAttachmentDescriptor,
+            TextureAttachment,
+        },
+        render_graph::{
+            base::MainPass, CameraNode, PassNode, RenderGraph, WindowSwapChainNode,
+            WindowTextureNode,
+        },
+        texture::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsage},
+    },
+    window::{CreateWindow, WindowId},
+};
+
+use crate::{
+    terrain::{self, CHUNK_SIZE},
+    Player,
+};
+
+const MAP_CAMERA: &str = "MapView";
+mod node {
+    pub const SWAP_CHAIN: &str = "map_window_swap_chain";
+    pub const DEPTH_TEXTURE: &str = "map_window_depth_texture";
+    pub const SAMPLED_COLOR_ATTACHMENT: &str = "map_window_sampled_color_attachment";
+    pub const CAMERA: &str = "map_window_camera";
+    pub const PASS: &str = "map_window_pass";
+}
+
+// high enough above the player to be over any mountain, with the far plane past the sea floor
+const CAMERA_HEIGHT: f32 = 2000.0;
+const FAR: f32 = 4000.0;
+
+/// Whether to open the map window, when launched with `--map-window`
+pub fn map_window_requested() -> bool {
+    env::args().any(|arg| arg == "--map-window")
+}
+
+/// Opens a second window looking straight down on the chunks loaded around the player, for
+/// watching them stream in and out on another monitor while flying in the main window.
+/// Closing either window quits.
+pub struct MapWindowPlugin;
+
+impl Plugin for MapWindowPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(MapWindow {
+            id: WindowId::new(),
+            ready: false,
+        })
+        .add_startup_system(open_window.system())
+        .add_system(setup_pipeline.system())
+        .add_system(follow_player.system());
+    }
+}
+
+struct MapWindow {
+    id: WindowId,
+    // the window only exists once winit has handled the event asking for it, so its render
+    // pass is set up a few frames in
+    ready: bool,
+}
+
+fn open_window(map_window: Res<MapWindow>, mut create_window_events: EventWriter<CreateWindow>) {
+    create_window_events.send(CreateWindow {
+        id: map_window.id,
+        descriptor: WindowDescriptor {
+            title: "Josh's World - Map".to_string(),
+            width: 800.,
+            height: 800.,
+            vsync: false,
+            ..Default::default()
+        },
+    });
+}
+
+// Draws the main pass a second time into the map window, from the map camera
+fn setup_pipeline(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    msaa: Res<Msaa>,
+    clear_color: Res<ClearColor>,
+    mut map_window: ResMut<MapWindow>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut graph: ResMut<RenderGraph>,
+) {
+    if map_window.ready || windows.get(map_window.id).is_none() {
+        return;
+    }
+    map_window.ready = true;
+    let window_id = map_window.id;
+
+    graph.add_node(node::SWAP_CHAIN, WindowSwapChainNode::new(window_id));
+    graph.add_node(
+        node::DEPTH_TEXTURE,
+        WindowTextureNode::new(
+            window_id,
+            TextureDescriptor {
+                format: TextureFormat::Depth32Float,
+                usage: TextureUsage::OUTPUT_ATTACHMENT,
+                sample_count: msaa.samples,
+                ..Default::default()
+            },
+        ),
+    );
+    graph.add_system_node(node::CAMERA, CameraNode::new(MAP_CAMERA));
+
+    let mut pass = PassNode::<&MainPass>::new(PassDescriptor {
+        color_attachments: vec![msaa.color_attachment_descriptor(
+            TextureAttachment::Input("color_attachment".to_string()),
+            TextureAttachment::Input("color_resolve_target".to_string()),
+            Operations {
+                load: LoadOp::Clear(clear_color.0),
+                store: true,
+            },
+        )],
+        depth_stencil_attachment: Some(RenderPassDepthStencilAttachmentDescriptor {
+            attachment: TextureAttachment::Input("depth".to_string()),
+            depth_ops: Some(Operations {
+                load: LoadOp::Clear(1.0),
+                store: true,
+            }),
+            stencil_ops: None,
+        }),
+        sample_count: msaa.samples,
+    });
+    pass.add_camera(MAP_CAMERA);
+    active_cameras.add(MAP_CAMERA);
+    graph.add_node(node::PASS, pass);
+
+    graph
+        .add_slot_edge(
+            node::SWAP_CHAIN,
+            WindowSwapChainNode::OUT_TEXTURE,
+            node::PASS,
+            if msaa.samples > 1 {
+                "color_resolve_target"
+            } else {
+                "color_attachment"
+            },
+        )
+        .unwrap();
+    graph
+        .add_slot_edge(
+            node::DEPTH_TEXTURE,
+            WindowTextureNode::OUT_TEXTURE,
+            node::PASS,
+            "depth",
+        )
+        .unwrap();
+    graph.add_node_edge(node::CAMERA, node::PASS).unwrap();
+
+    if msaa.samples > 1 {
+        graph.add_node(
+            node::SAMPLED_COLOR_ATTACHMENT,
+            WindowTextureNode::new(
+                window_id,
+                TextureDescriptor {
+                    size: Extent3d::new(1, 1, 1),
+                    mip_level_count: 1,
+                    sample_count: msaa.samples,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::default(),
+                    usage: TextureUsage::OUTPUT_ATTACHMENT,
+                },
+            ),
+        );
+        graph
+            .add_slot_edge(
+                node::SAMPLED_COLOR_ATTACHMENT,
+                WindowTextureNode::OUT_TEXTURE,
+                node::PASS,
+                "color_attachment",
+            )
+            .unwrap();
+    }
+
+    // facing straight down with north at the top of the window
+    let mut camera = OrthographicCameraBundle::new_3d();
+    camera.camera = Camera {
+        name: Some(MAP_CAMERA.to_string()),
+        window: window_id,
+        ..Default::default()
+    };
+    camera.orthographic_projection.far = FAR;
+    camera.transform = Transform {
+        translation: Vec3::Y * CAMERA_HEIGHT,
+        rotation: Quat::from_rotation_x(-FRAC_PI_2),
+        ..Default::default()
+    };
+    commands.spawn_bundle(camera).insert(MapCamera);
+}
+
+struct MapCamera;
+
+// Keeps the map centred over the player, zoomed out to fit every chunk kept loaded
+fn follow_player(
+    config: Res<terrain::Config>,
+    player_query: Query<&Transform, (With<Player>, Without<MapCamera>)>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<MapCamera>>,
+) {
+    let player = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+    // half the window's height covers the kept chunks on one side, plus the player's own
+    let extent = (terrain::kept_chunk_radius(&config) as f32 + 0.5) * CHUNK_SIZE as f32;
+    for (mut transform, mut projection) in camera_query.iter_mut() {
+        transform.translation = Vec3::new(player.x, player.y + CAMERA_HEIGHT, player.z);
+        projection.scale = extent;
+    }
+}
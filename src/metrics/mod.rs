@@ -0,0 +1,192 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    net::TcpListener,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use bevy::{
+    diagnostic::{Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use serde::Serialize;
+
+use crate::{
+    error_log::ErrorLog,
+    settings::{AddSettings, SettingsTab},
+    terrain::{Chunk, SeenChunks},
+};
+
+const METRICS_PATH: &str = "metrics/latest.json";
+
+/// Publishes live numbers about the app for scripts and dashboards watching long runs,
+/// as a JSON file rewritten every interval and optionally over plain HTTP on localhost.
+/// Nothing leaves the machine.
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<MetricsConfig>(SettingsTab::Debug, "Metrics export")
+            .init_resource::<MetricsServer>()
+            .add_system(export.system());
+    }
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct MetricsConfig {
+    pub write_file: bool,
+    // serves the latest metrics at http://127.0.0.1:<port>/, the port is fixed once it starts
+    pub serve_http: bool,
+    pub port: u16,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    pub interval: f32,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            write_file: false,
+            serve_http: false,
+            port: 9184,
+            interval: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Metrics {
+    uptime: f64,
+    fps: Option<f64>,
+    frame_time_ms: Option<f64>,
+    entities: Option<f64>,
+    // chunks within the view distance, and how many of those have their mesh yet
+    chunks_seen: usize,
+    chunks_generated: usize,
+    // resident memory, only known on Linux
+    memory_bytes: Option<u64>,
+}
+
+// The latest metrics, shared with the thread answering HTTP requests once it's started
+#[derive(Default)]
+struct MetricsServer {
+    latest: Arc<Mutex<String>>,
+    started: bool,
+}
+
+fn export(
+    time: Res<Time>,
+    config: Res<MetricsConfig>,
+    diagnostics: Res<Diagnostics>,
+    seen_chunks: Res<SeenChunks>,
+    mut server: ResMut<MetricsServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut since_last: Local<f32>,
+    chunks_query: Query<&Chunk>,
+) {
+    if !config.write_file && !config.serve_http {
+        return;
+    }
+    *since_last += time.delta_seconds();
+    if *since_last < config.interval {
+        return;
+    }
+    *since_last = 0.0;
+
+    let average = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+    };
+    let metrics = Metrics {
+        uptime: time.seconds_since_startup(),
+        fps: average(FrameTimeDiagnosticsPlugin::FPS),
+        frame_time_ms: average(FrameTimeDiagnosticsPlugin::FRAME_TIME).map(|time| time * 1000.0),
+        entities: average(EntityCountDiagnosticsPlugin::ENTITY_COUNT),
+        chunks_seen: seen_chunks.len(),
+        chunks_generated: chunks_query
+            .iter()
+            .filter(|chunk| chunk.mesh().is_some())
+            .count(),
+        memory_bytes: resident_memory(),
+    };
+    let json = match serde_json::to_string_pretty(&metrics) {
+        Ok(json) => json,
+        Err(error) => {
+            errors.report(format!("Failed to encode metrics: {}", error));
+            return;
+        }
+    };
+
+    if config.write_file {
+        if let Err(error) = write_file(METRICS_PATH, &json) {
+            errors.report(format!(
+                "Failed to write metrics {}: {}",
+                METRICS_PATH, error
+            ));
+        }
+    }
+    if config.serve_http {
+        if !server.started {
+            server.started = true;
+            if let Err(error) = serve(config.port, server.latest.clone()) {
+                errors.report(format!(
+                    "Failed to serve metrics on {}: {}",
+                    config.port, error
+                ));
+            }
+        }
+        *server.latest.lock().unwrap() = json;
+    }
+}
+
+fn write_file(path: impl AsRef<Path>, json: &str) -> std::io::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // written alongside and moved into place, so readers never see half a file
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, json)?;
+    fs::rename(partial, path)
+}
+
+// Answers every request on the port with the latest metrics, from a thread of its own
+fn serve(port: u16, latest: Arc<Mutex<String>>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    info!("Serving metrics at http://127.0.0.1:{}/", port);
+    thread::Builder::new()
+        .name("Metrics server".to_string())
+        .spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                // the request itself doesn't matter, but it's read so the client isn't reset
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                let body = latest.lock().unwrap().clone();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn resident_memory() -> Option<u64> {
+    // the second field is the resident set, in pages
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn resident_memory() -> Option<u64> {
+    None
+}
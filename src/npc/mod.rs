@@ -0,0 +1,320 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use rand::{seq::IteratorRandom, Rng};
+
+use crate::{
+    settings::{AddSettings, SettingsTab},
+    terrain::{self, query, ChunkCoords, SeenChunks, CHUNK_SIZE},
+    Player,
+};
+
+use self::path::PathParams;
+
+mod path;
+
+const AGENT_RADIUS: f32 = 0.5;
+const AGENT_DEPTH: f32 = 1.0;
+// distance at which a waypoint counts as reached
+const ARRIVAL_DISTANCE: f32 = 0.5;
+
+pub struct NpcPlugin;
+
+impl Plugin for NpcPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<NpcConfig>(SettingsTab::World, "Wanderers")
+            .add_event::<SpawnWanderersEvent>()
+            .add_startup_system(setup.system())
+            .add_system(spawn_wanderers.system())
+            .add_system(spawn_requested.system())
+            .add_system(plan_paths.system())
+            .add_system(follow_paths.system())
+            .add_system(despawn_unloaded.system());
+    }
+}
+
+/// A capsule agent that walks between random points around the chunk it was spawned in
+pub struct Wanderer {
+    home_chunk: ChunkCoords,
+    // remaining waypoints, the next one last
+    path: Vec<Vec2>,
+    idle_time: f32,
+}
+
+/// Asks for wanderers to be spawned around a point, on top of the usual population
+#[derive(Clone, Copy, Debug)]
+pub struct SpawnWanderersEvent {
+    pub position: Vec2,
+    pub count: usize,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct NpcConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(max = 200))]
+    pub max_agents: usize,
+    // agents only spawn on loaded chunks within this distance of the player
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub spawn_radius: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub wander_radius: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub speed: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 89.0))]
+    pub max_slope: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.5))]
+    pub path_cell_size: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 10, max = 20000))]
+    pub max_path_search: usize,
+    // pathfinding is expensive, so only this many agents plan a route each frame
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1, max = 50))]
+    pub paths_per_frame: usize,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub idle_time: f32,
+}
+
+impl Default for NpcConfig {
+    fn default() -> Self {
+        Self {
+            max_agents: 20,
+            spawn_radius: 300.0,
+            wander_radius: 40.0,
+            speed: 3.0,
+            max_slope: 35.0,
+            path_cell_size: 2.0,
+            max_path_search: 2000,
+            paths_per_frame: 2,
+            idle_time: 2.0,
+        }
+    }
+}
+
+struct NpcAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(NpcAssets {
+        mesh: meshes.add(Mesh::from(shape::Capsule {
+            radius: AGENT_RADIUS,
+            depth: AGENT_DEPTH,
+            ..Default::default()
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.8, 0.3, 0.25),
+            ..Default::default()
+        }),
+    });
+}
+
+// Tops the population up one agent per frame, on dry and walkable ground near the player
+fn spawn_wanderers(
+    mut commands: Commands,
+    config: Res<NpcConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<NpcAssets>,
+    seen_chunks: Res<SeenChunks>,
+    player_query: Query<&Transform, With<Player>>,
+    wanderers_query: Query<(), With<Wanderer>>,
+) {
+    if wanderers_query.iter().count() >= config.max_agents {
+        return;
+    }
+
+    let player_position = match player_query.iter().next() {
+        Some(transform) => transform.translation.xz(),
+        None => return,
+    };
+
+    let mut rng = rand::thread_rng();
+    let home_chunk = match seen_chunks
+        .keys()
+        .filter(|coords| coords.to_position().distance(player_position) < config.spawn_radius)
+        .choose(&mut rng)
+    {
+        Some(coords) => *coords,
+        None => return,
+    };
+
+    let half_size = CHUNK_SIZE as f32 / 2.0;
+    let point = home_chunk.to_position()
+        + Vec2::new(
+            rng.gen_range(-half_size..half_size),
+            rng.gen_range(-half_size..half_size),
+        );
+    if !walkable(&terrain_config, point, config.max_slope) {
+        return;
+    }
+    spawn_wanderer(
+        &mut commands,
+        &config,
+        &terrain_config,
+        &assets,
+        home_chunk,
+        point,
+    );
+}
+
+// Spawns the requested wanderers on walkable ground within a few metres of the point
+fn spawn_requested(
+    mut commands: Commands,
+    config: Res<NpcConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<NpcAssets>,
+    mut events: EventReader<SpawnWanderersEvent>,
+) {
+    const SPREAD: f32 = 5.0;
+    let mut rng = rand::thread_rng();
+
+    for event in events.iter() {
+        for _ in 0..event.count {
+            let point = event.position
+                + Vec2::new(
+                    rng.gen_range(-SPREAD..SPREAD),
+                    rng.gen_range(-SPREAD..SPREAD),
+                );
+            if !walkable(&terrain_config, point, config.max_slope) {
+                continue;
+            }
+            spawn_wanderer(
+                &mut commands,
+                &config,
+                &terrain_config,
+                &assets,
+                ChunkCoords::containing(point),
+                point,
+            );
+        }
+    }
+}
+
+fn spawn_wanderer(
+    commands: &mut Commands,
+    config: &NpcConfig,
+    terrain_config: &terrain::Config,
+    assets: &NpcAssets,
+    home_chunk: ChunkCoords,
+    point: Vec2,
+) {
+    let mut rng = rand::thread_rng();
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: assets.mesh.clone(),
+            material: assets.material.clone(),
+            transform: Transform::from_translation(surface_position(terrain_config, point)),
+            ..Default::default()
+        })
+        .insert(Wanderer {
+            home_chunk,
+            path: Vec::new(),
+            idle_time: rng.gen_range(0.0..config.idle_time.max(0.01)),
+        });
+}
+
+// Idle agents with nowhere to go pick a random nearby target and path to it
+fn plan_paths(
+    time: Res<Time>,
+    config: Res<NpcConfig>,
+    terrain_config: Res<terrain::Config>,
+    mut wanderers_query: Query<(&mut Wanderer, &Transform)>,
+) {
+    let params = PathParams {
+        cell_size: config.path_cell_size,
+        max_slope: config.max_slope,
+        max_search: config.max_path_search,
+    };
+    let mut rng = rand::thread_rng();
+    let mut planned = 0;
+
+    for (mut wanderer, transform) in wanderers_query.iter_mut() {
+        if !wanderer.path.is_empty() {
+            continue;
+        }
+
+        wanderer.idle_time -= time.delta_seconds();
+        if wanderer.idle_time > 0.0 || planned >= config.paths_per_frame {
+            continue;
+        }
+        planned += 1;
+
+        let start = transform.translation.xz();
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let distance = rng.gen_range(0.0..config.wander_radius);
+        let goal = start + Vec2::new(angle.cos(), angle.sin()) * distance;
+
+        match path::find_path(&terrain_config, start, goal, &params) {
+            Some(mut path) => {
+                path.reverse();
+                wanderer.path = path;
+            }
+            // unreachable, so wait a moment before trying somewhere else
+            None => wanderer.idle_time = config.idle_time,
+        }
+    }
+}
+
+fn follow_paths(
+    time: Res<Time>,
+    config: Res<NpcConfig>,
+    terrain_config: Res<terrain::Config>,
+    mut wanderers_query: Query<(&mut Wanderer, &mut Transform)>,
+) {
+    let step = config.speed * time.delta_seconds();
+
+    for (mut wanderer, mut transform) in wanderers_query.iter_mut() {
+        let target = match wanderer.path.last() {
+            Some(target) => *target,
+            None => continue,
+        };
+
+        let position = transform.translation.xz();
+        let offset = target - position;
+        let distance = offset.length();
+        let next = if distance <= step.max(ARRIVAL_DISTANCE) {
+            wanderer.path.pop();
+            if wanderer.path.is_empty() {
+                wanderer.idle_time = config.idle_time;
+            }
+            target
+        } else {
+            position + offset / distance * step
+        };
+
+        transform.translation = surface_position(&terrain_config, next);
+        if distance > f32::EPSILON {
+            transform.rotation = Quat::from_rotation_y(f32::atan2(-offset.x, -offset.y));
+        }
+    }
+}
+
+// Agents go away along with the chunk they live on
+fn despawn_unloaded(
+    mut commands: Commands,
+    seen_chunks: Res<SeenChunks>,
+    wanderers_query: Query<(Entity, &Wanderer)>,
+) {
+    for (entity, wanderer) in wanderers_query.iter() {
+        if !seen_chunks.contains_key(&wanderer.home_chunk) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn walkable(config: &terrain::Config, point: Vec2, max_slope: f32) -> bool {
+    let slope = query::normal_at(config, point).y.acos().to_degrees();
+    query::height_at(config, point) >= config.sea_level() && slope <= max_slope
+}
+
+// Stands the capsule on the terrain at a point
+fn surface_position(config: &terrain::Config, point: Vec2) -> Vec3 {
+    let half_height = AGENT_RADIUS + AGENT_DEPTH / 2.0;
+    Vec3::new(
+        point.x,
+        query::height_at(config, point) + half_height,
+        point.y,
+    )
+}
@@ -0,0 +1,160 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use bevy::math::Vec2;
+
+use crate::terrain::{self, query};
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+pub struct PathParams {
+    pub cell_size: f32,
+    // steepest slope an agent can walk up or down, in degrees
+    pub max_slope: f32,
+    // gives up once this many cells have been explored
+    pub max_search: usize,
+}
+
+/// Finds a walkable route over the terrain from `start` to `goal` with A* on a grid of
+/// height samples, avoiding water and anything steeper than the max slope.
+/// Returns the waypoints to visit after `start`, or None if the goal can't be reached.
+pub fn find_path(
+    config: &terrain::Config,
+    start: Vec2,
+    goal: Vec2,
+    params: &PathParams,
+) -> Option<Vec<Vec2>> {
+    let cell_size = params.cell_size.max(0.1);
+    let max_rise = params.max_slope.to_radians().tan();
+    let water_height = config.sea_level();
+
+    let to_world = |cell: (i32, i32)| start + Vec2::new(cell.0 as f32, cell.1 as f32) * cell_size;
+    let goal_offset = (goal - start) / cell_size;
+    let goal_cell = (goal_offset.x.round() as i32, goal_offset.y.round() as i32);
+
+    let mut heights = HashMap::new();
+    let mut height = |cell: (i32, i32)| {
+        *heights
+            .entry(cell)
+            .or_insert_with(|| query::height_at(config, to_world(cell)))
+    };
+
+    if height(goal_cell) < water_height {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from = HashMap::new();
+    let mut cost_so_far = HashMap::new();
+
+    open.push(Node {
+        cell: (0, 0),
+        estimate: 0.0,
+    });
+    cost_so_far.insert((0, 0), 0.0);
+
+    let mut explored = 0;
+    while let Some(Node { cell, .. }) = open.pop() {
+        if cell == goal_cell {
+            return Some(reconstruct(&came_from, goal_cell, to_world, goal));
+        }
+
+        explored += 1;
+        if explored > params.max_search {
+            return None;
+        }
+
+        let current_height = height(cell);
+        for (dx, dy) in NEIGHBOURS.iter() {
+            let next = (cell.0 + dx, cell.1 + dy);
+            let next_height = height(next);
+            if next_height < water_height {
+                continue;
+            }
+
+            let distance = Vec2::new(*dx as f32, *dy as f32).length() * cell_size;
+            let rise = (next_height - current_height).abs();
+            if rise / distance > max_rise {
+                continue;
+            }
+
+            // walking uphill or downhill costs a little more than walking on the flat
+            let cost = cost_so_far[&cell] + distance + rise;
+            if cost_so_far.get(&next).map_or(true, |&known| cost < known) {
+                cost_so_far.insert(next, cost);
+                came_from.insert(next, cell);
+
+                let remaining =
+                    Vec2::new((goal_cell.0 - next.0) as f32, (goal_cell.1 - next.1) as f32)
+                        .length()
+                        * cell_size;
+                open.push(Node {
+                    cell: next,
+                    estimate: cost + remaining,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+fn reconstruct(
+    came_from: &HashMap<(i32, i32), (i32, i32)>,
+    goal_cell: (i32, i32),
+    to_world: impl Fn((i32, i32)) -> Vec2,
+    goal: Vec2,
+) -> Vec<Vec2> {
+    let mut path = vec![goal];
+    let mut cell = goal_cell;
+    while let Some(&previous) = came_from.get(&cell) {
+        if previous == (0, 0) {
+            break;
+        }
+        path.push(to_world(previous));
+        cell = previous;
+    }
+
+    path.reverse();
+    path
+}
+
+// Open set entry, ordered so the binary heap pops the lowest estimate first
+struct Node {
+    cell: (i32, i32),
+    estimate: f32,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+    }
+}
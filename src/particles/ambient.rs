@@ -0,0 +1,145 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use rand::Rng;
+
+use super::{GroundContact, ParticleBurstEvent};
+use crate::{
+    first_person::PlayerEyes,
+    terrain::{self, query, Biome},
+    weather::Wind,
+};
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct AmbientParticlesConfig {
+    pub enabled: bool,
+    // particles released around the player each second, before the biome's own density
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub rate: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub radius: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub sand_density: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub leaf_density: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub snow_density: f32,
+    // the sand only lifts off the dunes once the wind is blowing this fast
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub sand_wind_speed: f32,
+}
+
+impl Default for AmbientParticlesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            rate: 30.0,
+            radius: 20.0,
+            sand_density: 1.0,
+            leaf_density: 0.4,
+            snow_density: 0.6,
+            sand_wind_speed: 4.0,
+        }
+    }
+}
+
+// What drifts through the air in a biome
+struct Ambience {
+    density: f32,
+    color: Color,
+    size: f32,
+    lifetime: f32,
+    gravity: f32,
+    // how far above the ground the particles start out
+    height: (f32, f32),
+    ground: GroundContact,
+    wind: f32,
+}
+
+fn ambience(biome: Biome, config: &AmbientParticlesConfig, wind: &Wind) -> Option<Ambience> {
+    match biome {
+        Biome::Sand => {
+            let gusting = (wind.velocity.length() / config.sand_wind_speed.max(0.01)).min(1.0);
+            Some(Ambience {
+                density: config.sand_density * gusting * gusting,
+                color: Color::rgba(0.9, 0.8, 0.55, 0.7),
+                size: 0.06,
+                lifetime: 1.5,
+                gravity: 0.5,
+                height: (0.1, 1.0),
+                ground: GroundContact::Vanish,
+                wind: 1.0,
+            })
+        }
+        Biome::Forest => Some(Ambience {
+            density: config.leaf_density,
+            color: Color::rgb(0.55, 0.45, 0.15),
+            size: 0.15,
+            lifetime: 6.0,
+            gravity: 0.6,
+            height: (3.0, 8.0),
+            ground: GroundContact::Settle,
+            wind: 0.4,
+        }),
+        Biome::Snow => Some(Ambience {
+            density: config.snow_density,
+            color: Color::WHITE,
+            size: 0.08,
+            lifetime: 3.0,
+            gravity: 0.3,
+            height: (0.2, 4.0),
+            ground: GroundContact::Settle,
+            wind: 0.8,
+        }),
+        Biome::Water | Biome::Lowland | Biome::Rock => None,
+    }
+}
+
+// Releases the particles of the biome the player is standing in around them
+pub fn emit(
+    time: Res<Time>,
+    config: Res<AmbientParticlesConfig>,
+    terrain_config: Res<terrain::Config>,
+    wind: Res<Wind>,
+    mut owed: Local<f32>,
+    mut bursts: EventWriter<ParticleBurstEvent>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation,
+        None => return,
+    };
+    let ambience = match ambience(query::biome_at(&terrain_config, eyes.xz()), &config, &wind) {
+        Some(ambience) if config.enabled => ambience,
+        _ => {
+            *owed = 0.0;
+            return;
+        }
+    };
+
+    *owed += config.rate * ambience.density * time.delta_seconds();
+    let count = *owed as usize;
+    *owed -= count as f32;
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..count {
+        let point = eyes.xz()
+            + Vec2::new(
+                rng.gen_range(-config.radius..config.radius),
+                rng.gen_range(-config.radius..config.radius),
+            );
+        let height = query::height_at(&terrain_config, point)
+            + rng.gen_range(ambience.height.0..ambience.height.1);
+        bursts.send(ParticleBurstEvent {
+            position: Vec3::new(point.x, height, point.y),
+            color: ambience.color,
+            count: 1,
+            speed: 0.3,
+            size: ambience.size,
+            lifetime: ambience.lifetime,
+            gravity: ambience.gravity,
+            ground: ambience.ground,
+            wind: ambience.wind,
+        });
+    }
+}
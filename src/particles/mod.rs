@@ -0,0 +1,183 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use rand::Rng;
+
+use crate::{
+    settings::{AddSettings, SettingsTab},
+    terrain::{self, query},
+    weather::Wind,
+};
+
+pub use self::{ambient::AmbientParticlesConfig, water_life::WaterLifeConfig};
+
+mod ambient;
+mod water_life;
+
+// fraction of their speed bouncing particles keep each time they hit the ground
+const BOUNCE_RESTITUTION: f32 = 0.4;
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<ParticleBurstEvent>()
+            .add_startup_system(setup.system())
+            .add_system(spawn_bursts.system())
+            .add_system(update_particles.system())
+            .add_settings::<AmbientParticlesConfig>(SettingsTab::World, "Ambient particles")
+            .add_system(ambient::emit.system())
+            .add_settings::<WaterLifeConfig>(SettingsTab::World, "Water life")
+            .add_startup_system(water_life::setup.system())
+            .add_system(water_life::spawn.system())
+            .add_system(water_life::swim.system())
+            .add_system(water_life::rise_bubbles.system())
+            .add_system(water_life::cull.system());
+    }
+}
+
+/// Requests a burst of short lived particles thrown out from a point in the world
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleBurstEvent {
+    pub position: Vec3,
+    pub color: Color,
+    pub count: usize,
+    pub speed: f32,
+    pub size: f32,
+    pub lifetime: f32,
+    pub gravity: f32,
+    pub ground: GroundContact,
+    // how much of the wind's speed carries the particles along, none at 0
+    pub wind: f32,
+}
+
+/// What a burst's particles do when they reach the terrain
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GroundContact {
+    // carry on through it, for particles that never fall
+    PassThrough,
+    // disappear, like rain soaking in
+    Vanish,
+    // stop where they land and fade out there, like snow
+    Settle,
+    // hop back up with some of their speed, like kicked up dust
+    Bounce,
+}
+
+pub struct Particle {
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    gravity: f32,
+    size: f32,
+    ground: GroundContact,
+    wind: f32,
+    // the terrain is only sampled once per burst, as its particles all start out together
+    ground_height: f32,
+}
+
+// All particles share the same small cube mesh
+struct ParticleMesh(Handle<Mesh>);
+
+fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(ParticleMesh(
+        meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+    ));
+}
+
+fn spawn_bursts(
+    mut commands: Commands,
+    mut events: EventReader<ParticleBurstEvent>,
+    terrain_config: Res<terrain::Config>,
+    particle_mesh: Res<ParticleMesh>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for burst in events.iter() {
+        let material = materials.add(StandardMaterial {
+            base_color: burst.color,
+            unlit: true,
+            ..Default::default()
+        });
+        // the water's surface stops them as well as the ground under it
+        let ground_height = match burst.ground {
+            GroundContact::PassThrough => f32::MIN,
+            _ => query::height_at(&terrain_config, burst.position.xz())
+                .max(terrain_config.sea_level()),
+        };
+
+        for _ in 0..burst.count {
+            // Throw the particles outwards and upwards in a rough hemisphere
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(0.2..1.0),
+                rng.gen_range(-1.0..1.0),
+            )
+            .normalize();
+
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: particle_mesh.0.clone(),
+                    material: material.clone(),
+                    transform: Transform {
+                        translation: burst.position,
+                        scale: Vec3::splat(burst.size),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Particle {
+                    velocity: direction * burst.speed * rng.gen_range(0.5..1.0),
+                    age: 0.0,
+                    lifetime: burst.lifetime * rng.gen_range(0.75..1.25),
+                    gravity: burst.gravity,
+                    size: burst.size,
+                    ground: burst.ground,
+                    wind: burst.wind,
+                    ground_height,
+                });
+        }
+    }
+}
+
+// Moves the particles along, shrinking them as they age until they disappear
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    wind: Res<Wind>,
+    mut particles_query: Query<(Entity, &mut Particle, &mut Transform)>,
+) {
+    let delta = time.delta_seconds();
+
+    for (entity, mut particle, mut transform) in particles_query.iter_mut() {
+        particle.age += delta;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity.y -= particle.gravity * delta;
+        transform.translation += (particle.velocity + wind.velocity * particle.wind) * delta;
+
+        if transform.translation.y < particle.ground_height {
+            transform.translation.y = particle.ground_height;
+            match particle.ground {
+                GroundContact::PassThrough => {}
+                GroundContact::Vanish => {
+                    commands.entity(entity).despawn();
+                    continue;
+                }
+                GroundContact::Settle => {
+                    particle.velocity = Vec3::ZERO;
+                    particle.gravity = 0.0;
+                    particle.wind = 0.0;
+                }
+                GroundContact::Bounce => {
+                    particle.velocity.y = -particle.velocity.y * BOUNCE_RESTITUTION;
+                    particle.velocity.x *= BOUNCE_RESTITUTION;
+                    particle.velocity.z *= BOUNCE_RESTITUTION;
+                }
+            }
+        }
+        transform.scale = Vec3::splat(particle.size * (1.0 - particle.age / particle.lifetime));
+    }
+}
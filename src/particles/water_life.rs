@@ -0,0 +1,240 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use rand::Rng;
+
+use crate::{
+    terrain::{self, query, ChunkCoords, SeenChunks},
+    Player,
+};
+
+// a few attempts per frame at finding a spot that is actually under water
+const SPAWN_ATTEMPTS: usize = 8;
+
+/// A small fish drifting around under the water near the player
+pub struct Fish {
+    velocity: Vec3,
+    chunk: ChunkCoords,
+}
+
+/// A bubble rising from the lake or sea floor, popping at the surface
+pub struct Bubble {
+    speed: f32,
+    chunk: ChunkCoords,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct WaterLifeConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(max = 300))]
+    pub max_fish: usize,
+    #[cfg_attr(feature = "dev-tools", inspectable(max = 300))]
+    pub max_bubbles: usize,
+    // fish and bubbles only live within this distance of the player
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub radius: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub fish_speed: f32,
+    // how sharply the fish change direction
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub fish_turn_rate: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub bubble_speed: f32,
+}
+
+impl Default for WaterLifeConfig {
+    fn default() -> Self {
+        Self {
+            max_fish: 60,
+            max_bubbles: 80,
+            radius: 60.0,
+            fish_speed: 2.0,
+            fish_turn_rate: 1.5,
+            bubble_speed: 1.5,
+        }
+    }
+}
+
+pub struct WaterLifeAssets {
+    fish_mesh: Handle<Mesh>,
+    fish_material: Handle<StandardMaterial>,
+    bubble_mesh: Handle<Mesh>,
+    bubble_material: Handle<StandardMaterial>,
+}
+
+pub fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(WaterLifeAssets {
+        fish_mesh: meshes.add(Mesh::from(shape::Box::new(0.15, 0.2, 0.6))),
+        fish_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.85, 0.55, 0.2),
+            unlit: true,
+            ..Default::default()
+        }),
+        bubble_mesh: meshes.add(Mesh::from(shape::Icosphere {
+            radius: 0.08,
+            subdivisions: 1,
+        })),
+        bubble_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.8, 0.9, 1.0),
+            unlit: true,
+            ..Default::default()
+        }),
+    });
+}
+
+// Tops up the fish and bubbles at random under water spots around the player
+pub fn spawn(
+    mut commands: Commands,
+    config: Res<WaterLifeConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<WaterLifeAssets>,
+    seen_chunks: Res<SeenChunks>,
+    player_query: Query<&Transform, With<Player>>,
+    fish_query: Query<(), With<Fish>>,
+    bubbles_query: Query<(), With<Bubble>>,
+) {
+    let player_position = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    let water_height = terrain_config.sea_level();
+    let mut fish_count = fish_query.iter().count();
+    let mut bubble_count = bubbles_query.iter().count();
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..SPAWN_ATTEMPTS {
+        let wants_fish = fish_count < config.max_fish;
+        let wants_bubble = bubble_count < config.max_bubbles;
+        if !wants_fish && !wants_bubble {
+            return;
+        }
+
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let point = player_position.xz()
+            + Vec2::new(angle.cos(), angle.sin()) * rng.gen_range(0.0..config.radius);
+        let chunk = ChunkCoords::containing(point);
+        let floor = query::height_at(&terrain_config, point);
+        if floor >= water_height || !seen_chunks.contains_key(&chunk) {
+            continue;
+        }
+
+        if wants_fish {
+            fish_count += 1;
+            let depth = rng.gen_range(floor..water_height);
+            let heading = rng.gen_range(0.0..std::f32::consts::TAU);
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: assets.fish_mesh.clone(),
+                    material: assets.fish_material.clone(),
+                    transform: Transform::from_xyz(point.x, depth, point.y),
+                    ..Default::default()
+                })
+                .insert(Fish {
+                    velocity: Vec3::new(heading.cos(), 0.0, heading.sin()) * config.fish_speed,
+                    chunk,
+                });
+        } else {
+            bubble_count += 1;
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: assets.bubble_mesh.clone(),
+                    material: assets.bubble_material.clone(),
+                    transform: Transform::from_xyz(point.x, floor, point.y),
+                    ..Default::default()
+                })
+                .insert(Bubble {
+                    speed: config.bubble_speed * rng.gen_range(0.6..1.4),
+                    chunk,
+                });
+        }
+    }
+}
+
+// Fish meander randomly, turning back when they reach the surface, the floor or the shore
+pub fn swim(
+    time: Res<Time>,
+    config: Res<WaterLifeConfig>,
+    terrain_config: Res<terrain::Config>,
+    mut fish_query: Query<(&mut Fish, &mut Transform)>,
+) {
+    let delta = time.delta_seconds();
+    let water_height = terrain_config.sea_level();
+    let mut rng = rand::thread_rng();
+
+    for (mut fish, mut transform) in fish_query.iter_mut() {
+        let wander = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-0.3..0.3),
+            rng.gen_range(-1.0..1.0),
+        );
+        let mut velocity =
+            fish.velocity + wander * config.fish_turn_rate * config.fish_speed * delta;
+
+        let next = transform.translation + velocity * delta;
+        let floor = query::height_at(&terrain_config, next.xz());
+        if floor >= water_height {
+            // heading onto land, so turn around
+            velocity = -velocity;
+        } else if next.y > water_height - 0.2 || next.y < floor + 0.2 {
+            velocity.y = -velocity.y;
+        }
+
+        fish.velocity = velocity.normalize_or_zero() * config.fish_speed;
+        transform.translation += fish.velocity * delta;
+        if fish.velocity.length_squared() > f32::EPSILON {
+            let target = transform.translation + fish.velocity;
+            transform.look_at(target, Vec3::Y);
+        }
+    }
+}
+
+pub fn rise_bubbles(
+    mut commands: Commands,
+    time: Res<Time>,
+    terrain_config: Res<terrain::Config>,
+    mut bubbles_query: Query<(Entity, &Bubble, &mut Transform)>,
+) {
+    let delta = time.delta_seconds();
+    let water_height = terrain_config.sea_level();
+
+    for (entity, bubble, mut transform) in bubbles_query.iter_mut() {
+        transform.translation.y += bubble.speed * delta;
+        if transform.translation.y >= water_height {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Removes water life that has drifted away from the player or whose chunk has unloaded
+pub fn cull(
+    mut commands: Commands,
+    config: Res<WaterLifeConfig>,
+    seen_chunks: Res<SeenChunks>,
+    player_query: Query<&Transform, With<Player>>,
+    fish_query: Query<(Entity, &Fish, &Transform)>,
+    bubbles_query: Query<(Entity, &Bubble, &Transform)>,
+) {
+    let player_position = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+    let keep = |chunk: &ChunkCoords, transform: &Transform| {
+        seen_chunks.contains_key(chunk)
+            && transform.translation.distance(player_position) <= config.radius * 1.5
+    };
+
+    for (entity, fish, transform) in fish_query.iter() {
+        if !keep(&fish.chunk, transform) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for (entity, bubble, transform) in bubbles_query.iter() {
+        if !keep(&bubble.chunk, transform) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::cell::Cell;
+
+use bevy::{
+    prelude::*,
+    render::renderer::{BufferId, BufferInfo, BufferMapMode, BufferUsage, RenderResourceContext},
+};
+
+use super::{PostProcessConfig, SceneTarget};
+
+/// The luminance is measured from a grid of this many pixels along each side of the screen
+pub const SAMPLE_GRID: u32 = 8;
+// each sampled pixel lands on its own row of the readback buffer, which have to be aligned
+// to 256 bytes
+pub const SAMPLE_STRIDE: u32 = 256;
+// keeps pure black pixels from dragging the log average down to negative infinity
+const MIN_LUMINANCE: f32 = 0.0001;
+
+/// The current exposure applied to the scene, and the luminance it was adapted to
+pub struct Exposure {
+    pub value: f32,
+    pub average_luminance: f32,
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self {
+            value: 1.0,
+            average_luminance: 0.18,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LuminanceReadback {
+    pub buffer: Option<BufferId>,
+    // set for the frame the graph should copy new samples into the buffer
+    pub copy_requested: bool,
+    since_last: f32,
+}
+
+// Reads back the pixels copied last frame and asks for a new set every measure interval
+pub fn read_luminance(
+    time: Res<Time>,
+    config: Res<PostProcessConfig>,
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    target: Res<SceneTarget>,
+    mut readback: ResMut<LuminanceReadback>,
+    mut exposure: ResMut<Exposure>,
+) {
+    let render_resource_context = &**render_resource_context;
+    let size = (SAMPLE_GRID * SAMPLE_GRID * SAMPLE_STRIDE) as u64;
+    let buffer = *readback.buffer.get_or_insert_with(|| {
+        render_resource_context.create_buffer(BufferInfo {
+            size: size as usize,
+            buffer_usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        })
+    });
+
+    if readback.copy_requested {
+        readback.copy_requested = false;
+
+        let log_total = Cell::new(0.0);
+        render_resource_context.map_buffer(buffer, BufferMapMode::Read);
+        render_resource_context.read_mapped_buffer(buffer, 0..size, &|data, _| {
+            for sample in data.chunks(SAMPLE_STRIDE as usize) {
+                // the scene texture is bgra with srgb encoding
+                let blue = srgb_to_linear(sample[0]);
+                let green = srgb_to_linear(sample[1]);
+                let red = srgb_to_linear(sample[2]);
+                let luminance = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+                log_total.set(log_total.get() + (luminance + MIN_LUMINANCE).ln());
+            }
+        });
+        render_resource_context.unmap_buffer(buffer);
+
+        let samples = (SAMPLE_GRID * SAMPLE_GRID) as f32;
+        exposure.average_luminance = (log_total.get() / samples).exp();
+    }
+
+    readback.since_last += time.delta_seconds();
+    if config.auto_exposure
+        && target.texture.is_some()
+        && readback.since_last >= config.measure_interval
+    {
+        readback.since_last = 0.0;
+        readback.copy_requested = true;
+    }
+}
+
+// Eases the exposure towards bringing the average luminance up or down to the key value,
+// like an eye slowly adjusting to the light
+pub fn adapt(time: Res<Time>, config: Res<PostProcessConfig>, mut exposure: ResMut<Exposure>) {
+    if !config.auto_exposure {
+        exposure.value = config.exposure;
+        return;
+    }
+
+    let target = (config.key_value / exposure.average_luminance.max(MIN_LUMINANCE))
+        .clamp(config.min_exposure, config.max_exposure);
+    let blend = 1.0 - (-config.adaptation_speed * time.delta_seconds()).exp();
+    exposure.value += (target - exposure.value) * blend;
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+
+use crate::{first_person::PlayerEyes, sky::Sun};
+
+use super::PostProcessConfig;
+
+// how far past the edge of the screen the sun can be before its rays have faded out, in uv
+const OFF_SCREEN_FADE: f32 = 0.5;
+// sun elevation over which the rays fade in after sunrise and out before sunset
+const HORIZON_FADE: f32 = 0.05;
+
+/// Where the sun is on screen and how strong its light shafts should be this frame
+#[derive(Default)]
+pub struct Godrays {
+    pub screen_position: Vec2,
+    pub intensity: f32,
+}
+
+// Projects the sun onto the screen and weakens the rays as it leaves the view, dips under
+// the horizon or disappears behind the terrain
+pub fn locate_sun(
+    config: Res<PostProcessConfig>,
+    sun: Res<Sun>,
+    mut godrays: ResMut<Godrays>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PlayerEyes>>,
+) {
+    let (camera, transform) = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    let ndc = match sun.ndc(camera, transform) {
+        Some(ndc) => ndc,
+        // behind the camera
+        None => {
+            godrays.intensity = 0.0;
+            return;
+        }
+    };
+
+    let screen_position = Vec2::new(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let outside = (screen_position - Vec2::splat(0.5)).abs().max_element() - 0.5;
+    let on_screen = 1.0 - (outside / OFF_SCREEN_FADE).clamp(0.0, 1.0);
+    let above_horizon = (sun.elevation() / HORIZON_FADE).clamp(0.0, 1.0);
+
+    godrays.screen_position = screen_position;
+    godrays.intensity = config.godray_intensity * on_screen * above_horizon * sun.visibility;
+}
@@ -0,0 +1,251 @@
+use std::borrow::Cow;
+
+use bevy::{
+    app::PluginGroupBuilder,
+    prelude::*,
+    render::{
+        camera::ActiveCameras,
+        pass::{LoadOp, Operations, PassDescriptor, TextureAttachment},
+        render_graph::{
+            base::{self, BaseRenderGraphConfig},
+            Node, PassNode, RenderGraph, RenderResourcesNode, ResourceSlotInfo, ResourceSlots,
+            WindowSwapChainNode, WindowTextureNode,
+        },
+        renderer::{RenderContext, RenderResourceId, RenderResourceType},
+        texture::Extent3d,
+        RenderPlugin,
+    },
+};
+
+use super::{
+    exposure::{LuminanceReadback, SAMPLE_GRID, SAMPLE_STRIDE},
+    screenshot::{bytes_per_row, Screenshot},
+    ColorGrading, PostProcessPass, PostProcessUniform, SceneTarget,
+};
+
+pub mod node {
+    pub const SCENE_TARGET: &str = "post_process_scene_target";
+    pub const LUMINANCE_SAMPLES: &str = "post_process_luminance_samples";
+    pub const SCREENSHOT: &str = "post_process_screenshot";
+    pub const UNIFORM: &str = "post_process_uniform";
+    pub const COLOR_GRADING: &str = "post_process_color_grading";
+    pub const POST_PASS: &str = "post_process_pass";
+}
+
+pub const POST_PROCESS_CAMERA: &str = "PostProcess";
+
+/// Swaps the default render plugin for one whose main pass doesn't draw straight to the
+/// window, so the scene can be post processed first. Use with `add_plugins_with`.
+pub fn reroute_main_pass(group: &mut PluginGroupBuilder) -> &mut PluginGroupBuilder {
+    group
+        .disable::<RenderPlugin>()
+        .add_after::<RenderPlugin, SceneRenderPlugin>(SceneRenderPlugin)
+}
+
+pub struct SceneRenderPlugin;
+
+impl Plugin for SceneRenderPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        RenderPlugin {
+            base_render_graph_config: Some(BaseRenderGraphConfig {
+                connect_main_pass_to_swapchain: false,
+                ..Default::default()
+            }),
+        }
+        .build(app);
+    }
+}
+
+/// Renders the main pass into the scene texture, then draws the full screen post process
+/// quad from it into the window before the ui goes on top
+pub fn add_post_process_graph(world: &mut World) {
+    world
+        .get_resource_mut::<ActiveCameras>()
+        .unwrap()
+        .add(POST_PROCESS_CAMERA);
+
+    let samples = world.get_resource::<Msaa>().unwrap().samples;
+    let mut graph = world.get_resource_mut::<RenderGraph>().unwrap();
+
+    graph.add_node(node::SCENE_TARGET, SceneTargetNode);
+    graph
+        .add_slot_edge(
+            node::SCENE_TARGET,
+            SceneTargetNode::OUT_TEXTURE,
+            base::node::MAIN_PASS,
+            if samples > 1 {
+                "color_resolve_target"
+            } else {
+                "color_attachment"
+            },
+        )
+        .unwrap();
+
+    graph.add_node(node::LUMINANCE_SAMPLES, LuminanceSampleNode);
+    graph
+        .add_node_edge(base::node::MAIN_PASS, node::LUMINANCE_SAMPLES)
+        .unwrap();
+    graph.add_node(node::SCREENSHOT, ScreenshotNode);
+    graph
+        .add_node_edge(base::node::MAIN_PASS, node::SCREENSHOT)
+        .unwrap();
+
+    let msaa = Msaa { samples };
+    let mut post_pass = PassNode::<&PostProcessPass>::new(PassDescriptor {
+        color_attachments: vec![msaa.color_attachment_descriptor(
+            TextureAttachment::Input("color_attachment".to_string()),
+            TextureAttachment::Input("color_resolve_target".to_string()),
+            Operations {
+                load: LoadOp::Clear(Color::BLACK),
+                store: true,
+            },
+        )],
+        depth_stencil_attachment: None,
+        sample_count: samples,
+    });
+    post_pass.add_camera(POST_PROCESS_CAMERA);
+    graph.add_node(node::POST_PASS, post_pass);
+
+    graph.add_system_node(
+        node::UNIFORM,
+        RenderResourcesNode::<PostProcessUniform>::new(true),
+    );
+    graph.add_node_edge(node::UNIFORM, node::POST_PASS).unwrap();
+
+    graph
+        .add_slot_edge(
+            base::node::PRIMARY_SWAP_CHAIN,
+            WindowSwapChainNode::OUT_TEXTURE,
+            node::POST_PASS,
+            if samples > 1 {
+                "color_resolve_target"
+            } else {
+                "color_attachment"
+            },
+        )
+        .unwrap();
+    if samples > 1 {
+        graph
+            .add_slot_edge(
+                base::node::MAIN_SAMPLED_COLOR_ATTACHMENT,
+                WindowTextureNode::OUT_TEXTURE,
+                node::POST_PASS,
+                "color_attachment",
+            )
+            .unwrap();
+    }
+
+    graph
+        .add_node_edge(base::node::MAIN_PASS, node::POST_PASS)
+        .unwrap();
+    // everything drawn over the scene has to wait for the post process pass
+    for overlay in [bevy::ui::node::UI_PASS, bevy_egui::node::EGUI_PASS].iter() {
+        if graph.get_node_id(*overlay).is_ok() {
+            graph.add_node_edge(node::POST_PASS, *overlay).unwrap();
+        }
+    }
+}
+
+// Hands the scene texture kept in `SceneTarget` to the main pass as its render target
+struct SceneTargetNode;
+
+impl SceneTargetNode {
+    const OUT_TEXTURE: &'static str = "texture";
+}
+
+impl Node for SceneTargetNode {
+    fn output(&self) -> &[ResourceSlotInfo] {
+        static OUTPUT: &[ResourceSlotInfo] = &[ResourceSlotInfo {
+            name: Cow::Borrowed(SceneTargetNode::OUT_TEXTURE),
+            resource_type: RenderResourceType::Texture,
+        }];
+        OUTPUT
+    }
+
+    fn update(
+        &mut self,
+        world: &World,
+        _render_context: &mut dyn RenderContext,
+        _input: &ResourceSlots,
+        output: &mut ResourceSlots,
+    ) {
+        if let Some(texture) = world.get_resource::<SceneTarget>().unwrap().texture {
+            output.set(0, RenderResourceId::Texture(texture));
+        }
+    }
+}
+
+// Copies a sparse grid of pixels from the rendered scene into the readback buffer,
+// whenever the exposure system has asked for a new measurement
+struct LuminanceSampleNode;
+
+impl Node for LuminanceSampleNode {
+    fn update(
+        &mut self,
+        world: &World,
+        render_context: &mut dyn RenderContext,
+        _input: &ResourceSlots,
+        _output: &mut ResourceSlots,
+    ) {
+        let readback = world.get_resource::<LuminanceReadback>().unwrap();
+        let target = world.get_resource::<SceneTarget>().unwrap();
+        let (texture, buffer) = match (target.texture, readback.buffer) {
+            (Some(texture), Some(buffer)) if readback.copy_requested => (texture, buffer),
+            _ => return,
+        };
+
+        for y in 0..SAMPLE_GRID {
+            for x in 0..SAMPLE_GRID {
+                // sample the middle of each grid cell
+                let pixel_x = (2 * x + 1) * target.width / (2 * SAMPLE_GRID);
+                let pixel_y = (2 * y + 1) * target.height / (2 * SAMPLE_GRID);
+                render_context.copy_texture_to_buffer(
+                    texture,
+                    [pixel_x, pixel_y, 0],
+                    0,
+                    buffer,
+                    ((y * SAMPLE_GRID + x) * SAMPLE_STRIDE) as u64,
+                    SAMPLE_STRIDE,
+                    Extent3d::new(1, 1, 1),
+                );
+            }
+        }
+    }
+}
+
+// Copies the whole rendered scene into the screenshot buffer when one has been asked for
+struct ScreenshotNode;
+
+impl Node for ScreenshotNode {
+    fn update(
+        &mut self,
+        world: &World,
+        render_context: &mut dyn RenderContext,
+        _input: &ResourceSlots,
+        _output: &mut ResourceSlots,
+    ) {
+        let screenshot = world.get_resource::<Screenshot>().unwrap();
+        let target = world.get_resource::<SceneTarget>().unwrap();
+        let (texture, buffer) = match (target.texture, screenshot.buffer) {
+            // a buffer made before the window was resized wouldn't fit the scene
+            (Some(texture), Some((buffer, width, height)))
+                if screenshot.copy_requested
+                    && width == target.width
+                    && height == target.height =>
+            {
+                (texture, buffer)
+            }
+            _ => return,
+        };
+
+        render_context.copy_texture_to_buffer(
+            texture,
+            [0, 0, 0],
+            0,
+            buffer,
+            0,
+            bytes_per_row(target.width),
+            Extent3d::new(target.width, target.height, 1),
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{CullMode, PipelineDescriptor, RenderPipeline},
+        renderer::{
+            RenderResource, RenderResourceBinding, RenderResourceBindings, RenderResourceContext,
+            RenderResourceType, RenderResources, SamplerId, TextureId,
+        },
+        shader::ShaderStages,
+        texture::{
+            Extent3d, FilterMode, SamplerDescriptor, TextureDescriptor, TextureDimension,
+            TextureFormat, TextureUsage,
+        },
+    },
+};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    error_log::ErrorLog,
+    settings::{AddSettings, SettingsTab},
+};
+
+use self::{
+    exposure::{Exposure, LuminanceReadback},
+    godrays::Godrays,
+    graph::POST_PROCESS_CAMERA,
+};
+
+pub use self::{graph::reroute_main_pass, screenshot::Screenshot};
+
+mod exposure;
+mod godrays;
+mod graph;
+mod screenshot;
+
+// names the post process shader uses for the rendered scene
+const SCENE_TEXTURE: &str = "PostProcess_scene";
+const SCENE_SAMPLER: &str = "PostProcess_scene_sampler";
+
+/// Runs the rendered scene through a full screen pass before it reaches the window.
+/// Needs the main pass rerouted with `reroute_main_pass` when adding the default plugins.
+pub struct PostProcessPlugin;
+
+impl Plugin for PostProcessPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<PostProcessConfig>(SettingsTab::Graphics, "Post processing")
+            .init_resource::<SceneTarget>()
+            .init_resource::<LuminanceReadback>()
+            .init_resource::<Exposure>()
+            .init_resource::<Godrays>()
+            .init_resource::<Screenshot>()
+            .add_startup_system(setup.system())
+            .add_system(resize_scene_target.system())
+            .add_system(
+                exposure::read_luminance
+                    .system()
+                    .label("post_process::measure"),
+            )
+            .add_system(
+                exposure::adapt
+                    .system()
+                    .label("post_process::adapt")
+                    .after("post_process::measure"),
+            )
+            .add_system(
+                godrays::locate_sun
+                    .system()
+                    .label("post_process::godrays")
+                    .after("sky::sun"),
+            )
+            .add_system(
+                update_uniform
+                    .system()
+                    .after("post_process::adapt")
+                    .after("post_process::godrays"),
+            )
+            .add_system(
+                screenshot::read_screenshot
+                    .system()
+                    .label("post_process::screenshot"),
+            )
+            .add_system(load_lut.system())
+            .add_system(filter_lut.system());
+
+        graph::add_post_process_graph(app.world_mut());
+    }
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct PostProcessConfig {
+    pub auto_exposure: bool,
+    // used instead of the measured exposure when auto exposure is off
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub exposure: f32,
+    // average scene luminance the exposure tries to reach
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.01, max = 1.0))]
+    pub key_value: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub adaptation_speed: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.01))]
+    pub min_exposure: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.01))]
+    pub max_exposure: f32,
+    // seconds between each luminance measurement
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub measure_interval: f32,
+    pub tonemapper: Tonemapper,
+    // color grading lookup table, a strip of 16 slices of 16x16 along the blue axis
+    pub lut_path: String,
+    // how much of the graded color is blended in
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub lut_strength: f32,
+    // strength of the light shafts around the sun
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub godray_intensity: f32,
+    // how far towards the sun each shaft reaches, as a fraction of the way there
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub godray_density: f32,
+    // how quickly the shafts fade further from where they start
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub godray_decay: f32,
+    // luminance a pixel needs before it adds to the shafts, so only the sky casts them
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub godray_threshold: f32,
+}
+
+impl Default for PostProcessConfig {
+    fn default() -> Self {
+        Self {
+            auto_exposure: true,
+            exposure: 1.0,
+            key_value: 0.18,
+            adaptation_speed: 1.5,
+            min_exposure: 0.5,
+            max_exposure: 3.0,
+            measure_interval: 0.1,
+            tonemapper: Tonemapper::Aces,
+            lut_path: "textures/neutral_lut.png".to_string(),
+            lut_strength: 1.0,
+            godray_intensity: 0.6,
+            godray_density: 0.8,
+            godray_decay: 0.96,
+            godray_threshold: 0.6,
+        }
+    }
+}
+
+/// How the exposed scene colors are squeezed back into displayable range
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tonemapper {
+    None,
+    Reinhard,
+    Aces,
+}
+
+/// The texture the main pass renders the scene into, sized to match the window
+#[derive(Default)]
+pub struct SceneTarget {
+    pub texture: Option<TextureId>,
+    pub width: u32,
+    pub height: u32,
+    sampler: Option<SamplerId>,
+}
+
+/// Marks the full screen quad drawn by the post process pass
+#[derive(Default)]
+pub struct PostProcessPass;
+
+#[derive(RenderResources, TypeUuid)]
+#[render_resources(from_self)]
+#[uuid = "0f2bb5de-5d1a-4b4c-9a43-2f0c1e6d8a71"]
+pub struct PostProcessUniform {
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    pub lut_strength: f32,
+    pub godray_intensity: f32,
+    // in uv, with the origin at the top left of the screen
+    pub sun_screen_position: Vec2,
+    pub godray_density: f32,
+    pub godray_decay: f32,
+    pub godray_threshold: f32,
+}
+
+impl Default for PostProcessUniform {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapper: Tonemapper::None,
+            lut_strength: 0.0,
+            godray_intensity: 0.0,
+            sun_screen_position: Vec2::ZERO,
+            godray_density: 0.0,
+            godray_decay: 0.0,
+            godray_threshold: 1.0,
+        }
+    }
+}
+
+impl PostProcessUniform {
+    // laid out to match the uniform block in post_process.frag
+    fn packed(&self) -> [f32; 12] {
+        let tonemapper = match self.tonemapper {
+            Tonemapper::None => 0.0,
+            Tonemapper::Reinhard => 1.0,
+            Tonemapper::Aces => 2.0,
+        };
+        [
+            self.exposure,
+            tonemapper,
+            self.lut_strength,
+            self.godray_intensity,
+            self.sun_screen_position.x,
+            self.sun_screen_position.y,
+            self.godray_density,
+            self.godray_decay,
+            self.godray_threshold,
+            // pads the block out to a multiple of 16 bytes
+            0.0,
+            0.0,
+            0.0,
+        ]
+    }
+}
+
+/// The color grading lookup table sampled by the post process pass
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "8d3c2a4e-6b7f-4f0e-b1a9-3e5d7c9f1b24"]
+pub struct ColorGrading {
+    pub lut: Handle<Texture>,
+}
+
+impl RenderResource for PostProcessUniform {
+    fn resource_type(&self) -> Option<RenderResourceType> {
+        Some(RenderResourceType::Buffer)
+    }
+
+    fn buffer_byte_len(&self) -> Option<usize> {
+        Some(self.packed().len() * std::mem::size_of::<f32>())
+    }
+
+    fn write_buffer_bytes(&self, buffer: &mut [u8]) {
+        for (bytes, value) in buffer.chunks_mut(4).zip(self.packed().iter()) {
+            bytes.copy_from_slice(&value.to_ne_bytes());
+        }
+    }
+
+    fn texture(&self) -> Option<&Handle<Texture>> {
+        None
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: errors.load(&asset_server, "shaders/post_process.vert"),
+        fragment: Some(errors.load(&asset_server, "shaders/post_process.frag")),
+    });
+    // the quad covers the whole screen and there's no depth buffer in the post process pass
+    descriptor.depth_stencil = None;
+    descriptor.primitive.cull_mode = CullMode::None;
+
+    commands.spawn_bundle((
+        meshes.add(Mesh::from(shape::Quad::new(Vec2::new(2.0, 2.0)))),
+        Draw::default(),
+        Visible {
+            is_visible: true,
+            is_transparent: false,
+        },
+        RenderPipelines::from_pipelines(vec![RenderPipeline::new(pipelines.add(descriptor))]),
+        Transform::default(),
+        GlobalTransform::default(),
+        PostProcessPass,
+        PostProcessUniform::default(),
+        ColorGrading::default(),
+    ));
+
+    let mut camera = OrthographicCameraBundle::new_2d();
+    camera.camera.name = Some(POST_PROCESS_CAMERA.to_string());
+    commands.spawn_bundle(camera);
+}
+
+// (Re)creates the scene texture whenever the window changes size
+fn resize_scene_target(
+    windows: Res<Windows>,
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    mut bindings: ResMut<RenderResourceBindings>,
+    mut target: ResMut<SceneTarget>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let (width, height) = (window.physical_width(), window.physical_height());
+    if width == 0
+        || height == 0
+        || (target.texture.is_some() && target.width == width && target.height == height)
+    {
+        return;
+    }
+
+    let render_resource_context = &**render_resource_context;
+    if let Some(texture) = target.texture.take() {
+        render_resource_context.remove_texture(texture);
+    }
+
+    let texture = render_resource_context.create_texture(TextureDescriptor {
+        size: Extent3d::new(width, height, 1),
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::default(),
+        usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::SAMPLED | TextureUsage::COPY_SRC,
+    });
+    let sampler = *target.sampler.get_or_insert_with(|| {
+        render_resource_context.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        })
+    });
+
+    bindings.set(SCENE_TEXTURE, RenderResourceBinding::Texture(texture));
+    bindings.set(SCENE_SAMPLER, RenderResourceBinding::Sampler(sampler));
+    target.texture = Some(texture);
+    target.width = width;
+    target.height = height;
+}
+
+fn update_uniform(
+    config: Res<PostProcessConfig>,
+    exposure: Res<Exposure>,
+    godrays: Res<Godrays>,
+    mut uniform_query: Query<&mut PostProcessUniform>,
+) {
+    for mut uniform in uniform_query.iter_mut() {
+        uniform.exposure = exposure.value;
+        uniform.tonemapper = config.tonemapper;
+        uniform.lut_strength = config.lut_strength;
+        uniform.godray_intensity = godrays.intensity;
+        uniform.sun_screen_position = godrays.screen_position;
+        uniform.godray_density = config.godray_density;
+        uniform.godray_decay = config.godray_decay;
+        uniform.godray_threshold = config.godray_threshold;
+    }
+}
+
+// Swaps in a new lookup table whenever its path is changed in the config
+fn load_lut(
+    config: Res<PostProcessConfig>,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut loaded_path: Local<String>,
+    mut grading_query: Query<&mut ColorGrading>,
+) {
+    if *loaded_path == config.lut_path {
+        return;
+    }
+
+    let lut = errors.load(&asset_server, &config.lut_path);
+    for mut grading in grading_query.iter_mut() {
+        grading.lut = lut.clone();
+    }
+    *loaded_path = config.lut_path.clone();
+}
+
+// Neighbouring cells of the table have to blend together, so sample it smoothly
+fn filter_lut(
+    mut events: EventReader<AssetEvent<Texture>>,
+    mut textures: ResMut<Assets<Texture>>,
+    grading_query: Query<&ColorGrading>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Created { handle } = event {
+            if !grading_query.iter().any(|grading| grading.lut == *handle) {
+                continue;
+            }
+            if let Some(texture) = textures.get_mut(handle) {
+                texture.sampler.mag_filter = FilterMode::Linear;
+                texture.sampler.min_filter = FilterMode::Linear;
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+
+use bevy::{
+    prelude::*,
+    render::renderer::{BufferId, BufferInfo, BufferMapMode, BufferUsage, RenderResourceContext},
+};
+use image::RgbaImage;
+
+use super::SceneTarget;
+
+/// Copies the rendered scene back from the GPU when asked, ready to take the frame after.
+/// The scene is captured before post processing and without any ui on top.
+#[derive(Default)]
+pub struct Screenshot {
+    // the readback buffer, along with the size of scene it was made for
+    pub buffer: Option<(BufferId, u32, u32)>,
+    // set for the frame the graph should copy the scene into the buffer
+    pub copy_requested: bool,
+    requested: bool,
+    captured: Option<RgbaImage>,
+}
+
+impl Screenshot {
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// The last screenshot taken, if it hasn't been taken already
+    pub fn take(&mut self) -> Option<RgbaImage> {
+        self.captured.take()
+    }
+}
+
+/// Rows copied out of a texture have to be aligned to 256 bytes
+pub fn bytes_per_row(width: u32) -> u32 {
+    (width * 4 + 255) / 256 * 256
+}
+
+// Reads back the scene copied last frame, and has the graph copy it again when asked
+pub fn read_screenshot(
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    target: Res<SceneTarget>,
+    mut screenshot: ResMut<Screenshot>,
+) {
+    let render_resource_context = &**render_resource_context;
+
+    if screenshot.copy_requested {
+        screenshot.copy_requested = false;
+        if let Some((buffer, width, height)) = screenshot.buffer {
+            let stride = bytes_per_row(width) as usize;
+            let size = (stride * height as usize) as u64;
+            let image = RefCell::new(RgbaImage::new(width, height));
+            render_resource_context.map_buffer(buffer, BufferMapMode::Read);
+            render_resource_context.read_mapped_buffer(buffer, 0..size, &|data, _| {
+                let mut image = image.borrow_mut();
+                for (y, row) in data.chunks(stride).enumerate() {
+                    for x in 0..width as usize {
+                        // the scene texture is bgra with srgb encoding, which is what pngs want
+                        let pixel = &row[x * 4..x * 4 + 4];
+                        image.put_pixel(
+                            x as u32,
+                            y as u32,
+                            image::Rgba([pixel[2], pixel[1], pixel[0], 255]),
+                        );
+                    }
+                }
+            });
+            render_resource_context.unmap_buffer(buffer);
+            screenshot.captured = Some(image.into_inner());
+        }
+    }
+
+    if !screenshot.requested || target.texture.is_none() {
+        return;
+    }
+    screenshot.requested = false;
+    // the buffer is made again whenever the window has been resized since the last one
+    match screenshot.buffer {
+        Some((_, width, height)) if width == target.width && height == target.height => {}
+        previous => {
+            if let Some((buffer, _, _)) = previous {
+                render_resource_context.remove_buffer(buffer);
+            }
+            let buffer = render_resource_context.create_buffer(BufferInfo {
+                size: (bytes_per_row(target.width) * target.height) as usize,
+                buffer_usage: BufferUsage::COPY_DST | BufferUsage::MAP_READ,
+                mapped_at_creation: false,
+            });
+            screenshot.buffer = Some((buffer, target.width, target.height));
+        }
+    }
+    screenshot.copy_requested = true;
+}
@@ -0,0 +1,70 @@
+use std::{env, path::PathBuf};
+
+use bevy::{
+    app::PluginGroupBuilder,
+    log::{LogPlugin, LogSettings},
+    prelude::*,
+    utils::tracing::subscriber,
+};
+use tracing_chrome::ChromeLayerBuilder;
+use tracing_subscriber::{prelude::*, registry::Registry, EnvFilter};
+
+/// The file to write a Chrome trace to, given on the command line as `--trace out.json`
+pub fn trace_path() -> Option<PathBuf> {
+    let mut args = env::args().skip_while(|arg| arg != "--trace");
+    args.next()?;
+    match args.next() {
+        Some(path) => Some(path.into()),
+        None => {
+            eprintln!("--trace needs a file to write to, e.g. --trace out.json");
+            None
+        }
+    }
+}
+
+/// Swaps bevy's logging for `ChromeTracePlugin` when there's a trace to record.
+/// Use with `add_plugins_with`.
+pub fn trace_to_file(
+    group: &mut PluginGroupBuilder,
+    path: Option<PathBuf>,
+) -> &mut PluginGroupBuilder {
+    match path {
+        Some(path) => group
+            .disable::<LogPlugin>()
+            .add_after::<LogPlugin, ChromeTracePlugin>(ChromeTracePlugin { path }),
+        None => group,
+    }
+}
+
+/// Logs to the terminal like bevy's `LogPlugin`, while also recording every span into a
+/// trace that can be opened in chrome://tracing or Perfetto.
+/// Build with the `trace` feature to get a span for each system as well.
+pub struct ChromeTracePlugin {
+    path: PathBuf,
+}
+
+impl Plugin for ChromeTracePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let default_filter = {
+            let settings = app
+                .world_mut()
+                .get_resource_or_insert_with(LogSettings::default);
+            format!("{},{}", settings.level, settings.filter)
+        };
+        let filter_layer = EnvFilter::try_from_default_env()
+            .or_else(|_| EnvFilter::try_new(&default_filter))
+            .unwrap();
+
+        let (chrome_layer, guard) = ChromeLayerBuilder::new().file(self.path.clone()).build();
+        // the trace is only written out once the guard is dropped, as the app shuts down
+        app.insert_non_send_resource(guard);
+
+        let subscriber = Registry::default()
+            .with(filter_layer)
+            .with(tracing_subscriber::fmt::Layer::default())
+            .with(chrome_layer);
+        subscriber::set_global_default(subscriber)
+            .expect("Could not set the global tracing subscriber");
+        info!("Recording a trace to {:?}", self.path);
+    }
+}
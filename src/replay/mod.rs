@@ -0,0 +1,209 @@
+use std::{fs, path::Path};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{RigidBodyPosition, RigidBodyVelocity};
+use color_eyre::Report;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error_log::ErrorLog,
+    first_person::{self, MouseState},
+    terrain, Player,
+};
+
+const REPLAY_PATH: &str = "replays/path.ron";
+// seconds between each sample of the player's position and look
+const SAMPLE_INTERVAL: f32 = 0.1;
+const RECORD_KEY: KeyCode = KeyCode::F9;
+const PLAY_KEY: KeyCode = KeyCode::F10;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ReplayState>()
+            .add_system(toggle.system().label("replay::toggle"))
+            .add_system(record.system().after("replay::toggle"))
+            .add_system(play.system().after("replay::toggle").before("player::look"));
+    }
+}
+
+/// A path the player took through a world, sampled at a fixed interval
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Replay {
+    pub seed: u32,
+    pub interval: f32,
+    pub samples: Vec<ReplaySample>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+pub struct ReplaySample {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, Report> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    // Blends between the two samples either side of a time into the replay
+    fn sample_at(&self, time: f32) -> Option<ReplaySample> {
+        let at = time / self.interval;
+        let index = at as usize;
+        let from = self.samples.get(index)?;
+        let to = self.samples.get(index + 1).unwrap_or(from);
+        let t = at.fract();
+
+        let position = Vec3::from(from.position).lerp(Vec3::from(to.position), t);
+        Some(ReplaySample {
+            position: position.into(),
+            yaw: from.yaw + (to.yaw - from.yaw) * t,
+            pitch: from.pitch + (to.pitch - from.pitch) * t,
+        })
+    }
+}
+
+pub enum ReplayState {
+    Idle,
+    Recording { replay: Replay, since_last: f32 },
+    Playing { replay: Replay, elapsed: f32 },
+}
+
+impl Default for ReplayState {
+    fn default() -> Self {
+        ReplayState::Idle
+    }
+}
+
+// F9 starts and stops recording, F10 starts and stops playing back the last recording
+fn toggle(
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<ReplayState>,
+    mut errors: ResMut<ErrorLog>,
+    mut terrain_config: ResMut<terrain::Config>,
+) {
+    if keys.just_pressed(RECORD_KEY) {
+        *state = match std::mem::take(&mut *state) {
+            ReplayState::Recording { replay, .. } => {
+                match replay.save(REPLAY_PATH) {
+                    Ok(()) => info!(
+                        "Saved replay of {} samples to {}",
+                        replay.samples.len(),
+                        REPLAY_PATH
+                    ),
+                    Err(error) => errors.report(format!("Failed to write replay: {}", error)),
+                }
+                ReplayState::Idle
+            }
+            _ => {
+                info!("Recording replay");
+                ReplayState::Recording {
+                    replay: Replay {
+                        seed: terrain_config.seed(),
+                        interval: SAMPLE_INTERVAL,
+                        samples: Vec::new(),
+                    },
+                    // take the first sample straight away
+                    since_last: SAMPLE_INTERVAL,
+                }
+            }
+        };
+    }
+
+    if keys.just_pressed(PLAY_KEY) {
+        *state = match std::mem::take(&mut *state) {
+            ReplayState::Playing { .. } => {
+                info!("Stopped replay");
+                ReplayState::Idle
+            }
+            _ => match Replay::load(REPLAY_PATH) {
+                Ok(replay) => {
+                    // the same path over a different world isn't much use for reproducing bugs
+                    if terrain_config.seed() != replay.seed {
+                        terrain_config.set_seed(replay.seed);
+                    }
+                    info!("Playing replay of {} samples", replay.samples.len());
+                    ReplayState::Playing {
+                        replay,
+                        elapsed: 0.0,
+                    }
+                }
+                Err(error) => {
+                    errors.report(format!("Failed to load replay {}: {}", REPLAY_PATH, error));
+                    ReplayState::Idle
+                }
+            },
+        };
+    }
+}
+
+fn record(
+    time: Res<Time>,
+    mouse: Res<MouseState>,
+    mut state: ResMut<ReplayState>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let (replay, since_last) = match &mut *state {
+        ReplayState::Recording { replay, since_last } => (replay, since_last),
+        _ => return,
+    };
+    let transform = match player_query.iter().next() {
+        Some(transform) => transform,
+        None => return,
+    };
+
+    *since_last += time.delta_seconds();
+    while *since_last >= SAMPLE_INTERVAL {
+        *since_last -= SAMPLE_INTERVAL;
+        replay.samples.push(ReplaySample {
+            position: transform.translation.into(),
+            yaw: mouse.yaw,
+            pitch: mouse.pitch,
+        });
+    }
+}
+
+// Moves the player along the recorded path, overriding both their movement and their look
+fn play(
+    time: Res<Time>,
+    mut mouse: ResMut<MouseState>,
+    mut state: ResMut<ReplayState>,
+    mut player_query: Query<(&mut RigidBodyPosition, &mut RigidBodyVelocity), With<Player>>,
+) {
+    let (replay, elapsed) = match &mut *state {
+        ReplayState::Playing { replay, elapsed } => (replay, elapsed),
+        _ => return,
+    };
+
+    *elapsed += time.delta_seconds();
+    let sample = match replay.sample_at(*elapsed) {
+        Some(sample) => sample,
+        None => {
+            info!("Replay finished after {:.1}s", elapsed);
+            *state = ReplayState::Idle;
+            return;
+        }
+    };
+
+    for (mut position, mut velocity) in player_query.iter_mut() {
+        first_person::teleport(&mut position, &mut velocity, sample.position.into());
+    }
+    mouse.yaw = sample.yaw;
+    mouse.pitch = sample.pitch;
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use color_eyre::Report;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::PlacedPiece,
+    collectibles::CollectibleId,
+    error_log::ErrorLog,
+    scatter::{Inventory, PropId},
+    triggers::TriggerVolume,
+};
+
+const SAVE_PATH: &str = "saves/world.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let save = {
+            let mut errors = app
+                .world_mut()
+                .get_resource_or_insert_with(ErrorLog::default);
+            WorldSave::load_or_default(SAVE_PATH, &mut errors)
+        };
+        app.insert_resource(save)
+            .add_system_to_stage(CoreStage::Last, write_on_change.system());
+    }
+}
+
+/// Everything the player has changed about the world, persisted between runs
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct WorldSave {
+    pub collected: HashSet<CollectibleId>,
+    // placed campfire positions, keyed by the chunk they sit in
+    #[serde(default)]
+    pub campfires: HashMap<(i32, i32), Vec<[f32; 3]>>,
+    // trees and rocks that have been chopped down or mined away
+    #[serde(default)]
+    pub harvested: HashSet<PropId>,
+    #[serde(default)]
+    pub inventory: Inventory,
+    // pieces placed in build mode, keyed by the chunk they sit in
+    #[serde(default)]
+    pub built: HashMap<(i32, i32), Vec<PlacedPiece>>,
+    // trigger volumes keyed by the chunk they sit in, which can be written in by hand
+    #[serde(default)]
+    pub triggers: HashMap<(i32, i32), Vec<TriggerVolume>>,
+}
+
+impl WorldSave {
+    pub fn load(path: impl AsRef<Path>) -> Result<WorldSave, Report> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    fn load_or_default(path: impl AsRef<Path>, errors: &mut ErrorLog) -> WorldSave {
+        let path = path.as_ref();
+        if !path.exists() {
+            return WorldSave::default();
+        }
+
+        WorldSave::load(path).unwrap_or_else(|error| {
+            errors.report(format!("Failed to load world save {:?}: {}", path, error));
+            WorldSave::default()
+        })
+    }
+}
+
+fn write_on_change(save: Res<WorldSave>, mut errors: ResMut<ErrorLog>) {
+    if save.is_changed() && !save.is_added() {
+        if let Err(error) = save.save(SAVE_PATH) {
+            errors.report(format!("Failed to write world save: {}", error));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+use super::{Prop, PropKind};
+use crate::{
+    first_person::{MovementConfig, PlayerEyes},
+    locale::Locale,
+    particles::{GroundContact, ParticleBurstEvent},
+    save::WorldSave,
+};
+
+/// What the player has gathered from the props they've harvested
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy)]
+pub struct Inventory {
+    pub wood: u32,
+    pub stone: u32,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct HarvestConfig {
+    // how far away a prop can be chopped or mined
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.5))]
+    pub reach: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1))]
+    pub tree_hits: u32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1))]
+    pub rock_hits: u32,
+}
+
+impl Default for HarvestConfig {
+    fn default() -> Self {
+        Self {
+            reach: 6.0,
+            tree_hits: 3,
+            rock_hits: 4,
+        }
+    }
+}
+
+/// The prop under the crosshair and within reach, which the interact key harvests
+/// instead of doing anything else
+#[derive(Default)]
+pub struct HarvestTarget(pub Option<Entity>);
+
+// Props don't have colliders, so the eyes' ray is tested against a sphere around each one
+pub fn target(
+    config: Res<HarvestConfig>,
+    mut target: ResMut<HarvestTarget>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    props_query: Query<(Entity, &Prop, &Transform, &Visible)>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes,
+        None => return,
+    };
+    let origin = eyes.translation;
+    let direction = eyes.rotation * -Vec3::Z;
+
+    let hit = props_query
+        .iter()
+        .filter(|(_, _, _, visible)| visible.is_visible)
+        .filter_map(|(entity, prop, transform, _)| {
+            let radius = prop.id.kind.radius() * transform.scale.max_element();
+            let to_centre = transform.translation - origin;
+            let along = to_centre.dot(direction);
+            let miss = (to_centre - direction * along).length();
+            if along < 0.0 || miss > radius {
+                return None;
+            }
+            let distance = along - (radius * radius - miss * miss).sqrt();
+            Some((entity, distance))
+        })
+        .filter(|&(_, distance)| distance <= config.reach)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+        .map(|(entity, _)| entity);
+    if target.0 != hit {
+        target.0 = hit;
+    }
+}
+
+// Takes a hit out of the targeted prop, removing it for good and gathering it once it's
+// taken enough
+pub fn harvest(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<MovementConfig>,
+    harvest_config: Res<HarvestConfig>,
+    target: Res<HarvestTarget>,
+    mut save: ResMut<WorldSave>,
+    mut bursts: EventWriter<ParticleBurstEvent>,
+    mut props_query: Query<(&mut Prop, &Transform)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !window.cursor_locked() || !config.map.interact.iter().any(|&k| keys.just_pressed(k)) {
+        return;
+    }
+    let entity = match target.0 {
+        Some(entity) => entity,
+        None => return,
+    };
+    let (mut prop, transform) = match props_query.get_mut(entity) {
+        Ok(prop) => prop,
+        Err(_) => return,
+    };
+
+    let (color, needed) = match prop.id.kind {
+        PropKind::Tree => (Color::rgb(0.45, 0.3, 0.15), harvest_config.tree_hits),
+        PropKind::Rock => (Color::rgb(0.5, 0.48, 0.45), harvest_config.rock_hits),
+    };
+    prop.hits += 1;
+    let felled = prop.hits >= needed;
+    bursts.send(ParticleBurstEvent {
+        position: transform.translation,
+        color,
+        count: if felled { 25 } else { 6 },
+        speed: 4.0,
+        size: 0.2,
+        lifetime: 0.8,
+        gravity: 9.8,
+        ground: GroundContact::Bounce,
+        wind: 0.0,
+    });
+    if !felled {
+        return;
+    }
+
+    commands.entity(entity).despawn();
+    save.harvested.insert(prop.id);
+    match prop.id.kind {
+        PropKind::Tree => save.inventory.wood += 1,
+        PropKind::Rock => save.inventory.stone += 1,
+    }
+}
+
+pub fn inventory_hud(egui_context: Res<EguiContext>, locale: Res<Locale>, save: Res<WorldSave>) {
+    egui::Area::new("inventory_counter")
+        .anchor(egui::Align2::RIGHT_TOP, [-20.0, 44.0])
+        .show(egui_context.ctx(), |ui| {
+            ui.label(locale.format(
+                "hud-inventory",
+                &[
+                    ("wood", save.inventory.wood.into()),
+                    ("stone", save.inventory.stone.into()),
+                ],
+            ));
+        });
+}
@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use self::{harvest::HarvestConfig, placement::SpatialHash};
+use crate::{
+    bake::BakedWorld,
+    first_person::PlayerEyes,
+    save::WorldSave,
+    settings::{AddSettings, SettingsTab},
+    terrain::{self, query, scatter, Biome, ChunkCoords, SeenChunks, CHUNK_SIZE},
+};
+
+pub use self::harvest::{HarvestTarget, Inventory};
+
+// the tree mesh's trunk and canopy, for giving placed trees a matching collider
+pub const TREE_RADIUS: f32 = 1.5;
+pub const TREE_DEPTH: f32 = 4.0;
+
+mod harvest;
+mod placement;
+
+pub struct ScatterPlugin;
+
+impl Plugin for ScatterPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<ScatterConfig>(SettingsTab::World, "Scatter")
+            .add_settings::<HarvestConfig>(SettingsTab::Player, "Harvesting")
+            .init_resource::<ScatteredChunks>()
+            .init_resource::<HarvestTarget>()
+            .add_startup_system(setup.system())
+            .add_system(scatter_near_player.system().label("scatter::spawn"))
+            .add_system(despawn_unloaded.system())
+            .add_system(reset_on_terrain_change.system())
+            .add_system(fade.system().label("scatter::fade").after("scatter::spawn"))
+            .add_system(
+                harvest::target
+                    .system()
+                    .label("scatter::target")
+                    .after("scatter::fade"),
+            )
+            .add_system(harvest::harvest.system().after("scatter::target"))
+            .add_system(harvest::inventory_hud.system());
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PropKind {
+    Tree,
+    Rock,
+}
+
+impl PropKind {
+    const ALL: [PropKind; 2] = [PropKind::Tree, PropKind::Rock];
+
+    // keeps each kind of prop from landing on the same spots as other scattered objects
+    fn salt(self) -> u64 {
+        match self {
+            PropKind::Tree => 2,
+            PropKind::Rock => 3,
+        }
+    }
+
+    /// Where the middle of the prop sits and how it's scaled, stood on a point of the ground
+    pub fn standing_on(self, ground: Vec3, size: f32) -> (Vec3, Vec3) {
+        match self {
+            PropKind::Tree => (ground + Vec3::Y * 3.5 * size, Vec3::splat(size)),
+            PropKind::Rock => (ground, Vec3::new(1.4, 0.8, 1.2) * size),
+        }
+    }
+
+    // roughly how far the prop reaches from its centre at full size
+    fn radius(self) -> f32 {
+        match self {
+            PropKind::Tree => 2.5,
+            PropKind::Rock => 1.0,
+        }
+    }
+
+    fn grows_on(self, biome: Biome) -> bool {
+        match self {
+            PropKind::Tree => matches!(biome, Biome::Lowland | Biome::Forest),
+            PropKind::Rock => matches!(biome, Biome::Sand | Biome::Lowland | Biome::Rock),
+        }
+    }
+}
+
+/// Identifies a prop by the chunk it was scattered in and its index among its kind there
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PropId {
+    pub chunk_x: i32,
+    pub chunk_y: i32,
+    pub kind: PropKind,
+    pub index: u32,
+}
+
+impl PropId {
+    pub fn chunk(&self) -> ChunkCoords {
+        ChunkCoords {
+            x: self.chunk_x,
+            y: self.chunk_y,
+        }
+    }
+}
+
+/// A tree or rock scattered over the terrain
+pub struct Prop {
+    pub id: PropId,
+    // the scale the prop is drawn at when close enough not to be fading out
+    full_scale: Vec3,
+    // how many times it's been chopped or mined
+    hits: u32,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, Debug)]
+pub struct PropConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(max = 200))]
+    pub per_chunk: usize,
+    // props are only shown within this distance of the player
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub view_distance: f32,
+    // the distance, inside the view distance, over which props shrink away to nothing
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub fade_distance: f32,
+    // radius kept clear of other props, which can't be wider than a spatial hash cell
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 8.0))]
+    pub clearance: f32,
+    // how far from campfires and other structures props keep
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub structure_clearance: f32,
+    // how far above the sea the ground has to be, keeping props off the shoreline
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub water_margin: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 90.0))]
+    pub max_slope_degrees: f32,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct ScatterConfig {
+    pub trees: PropConfig,
+    pub rocks: PropConfig,
+}
+
+impl Default for ScatterConfig {
+    fn default() -> Self {
+        Self {
+            trees: PropConfig {
+                per_chunk: 60,
+                view_distance: 400.0,
+                fade_distance: 60.0,
+                clearance: 3.0,
+                structure_clearance: 4.0,
+                water_margin: 2.0,
+                max_slope_degrees: 35.0,
+            },
+            rocks: PropConfig {
+                per_chunk: 30,
+                view_distance: 200.0,
+                fade_distance: 30.0,
+                clearance: 2.0,
+                structure_clearance: 3.0,
+                water_margin: 0.0,
+                max_slope_degrees: 60.0,
+            },
+        }
+    }
+}
+
+impl ScatterConfig {
+    pub fn prop(&self, kind: PropKind) -> &PropConfig {
+        match kind {
+            PropKind::Tree => &self.trees,
+            PropKind::Rock => &self.rocks,
+        }
+    }
+}
+
+pub struct PropAssets {
+    tree_mesh: Handle<Mesh>,
+    tree_material: Handle<StandardMaterial>,
+    rock_mesh: Handle<Mesh>,
+    rock_material: Handle<StandardMaterial>,
+}
+
+impl PropAssets {
+    pub fn get(&self, kind: PropKind) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        match kind {
+            PropKind::Tree => (self.tree_mesh.clone(), self.tree_material.clone()),
+            PropKind::Rock => (self.rock_mesh.clone(), self.rock_material.clone()),
+        }
+    }
+}
+
+// Chunks that have already had their props placed, with where everything in them was put
+#[derive(Default)]
+struct ScatteredChunks(HashMap<ChunkCoords, SpatialHash>);
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(PropAssets {
+        tree_mesh: meshes.add(Mesh::from(shape::Capsule {
+            radius: TREE_RADIUS,
+            depth: TREE_DEPTH,
+            ..Default::default()
+        })),
+        tree_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.13, 0.4, 0.15),
+            ..Default::default()
+        }),
+        rock_mesh: meshes.add(Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 1,
+        })),
+        rock_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.45, 0.43, 0.4),
+            ..Default::default()
+        }),
+    });
+}
+
+// Places the props of every loaded chunk that's come within view of the player
+fn scatter_near_player(
+    mut commands: Commands,
+    config: Res<ScatterConfig>,
+    terrain_config: Res<terrain::Config>,
+    assets: Res<PropAssets>,
+    save: Res<WorldSave>,
+    baked: Res<BakedWorld>,
+    seen_chunks: Res<SeenChunks>,
+    mut scattered_chunks: ResMut<ScatteredChunks>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation.xz(),
+        None => return,
+    };
+    let view_distance = PropKind::ALL
+        .iter()
+        .map(|&kind| config.prop(kind).view_distance)
+        .fold(0.0, f32::max);
+    // the furthest a point in a chunk can be from its centre
+    let chunk_reach = CHUNK_SIZE as f32 * std::f32::consts::FRAC_1_SQRT_2;
+
+    for &coords in seen_chunks.0.keys() {
+        if coords.to_position().distance(eyes) > view_distance + chunk_reach
+            || scattered_chunks.0.contains_key(&coords)
+        {
+            continue;
+        }
+
+        // campfires are the only structures so far, there are no roads to keep clear of
+        let structures: Vec<Vec2> =
+            save.campfires
+                .get(&(coords.x, coords.y))
+                .map_or_else(Vec::new, |positions| {
+                    positions
+                        .iter()
+                        .map(|&position| Vec3::from(position).xz())
+                        .collect()
+                });
+        let placed = scattered_chunks.0.entry(coords).or_default();
+
+        for Candidate {
+            kind,
+            index,
+            point,
+            size,
+        } in baked
+            .0
+            .as_ref()
+            .and_then(|archive| archive.candidates(&config, &terrain_config, coords))
+            .unwrap_or_else(|| candidates(&config, &terrain_config, coords))
+        {
+            let prop_config = config.prop(kind);
+            let clearance = prop_config.clearance * size;
+            if structures.iter().any(|structure| {
+                structure.distance(point.xz()) < prop_config.structure_clearance + clearance
+            }) || placed.overlaps(point.xz(), clearance)
+            {
+                continue;
+            }
+            placed.insert(point.xz(), clearance);
+
+            // harvested props keep their spot, so the rest of the chunk is laid out the same
+            let id = PropId {
+                chunk_x: coords.x,
+                chunk_y: coords.y,
+                kind,
+                index,
+            };
+            if save.harvested.contains(&id) {
+                continue;
+            }
+
+            let (mesh, material) = assets.get(kind);
+            let (translation, full_scale) = kind.standing_on(point, size);
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: Transform {
+                        translation,
+                        scale: Vec3::ZERO,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(Prop {
+                    id,
+                    full_scale,
+                    hits: 0,
+                });
+        }
+    }
+}
+
+/// A spot in a chunk a prop could stand on, going by the ground alone. Structures, the other
+/// props and harvesting are only checked as it's placed.
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate {
+    pub kind: PropKind,
+    // its index among the points scattered for its kind, which identifies it once placed
+    pub index: u32,
+    pub point: Vec3,
+    pub size: f32,
+}
+
+/// Every spot in a chunk a prop could go, in the order they're placed
+pub fn candidates(
+    config: &ScatterConfig,
+    terrain_config: &terrain::Config,
+    coords: ChunkCoords,
+) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for &kind in PropKind::ALL.iter() {
+        let prop_config = config.prop(kind);
+        // a different salt to the points, so sizes don't follow where the props land
+        let mut rng = scatter::chunk_rng(terrain_config, coords, !kind.salt());
+        let points =
+            scatter::scatter_points(terrain_config, coords, kind.salt(), prop_config.per_chunk);
+
+        for (index, point) in points.into_iter().enumerate() {
+            let size = rng.gen_range(0.7..1.3);
+            let slope = query::normal_at(terrain_config, point.xz())
+                .angle_between(Vec3::Y)
+                .to_degrees();
+            if point.y < terrain_config.sea_level() + prop_config.water_margin
+                || slope > prop_config.max_slope_degrees
+                || !kind.grows_on(query::biome_at(terrain_config, point.xz()))
+            {
+                continue;
+            }
+            candidates.push(Candidate {
+                kind,
+                index: index as u32,
+                point,
+                size,
+            });
+        }
+    }
+    candidates
+}
+
+fn despawn_unloaded(
+    mut commands: Commands,
+    seen_chunks: Res<SeenChunks>,
+    mut scattered_chunks: ResMut<ScatteredChunks>,
+    props_query: Query<(Entity, &Prop)>,
+) {
+    for (entity, prop) in props_query.iter() {
+        if !seen_chunks.contains_key(&prop.id.chunk()) {
+            commands.entity(entity).despawn();
+        }
+    }
+    scattered_chunks
+        .0
+        .retain(|coords, _| seen_chunks.contains_key(coords));
+}
+
+// The terrain moves under the props when its config changes, so scatter them again
+fn reset_on_terrain_change(
+    mut commands: Commands,
+    terrain_config: Res<terrain::Config>,
+    mut scattered_chunks: ResMut<ScatteredChunks>,
+    props_query: Query<Entity, With<Prop>>,
+) {
+    if !terrain_config.is_changed() || terrain_config.is_added() {
+        return;
+    }
+
+    for entity in props_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    scattered_chunks.0.clear();
+}
+
+// Shrinks props away as they near the edge of their view distance, rather than popping
+fn fade(
+    config: Res<ScatterConfig>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    mut props_query: Query<(&Prop, &mut Transform, &mut Visible)>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation,
+        None => return,
+    };
+
+    for (prop, mut transform, mut visible) in props_query.iter_mut() {
+        let prop_config = config.prop(prop.id.kind);
+        let distance = transform.translation.distance(eyes);
+        let amount = ((prop_config.view_distance - distance)
+            / prop_config.fade_distance.max(f32::EPSILON))
+        .clamp(0.0, 1.0);
+        // ease out so the last of the shrinking isn't rushed
+        let amount = amount * amount * (3.0 - 2.0 * amount);
+
+        let shown = amount > 0.0;
+        if visible.is_visible != shown {
+            visible.is_visible = shown;
+        }
+        let scale = prop.full_scale * amount;
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+    }
+}
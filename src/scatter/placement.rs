@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use bevy::math::Vec2;
+
+// width of each cell, about the largest gap anything needs to keep from its neighbours
+const CELL_SIZE: f32 = 8.0;
+
+/// Whatever has been placed in a chunk, bucketed into cells for finding what's nearby
+#[derive(Default)]
+pub struct SpatialHash {
+    cells: HashMap<(i32, i32), Vec<(Vec2, f32)>>,
+}
+
+impl SpatialHash {
+    fn cell(point: Vec2) -> (i32, i32) {
+        let cell = (point / CELL_SIZE).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    /// Records something taking up a circle of the given radius
+    pub fn insert(&mut self, point: Vec2, radius: f32) {
+        self.cells
+            .entry(SpatialHash::cell(point))
+            .or_default()
+            .push((point, radius));
+    }
+
+    /// Whether a circle would overlap anything already in the hash
+    pub fn overlaps(&self, point: Vec2, radius: f32) -> bool {
+        // entries can reach into neighbouring cells by up to their own radius
+        let reach = (radius / CELL_SIZE).ceil() as i32 + 1;
+        let (cell_x, cell_y) = SpatialHash::cell(point);
+
+        (cell_x - reach..=cell_x + reach).any(|x| {
+            (cell_y - reach..=cell_y + reach).any(|y| {
+                self.cells.get(&(x, y)).map_or(false, |entries| {
+                    entries
+                        .iter()
+                        .any(|&(other, other_radius)| other.distance(point) < radius + other_radius)
+                })
+            })
+        })
+    }
+}
@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use bevy_egui::{
+    egui::{self, Color32, Stroke},
+    EguiContext, EguiInput, EguiSettings,
+};
+
+use super::PlayerSettings;
+use crate::locale::Locale;
+
+#[derive(Clone, PartialEq)]
+pub struct AccessibilityConfig {
+    // scales the settings window and the HUD together
+    pub ui_scale: f32,
+    // white text and thick outlines on black, for reading the HUD over bright terrain
+    pub high_contrast: bool,
+    // moves between the menu's controls with the d-pad, pressing them with the south button
+    pub gamepad_navigation: bool,
+}
+
+impl Default for AccessibilityConfig {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            high_contrast: false,
+            gamepad_navigation: true,
+        }
+    }
+}
+
+impl PlayerSettings for AccessibilityConfig {
+    fn ui(&mut self, ui: &mut egui::Ui, locale: &Locale) {
+        ui.add(
+            egui::Slider::new(&mut self.ui_scale, 0.5..=3.0).text(locale.text("settings-ui-scale")),
+        );
+        ui.checkbox(
+            &mut self.high_contrast,
+            locale.text("settings-high-contrast"),
+        );
+        ui.checkbox(
+            &mut self.gamepad_navigation,
+            locale.text("settings-gamepad-navigation"),
+        );
+    }
+}
+
+pub fn apply_ui_scale(config: Res<AccessibilityConfig>, mut egui_settings: ResMut<EguiSettings>) {
+    if config.is_changed() {
+        egui_settings.scale_factor = config.ui_scale as f64;
+    }
+}
+
+pub fn apply_theme(config: Res<AccessibilityConfig>, egui_context: Res<EguiContext>) {
+    if !config.is_changed() {
+        return;
+    }
+
+    let visuals = if config.high_contrast {
+        high_contrast()
+    } else {
+        egui::Visuals::dark()
+    };
+    egui_context.ctx().set_visuals(visuals);
+}
+
+fn high_contrast() -> egui::Visuals {
+    let mut visuals = egui::Visuals::dark();
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.widgets.noninteractive.bg_fill = Color32::BLACK;
+    visuals.widgets.noninteractive.bg_stroke = Stroke::new(2.0, Color32::WHITE);
+    for widget in [
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+    ]
+    .iter_mut()
+    {
+        widget.bg_fill = Color32::BLACK;
+        widget.bg_stroke = Stroke::new(2.0, Color32::WHITE);
+        widget.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+    }
+    // whatever has keyboard focus or is selected stands out in yellow
+    visuals.widgets.hovered.bg_stroke = Stroke::new(3.0, Color32::YELLOW);
+    visuals.selection.bg_fill = Color32::from_rgb(120, 100, 0);
+    visuals.selection.stroke = Stroke::new(2.0, Color32::YELLOW);
+    visuals
+}
+
+// egui already moves focus with tab and presses the focused control with enter, so the
+// gamepad's buttons are handed to it as those keys
+pub fn gamepad_navigation(
+    config: Res<AccessibilityConfig>,
+    buttons: Res<Input<GamepadButton>>,
+    mut egui_input: ResMut<EguiInput>,
+) {
+    if !config.gamepad_navigation {
+        return;
+    }
+
+    for GamepadButton(_, button) in buttons.get_just_pressed() {
+        let (key, shift) = match button {
+            GamepadButtonType::DPadDown | GamepadButtonType::DPadRight => (egui::Key::Tab, false),
+            GamepadButtonType::DPadUp | GamepadButtonType::DPadLeft => (egui::Key::Tab, true),
+            GamepadButtonType::South => (egui::Key::Enter, false),
+            GamepadButtonType::East => (egui::Key::Escape, false),
+            _ => continue,
+        };
+        let modifiers = egui::Modifiers {
+            shift,
+            ..Default::default()
+        };
+        for &pressed in [true, false].iter() {
+            egui_input.raw_input.events.push(egui::Event::Key {
+                key,
+                pressed,
+                modifiers,
+            });
+        }
+    }
+}
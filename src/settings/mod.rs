@@ -0,0 +1,240 @@
+use bevy::{ecs::component::Component, prelude::*};
+use bevy_egui::{
+    egui::{self, CtxRef},
+    EguiContext, EguiSystem,
+};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::{Context, Inspectable};
+
+use crate::locale::Locale;
+
+use self::accessibility::AccessibilityConfig;
+
+mod accessibility;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.world_mut()
+            .get_resource_or_insert_with(Settings::default);
+        app.add_system(settings_window.exclusive_system())
+            .add_player_settings::<AccessibilityConfig>(SettingsTab::Graphics, "Accessibility")
+            .add_system(accessibility::apply_ui_scale.system())
+            .add_system(accessibility::apply_theme.system())
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                accessibility::gamepad_navigation
+                    .system()
+                    .after(EguiSystem::ProcessInput)
+                    .before(EguiSystem::BeginFrame),
+            );
+    }
+}
+
+/// The tabs of the settings window, each gathering the configs of related plugins
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingsTab {
+    World,
+    Graphics,
+    Player,
+    Debug,
+}
+
+impl SettingsTab {
+    const ALL: [SettingsTab; 4] = [
+        SettingsTab::World,
+        SettingsTab::Graphics,
+        SettingsTab::Player,
+        SettingsTab::Debug,
+    ];
+
+    fn message_id(&self) -> &'static str {
+        match self {
+            SettingsTab::World => "settings-tab-world",
+            SettingsTab::Graphics => "settings-tab-graphics",
+            SettingsTab::Player => "settings-tab-player",
+            SettingsTab::Debug => "settings-tab-debug",
+        }
+    }
+}
+
+struct SettingsPanel {
+    tab: SettingsTab,
+    name: &'static str,
+    show: fn(&mut egui::Ui, &mut World, &CtxRef, &Locale),
+}
+
+impl SettingsPanel {
+    // "Clear colour" is looked up as settings-panel-clear-colour
+    fn message_id(&self) -> String {
+        let words: Vec<String> = self
+            .name
+            .split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect()
+            })
+            .collect();
+        format!("settings-panel-{}", words.join("-"))
+    }
+}
+
+pub struct Settings {
+    tab: SettingsTab,
+    panels: Vec<SettingsPanel>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tab: SettingsTab::World,
+            panels: Vec::new(),
+        }
+    }
+}
+
+/// A config resource the inspector can draw. Without the dev-tools feature there's no
+/// inspector, and any resource will do.
+#[cfg(feature = "dev-tools")]
+pub trait DevSettings: Inspectable + FromWorld + Component {}
+#[cfg(feature = "dev-tools")]
+impl<T: Inspectable + FromWorld + Component> DevSettings for T {}
+#[cfg(not(feature = "dev-tools"))]
+pub trait DevSettings: FromWorld + Component {}
+#[cfg(not(feature = "dev-tools"))]
+impl<T: FromWorld + Component> DevSettings for T {}
+
+/// A config the player sets themselves, drawn by hand so it's in the settings window of
+/// every build, with or without the inspector
+pub trait PlayerSettings: FromWorld + Component + Clone + PartialEq {
+    fn ui(&mut self, ui: &mut egui::Ui, locale: &Locale);
+}
+
+pub trait AddSettings {
+    /// Adds an inspectable config resource to a tab of the settings window, inserting its
+    /// default value if it isn't there already. Builds without the dev-tools feature only
+    /// insert the resource.
+    fn add_settings<T: DevSettings>(&mut self, tab: SettingsTab, name: &'static str) -> &mut Self;
+
+    /// Adds a config resource the player sets to a tab of the settings window, inserting its
+    /// default value if it isn't there already
+    fn add_player_settings<T: PlayerSettings>(
+        &mut self,
+        tab: SettingsTab,
+        name: &'static str,
+    ) -> &mut Self;
+}
+
+impl AddSettings for AppBuilder {
+    fn add_settings<T: DevSettings>(&mut self, tab: SettingsTab, name: &'static str) -> &mut Self {
+        self.init_resource::<T>();
+        #[cfg(feature = "dev-tools")]
+        self.world_mut()
+            .get_resource_or_insert_with(Settings::default)
+            .panels
+            .push(SettingsPanel {
+                tab,
+                name,
+                show: show_resource::<T>,
+            });
+        #[cfg(not(feature = "dev-tools"))]
+        let _ = (tab, name);
+        self
+    }
+
+    fn add_player_settings<T: PlayerSettings>(
+        &mut self,
+        tab: SettingsTab,
+        name: &'static str,
+    ) -> &mut Self {
+        self.init_resource::<T>();
+        self.world_mut()
+            .get_resource_or_insert_with(Settings::default)
+            .panels
+            .push(SettingsPanel {
+                tab,
+                name,
+                show: show_player_settings::<T>,
+            });
+        self
+    }
+}
+
+fn show_player_settings<T: PlayerSettings>(
+    ui: &mut egui::Ui,
+    world: &mut World,
+    _: &CtxRef,
+    locale: &Locale,
+) {
+    world.resource_scope(|_, mut value: Mut<T>| {
+        // edit a copy, so the resource is only marked changed when something was
+        let mut edited = value.clone();
+        edited.ui(ui, locale);
+        if edited != *value {
+            *value = edited;
+        }
+    });
+}
+
+#[cfg(feature = "dev-tools")]
+fn show_resource<T: Inspectable + Component>(
+    ui: &mut egui::Ui,
+    world: &mut World,
+    ctx: &CtxRef,
+    _: &Locale,
+) {
+    world.resource_scope(|world, mut value: Mut<T>| {
+        // the inspector reaches back into the world for things like resource handles
+        // SAFETY: the pointer is taken from the world the scope lends out, which nothing else
+        // borrows while the inspector runs, and the value being shown is outside it for now
+        let context = unsafe { Context::new_ptr(Some(ctx), world as *mut World) };
+        value.ui(ui, T::Attributes::default(), &context);
+    });
+}
+
+fn settings_window(world: &mut World) {
+    // the egui context is cloned and the strings taken out of the world, so each panel can
+    // borrow its own resource out of it
+    let ctx = match world.get_resource::<EguiContext>() {
+        Some(egui_context) => egui_context.ctx().clone(),
+        None => return,
+    };
+    if world.get_resource::<Locale>().is_none() {
+        return;
+    }
+
+    world.resource_scope(|world, locale: Mut<Locale>| {
+        world.resource_scope(|world, mut settings: Mut<Settings>| {
+            show_settings(world, &mut settings, &ctx, &locale)
+        })
+    });
+}
+
+fn show_settings(world: &mut World, settings: &mut Settings, ctx: &CtxRef, locale: &Locale) {
+    let Settings { tab, panels } = settings;
+    egui::Window::new(locale.text("settings-title"))
+        .id(egui::Id::new("settings"))
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for &option in SettingsTab::ALL.iter() {
+                    ui.selectable_value(tab, option, locale.text(option.message_id()));
+                }
+            });
+            ui.separator();
+
+            egui::ScrollArea::auto_sized().show(ui, |ui| {
+                for panel in panels.iter().filter(|panel| panel.tab == *tab) {
+                    let heading = locale
+                        .get(&panel.message_id(), None)
+                        .unwrap_or_else(|| panel.name.to_string());
+                    egui::CollapsingHeader::new(heading)
+                        .id_source(panel.name)
+                        .default_open(false)
+                        .show(ui, |ui| (panel.show)(ui, world, ctx, locale));
+                }
+            });
+        });
+}
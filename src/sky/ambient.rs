@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+
+use super::{lerp_color, SkyConfig, Sun};
+use crate::weather::Precipitation;
+
+// how much of the ambient light a full storm blots out
+const STORM_DIMMING: f32 = 0.5;
+
+// Blends the light from the rest of the sky between night, sunset and noon, dimmed and greyed
+// by any rain or snow, for the lit objects and the terrain's tint alike
+pub fn update_ambient(
+    config: Res<SkyConfig>,
+    sun: Res<Sun>,
+    precipitation: Res<Precipitation>,
+    mut ambient: ResMut<AmbientLight>,
+) {
+    let elevation = sun.elevation();
+    let (color, brightness) = if elevation > 0.0 {
+        let day = (elevation / 0.3).min(1.0);
+        (
+            lerp_color(config.sunset_ambient, config.noon_ambient, day),
+            config.day_ambient_brightness,
+        )
+    } else {
+        let night = sun.night();
+        (
+            lerp_color(config.sunset_ambient, config.night_ambient, night),
+            config.day_ambient_brightness
+                + (config.night_ambient_brightness - config.day_ambient_brightness) * night,
+        )
+    };
+
+    let storm = precipitation.intensity.clamp(0.0, 1.0);
+    let grey = Color::rgb(0.5, 0.5, 0.5);
+    ambient.color = lerp_color(color, grey, storm * 0.6);
+    ambient.brightness = brightness * (1.0 - storm * STORM_DIMMING);
+}
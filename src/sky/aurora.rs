@@ -0,0 +1,127 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{BlendFactor, CullMode, PipelineDescriptor, RenderPipeline},
+        render_graph::{base, RenderGraph, RenderResourcesNode},
+        renderer::RenderResources,
+        shader::ShaderStages,
+    },
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{error_log::ErrorLog, first_person::PlayerEyes, terrain, TimeUniform};
+
+use super::{SkyConfig, Sun};
+
+pub const AURORA_NODE: &str = "aurora";
+pub const TIME_UNIFORM_NODE: &str = "time_uniform";
+
+// in front of the stars but behind the sun and moon
+const AURORA_DISTANCE: f32 = 4400.0;
+// latitudes, in degrees, between which the aurora goes from never seen to at its brightest
+const AURORA_LATITUDES: (f32, f32) = (25.0, 65.0);
+
+/// Ribbons of light hanging in the northern sky at night
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "e7a1c4d9-3f62-4b8e-a5d0-9c2b6f4e1a73"]
+pub struct Aurora {
+    pub intensity: f32,
+    // offsets the animation so each world's aurora moves differently
+    pub phase: f32,
+}
+
+pub fn add_aurora_graph(world: &mut World) {
+    let mut graph = world.get_resource_mut::<RenderGraph>().unwrap();
+    graph.add_system_node(AURORA_NODE, RenderResourcesNode::<Aurora>::new(true));
+    graph
+        .add_node_edge(AURORA_NODE, base::node::MAIN_PASS)
+        .unwrap();
+    graph.add_system_node(
+        TIME_UNIFORM_NODE,
+        RenderResourcesNode::<TimeUniform>::new(true),
+    );
+    graph
+        .add_node_edge(TIME_UNIFORM_NODE, base::node::MAIN_PASS)
+        .unwrap();
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    // shares the star dome's vertex shader, which hands on the direction into the sky
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: errors.load(&asset_server, "shaders/stars.vert"),
+        fragment: Some(errors.load(&asset_server, "shaders/aurora.frag")),
+    });
+    descriptor.primitive.cull_mode = CullMode::None;
+    descriptor.color_target_states[0].color_blend.dst_factor = BlendFactor::One;
+    descriptor
+        .depth_stencil
+        .as_mut()
+        .unwrap()
+        .depth_write_enabled = false;
+
+    commands
+        .spawn_bundle(MeshBundle {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: AURORA_DISTANCE,
+                subdivisions: 4,
+            })),
+            render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                pipelines.add(descriptor),
+            )]),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(Aurora::default())
+        .insert(TimeUniform::default());
+}
+
+// Keeps the aurora around the camera and brings it out at night, more so the further from
+// the equator the world is
+pub fn update(
+    config: Res<SkyConfig>,
+    terrain_config: Res<terrain::Config>,
+    sun: Res<Sun>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    mut aurora_query: Query<(&mut Aurora, &mut Transform, &mut Visible)>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation,
+        None => return,
+    };
+
+    let (strength, phase) = aurora_strength(terrain_config.seed(), config.latitude);
+    let intensity = if config.aurora {
+        config.aurora_intensity * strength * sun.night()
+    } else {
+        0.0
+    };
+
+    for (mut aurora, mut transform, mut visible) in aurora_query.iter_mut() {
+        transform.translation = eyes;
+        aurora.intensity = intensity;
+        aurora.phase = phase;
+        visible.is_visible = intensity > 0.0;
+    }
+}
+
+/// How strong the aurora is for a world, from 0 to 1, and the phase its animation starts at.
+/// Stronger towards the poles, with each seed getting a calmer or livelier sky.
+pub fn aurora_strength(seed: u32, latitude: f32) -> (f32, f32) {
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let activity = rng.gen_range(0.4..1.0);
+    let phase = rng.gen_range(0.0..1000.0);
+
+    let (low, high) = AURORA_LATITUDES;
+    let polar = ((latitude - low) / (high - low)).clamp(0.0, 1.0);
+    (polar * activity, phase)
+}
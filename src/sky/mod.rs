@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    first_person::PlayerEyes,
+    settings::{AddSettings, SettingsTab},
+    terrain::{self, query},
+    timescale::Timescale,
+};
+
+mod ambient;
+mod aurora;
+mod night;
+mod sun;
+
+// rays cast towards the edge of the sun's disc when checking how much of it is hidden
+const OCCLUSION_RAYS: usize = 8;
+// angular radius of the sun used for the occlusion rays, in radians
+const SUN_RADIUS: f32 = 0.02;
+const OCCLUSION_STEPS: usize = 48;
+const OCCLUSION_DISTANCE: f32 = 3000.0;
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<SkyConfig>(SettingsTab::World, "Sky")
+            .init_resource::<Sun>()
+            .init_resource::<PrimaryLight>()
+            .add_startup_system(setup.system())
+            .add_system(
+                advance_time
+                    .system()
+                    .label("sky::time")
+                    .after("timescale::clock"),
+            )
+            .add_system(update_sun.system().label("sky::sun").after("sky::time"))
+            .add_system(sky_color.system().label("sky::color").after("sky::sun"))
+            .add_startup_system(sun::setup.system())
+            .add_system(sun::update_sprites.system().after("sky::sun"))
+            .add_startup_system(night::setup.system())
+            .add_system(night::turn_stars.system().after("sky::sun"))
+            .add_system(
+                night::update_primary_light
+                    .system()
+                    .label("sky::light")
+                    .after("sky::sun"),
+            )
+            .add_system(
+                ambient::update_ambient
+                    .system()
+                    .label("sky::ambient")
+                    .after("sky::sun")
+                    .after("weather::precipitation"),
+            )
+            .add_system(
+                night::light_terrain
+                    .system()
+                    .after("sky::light")
+                    .after("sky::ambient")
+                    .after("weather::flash")
+                    .after("player::torch_flicker"),
+            )
+            .add_startup_system(aurora::setup.system())
+            .add_system(aurora::update.system().after("sky::sun"));
+
+        sun::add_sun_graph(app.world_mut());
+        night::add_star_graph(app.world_mut());
+        aurora::add_aurora_graph(app.world_mut());
+    }
+}
+
+/// How far through the day it is, from 0 at midnight through 0.5 at noon back round to 1
+#[derive(Clone, Copy, Debug)]
+pub struct TimeOfDay(pub f32);
+
+impl TimeOfDay {
+    /// How far the sky has turned since sunrise, in radians
+    pub fn angle(&self) -> f32 {
+        (self.0 - 0.25) * std::f32::consts::TAU
+    }
+}
+
+/// Where the sun is and how much of it can be seen from the player's eyes
+#[derive(Clone, Copy, Debug)]
+pub struct Sun {
+    // points from the world towards the sun
+    pub direction: Vec3,
+    // fraction of the sun's disc not hidden behind the terrain
+    pub visibility: f32,
+}
+
+impl Default for Sun {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::Y,
+            visibility: 1.0,
+        }
+    }
+}
+
+impl Sun {
+    /// How high the sun is, from -1 straight below to 1 straight above
+    pub fn elevation(&self) -> f32 {
+        self.direction.y
+    }
+
+    /// How far into the night it is, from 0 while the sun is up to 1 once it's well below the
+    /// horizon
+    pub fn night(&self) -> f32 {
+        (-self.elevation() / 0.2).clamp(0.0, 1.0)
+    }
+
+    /// Where the sun appears on the camera's screen in normalized device coordinates, or
+    /// `None` when it's behind the camera
+    pub fn ndc(&self, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Vec2> {
+        let view_projection =
+            camera.projection_matrix * camera_transform.compute_matrix().inverse();
+        let point = camera_transform.translation + self.direction * 1000.0;
+        let clip = view_projection * point.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        Some(clip.truncate().truncate() / clip.w)
+    }
+}
+
+/// Whichever of the sun or moon is lighting the world
+#[derive(Clone, Copy, Debug)]
+pub struct PrimaryLight {
+    // points from the world towards the light
+    pub direction: Vec3,
+    pub color: Color,
+}
+
+impl Default for PrimaryLight {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::Y,
+            color: Color::WHITE,
+        }
+    }
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct SkyConfig {
+    // real seconds for a full day and night
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub day_length: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub start_time: f32,
+    pub paused: bool,
+    // how far the sun's path is tilted away from straight overhead, in degrees
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 89.0))]
+    pub latitude: f32,
+    pub day_color: Color,
+    pub sunset_color: Color,
+    pub night_color: Color,
+    pub sun_color: Color,
+    // angular diameter of the sun's disc, in degrees
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1, max = 20.0))]
+    pub sun_size: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub bloom_intensity: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub flare_intensity: f32,
+    pub moon_color: Color,
+    // angular diameter of the moon's disc, in degrees
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1, max = 20.0))]
+    pub moon_size: f32,
+    // how brightly the moon lights the terrain compared to the sun
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub moon_light: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub star_brightness: f32,
+    pub aurora: bool,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 2.0))]
+    pub aurora_intensity: f32,
+    // light reaching everything from the rest of the sky, at different times of day
+    pub noon_ambient: Color,
+    pub sunset_ambient: Color,
+    pub night_ambient: Color,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub day_ambient_brightness: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub night_ambient_brightness: f32,
+}
+
+impl Default for SkyConfig {
+    fn default() -> Self {
+        Self {
+            day_length: 600.0,
+            start_time: 0.35,
+            paused: false,
+            latitude: 30.0,
+            day_color: Color::rgb_u8(190, 246, 255),
+            sunset_color: Color::rgb_u8(250, 160, 110),
+            night_color: Color::rgb_u8(8, 12, 30),
+            sun_color: Color::rgb(1.0, 0.91, 0.41),
+            sun_size: 3.0,
+            bloom_intensity: 0.6,
+            flare_intensity: 0.3,
+            moon_color: Color::rgb(0.75, 0.82, 1.0),
+            moon_size: 2.5,
+            moon_light: 0.35,
+            star_brightness: 1.0,
+            aurora: true,
+            aurora_intensity: 1.0,
+            noon_ambient: Color::rgb(0.6, 0.72, 0.9),
+            sunset_ambient: Color::rgb(1.0, 0.6, 0.35),
+            night_ambient: Color::rgb(0.2, 0.26, 0.55),
+            day_ambient_brightness: 0.22,
+            night_ambient_brightness: 0.18,
+        }
+    }
+}
+
+impl SkyConfig {
+    /// Tilts the path the sun, moon and stars take across the sky away from overhead
+    pub fn sky_tilt(&self) -> Quat {
+        Quat::from_rotation_x(-self.latitude.to_radians())
+    }
+}
+
+fn setup(mut commands: Commands, config: Res<SkyConfig>) {
+    commands.insert_resource(TimeOfDay(config.start_time));
+}
+
+fn advance_time(
+    timescale: Res<Timescale>,
+    config: Res<SkyConfig>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    if config.paused {
+        return;
+    }
+
+    time_of_day.0 = (time_of_day.0 + timescale.delta_seconds() / config.day_length).fract();
+}
+
+// Swings the sun round the sky and works out how much of it the terrain is hiding
+fn update_sun(
+    config: Res<SkyConfig>,
+    terrain_config: Res<terrain::Config>,
+    time_of_day: Res<TimeOfDay>,
+    mut sun: ResMut<Sun>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    // rises in the east (+x) at 0.25, overhead-ish at noon and sets in the west at 0.75
+    let angle = time_of_day.angle();
+    sun.direction = config.sky_tilt() * Vec3::new(angle.cos(), angle.sin(), 0.0);
+
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation,
+        None => return,
+    };
+
+    sun.visibility = if sun.elevation() < -SUN_RADIUS {
+        0.0
+    } else {
+        sun_visibility(&terrain_config, eyes, sun.direction)
+    };
+}
+
+// Fades the clear color between night, sunset and day as the sun rises and sets
+fn sky_color(config: Res<SkyConfig>, sun: Res<Sun>, mut clear_color: ResMut<ClearColor>) {
+    let elevation = sun.elevation();
+
+    let color = if elevation > 0.0 {
+        let day = (elevation / 0.3).min(1.0);
+        lerp_color(config.sunset_color, config.day_color, day)
+    } else {
+        lerp_color(config.sunset_color, config.night_color, sun.night())
+    };
+    clear_color.0 = color;
+}
+
+/// Fraction of rays towards the sun's disc that make it past the terrain
+pub fn sun_visibility(config: &terrain::Config, from: Vec3, direction: Vec3) -> f32 {
+    let side = direction.cross(Vec3::Y).normalize_or_zero();
+    let up = side.cross(direction).normalize_or_zero();
+
+    let visible = (0..OCCLUSION_RAYS)
+        .map(|i| {
+            let angle = i as f32 / OCCLUSION_RAYS as f32 * std::f32::consts::TAU;
+            (direction + (side * angle.cos() + up * angle.sin()) * SUN_RADIUS).normalize()
+        })
+        .filter(|ray| !ray_hits_terrain(config, from, *ray))
+        .count();
+
+    visible as f32 / OCCLUSION_RAYS as f32
+}
+
+fn ray_hits_terrain(config: &terrain::Config, from: Vec3, direction: Vec3) -> bool {
+    (1..=OCCLUSION_STEPS).any(|step| {
+        // space the steps out with distance, the far terrain only blocks low suns anyway
+        let t = (step as f32 / OCCLUSION_STEPS as f32).powi(2) * OCCLUSION_DISTANCE;
+        let point = from + direction * t;
+        query::height_at(config, Vec2::new(point.x, point.z)) > point.y
+    })
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let from = Vec4::from(from);
+    let to = Vec4::from(to);
+    (from + (to - from) * t).into()
+}
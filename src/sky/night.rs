@@ -0,0 +1,153 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{BlendFactor, CullMode, PipelineDescriptor, RenderPipeline},
+        render_graph::{base, RenderGraph, RenderResourcesNode},
+        renderer::RenderResources,
+        shader::ShaderStages,
+    },
+};
+
+use crate::{
+    error_log::ErrorLog,
+    first_person::{PlayerEyes, Torch},
+    terrain::Chunk,
+};
+
+use super::{PrimaryLight, SkyConfig, Sun, TimeOfDay};
+
+pub const STAR_DOME_NODE: &str = "star_dome";
+
+// just inside the sun so it's drawn in front of the stars, and well inside the far plane
+const STAR_DISTANCE: f32 = 4500.0;
+// skip touching every terrain material for changes too small to see
+const TINT_EPSILON: f32 = 0.002;
+
+/// The sphere of stars around the camera, turning with the time of day
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "b2d5e8f1-93c4-4a6e-8f27-5c1d0a9e3b46"]
+pub struct StarDome {
+    pub brightness: f32,
+}
+
+pub fn add_star_graph(world: &mut World) {
+    let mut graph = world.get_resource_mut::<RenderGraph>().unwrap();
+    graph.add_system_node(STAR_DOME_NODE, RenderResourcesNode::<StarDome>::new(true));
+    graph
+        .add_node_edge(STAR_DOME_NODE, base::node::MAIN_PASS)
+        .unwrap();
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: errors.load(&asset_server, "shaders/stars.vert"),
+        fragment: Some(errors.load(&asset_server, "shaders/stars.frag")),
+    });
+    // seen from the inside, and hidden behind the terrain without hiding anything itself
+    descriptor.primitive.cull_mode = CullMode::None;
+    descriptor.color_target_states[0].color_blend.dst_factor = BlendFactor::One;
+    descriptor
+        .depth_stencil
+        .as_mut()
+        .unwrap()
+        .depth_write_enabled = false;
+
+    commands
+        .spawn_bundle(MeshBundle {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: STAR_DISTANCE,
+                subdivisions: 4,
+            })),
+            render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                pipelines.add(descriptor),
+            )]),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(StarDome::default());
+}
+
+// Keeps the stars centred on the camera and turns them about the same axis as the sun
+pub fn turn_stars(
+    config: Res<SkyConfig>,
+    time_of_day: Res<TimeOfDay>,
+    sun: Res<Sun>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    mut dome_query: Query<(&mut StarDome, &mut Transform, &mut Visible)>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation,
+        None => return,
+    };
+
+    let brightness = sun.night() * config.star_brightness;
+    for (mut dome, mut transform, mut visible) in dome_query.iter_mut() {
+        transform.translation = eyes;
+        transform.rotation = config.sky_tilt() * Quat::from_rotation_z(time_of_day.angle());
+        dome.brightness = brightness;
+        visible.is_visible = brightness > 0.0;
+    }
+}
+
+// Hands over from the sun to the moon as the main light once the sun has set
+pub fn update_primary_light(
+    config: Res<SkyConfig>,
+    sun: Res<Sun>,
+    mut light: ResMut<PrimaryLight>,
+) {
+    let day = (sun.elevation() / 0.3).clamp(0.0, 1.0);
+    let night = sun.night();
+
+    // the sunlight reddens towards sunset then dims through dusk as the moonlight comes up
+    let sunset = Vec4::from(config.sunset_color);
+    let sunlight = sunset + (Vec4::from(Color::WHITE) - sunset) * day;
+    let moonlight = Vec4::from(config.moon_color) * config.moon_light;
+    light.color = (sunlight * (1.0 - night) + moonlight * night).into();
+    light.direction = if night > 0.5 {
+        -sun.direction
+    } else {
+        sun.direction
+    };
+}
+
+// The terrain is unlit, so light it by tinting its materials with the primary light on top
+// of the sky's ambient light, keeping it readable under the moon. A lit torch can't light
+// just the ground around it this way, so it brightens the whole terrain a little
+pub fn light_terrain(
+    ambient: Res<AmbientLight>,
+    light: Res<PrimaryLight>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    torch_query: Query<&Torch>,
+    chunks_query: Query<&Handle<StandardMaterial>, With<Chunk>>,
+) {
+    let torch = torch_query
+        .iter()
+        .fold(Vec4::ZERO, |glow, torch| glow + Vec4::from(torch.glow));
+    let tint = (Vec4::from(light.color) + Vec4::from(ambient.color) * ambient.brightness + torch)
+        .min(Vec4::ONE)
+        .truncate()
+        .extend(1.0);
+
+    for handle in chunks_query.iter() {
+        let current = match materials.get(handle) {
+            Some(material) => Vec4::from(material.base_color),
+            None => continue,
+        };
+        if (current - tint).abs().max_element() < TINT_EPSILON {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = tint.into();
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{BlendFactor, CompareFunction, CullMode, PipelineDescriptor, RenderPipeline},
+        render_graph::{base, RenderGraph, RenderResourcesNode},
+        renderer::RenderResources,
+        shader::ShaderStages,
+    },
+};
+
+use crate::{error_log::ErrorLog, first_person::PlayerEyes};
+
+use super::{SkyConfig, Sun};
+
+pub const SUN_SPRITE_NODE: &str = "sun_sprite";
+
+// far enough to sit behind all the nearby terrain while staying inside the camera's far plane
+const SUN_DISTANCE: f32 = 4000.0;
+// the bloom quad is this many times wider than the disc
+const BLOOM_SCALE: f32 = 6.0;
+// flares are drawn just in front of the camera, over everything else
+const FLARE_DISTANCE: f32 = 10.0;
+// sun elevation over which the sun fades in as it rises over the horizon
+const HORIZON_FADE: f32 = 0.05;
+
+// Position along the line from the sun through the middle of the screen (0 at the sun, 1 in
+// the middle), size as a fraction of the screen height and tint of each lens flare sprite
+const FLARES: &[(f32, f32, [f32; 3])] = &[
+    (0.4, 0.05, [1.0, 0.8, 0.5]),
+    (0.7, 0.02, [0.6, 1.0, 0.7]),
+    (1.2, 0.08, [0.5, 0.7, 1.0]),
+    (1.5, 0.03, [1.0, 0.6, 0.9]),
+    (1.9, 0.12, [0.7, 0.6, 1.0]),
+];
+
+/// Colors one of the camera facing quads drawn by the sun pipelines
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "4b8f6a1c-2e7d-4c59-9d3a-7f1e0b6c5a82"]
+pub struct SunSprite {
+    pub color: Color,
+    // radius of the solid disc, as a fraction of the quad
+    pub core: f32,
+    // strength of the soft glow around it
+    pub glow: f32,
+}
+
+pub enum SunPart {
+    Disc,
+    Bloom,
+    Moon,
+    Flare(usize),
+}
+
+pub fn add_sun_graph(world: &mut World) {
+    let mut graph = world.get_resource_mut::<RenderGraph>().unwrap();
+    graph.add_system_node(SUN_SPRITE_NODE, RenderResourcesNode::<SunSprite>::new(true));
+    graph
+        .add_node_edge(SUN_SPRITE_NODE, base::node::MAIN_PASS)
+        .unwrap();
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: errors.load(&asset_server, "shaders/sun.vert"),
+        fragment: Some(errors.load(&asset_server, "shaders/sun.frag")),
+    });
+    descriptor.primitive.cull_mode = CullMode::None;
+    // light adds up rather than covering what's behind it
+    descriptor.color_target_states[0].color_blend.dst_factor = BlendFactor::One;
+    let depth_stencil = descriptor.depth_stencil.as_mut().unwrap();
+    depth_stencil.depth_write_enabled = false;
+
+    // the disc is depth tested so the terrain in front of it hides it, while the glow and
+    // flares come from the lens and go over everything
+    let mut overlay = descriptor.clone();
+    overlay.depth_stencil.as_mut().unwrap().depth_compare = CompareFunction::Always;
+
+    let disc_pipeline = pipelines.add(descriptor);
+    let overlay_pipeline = pipelines.add(overlay);
+    let quad = meshes.add(Mesh::from(shape::Quad::new(Vec2::ONE)));
+
+    let mut spawn_part = |part: SunPart, pipeline: &Handle<PipelineDescriptor>, core: f32| {
+        commands
+            .spawn_bundle(MeshBundle {
+                mesh: quad.clone(),
+                render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                    pipeline.clone(),
+                )]),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(SunSprite {
+                color: Color::NONE,
+                core,
+                glow: if core > 0.0 { 0.0 } else { 1.0 },
+            })
+            .insert(part);
+    };
+
+    spawn_part(SunPart::Disc, &disc_pipeline, 0.95);
+    spawn_part(SunPart::Bloom, &overlay_pipeline, 0.0);
+    spawn_part(SunPart::Moon, &disc_pipeline, 0.95);
+    for i in 0..FLARES.len() {
+        spawn_part(SunPart::Flare(i), &overlay_pipeline, 0.0);
+    }
+}
+
+// Keeps the sun's sprites facing the camera, with the glow and flares fading out as the
+// terrain covers the sun (going by the rays cast against the height map for `Sun::visibility`)
+pub fn update_sprites(
+    config: Res<SkyConfig>,
+    sun: Res<Sun>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PlayerEyes>>,
+    mut sprites_query: Query<(&SunPart, &mut SunSprite, &mut Transform, &mut Visible)>,
+) {
+    let (camera, eyes) = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+
+    let risen = (sun.elevation() / HORIZON_FADE + 1.0).clamp(0.0, 1.0);
+    let sun_ndc = sun.ndc(camera, eyes);
+    let disc_size = 2.0 * SUN_DISTANCE * (config.sun_size.to_radians() * 0.5).tan();
+
+    for (part, mut sprite, mut transform, mut visible) in sprites_query.iter_mut() {
+        transform.rotation = eyes.rotation;
+
+        match *part {
+            SunPart::Disc | SunPart::Bloom => {
+                let bloom = matches!(part, SunPart::Bloom);
+                transform.translation = eyes.translation + sun.direction * SUN_DISTANCE;
+                transform.scale = Vec3::splat(if bloom {
+                    disc_size * BLOOM_SCALE
+                } else {
+                    disc_size
+                });
+
+                let fade = if bloom {
+                    risen * sun.visibility * config.bloom_intensity
+                } else {
+                    risen
+                };
+                sprite.color = config.sun_color;
+                sprite.color.set_a(fade);
+                visible.is_visible = fade > 0.0;
+            }
+            SunPart::Moon => {
+                // always full and opposite the sun
+                transform.translation = eyes.translation - sun.direction * SUN_DISTANCE;
+                transform.scale =
+                    Vec3::splat(2.0 * SUN_DISTANCE * (config.moon_size.to_radians() * 0.5).tan());
+
+                let fade = sun.night();
+                sprite.color = config.moon_color;
+                sprite.color.set_a(fade);
+                visible.is_visible = fade > 0.0;
+            }
+            SunPart::Flare(i) => {
+                let (along, size, tint) = FLARES[i];
+                let sun_ndc = match sun_ndc {
+                    Some(ndc) => ndc,
+                    None => {
+                        visible.is_visible = false;
+                        continue;
+                    }
+                };
+
+                // the flares line up through the middle of the screen, opposite the sun
+                let ndc = sun_ndc * (1.0 - along);
+                let view = Vec3::new(
+                    ndc.x * FLARE_DISTANCE / camera.projection_matrix.x_axis.x,
+                    ndc.y * FLARE_DISTANCE / camera.projection_matrix.y_axis.y,
+                    -FLARE_DISTANCE,
+                );
+                transform.translation = eyes.mul_vec3(view);
+                transform.scale =
+                    Vec3::splat(size * 2.0 * FLARE_DISTANCE / camera.projection_matrix.y_axis.y);
+
+                // fade out as the sun heads off screen, where the lens would stop catching it
+                let centred = 1.0 - (sun_ndc.length() - 1.0).clamp(0.0, 1.0);
+                let fade = risen * sun.visibility * centred * config.flare_intensity;
+                sprite.color = Color::rgba(tint[0], tint[1], tint[2], fade);
+                visible.is_visible = fade > 0.0;
+            }
+        }
+    }
+}
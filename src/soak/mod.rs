@@ -0,0 +1,157 @@
+use std::{env, f32::consts::PI, process};
+
+use bevy::{
+    app::AppExit,
+    log::{error, info},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::{RigidBodyPosition, RigidBodyVelocity};
+use rand::Rng;
+
+use crate::{
+    first_person::{self, MouseState},
+    metrics,
+    terrain::{self, SeenChunks},
+    Player,
+};
+
+// fast enough to cross a chunk every second or so, so chunks load and unload constantly
+const SPEED: f32 = 150.0;
+const ALTITUDE: f32 = 40.0;
+// how sharply the heading wanders, in radians per second
+const TURN_RATE: f32 = 1.5;
+const CHECK_INTERVAL: f32 = 5.0;
+// memory is measured against where it settles once the first chunks have loaded
+const WARMUP: f32 = 30.0;
+const MAX_MEMORY_GROWTH: f64 = 2.0;
+
+/// The number of minutes to soak for when launched with `--soak <minutes>`
+pub fn soak_minutes() -> Option<f32> {
+    let mut args = env::args().skip_while(|arg| arg != "--soak");
+    args.next()?;
+    match args.next().and_then(|minutes| minutes.parse().ok()) {
+        Some(minutes) => Some(minutes),
+        None => {
+            eprintln!("--soak needs a number of minutes to run for, e.g. --soak 30");
+            None
+        }
+    }
+}
+
+/// Burns in the chunk lifecycle by flying the player in a random walk over the terrain,
+/// failing the run if memory or the number of chunks kept grows without bound. A panic ends
+/// the run with a failing exit code by itself.
+pub struct SoakPlugin {
+    pub minutes: f32,
+}
+
+impl Plugin for SoakPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Soak {
+            duration: self.minutes * 60.0,
+            ..Default::default()
+        })
+        .add_system(wander.system().before("player::look"))
+        .add_system(check.system());
+    }
+}
+
+#[derive(Default)]
+struct Soak {
+    duration: f32,
+    elapsed: f32,
+    position: Vec2,
+    heading: f32,
+    since_check: f32,
+    baseline_memory: Option<u64>,
+    peak_memory: u64,
+    peak_seen_chunks: usize,
+}
+
+// Flies the player along a wandering heading at a fixed height above the ground, overriding
+// both their movement and their look
+fn wander(
+    time: Res<Time>,
+    config: Res<terrain::Config>,
+    mut soak: ResMut<Soak>,
+    mut mouse: ResMut<MouseState>,
+    mut player_query: Query<(&mut RigidBodyPosition, &mut RigidBodyVelocity), With<Player>>,
+) {
+    let delta = time.delta_seconds();
+    soak.heading += rand::thread_rng().gen_range(-1.0..1.0) * TURN_RATE * delta;
+    soak.heading %= 2.0 * PI;
+    // the player faces down -z when their yaw is zero
+    let direction = Vec2::new(-soak.heading.sin(), -soak.heading.cos());
+    soak.position += direction * SPEED * delta;
+
+    let ground = terrain::query::height_at(&config, soak.position).max(config.sea_level());
+    let to = Vec3::new(soak.position.x, ground + ALTITUDE, soak.position.y);
+    for (mut position, mut velocity) in player_query.iter_mut() {
+        first_person::teleport(&mut position, &mut velocity, to);
+    }
+    mouse.yaw = soak.heading;
+    mouse.pitch = -0.2;
+}
+
+fn check(
+    time: Res<Time>,
+    config: Res<terrain::Config>,
+    seen_chunks: Res<SeenChunks>,
+    mut soak: ResMut<Soak>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    let delta = time.delta_seconds();
+    soak.elapsed += delta;
+    soak.since_check += delta;
+    if soak.since_check < CHECK_INTERVAL {
+        return;
+    }
+    soak.since_check = 0.0;
+
+    // every chunk in the square kept around the player, and nothing past it
+    let radius = terrain::kept_chunk_radius(&config) as usize;
+    let max_seen_chunks = (2 * radius + 1).pow(2);
+    soak.peak_seen_chunks = soak.peak_seen_chunks.max(seen_chunks.len());
+    if seen_chunks.len() > max_seen_chunks {
+        fail(format!(
+            "{} chunks kept, more than the {} around the player",
+            seen_chunks.len(),
+            max_seen_chunks
+        ));
+    }
+
+    // memory isn't known off Linux, where only the chunks are checked
+    if let Some(memory) = metrics::resident_memory() {
+        soak.peak_memory = soak.peak_memory.max(memory);
+        match soak.baseline_memory {
+            None if soak.elapsed >= WARMUP => {
+                info!("Soak memory baseline: {} MiB", memory / (1024 * 1024));
+                soak.baseline_memory = Some(memory);
+            }
+            Some(baseline) if memory as f64 > baseline as f64 * MAX_MEMORY_GROWTH => {
+                fail(format!(
+                    "Memory grew from {} MiB to {} MiB",
+                    baseline / (1024 * 1024),
+                    memory / (1024 * 1024)
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    if soak.elapsed >= soak.duration {
+        info!(
+            "Soak passed after {:.0}s: peak of {} chunks kept (at most {}), peak memory {} MiB",
+            soak.elapsed,
+            soak.peak_seen_chunks,
+            max_seen_chunks,
+            soak.peak_memory / (1024 * 1024)
+        );
+        app_exit_events.send(AppExit);
+    }
+}
+
+fn fail(message: String) -> ! {
+    error!("Soak failed: {}", message);
+    process::exit(1);
+}
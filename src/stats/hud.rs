@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+
+use super::{Breath, Health, Stamina};
+use crate::Player;
+
+const BAR_WIDTH: f32 = 240.0;
+const BAR_HEIGHT: f32 = 14.0;
+
+/// The filled part of the health bar
+pub struct HealthBar;
+
+/// The filled part of the stamina bar
+pub struct StaminaBar;
+
+/// The filled part of the breath bar
+pub struct BreathBar;
+
+/// The whole breath bar, which is only shown while the player is short of breath
+pub struct BreathMeter;
+
+pub fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    let background = materials.add(Color::rgba(0.0, 0.0, 0.0, 0.5).into());
+    let health_color = materials.add(Color::rgb_u8(200, 40, 40).into());
+    let stamina_color = materials.add(Color::rgb_u8(230, 190, 40).into());
+    let breath_color = materials.add(Color::rgb_u8(70, 150, 230).into());
+
+    let mut breath_meter = None;
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..Default::default()
+            },
+            material: materials.add(Color::NONE.into()),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            spawn_bar(parent, background.clone(), health_color, HealthBar);
+            spawn_bar(parent, background.clone(), stamina_color, StaminaBar);
+            breath_meter = Some(spawn_bar(parent, background, breath_color, BreathBar));
+        });
+
+    if let Some(breath_meter) = breath_meter {
+        commands.entity(breath_meter).insert(BreathMeter);
+    }
+}
+
+fn spawn_bar<T: Send + Sync + 'static>(
+    parent: &mut ChildBuilder,
+    background: Handle<ColorMaterial>,
+    fill: Handle<ColorMaterial>,
+    marker: T,
+) -> Entity {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(BAR_WIDTH), Val::Px(BAR_HEIGHT)),
+                margin: Rect {
+                    bottom: Val::Px(6.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: background,
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    material: fill,
+                    ..Default::default()
+                })
+                .insert(marker);
+        })
+        .id()
+}
+
+pub fn update_bars(
+    player_query: Query<(&Health, &Stamina, &Breath), With<Player>>,
+    mut bars_query: QuerySet<(
+        Query<&mut Style, With<HealthBar>>,
+        Query<&mut Style, With<StaminaBar>>,
+        Query<&mut Style, With<BreathBar>>,
+        Query<&mut Style, With<BreathMeter>>,
+    )>,
+) {
+    let (health, stamina, breath) = match player_query.iter().next() {
+        Some(stats) => stats,
+        None => return,
+    };
+
+    for mut style in bars_query.q0_mut().iter_mut() {
+        style.size.width = Val::Percent(100.0 * health.current / health.max);
+    }
+    for mut style in bars_query.q1_mut().iter_mut() {
+        style.size.width = Val::Percent(100.0 * stamina.current / stamina.max);
+    }
+    for mut style in bars_query.q2_mut().iter_mut() {
+        style.size.width = Val::Percent(100.0 * breath.current / breath.max);
+    }
+    for mut style in bars_query.q3_mut().iter_mut() {
+        style.display = if breath.underwater || breath.current < breath.max {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
@@ -0,0 +1,227 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+
+use crate::{
+    first_person::{FallDamageEvent, Gliding, MovementState, PlayerEyes},
+    settings::{AddSettings, SettingsTab},
+    terrain, Player,
+};
+
+mod hud;
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<StatsConfig>(SettingsTab::Player, "Stats")
+            .add_event::<PlayerDiedEvent>()
+            .add_startup_system(hud::setup.system())
+            .add_system(attach_stats.system())
+            .add_system(apply_fall_damage.system().label("stats::damage"))
+            .add_system(update_stamina.system())
+            .add_system(update_breath.system().label("stats::damage"))
+            .add_system(check_death.system().after("stats::damage"))
+            .add_system(hud::update_bars.system());
+    }
+}
+
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    last_damage: DamageCause,
+}
+
+impl Health {
+    pub fn damage(&mut self, amount: f32, cause: DamageCause) {
+        self.current = (self.current - amount).max(0.0);
+        self.last_damage = cause;
+    }
+}
+
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+    // seconds since stamina was last used, it only regenerates once this passes the regen delay
+    idle_time: f32,
+}
+
+impl Stamina {
+    pub fn exhausted(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// Seconds of air left while the player's head is under water
+pub struct Breath {
+    pub current: f32,
+    pub max: f32,
+    pub underwater: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageCause {
+    Fall,
+    Drowning,
+}
+
+/// Sent when the player's health runs out
+#[derive(Clone, Copy, Debug)]
+pub struct PlayerDiedEvent {
+    pub cause: DamageCause,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct StatsConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub max_health: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub max_stamina: f32,
+    // stamina used per second
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub sprint_drain: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub glide_drain: f32,
+    // stamina recovered per second once idle
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub regen_rate: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub regen_delay: f32,
+    // seconds the player can hold their breath
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub max_breath: f32,
+    // seconds of breath recovered per second at the surface
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub breath_recovery_rate: f32,
+    // health lost per second once out of breath
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub drowning_damage: f32,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            max_health: 100.0,
+            max_stamina: 100.0,
+            sprint_drain: 15.0,
+            glide_drain: 5.0,
+            regen_rate: 25.0,
+            regen_delay: 1.0,
+            max_breath: 20.0,
+            breath_recovery_rate: 5.0,
+            drowning_damage: 20.0,
+        }
+    }
+}
+
+// Gives the player health and stamina once it has been spawned
+fn attach_stats(
+    mut commands: Commands,
+    config: Res<StatsConfig>,
+    player_query: Query<Entity, (With<Player>, Without<Health>)>,
+) {
+    for player in player_query.iter() {
+        commands
+            .entity(player)
+            .insert(Health {
+                current: config.max_health,
+                max: config.max_health,
+                last_damage: DamageCause::Fall,
+            })
+            .insert(Stamina {
+                current: config.max_stamina,
+                max: config.max_stamina,
+                idle_time: 0.0,
+            })
+            .insert(Breath {
+                current: config.max_breath,
+                max: config.max_breath,
+                underwater: false,
+            });
+    }
+}
+
+fn apply_fall_damage(
+    mut events: EventReader<FallDamageEvent>,
+    mut health_query: Query<&mut Health, With<Player>>,
+) {
+    for event in events.iter() {
+        for mut health in health_query.iter_mut() {
+            health.damage(event.damage, DamageCause::Fall);
+        }
+    }
+}
+
+// Sprinting and gliding use stamina, which comes back after a short rest
+fn update_stamina(
+    time: Res<Time>,
+    config: Res<StatsConfig>,
+    mut player_query: Query<(&mut Stamina, &MovementState, Option<&Gliding>), With<Player>>,
+) {
+    let delta = time.delta_seconds();
+
+    for (mut stamina, movement_state, gliding) in player_query.iter_mut() {
+        let mut drain = 0.0;
+        if movement_state.sprinting {
+            drain += config.sprint_drain;
+        }
+        if gliding.is_some() {
+            drain += config.glide_drain;
+        }
+
+        if drain > 0.0 {
+            stamina.current = (stamina.current - drain * delta).max(0.0);
+            stamina.idle_time = 0.0;
+        } else {
+            stamina.idle_time += delta;
+            if stamina.idle_time >= config.regen_delay {
+                stamina.current = (stamina.current + config.regen_rate * delta).min(stamina.max);
+            }
+        }
+    }
+}
+
+// Uses up breath while the player's eyes are below the water surface, drowning them once it runs out
+fn update_breath(
+    time: Res<Time>,
+    config: Res<StatsConfig>,
+    terrain_config: Res<terrain::Config>,
+    mut player_query: Query<(&mut Breath, &mut Health), With<Player>>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    let delta = time.delta_seconds();
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes,
+        None => return,
+    };
+
+    for (mut breath, mut health) in player_query.iter_mut() {
+        breath.underwater = eyes.translation.y < terrain_config.sea_level();
+
+        if breath.underwater {
+            breath.current = (breath.current - delta).max(0.0);
+            if breath.current <= 0.0 {
+                health.damage(config.drowning_damage * delta, DamageCause::Drowning);
+            }
+        } else {
+            breath.current = (breath.current + config.breath_recovery_rate * delta).min(breath.max);
+        }
+    }
+}
+
+// Restores the player's stats and lets everyone else know they died
+fn check_death(
+    mut events: EventWriter<PlayerDiedEvent>,
+    mut player_query: Query<(&mut Health, &mut Stamina, &mut Breath), With<Player>>,
+) {
+    for (mut health, mut stamina, mut breath) in player_query.iter_mut() {
+        if health.current <= 0.0 {
+            health.current = health.max;
+            stamina.current = stamina.max;
+            breath.current = breath.max;
+            events.send(PlayerDiedEvent {
+                cause: health.last_damage,
+            });
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use bevy::math::Vec2;
+use noise::{NoiseFn, Perlin};
+
+use crate::terrain::Biome;
+
+// how far in from the radius a jittered brush's edge can wander, as a fraction of it
+const JITTER_DEPTH: f32 = 0.35;
+// how many times the jittered edge wanders in and out around the brush, roughly
+const JITTER_FREQUENCY: f64 = 2.5;
+// metres across the bumps the noise operation raises
+const NOISE_SCALE: f32 = 12.0;
+
+/// The outline of the ground a brush reaches
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BrushShape {
+    Circle,
+    Square,
+    // a circle whose edge wanders in and out, for less regular marks
+    Jittered,
+}
+
+impl BrushShape {
+    pub const ALL: [BrushShape; 3] = [BrushShape::Circle, BrushShape::Square, BrushShape::Jittered];
+
+    pub fn next(self) -> BrushShape {
+        match self {
+            BrushShape::Circle => BrushShape::Square,
+            BrushShape::Square => BrushShape::Jittered,
+            BrushShape::Jittered => BrushShape::Circle,
+        }
+    }
+
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            BrushShape::Circle => "brush-shape-circle",
+            BrushShape::Square => "brush-shape-square",
+            BrushShape::Jittered => "brush-shape-jittered",
+        }
+    }
+}
+
+/// How a brush's strength fades from its middle out to its edge
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    // full strength right up to the edge
+    Constant,
+    Linear,
+    // eased in and out, for rounded hills
+    Smooth,
+    // the profile of a dome, staying strong until close to the edge
+    Spherical,
+}
+
+impl Falloff {
+    pub const ALL: [Falloff; 4] = [
+        Falloff::Constant,
+        Falloff::Linear,
+        Falloff::Smooth,
+        Falloff::Spherical,
+    ];
+
+    pub fn next(self) -> Falloff {
+        match self {
+            Falloff::Constant => Falloff::Linear,
+            Falloff::Linear => Falloff::Smooth,
+            Falloff::Smooth => Falloff::Spherical,
+            Falloff::Spherical => Falloff::Constant,
+        }
+    }
+
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            Falloff::Constant => "brush-falloff-constant",
+            Falloff::Linear => "brush-falloff-linear",
+            Falloff::Smooth => "brush-falloff-smooth",
+            Falloff::Spherical => "brush-falloff-spherical",
+        }
+    }
+
+    /// The strength at a distance from the middle, as a fraction of the way to the edge
+    fn at(self, distance: f32) -> f32 {
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1.0 - distance,
+            Falloff::Smooth => {
+                let t = 1.0 - distance;
+                t * t * (3.0 - 2.0 * t)
+            }
+            Falloff::Spherical => (1.0 - distance * distance).sqrt(),
+        }
+    }
+}
+
+/// What a brush does to the ground under it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BrushOperation {
+    Raise,
+    Lower,
+    // levels the ground out at the height under the brush when the stroke started
+    Flatten,
+    // evens out bumps, pulling each cell towards the average of its neighbours
+    Smooth,
+    // roughens the ground up with noise
+    Noise,
+    // levels the ground out at the brush's target height
+    SetHeight,
+    // paints the brush's terrain type over the colours the heights give, leaving them be
+    Paint,
+}
+
+impl BrushOperation {
+    pub const ALL: [BrushOperation; 7] = [
+        BrushOperation::Raise,
+        BrushOperation::Lower,
+        BrushOperation::Flatten,
+        BrushOperation::Smooth,
+        BrushOperation::Noise,
+        BrushOperation::SetHeight,
+        BrushOperation::Paint,
+    ];
+
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            BrushOperation::Raise => "brush-operation-raise",
+            BrushOperation::Lower => "brush-operation-lower",
+            BrushOperation::Flatten => "brush-operation-flatten",
+            BrushOperation::Smooth => "brush-operation-smooth",
+            BrushOperation::Noise => "brush-operation-noise",
+            BrushOperation::SetHeight => "brush-operation-set-height",
+            BrushOperation::Paint => "brush-operation-paint",
+        }
+    }
+}
+
+/// The brush the terrain is shaped with, picked from the palette
+#[derive(Clone, Debug)]
+pub struct Brush {
+    pub shape: BrushShape,
+    pub falloff: Falloff,
+    pub operation: BrushOperation,
+    // metres from the middle to the edge
+    pub radius: f32,
+    // metres a second the ground moves at the brush's middle
+    pub strength: f32,
+    // the world space height set to height levels the ground at
+    pub target_height: f32,
+    // what painting lays down, or `None` to wipe the paint off
+    pub terrain_type: Option<Biome>,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            shape: BrushShape::Circle,
+            falloff: Falloff::Smooth,
+            operation: BrushOperation::Raise,
+            radius: 10.0,
+            strength: 5.0,
+            target_height: 50.0,
+            terrain_type: Some(Biome::Sand),
+        }
+    }
+}
+
+/// What a cell of the ground is like going into a step of a stroke, in world space metres
+pub struct Cell {
+    pub position: Vec2,
+    pub height: f32,
+    // the average height of the cells beside it
+    pub neighbours: f32,
+}
+
+impl Brush {
+    /// How strongly the brush acts at an offset from its middle, from 1 down to 0 past its edge
+    pub fn weight(&self, offset: Vec2, noise: &Perlin) -> f32 {
+        let radius = self.radius.max(f32::EPSILON);
+        let distance = match self.shape {
+            BrushShape::Circle => offset.length() / radius,
+            BrushShape::Square => offset.abs().max_element() / radius,
+            BrushShape::Jittered => {
+                // the edge wanders the same way around the brush wherever it is
+                let angle = offset.y.atan2(offset.x);
+                let wander = noise.get([
+                    angle.cos() as f64 * JITTER_FREQUENCY,
+                    angle.sin() as f64 * JITTER_FREQUENCY,
+                ]) as f32;
+                let edge = 1.0 - JITTER_DEPTH * (0.5 + 0.5 * wander);
+                offset.length() / (radius * edge)
+            }
+        };
+        if distance >= 1.0 {
+            0.0
+        } else {
+            self.falloff.at(distance)
+        }
+    }
+
+    /// The height a cell is moved to over a step of a stroke, `weight` being how strongly the
+    /// brush acts on it. Flattening levels out at `stroke_start`, the height under the brush
+    /// when the stroke started.
+    pub fn apply(
+        &self,
+        cell: &Cell,
+        weight: f32,
+        delta_seconds: f32,
+        stroke_start: f32,
+        noise: &Perlin,
+    ) -> f32 {
+        let step = self.strength * weight * delta_seconds;
+        // closes in on the target without overshooting it
+        let towards = |target: f32| cell.height + (target - cell.height).clamp(-step, step);
+        match self.operation {
+            BrushOperation::Raise => cell.height + step,
+            BrushOperation::Lower => cell.height - step,
+            BrushOperation::Flatten => towards(stroke_start),
+            BrushOperation::Smooth => towards(cell.neighbours),
+            BrushOperation::Noise => {
+                let point = cell.position / NOISE_SCALE;
+                cell.height + step * noise.get([point.x as f64, point.y as f64]) as f32
+            }
+            BrushOperation::SetHeight => towards(self.target_height),
+            BrushOperation::Paint => cell.height,
+        }
+    }
+}
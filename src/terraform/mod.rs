@@ -0,0 +1,487 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_egui::{egui, EguiContext};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    na::{Point3, Vector3},
+    physics::{
+        IntoEntity, QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet,
+    },
+    prelude::{ColliderHandle, InteractionGroups, QueryPipeline, Ray},
+};
+use noise::Perlin;
+
+use crate::{
+    bake::BakedWorld,
+    editor::{AppMode, EditorCamera},
+    error_log::ErrorLog,
+    locale::Locale,
+    settings::{AddSettings, SettingsTab},
+    terrain::{
+        self,
+        edits::{self, HeightEditor},
+        Biome, Chunk, GenerationPipeline, TerrainEdits,
+    },
+};
+
+use self::{
+    brush::{Brush, BrushOperation, BrushShape, Cell},
+    stamp::StampClipboard,
+};
+
+mod brush;
+mod stamp;
+
+const STROKE_BUTTON: MouseButton = MouseButton::Left;
+const SHAPE_KEY: KeyCode = KeyCode::H;
+const FALLOFF_KEY: KeyCode = KeyCode::J;
+const SHRINK_KEY: KeyCode = KeyCode::Comma;
+const GROW_KEY: KeyCode = KeyCode::Period;
+const WEAKEN_KEY: KeyCode = KeyCode::Minus;
+const STRENGTHEN_KEY: KeyCode = KeyCode::Equals;
+// the number keys pick the operations, in the order they're listed in the palette
+const OPERATION_KEYS: [KeyCode; 7] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+];
+// each press of the radius and strength keys scales them by this much
+const KEY_STEP: f32 = 1.25;
+const MAX_STRENGTH: f32 = 100.0;
+
+/// Reshapes the terrain under the crosshair with a brush while the editor's open, the changes
+/// being kept with the seed they were made to
+pub struct TerraformPlugin;
+
+impl Plugin for TerraformPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<TerraformConfig>(SettingsTab::Player, "Terraform")
+            .init_resource::<TerraformMode>()
+            .init_resource::<TerraformTarget>()
+            .init_resource::<Brush>()
+            .init_resource::<StampClipboard>()
+            .add_startup_system(setup.system())
+            .add_system(toggle.system().label("terraform::toggle"))
+            .add_system(
+                aim.system()
+                    .label("terraform::aim")
+                    .after("terraform::toggle"),
+            )
+            .add_system(stroke.system().after("terraform::aim"))
+            .add_system(preview.system().after("terraform::aim"))
+            .add_system(stamp::capture.system().after("terraform::aim"))
+            .add_system(stamp::stamp.system().after("terraform::aim"))
+            .add_system(hud.system())
+            .add_system(palette.system())
+            .add_system(stamp::panel.system());
+    }
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct TerraformConfig {
+    // how far away the ground can be shaped
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub reach: f32,
+    // the largest brush, as every cell under it is read and written every frame
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub max_radius: f32,
+    // the longest side of a height stamp, in metres
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub max_stamp_size: f32,
+}
+
+impl Default for TerraformConfig {
+    fn default() -> Self {
+        Self {
+            reach: 300.0,
+            max_radius: 60.0,
+            max_stamp_size: 200.0,
+        }
+    }
+}
+
+/// Whether the terrain is being shaped, which it is whenever the editor's open
+#[derive(Default)]
+pub struct TerraformMode {
+    pub active: bool,
+    // the height under the brush when the current stroke started, for flattening to
+    stroke_start: Option<f32>,
+}
+
+// The point on the ground the brush is over, if any
+#[derive(Default)]
+struct TerraformTarget(Option<Vec3>);
+
+// The see-through outline of the brush on the ground
+struct BrushPreview;
+
+struct PreviewMeshes {
+    round: Handle<Mesh>,
+    square: Handle<Mesh>,
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let preview_meshes = PreviewMeshes {
+        round: meshes.add(Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 3,
+        })),
+        square: meshes.add(Mesh::from(shape::Cube { size: 2.0 })),
+    };
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: preview_meshes.round.clone(),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(1.0, 0.7, 0.3, 0.3),
+                unlit: true,
+                ..Default::default()
+            }),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(BrushPreview);
+    commands.insert_resource(preview_meshes);
+}
+
+// Brings the brush out along with the editor, and changes it from the keyboard while it's out
+fn toggle(
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<TerraformConfig>,
+    app_mode: Res<State<AppMode>>,
+    mut mode: ResMut<TerraformMode>,
+    mut brush: ResMut<Brush>,
+) {
+    let editing = *app_mode.current() == AppMode::Editor;
+    if mode.active != editing {
+        mode.active = editing;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !mode.active || !window.cursor_locked() {
+        return;
+    }
+
+    for (&key, &operation) in OPERATION_KEYS.iter().zip(BrushOperation::ALL.iter()) {
+        if keys.just_pressed(key) {
+            brush.operation = operation;
+        }
+    }
+    if keys.just_pressed(SHAPE_KEY) {
+        brush.shape = brush.shape.next();
+    }
+    if keys.just_pressed(FALLOFF_KEY) {
+        brush.falloff = brush.falloff.next();
+    }
+    if keys.just_pressed(SHRINK_KEY) {
+        brush.radius = (brush.radius / KEY_STEP).max(1.0);
+    }
+    if keys.just_pressed(GROW_KEY) {
+        brush.radius = (brush.radius * KEY_STEP).min(config.max_radius);
+    }
+    if keys.just_pressed(WEAKEN_KEY) {
+        brush.strength = (brush.strength / KEY_STEP).max(0.1);
+    }
+    if keys.just_pressed(STRENGTHEN_KEY) {
+        brush.strength = (brush.strength * KEY_STEP).min(MAX_STRENGTH);
+    }
+}
+
+// Casts a ray from the editor's camera onto the terrain, ignoring anything standing on it
+fn aim(
+    config: Res<TerraformConfig>,
+    mode: Res<TerraformMode>,
+    mut target: ResMut<TerraformTarget>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    camera_query: Query<&GlobalTransform, With<EditorCamera>>,
+    chunks_query: Query<(), With<Chunk>>,
+) {
+    target.0 = None;
+    if !mode.active {
+        return;
+    }
+    let camera = match camera_query.iter().next() {
+        Some(camera) => camera,
+        None => return,
+    };
+    let origin = camera.translation;
+    let direction = camera.rotation * -Vec3::Z;
+
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    let ray = Ray::new(
+        Point3::new(origin.x, origin.y, origin.z),
+        Vector3::new(direction.x, direction.y, direction.z),
+    );
+    let filter = |handle: ColliderHandle| chunks_query.get(handle.entity()).is_ok();
+    if let Some((_, distance)) = query_pipeline.cast_ray(
+        &collider_set,
+        &ray,
+        config.reach,
+        true,
+        InteractionGroups::all(),
+        Some(&filter),
+    ) {
+        target.0 = Some(origin + direction * distance);
+    }
+}
+
+// Works the brush over the ground under it for as long as the button's held, and saves the
+// edits once it's let go
+fn stroke(
+    time: Res<Time>,
+    buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    brush: Res<Brush>,
+    target: Res<TerraformTarget>,
+    config: Res<terrain::Config>,
+    pipeline: Res<GenerationPipeline>,
+    baked: Res<BakedWorld>,
+    mut mode: ResMut<TerraformMode>,
+    mut edits: ResMut<TerrainEdits>,
+    mut errors: ResMut<ErrorLog>,
+) {
+    // the chunks whose meshes can be patched in place while the ground's being shaped are only
+    // regenerated once the button's let go. Paint only shows in the chunks' textures, so isn't
+    // held back.
+    let shaping =
+        mode.active && buttons.pressed(STROKE_BUTTON) && brush.operation != BrushOperation::Paint;
+    edits.defer_regeneration(shaping);
+    if buttons.just_released(STROKE_BUTTON) && mode.stroke_start.take().is_some() {
+        if let Err(error) = edits.save_if_changed() {
+            errors.report(format!("Failed to save the terrain edits: {}", error));
+        }
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let centre = match target.0 {
+        Some(centre) if mode.active && window.cursor_locked() => centre,
+        _ => return,
+    };
+    if !buttons.pressed(STROKE_BUTTON) {
+        return;
+    }
+    let stroke_start = *mode.stroke_start.get_or_insert(centre.y);
+
+    let noise = Perlin::new();
+    let mut editor = edits.editor(&config, &pipeline, baked.0.as_deref());
+    let min = edits::cell_at(centre.xz() - Vec2::splat(brush.radius));
+    let max = edits::cell_at(centre.xz() + Vec2::splat(brush.radius));
+
+    // paint covers every cell the brush reaches, however faintly
+    if brush.operation == BrushOperation::Paint {
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = IVec2::new(x, y);
+                let position = edits::cell_position(cell);
+                if brush.weight(position - centre.xz(), &noise) > 0.0 {
+                    editor.set_terrain_type(cell, brush.terrain_type);
+                }
+            }
+        }
+        return;
+    }
+
+    // every cell is worked out from the ground as it was before this step, so the smoothing
+    // doesn't run on ahead in the direction the cells are visited
+    let mut changes = Vec::new();
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let cell = IVec2::new(x, y);
+            let position = edits::cell_position(cell);
+            let weight = brush.weight(position - centre.xz(), &noise);
+            if weight <= 0.0 {
+                continue;
+            }
+            let cell_state = Cell {
+                position,
+                height: editor.height(cell),
+                neighbours: neighbour_average(&mut editor, cell),
+            };
+            let height = brush.apply(
+                &cell_state,
+                weight,
+                time.delta_seconds(),
+                stroke_start,
+                &noise,
+            );
+            changes.push((cell, height));
+        }
+    }
+    for (cell, height) in changes {
+        editor.set_height(cell, height);
+    }
+}
+
+fn neighbour_average(editor: &mut HeightEditor, cell: IVec2) -> f32 {
+    let sides = [
+        IVec2::new(1, 0),
+        IVec2::new(-1, 0),
+        IVec2::new(0, 1),
+        IVec2::new(0, -1),
+    ];
+    sides
+        .iter()
+        .map(|&side| editor.height(cell + side))
+        .sum::<f32>()
+        / sides.len() as f32
+}
+
+fn preview(
+    brush: Res<Brush>,
+    target: Res<TerraformTarget>,
+    preview_meshes: Res<PreviewMeshes>,
+    mut preview_query: Query<(&mut Handle<Mesh>, &mut Transform, &mut Visible), With<BrushPreview>>,
+) {
+    for (mut mesh, mut transform, mut visible) in preview_query.iter_mut() {
+        let centre = match target.0 {
+            Some(centre) => centre,
+            None => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+        visible.is_visible = true;
+        *mesh = match brush.shape {
+            BrushShape::Square => preview_meshes.square.clone(),
+            BrushShape::Circle | BrushShape::Jittered => preview_meshes.round.clone(),
+        };
+        // squashed flat enough to show the brush's reach without hiding the ground
+        *transform = Transform {
+            translation: centre,
+            scale: Vec3::new(brush.radius, brush.radius * 0.1, brush.radius),
+            ..Default::default()
+        };
+    }
+}
+
+fn hud(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    mode: Res<TerraformMode>,
+    brush: Res<Brush>,
+) {
+    if !mode.active {
+        return;
+    }
+    egui::Area::new("terraform_mode")
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+        .show(egui_context.ctx(), |ui| {
+            ui.label(locale.format(
+                "hud-terraforming",
+                &[
+                    (
+                        "operation",
+                        locale.text(brush.operation.message_id()).into(),
+                    ),
+                    ("shape", locale.text(brush.shape.message_id()).into()),
+                    ("falloff", locale.text(brush.falloff.message_id()).into()),
+                    ("radius", format!("{:.1}", brush.radius).into()),
+                    ("strength", format!("{:.1}", brush.strength).into()),
+                ],
+            ));
+        });
+}
+
+// Every setting of the brush, for when the cursor's free
+fn palette(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    config: Res<TerraformConfig>,
+    mode: Res<TerraformMode>,
+    mut brush: ResMut<Brush>,
+) {
+    if !mode.active {
+        return;
+    }
+    let brush = &mut *brush;
+
+    egui::Window::new(locale.text("brush-title"))
+        .id(egui::Id::new("brush_palette"))
+        .show(egui_context.ctx(), |ui| {
+            ui.label(locale.text("brush-operation"));
+            ui.horizontal_wrapped(|ui| {
+                for &operation in BrushOperation::ALL.iter() {
+                    ui.selectable_value(
+                        &mut brush.operation,
+                        operation,
+                        locale.text(operation.message_id()),
+                    );
+                }
+            });
+            ui.label(locale.text("brush-shape"));
+            ui.horizontal(|ui| {
+                for &shape in BrushShape::ALL.iter() {
+                    ui.selectable_value(&mut brush.shape, shape, locale.text(shape.message_id()));
+                }
+            });
+            ui.label(locale.text("brush-falloff"));
+            ui.horizontal(|ui| {
+                for &falloff in brush::Falloff::ALL.iter() {
+                    ui.selectable_value(
+                        &mut brush.falloff,
+                        falloff,
+                        locale.text(falloff.message_id()),
+                    );
+                }
+            });
+            ui.separator();
+            ui.add(
+                egui::Slider::new(&mut brush.radius, 1.0..=config.max_radius)
+                    .text(locale.text("brush-radius")),
+            );
+            ui.add(
+                egui::Slider::new(&mut brush.strength, 0.1..=MAX_STRENGTH)
+                    .text(locale.text("brush-strength")),
+            );
+            if brush.operation == BrushOperation::SetHeight {
+                ui.add(
+                    egui::Slider::new(&mut brush.target_height, -50.0..=300.0)
+                        .text(locale.text("brush-target-height")),
+                );
+            }
+            if brush.operation == BrushOperation::Paint {
+                ui.label(locale.text("brush-terrain-type"));
+                ui.horizontal_wrapped(|ui| {
+                    for &biome in Biome::ALL.iter() {
+                        ui.selectable_value(
+                            &mut brush.terrain_type,
+                            Some(biome),
+                            locale.text(biome_message_id(biome)),
+                        );
+                    }
+                    ui.selectable_value(
+                        &mut brush.terrain_type,
+                        None,
+                        locale.text("brush-terrain-type-none"),
+                    );
+                });
+            }
+        });
+}
+
+fn biome_message_id(biome: Biome) -> &'static str {
+    match biome {
+        Biome::Water => "terrain-type-water",
+        Biome::Sand => "terrain-type-sand",
+        Biome::Lowland => "terrain-type-lowland",
+        Biome::Forest => "terrain-type-forest",
+        Biome::Rock => "terrain-type-rock",
+        Biome::Snow => "terrain-type-snow",
+    }
+}
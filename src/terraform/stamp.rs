@@ -0,0 +1,350 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_egui::{egui, EguiContext};
+use color_eyre::{eyre::eyre, Report};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use super::{TerraformConfig, TerraformMode, TerraformTarget};
+use crate::{
+    bake::BakedWorld,
+    error_log::ErrorLog,
+    locale::Locale,
+    terrain::{self, edits, GenerationPipeline, TerrainEdits},
+};
+
+const STAMP_DIR: &str = "stamps";
+const CAPTURE_KEY: KeyCode = KeyCode::K;
+const STAMP_KEY: KeyCode = KeyCode::V;
+const ROTATE_KEY: KeyCode = KeyCode::R;
+// how far in from its edge, as a fraction of the way to its middle, a stamp fades into the
+// ground around it
+const FEATHER: f32 = 0.2;
+
+/// The heights of a rectangle of the terrain, relative to the cell in its middle, which can be
+/// stamped down anywhere and shared between worlds as a RON file
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct HeightStamp {
+    // cells along x and along z, a metre apart
+    pub width: usize,
+    pub depth: usize,
+    // in world space metres, row by row along z
+    pub heights: Vec<f32>,
+}
+
+impl HeightStamp {
+    pub fn load(path: impl AsRef<Path>) -> Result<HeightStamp, Report> {
+        let contents = fs::read_to_string(&path)?;
+        let stamp: HeightStamp = ron::de::from_str(&contents)?;
+        if stamp.width == 0 || stamp.depth == 0 || stamp.heights.len() != stamp.width * stamp.depth
+        {
+            return Err(eyre!(
+                "{:?} doesn't have a height for every cell",
+                path.as_ref()
+            ));
+        }
+        Ok(stamp)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    // The half size of the stamp, in cells
+    fn half_extent(&self) -> Vec2 {
+        Vec2::new((self.width - 1) as f32, (self.depth - 1) as f32) / 2.0
+    }
+
+    /// The height at a point measured in cells from the stamp's middle, blended between the
+    /// cells around it, along with how far it is from the stamp's edge towards its middle,
+    /// from 0 to 1. `None` past the edge.
+    fn sample(&self, local: Vec2) -> Option<(f32, f32)> {
+        let half = self.half_extent();
+        let point = local + half;
+        let last = half * 2.0;
+        if point.x < 0.0 || point.y < 0.0 || point.x > last.x || point.y > last.y {
+            return None;
+        }
+        let (x, y) = (point.x.floor() as usize, point.y.floor() as usize);
+        let (next_x, next_y) = ((x + 1).min(self.width - 1), (y + 1).min(self.depth - 1));
+        let (tx, ty) = (point.x.fract(), point.y.fract());
+        let at = |x: usize, y: usize| self.heights[y * self.width + x];
+        let near = at(x, y) + (at(next_x, y) - at(x, y)) * tx;
+        let far = at(x, next_y) + (at(next_x, next_y) - at(x, next_y)) * tx;
+
+        // a stamp a cell wide along either side has no middle to fade towards
+        let inward = |value: f32, half: f32| {
+            if half > 0.0 {
+                (half - (value - half).abs()) / half
+            } else {
+                1.0
+            }
+        };
+        let edge = inward(point.x, half.x).min(inward(point.y, half.y));
+        Some((near + (far - near) * ty, edge))
+    }
+}
+
+// The stamp last captured or loaded, and how it's turned and sized to be stamped
+pub(super) struct StampClipboard {
+    stamp: Option<HeightStamp>,
+    // the first corner of the rectangle being captured, until the second is picked
+    corner: Option<IVec2>,
+    rotation_degrees: f32,
+    // how far the stamp is stretched across the ground and up from it
+    scale: f32,
+    height_scale: f32,
+    // what the stamp will be saved as from the panel
+    name: String,
+    // the stamps in the folder, looked up again whenever this is cleared
+    files: Option<Vec<PathBuf>>,
+}
+
+impl Default for StampClipboard {
+    fn default() -> Self {
+        Self {
+            stamp: None,
+            corner: None,
+            rotation_degrees: 0.0,
+            scale: 1.0,
+            height_scale: 1.0,
+            name: String::new(),
+            files: None,
+        }
+    }
+}
+
+// Captures the rectangle between two corners picked out with the capture key
+pub(super) fn capture(
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    config: Res<TerraformConfig>,
+    mode: Res<TerraformMode>,
+    target: Res<TerraformTarget>,
+    terrain_config: Res<terrain::Config>,
+    pipeline: Res<GenerationPipeline>,
+    baked: Res<BakedWorld>,
+    mut clipboard: ResMut<StampClipboard>,
+    mut edits: ResMut<TerrainEdits>,
+) {
+    if !mode.active {
+        clipboard.corner = None;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !mode.active || !window.cursor_locked() || !keys.just_pressed(CAPTURE_KEY) {
+        return;
+    }
+    let cell = match target.0 {
+        Some(target) => edits::cell_at(target.xz()),
+        None => return,
+    };
+    let first = match clipboard.corner.take() {
+        Some(first) => first,
+        None => {
+            clipboard.corner = Some(cell);
+            return;
+        }
+    };
+
+    let min = first.min(cell);
+    let max = first.max(cell);
+    let (width, depth) = ((max.x - min.x + 1) as usize, (max.y - min.y + 1) as usize);
+    let largest = config.max_stamp_size as usize;
+    if width > largest || depth > largest {
+        warn!(
+            "Can't capture a {}x{} stamp, the largest is {}x{}",
+            width, depth, largest, largest
+        );
+        return;
+    }
+
+    let mut editor = edits.editor(&terrain_config, &pipeline, baked.0.as_deref());
+    let base = editor.height((min + max) / 2);
+    let mut heights = Vec::with_capacity(width * depth);
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            heights.push(editor.height(IVec2::new(x, y)) - base);
+        }
+    }
+    info!("Captured a {}x{} height stamp", width, depth);
+    clipboard.stamp = Some(HeightStamp {
+        width,
+        depth,
+        heights,
+    });
+}
+
+// Stamps the clipboard down centred on the ground under the crosshair, raised or lowered to
+// its height there
+pub(super) fn stamp(
+    keys: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    mode: Res<TerraformMode>,
+    target: Res<TerraformTarget>,
+    terrain_config: Res<terrain::Config>,
+    pipeline: Res<GenerationPipeline>,
+    baked: Res<BakedWorld>,
+    mut clipboard: ResMut<StampClipboard>,
+    mut edits: ResMut<TerrainEdits>,
+    mut errors: ResMut<ErrorLog>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    if !mode.active || !window.cursor_locked() {
+        return;
+    }
+    if keys.just_pressed(ROTATE_KEY) {
+        clipboard.rotation_degrees = (clipboard.rotation_degrees + 90.0).rem_euclid(360.0);
+    }
+    if !keys.just_pressed(STAMP_KEY) {
+        return;
+    }
+    let (stamp, centre) = match (&clipboard.stamp, target.0) {
+        (Some(stamp), Some(centre)) => (stamp, edits::cell_at(centre.xz())),
+        _ => return,
+    };
+
+    let mut editor = edits.editor(&terrain_config, &pipeline, baked.0.as_deref());
+    let base = editor.height(centre);
+    let (sin, cos) = clipboard.rotation_degrees.to_radians().sin_cos();
+    let reach = (stamp.half_extent().length() * clipboard.scale).ceil() as i32 + 1;
+
+    // every cell is worked out before any are written, as stamping over the middle would
+    // otherwise move the height it's all stamped relative to
+    let mut changes = Vec::new();
+    for y in -reach..=reach {
+        for x in -reach..=reach {
+            let offset = Vec2::new(x as f32, y as f32);
+            // back into the stamp's own cells, undoing its turn and stretch
+            let local = Vec2::new(
+                offset.x * cos + offset.y * sin,
+                offset.y * cos - offset.x * sin,
+            ) / clipboard.scale;
+            let (height, edge) = match stamp.sample(local) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let cell = centre + IVec2::new(x, y);
+            let current = editor.height(cell);
+            let stamped = base + height * clipboard.height_scale;
+            let blend = (edge / FEATHER).min(1.0);
+            changes.push((cell, current + (stamped - current) * blend));
+        }
+    }
+    for (cell, height) in changes {
+        editor.set_height(cell, height);
+    }
+    if let Err(error) = edits.save_if_changed() {
+        errors.report(format!("Failed to save the terrain edits: {}", error));
+    }
+}
+
+// Turns and sizes the stamp, saves it to the stamps folder, and loads any stamp in it back
+pub(super) fn panel(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    mode: Res<TerraformMode>,
+    mut clipboard: ResMut<StampClipboard>,
+    mut errors: ResMut<ErrorLog>,
+) {
+    if !mode.active {
+        return;
+    }
+    let clipboard = &mut *clipboard;
+
+    egui::Window::new(locale.text("stamps-title"))
+        .id(egui::Id::new("stamps"))
+        .show(egui_context.ctx(), |ui| {
+            match (&clipboard.stamp, clipboard.corner) {
+                (_, Some(_)) => ui.label(locale.text("stamps-capturing")),
+                (Some(stamp), None) => ui.label(locale.format(
+                    "stamps-clipboard",
+                    &[("width", stamp.width.into()), ("depth", stamp.depth.into())],
+                )),
+                (None, None) => ui.label(locale.text("stamps-clipboard-empty")),
+            };
+            ui.add(
+                egui::Slider::new(&mut clipboard.rotation_degrees, 0.0..=360.0)
+                    .text(locale.text("stamps-rotation")),
+            );
+            ui.add(
+                egui::Slider::new(&mut clipboard.scale, 0.25..=4.0)
+                    .text(locale.text("stamps-scale")),
+            );
+            ui.add(
+                egui::Slider::new(&mut clipboard.height_scale, -2.0..=4.0)
+                    .text(locale.text("stamps-height-scale")),
+            );
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut clipboard.name);
+                let name = clipboard.name.trim().to_string();
+                if ui.button(locale.text("stamps-save")).clicked() && !name.is_empty() {
+                    if let Some(stamp) = &clipboard.stamp {
+                        let path = Path::new(STAMP_DIR).join(format!("{}.ron", name));
+                        if let Err(error) = stamp.save(&path) {
+                            errors.report(format!("Failed to save stamp {:?}: {}", path, error));
+                        }
+                        clipboard.files = None;
+                    }
+                }
+            });
+
+            ui.separator();
+            if ui.button(locale.text("stamps-refresh")).clicked() {
+                clipboard.files = None;
+            }
+            let paths = clipboard.files.get_or_insert_with(stamp_files).clone();
+            for path in paths {
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if ui
+                    .button(locale.format("stamps-load", &[("name", name.as_str().into())]))
+                    .clicked()
+                {
+                    match HeightStamp::load(&path) {
+                        Ok(stamp) => {
+                            clipboard.stamp = Some(stamp);
+                            clipboard.name = name;
+                        }
+                        Err(error) => {
+                            errors.report(format!("Failed to load stamp {:?}: {}", path, error))
+                        }
+                    }
+                }
+            }
+        });
+}
+
+fn stamp_files() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(STAMP_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().map_or(false, |ext| ext == "ron"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+}
@@ -0,0 +1,180 @@
+use std::{env, fmt::Write as _, fs, path::Path};
+
+use bevy::math::Vec2;
+use color_eyre::Report;
+use serde::Serialize;
+
+use super::{query, Biome, Config, HeightMap, CHUNK_SIZE};
+
+const ANALYSIS_DIR: &str = "analysis";
+// the square sampled, centred on the spawn point
+const REGION_SIZE: f32 = 4096.0;
+const SAMPLES_PER_SIDE: usize = 256;
+// tenths of the normalized height, like the altitude colour mode
+const HEIGHT_BINS: usize = 10;
+const SLOPE_BIN_DEGREES: f32 = 10.0;
+const SLOPE_BINS: usize = 9;
+
+/// The seeds to report on when launched with `--analyze <seeds>`, in the same form as
+/// `--gallery`, e.g. `--analyze 1-8,42`
+pub fn analysis_seeds() -> Option<Vec<u32>> {
+    let mut args = env::args().skip_while(|arg| arg != "--analyze");
+    args.next()?;
+    match args.next().and_then(|list| super::parse_seeds(&list)) {
+        Some(seeds) => Some(seeds),
+        None => {
+            eprintln!("--analyze needs a list of seeds to report on, e.g. --analyze 1-8,42");
+            None
+        }
+    }
+}
+
+/// How the terrain of one seed is distributed over the region around the spawn point
+#[derive(Serialize)]
+pub struct TerrainReport {
+    pub seed: u32,
+    pub region_size: f32,
+    pub samples: usize,
+    pub land_ratio: f32,
+    pub water_ratio: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    pub mean_height: f32,
+    // the fraction of samples in each tenth of the normalized height, lowest first
+    pub height_histogram: Vec<f32>,
+    // the fraction of samples in each ten degrees of slope, flattest first
+    pub slope_histogram: Vec<f32>,
+    pub biome_coverage: Vec<BiomeCoverage>,
+}
+
+#[derive(Serialize)]
+pub struct BiomeCoverage {
+    pub biome: String,
+    pub fraction: f32,
+}
+
+impl TerrainReport {
+    /// Samples a grid over the region with the default generation parameters and the seed
+    pub fn sample(seed: u32) -> TerrainReport {
+        let mut config = Config::default();
+        config.set_seed(seed);
+
+        let spacing = REGION_SIZE / (SAMPLES_PER_SIDE - 1) as f32;
+        let mut heights = Vec::with_capacity(SAMPLES_PER_SIDE * SAMPLES_PER_SIDE);
+        let mut height_counts = [0usize; HEIGHT_BINS];
+        let mut slope_counts = [0usize; SLOPE_BINS];
+        let mut biome_counts = [0usize; Biome::ALL.len()];
+        for row in 0..SAMPLES_PER_SIDE {
+            for column in 0..SAMPLES_PER_SIDE {
+                let position =
+                    Vec2::new(column as f32, row as f32) * spacing - Vec2::splat(REGION_SIZE / 2.0);
+                // the same shift into grid space the queries make
+                let grid_point = position + Vec2::splat(CHUNK_SIZE as f32 / 2.0);
+                let normalized = HeightMap::sample(&config, grid_point);
+                heights.push(normalized * config.height_scale);
+
+                let height_bin = (normalized.max(0.0) * HEIGHT_BINS as f32) as usize;
+                height_counts[height_bin.min(HEIGHT_BINS - 1)] += 1;
+
+                let slope = query::normal_at(&config, position).y.acos().to_degrees();
+                let slope_bin = (slope / SLOPE_BIN_DEGREES) as usize;
+                slope_counts[slope_bin.min(SLOPE_BINS - 1)] += 1;
+
+                // the biomes are listed in ALL in the order they're declared
+                biome_counts[config.biome(normalized, grid_point) as usize] += 1;
+            }
+        }
+
+        let samples = heights.len();
+        let fraction = |count: usize| count as f32 / samples as f32;
+        let water = heights
+            .iter()
+            .filter(|&&height| height < config.sea_level())
+            .count();
+        TerrainReport {
+            seed,
+            region_size: REGION_SIZE,
+            samples,
+            land_ratio: 1.0 - fraction(water),
+            water_ratio: fraction(water),
+            min_height: heights.iter().cloned().fold(f32::MAX, f32::min),
+            max_height: heights.iter().cloned().fold(f32::MIN, f32::max),
+            mean_height: heights.iter().sum::<f32>() / samples as f32,
+            height_histogram: height_counts.iter().map(|&count| fraction(count)).collect(),
+            slope_histogram: slope_counts.iter().map(|&count| fraction(count)).collect(),
+            biome_coverage: Biome::ALL
+                .iter()
+                .zip(biome_counts.iter())
+                .map(|(biome, &count)| BiomeCoverage {
+                    biome: format!("{:?}", biome),
+                    fraction: fraction(count),
+                })
+                .collect(),
+        }
+    }
+
+    fn csv_header() -> String {
+        let mut header =
+            "seed,land_ratio,water_ratio,min_height,max_height,mean_height".to_string();
+        for bin in 0..HEIGHT_BINS {
+            write!(header, ",height_{}", bin).unwrap();
+        }
+        for bin in 0..SLOPE_BINS {
+            write!(header, ",slope_{}", bin as f32 * SLOPE_BIN_DEGREES).unwrap();
+        }
+        for biome in Biome::ALL.iter() {
+            write!(header, ",{:?}", biome).unwrap();
+        }
+        header
+    }
+
+    fn csv_row(&self) -> String {
+        let mut row = format!(
+            "{},{},{},{},{},{}",
+            self.seed,
+            self.land_ratio,
+            self.water_ratio,
+            self.min_height,
+            self.max_height,
+            self.mean_height
+        );
+        let bins = self
+            .height_histogram
+            .iter()
+            .chain(self.slope_histogram.iter())
+            .chain(
+                self.biome_coverage
+                    .iter()
+                    .map(|coverage| &coverage.fraction),
+            );
+        for value in bins {
+            write!(row, ",{}", value).unwrap();
+        }
+        row
+    }
+}
+
+/// Writes a JSON report for each seed and a CSV with a row per seed, for comparing them
+pub fn write_reports(seeds: &[u32]) -> Result<(), Report> {
+    fs::create_dir_all(ANALYSIS_DIR)?;
+    let mut csv = TerrainReport::csv_header();
+    csv.push('\n');
+    for &seed in seeds {
+        let report = TerrainReport::sample(seed);
+        let path = Path::new(ANALYSIS_DIR).join(format!("seed-{}.json", seed));
+        fs::write(&path, serde_json::to_string_pretty(&report)?)?;
+        println!(
+            "Seed {}: {:.0}% land, mean height {:.1}, written to {:?}",
+            seed,
+            report.land_ratio * 100.0,
+            report.mean_height,
+            path
+        );
+        csv.push_str(&report.csv_row());
+        csv.push('\n');
+    }
+    let path = Path::new(ANALYSIS_DIR).join("summary.csv");
+    fs::write(&path, csv)?;
+    println!("Wrote the summary of {} seeds to {:?}", seeds.len(), path);
+    Ok(())
+}
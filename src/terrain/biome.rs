@@ -0,0 +1,197 @@
+use bevy::{math::Vec2, render::color::Color};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use nalgebra_glm::smoothstep;
+use noise::{NoiseFn, Perlin, Seedable};
+use serde::{Deserialize, Serialize};
+
+use super::{TerrainThreshold, MAP_CHUNK_SIZE};
+
+// keeps the climate noise apart from the height noise made from the same seed
+const TEMPERATURE_SEED_OFFSET: u32 = 1013;
+const MOISTURE_SEED_OFFSET: u32 = 2027;
+
+/// The broad kind of land an area is, picked from how warm and wet it is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Desert,
+    Plains,
+    Mountains,
+}
+
+impl Region {
+    pub const ALL: [Region; 3] = [Region::Desert, Region::Plains, Region::Mountains];
+}
+
+/// The generation parameters used in place of the terrain's own within one kind of region
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegionParams {
+    // how far the land reaches up and down from the middle height, relative to the terrain's
+    // own height scale
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub(super) height_scale: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1))]
+    pub(super) octaves: usize,
+    pub(super) terrain_thresholds: [TerrainThreshold; 6],
+}
+
+/// Splits the world into deserts, plains and mountains by a low frequency temperature and
+/// moisture noise, blending their parameters across the borders so they meet without cliffs
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeConfig {
+    // off to use the terrain's own octaves and thresholds everywhere
+    pub(super) enabled: bool,
+    // how many chunks across the climate noise's features are
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub(super) climate_scale: f32,
+    // colder than this is mountains, and drier than this is desert, both from 0 to 1
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub(super) cold_threshold: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub(super) dry_threshold: f32,
+    // how far either side of each threshold two regions blend into each other
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.001, max = 0.5))]
+    pub(super) blend: f32,
+    pub(super) desert: RegionParams,
+    pub(super) plains: RegionParams,
+    pub(super) mountains: RegionParams,
+}
+
+impl Default for BiomeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            climate_scale: 12.0,
+            cold_threshold: 0.4,
+            dry_threshold: 0.4,
+            blend: 0.06,
+            desert: RegionParams {
+                height_scale: 0.6,
+                octaves: 4,
+                terrain_thresholds: [
+                    threshold(0.35, Color::rgb(0.0, 0.1, 0.8)),
+                    threshold(0.6, Color::rgb_u8(227, 196, 120)),
+                    threshold(0.7, Color::rgb_u8(196, 158, 92)),
+                    threshold(0.72, Color::rgb_u8(128, 128, 60)),
+                    threshold(0.9, Color::rgb_u8(150, 82, 50)),
+                    threshold(2.0, Color::rgb_u8(240, 224, 200)),
+                ],
+            },
+            plains: RegionParams {
+                height_scale: 0.8,
+                octaves: 5,
+                terrain_thresholds: [
+                    threshold(0.35, Color::rgb(0.0, 0.1, 0.8)),
+                    threshold(0.4, Color::rgb(0.9, 0.78, 0.01)),
+                    threshold(0.55, Color::hex("339D35").unwrap()),
+                    threshold(0.75, Color::rgb_u8(61, 179, 72)),
+                    threshold(0.88, Color::rgb_u8(72, 56, 56)),
+                    threshold(2.0, Color::rgb(1.0, 1.0, 1.0)),
+                ],
+            },
+            mountains: RegionParams {
+                height_scale: 1.6,
+                octaves: 7,
+                terrain_thresholds: [
+                    threshold(0.35, Color::rgb(0.0, 0.1, 0.8)),
+                    threshold(0.38, Color::rgb_u8(150, 140, 120)),
+                    threshold(0.42, Color::hex("339D35").unwrap()),
+                    threshold(0.6, Color::rgb_u8(40, 110, 50)),
+                    threshold(0.75, Color::rgb_u8(90, 84, 84)),
+                    threshold(2.0, Color::rgb(1.0, 1.0, 1.0)),
+                ],
+            },
+        }
+    }
+}
+
+fn threshold(max_height: f32, color: Color) -> TerrainThreshold {
+    TerrainThreshold { max_height, color }
+}
+
+impl BiomeConfig {
+    pub fn params(&self, region: Region) -> &RegionParams {
+        match region {
+            Region::Desert => &self.desert,
+            Region::Plains => &self.plains,
+            Region::Mountains => &self.mountains,
+        }
+    }
+
+    pub(super) fn params_mut(&mut self) -> [&mut RegionParams; 3] {
+        [&mut self.desert, &mut self.plains, &mut self.mountains]
+    }
+
+    /// The most octaves any region layers up, which the noise is normalized for
+    pub fn max_octaves(&self) -> usize {
+        Region::ALL
+            .iter()
+            .map(|&region| self.params(region).octaves)
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+/// How much each region, in the order of `Region::ALL`, shapes a point. They add up to 1.
+#[derive(Clone, Copy, Debug)]
+pub struct RegionWeights(pub [f32; 3]);
+
+impl RegionWeights {
+    pub fn dominant(&self) -> Region {
+        let (index, _) = self
+            .0
+            .iter()
+            .enumerate()
+            .fold((0, f32::MIN), |best, (index, &weight)| {
+                if weight > best.1 {
+                    (index, weight)
+                } else {
+                    best
+                }
+            });
+        Region::ALL[index]
+    }
+
+    /// A parameter of each region blended by how much it shapes the point
+    pub fn blend(&self, config: &BiomeConfig, value: impl Fn(&RegionParams) -> f32) -> f32 {
+        Region::ALL
+            .iter()
+            .zip(self.0.iter())
+            .map(|(&region, weight)| value(config.params(region)) * weight)
+            .sum()
+    }
+}
+
+/// The temperature and moisture noise of one seed
+pub struct Climate {
+    temperature: Perlin,
+    moisture: Perlin,
+}
+
+impl Climate {
+    pub fn new(seed: u32) -> Climate {
+        Climate {
+            temperature: Perlin::new().set_seed(seed.wrapping_add(TEMPERATURE_SEED_OFFSET)),
+            moisture: Perlin::new().set_seed(seed.wrapping_add(MOISTURE_SEED_OFFSET)),
+        }
+    }
+
+    /// The regions at a point in the height map grid's space
+    pub fn weights(&self, config: &BiomeConfig, point: Vec2) -> RegionWeights {
+        let sample = point / (MAP_CHUNK_SIZE as f32 * config.climate_scale.max(1.0));
+        let sample = [sample.x as f64, sample.y as f64];
+        // perlin noise stays within about -1 to 1
+        let temperature = self.temperature.get(sample) as f32 * 0.5 + 0.5;
+        let moisture = self.moisture.get(sample) as f32 * 0.5 + 0.5;
+
+        let edge = |threshold: f32, value: f32| {
+            1.0 - smoothstep(threshold - config.blend, threshold + config.blend, value)
+        };
+        let cold = edge(config.cold_threshold, temperature);
+        let dry = edge(config.dry_threshold, moisture);
+        RegionWeights([(1.0 - cold) * dry, (1.0 - cold) * (1.0 - dry), cold])
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::{height_map::HeightMap, quadtree::Node};
+
+/// The height maps generated most recently, shared between the chunk workers so coming back
+/// to an area that was just unloaded doesn't run the generation stages over again. Once it's
+/// full, the map used longest ago makes way for the next.
+#[derive(Clone, Default)]
+pub struct HeightMapCache(Arc<Mutex<CachedMaps>>);
+
+#[derive(Default)]
+struct CachedMaps {
+    // bumped whenever the config changes, so maps from jobs sent before then aren't kept
+    generation: u64,
+    // counts up with every lookup, to tell which map was used longest ago
+    clock: u64,
+    maps: HashMap<Node, (HeightMap, u64)>,
+}
+
+impl HeightMapCache {
+    /// Forgets every map, as they were generated for a config that's since changed
+    pub fn clear(&self) {
+        let mut cached = self.0.lock().unwrap();
+        cached.generation += 1;
+        cached.maps.clear();
+    }
+
+    /// The generation to send jobs with, which their maps are only cached under if it's
+    /// still current when they're done
+    pub fn generation(&self) -> u64 {
+        self.0.lock().unwrap().generation
+    }
+
+    pub fn get(&self, node: Node, generation: u64) -> Option<HeightMap> {
+        let mut cached = self.0.lock().unwrap();
+        if cached.generation != generation {
+            return None;
+        }
+        cached.clock += 1;
+        let now = cached.clock;
+        let (map, last_used) = cached.maps.get_mut(&node)?;
+        *last_used = now;
+        Some(map.clone())
+    }
+
+    pub fn insert(&self, node: Node, generation: u64, map: HeightMap, capacity: usize) {
+        let mut cached = self.0.lock().unwrap();
+        if cached.generation != generation || capacity == 0 {
+            return;
+        }
+        while cached.maps.len() >= capacity {
+            let oldest = cached
+                .maps
+                .iter()
+                .min_by_key(|(_, &(_, last_used))| last_used)
+                .map(|(&node, _)| node);
+            match oldest {
+                Some(oldest) => cached.maps.remove(&oldest),
+                None => break,
+            };
+        }
+        cached.clock += 1;
+        let now = cached.clock;
+        cached.maps.insert(node, (map, now));
+    }
+}
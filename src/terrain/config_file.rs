@@ -0,0 +1,62 @@
+use std::{fs, path::Path};
+
+use bevy::{log::info, prelude::*};
+use color_eyre::Report;
+use ron::ser::PrettyConfig;
+
+use super::Config;
+use crate::error_log::ErrorLog;
+
+const CONFIG_PATH: &str = "assets/terrain.ron";
+const SAVE_KEY: KeyCode = KeyCode::F6;
+
+impl Config {
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Config, Report> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+
+    /// The config saved from the settings window, for the tools run from the command line
+    /// to work on the same terrain as the game. Defaults if there isn't one or it won't load.
+    pub fn saved_or_default() -> Config {
+        Config::load_from_path(CONFIG_PATH).unwrap_or_default()
+    }
+
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> Result<(), Report> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+}
+
+// Swaps the defaults for the config saved last time, if there is one
+pub fn load_on_startup(mut config: ResMut<Config>, mut errors: ResMut<ErrorLog>) {
+    let path = Path::new(CONFIG_PATH);
+    if !path.exists() {
+        return;
+    }
+    match Config::load_from_path(path) {
+        Ok(loaded) => *config = loaded,
+        Err(error) => errors.report(format!(
+            "Failed to load the terrain config {:?}: {}",
+            path, error
+        )),
+    }
+}
+
+// F6 writes the config as it's been tweaked in the settings window out to be loaded next time
+pub fn save_on_key(keys: Res<Input<KeyCode>>, config: Res<Config>, mut errors: ResMut<ErrorLog>) {
+    if !keys.just_pressed(SAVE_KEY) {
+        return;
+    }
+    match config.save_to_path(CONFIG_PATH) {
+        Ok(()) => info!("Saved the terrain config to {}", CONFIG_PATH),
+        Err(error) => errors.report(format!("Failed to save the terrain config: {}", error)),
+    }
+}
@@ -0,0 +1,293 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        pipeline::PrimitiveTopology,
+    },
+};
+use bevy_egui::{egui, EguiContext};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    na::{Point3, Vector3},
+    physics::{
+        IntoEntity, QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet,
+    },
+    prelude::{ColliderHandle, InteractionGroups, QueryPipeline, Ray},
+};
+
+use super::{height_map::HISTOGRAM_BINS, Chunk};
+use crate::{first_person::PlayerEyes, locale::Locale, Player};
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct TerrainDebugConfig {
+    // draw each vertex normal of the chunk under the crosshair
+    pub normals: bool,
+    // draw the direction the texture's u axis runs across the surface at each vertex
+    pub tangents: bool,
+    // show the spread of heights in the chunk under the crosshair
+    pub height_stats: bool,
+    // compare the chunk under the crosshair across a snapshot of the terrain config
+    pub config_diff: bool,
+    // hide the chunks outside the camera's view, turned off to see what's being culled from
+    // the overview
+    pub frustum_culling: bool,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    pub line_length: f32,
+    // how far away a chunk can be looked at
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub range: f32,
+}
+
+impl Default for TerrainDebugConfig {
+    fn default() -> Self {
+        Self {
+            normals: false,
+            tangents: false,
+            height_stats: false,
+            config_diff: false,
+            frustum_culling: true,
+            line_length: 2.0,
+            range: 1000.0,
+        }
+    }
+}
+
+/// The chunk the player is looking at, if any
+#[derive(Default)]
+pub struct TargetedChunk(pub Option<Entity>);
+
+#[derive(Clone, Copy, PartialEq)]
+enum LineKind {
+    Normal,
+    Tangent,
+}
+
+// Marks a line mesh drawn over the targeted chunk
+struct DebugLines(LineKind);
+
+pub fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (kind, color) in [
+        (LineKind::Normal, Color::rgb(0.2, 0.4, 1.0)),
+        (LineKind::Tangent, Color::rgb(1.0, 0.2, 0.2)),
+    ]
+    .iter()
+    {
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::new(PrimitiveTopology::LineList)),
+                material: materials.add(StandardMaterial {
+                    base_color: *color,
+                    unlit: true,
+                    ..Default::default()
+                }),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            })
+            .insert(DebugLines(*kind));
+    }
+}
+
+// Casts a ray out from the eyes, the chunk colliders sit on the chunk entities themselves
+pub fn target_chunk(
+    config: Res<TerrainDebugConfig>,
+    mut targeted: ResMut<TargetedChunk>,
+    query_pipeline: Res<QueryPipeline>,
+    collider_query: QueryPipelineColliderComponentsQuery,
+    player_query: Query<Entity, With<Player>>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    chunks_query: Query<(), With<Chunk>>,
+) {
+    let (player, eyes) = match (player_query.iter().next(), eyes_query.iter().next()) {
+        (Some(player), Some(eyes)) => (player, eyes),
+        _ => return,
+    };
+    let origin = eyes.translation;
+    let direction = eyes.rotation * -Vec3::Z;
+
+    let collider_set = QueryPipelineColliderComponentsSet(&collider_query);
+    let ray = Ray::new(
+        Point3::new(origin.x, origin.y, origin.z),
+        Vector3::new(direction.x, direction.y, direction.z),
+    );
+    let filter = |handle: ColliderHandle| handle.entity() != player;
+
+    let hit = query_pipeline
+        .cast_ray(
+            &collider_set,
+            &ray,
+            config.range,
+            true,
+            InteractionGroups::all(),
+            Some(&filter),
+        )
+        .map(|(handle, _)| handle.entity())
+        .filter(|&entity| chunks_query.get(entity).is_ok());
+    if targeted.0 != hit {
+        targeted.0 = hit;
+    }
+}
+
+// Rebuilds the line meshes whenever a different chunk, or a different mesh for it, is targeted
+pub fn draw_lines(
+    config: Res<TerrainDebugConfig>,
+    targeted: Res<TargetedChunk>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut drawn: Local<Option<Handle<Mesh>>>,
+    chunks_query: Query<(&Chunk, &Transform), Without<DebugLines>>,
+    mut lines_query: Query<(&DebugLines, &Handle<Mesh>, &mut Transform, &mut Visible)>,
+) {
+    let target = targeted
+        .0
+        .and_then(|entity| chunks_query.get(entity).ok())
+        .filter(|_| config.normals || config.tangents)
+        .and_then(|(chunk, transform)| Some((chunk.mesh()?.clone(), *transform)));
+
+    let chunk_mesh = target.as_ref().map(|(mesh, _)| mesh.clone());
+    if !config.is_changed() && *drawn == chunk_mesh {
+        return;
+    }
+    *drawn = chunk_mesh;
+
+    for (lines, lines_mesh, mut transform, mut visible) in lines_query.iter_mut() {
+        let shown = match lines.0 {
+            LineKind::Normal => config.normals,
+            LineKind::Tangent => config.tangents,
+        };
+        visible.is_visible = shown && target.is_some();
+        if !visible.is_visible {
+            continue;
+        }
+
+        let (chunk_mesh, chunk_transform) = target.as_ref().unwrap();
+        let positions = match meshes.get(chunk_mesh).and_then(|mesh| {
+            let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION)?;
+            let normals = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)?;
+            match (positions, normals) {
+                (
+                    VertexAttributeValues::Float3(positions),
+                    VertexAttributeValues::Float3(normals),
+                ) => Some(line_positions(
+                    positions,
+                    normals,
+                    lines.0,
+                    config.line_length,
+                )),
+                _ => None,
+            }
+        }) {
+            Some(positions) => positions,
+            None => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+
+        *transform = *chunk_transform;
+        if let Some(mesh) = meshes.get_mut(lines_mesh) {
+            let indices = (0..positions.len() as u32).collect();
+            mesh.set_attribute(
+                Mesh::ATTRIBUTE_NORMAL,
+                vec![[0.0, 1.0, 0.0]; positions.len()],
+            );
+            mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+            mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+            mesh.set_indices(Some(Indices::U32(indices)));
+        }
+    }
+}
+
+// A start and end point for each vertex's line
+fn line_positions(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    kind: LineKind,
+    length: f32,
+) -> Vec<[f32; 3]> {
+    positions
+        .iter()
+        .zip(normals.iter())
+        .flat_map(|(&position, &normal)| {
+            let position = Vec3::from(position);
+            // the face normals aren't unit length, so only their direction is trusted
+            let normal = Vec3::from(normal).normalize_or_zero();
+            let direction = match kind {
+                LineKind::Normal => normal,
+                // uvs run along x, so the tangent is x flattened onto the surface
+                LineKind::Tangent => (Vec3::X - normal * normal.dot(Vec3::X)).normalize_or_zero(),
+            };
+            vec![position.into(), (position + direction * length).into()]
+        })
+        .collect()
+}
+
+pub fn height_stats_panel(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    config: Res<TerrainDebugConfig>,
+    targeted: Res<TargetedChunk>,
+    chunks_query: Query<&Chunk>,
+) {
+    if !config.height_stats {
+        return;
+    }
+
+    egui::Window::new(locale.text("terrain-heights-title"))
+        .id(egui::Id::new("chunk_heights"))
+        .show(egui_context.ctx(), |ui| {
+            let chunk = match targeted.0.and_then(|entity| chunks_query.get(entity).ok()) {
+                Some(chunk) => chunk,
+                None => {
+                    ui.label(locale.text("terrain-heights-no-target"));
+                    return;
+                }
+            };
+            let coords = chunk.coords();
+            ui.label(locale.format(
+                "terrain-heights-chunk",
+                &[("x", coords.x.into()), ("y", coords.y.into())],
+            ));
+
+            let stats = match chunk.stats() {
+                Some(stats) => stats,
+                None => {
+                    ui.label(locale.text("terrain-heights-generating"));
+                    return;
+                }
+            };
+            ui.label(locale.format(
+                "terrain-heights-stats",
+                &[
+                    ("min", format!("{:.3}", stats.min).into()),
+                    ("max", format!("{:.3}", stats.max).into()),
+                    ("mean", format!("{:.3}", stats.mean).into()),
+                ],
+            ));
+
+            // one bar per slice of the normalized 0 to 1 range, lowest on the left
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(240.0, 80.0), egui::Sense::hover());
+            let painter = ui.painter();
+            painter.rect_filled(rect, 0.0, egui::Color32::from_gray(30));
+            let tallest = stats.histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+            let bar_width = rect.width() / HISTOGRAM_BINS as f32;
+            for (bin, &count) in stats.histogram.iter().enumerate() {
+                let left = rect.left() + bin as f32 * bar_width;
+                let top = rect.bottom() - rect.height() * count as f32 / tallest;
+                painter.rect_filled(
+                    egui::Rect::from_min_max(
+                        egui::pos2(left + 1.0, top),
+                        egui::pos2(left + bar_width - 1.0, rect.bottom()),
+                    ),
+                    0.0,
+                    egui::Color32::from_rgb(120, 170, 255),
+                );
+            }
+        });
+}
@@ -0,0 +1,183 @@
+use std::path::Path;
+
+use bevy::{
+    prelude::*,
+    render::texture::{Extent3d, TextureDimension, TextureFormat},
+};
+use bevy_egui::{egui, EguiContext};
+use color_eyre::Report;
+use image::RgbaImage;
+
+use super::{
+    debug::{TargetedChunk, TerrainDebugConfig},
+    height_map::HeightMap,
+    Chunk, ChunkCoords, Config, GenerationPipeline,
+};
+use crate::{error_log::ErrorLog, locale::Locale};
+
+const DIFF_DIR: &str = "diffs";
+const DIFF_TEXTURE_ID: u64 = 0x7e44_d1ff;
+// the gap left between the panels of the side by side image
+const GAP: u32 = 4;
+
+/// A copy of the terrain config to generate chunks under alongside the current one, and the
+/// last comparison made between them
+#[derive(Default)]
+pub struct ConfigDiff {
+    baseline: Option<Config>,
+    result: Option<DiffResult>,
+    texture: Option<Handle<Texture>>,
+}
+
+struct DiffResult {
+    coords: ChunkCoords,
+    // world-space heights moved from the baseline to the current config
+    mean_delta: f32,
+    max_delta: f32,
+    texture_size: egui::Vec2,
+}
+
+// Snapshots the config to compare against, then generates the chunk under the crosshair
+// under both configs and shows their heights side by side with the difference between them
+pub fn config_diff_panel(
+    mut egui_context: ResMut<EguiContext>,
+    locale: Res<Locale>,
+    debug_config: Res<TerrainDebugConfig>,
+    config: Res<Config>,
+    pipeline: Res<GenerationPipeline>,
+    targeted: Res<TargetedChunk>,
+    mut diff: ResMut<ConfigDiff>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut errors: ResMut<ErrorLog>,
+    chunks_query: Query<&Chunk>,
+) {
+    if !debug_config.config_diff {
+        return;
+    }
+
+    let target = targeted
+        .0
+        .and_then(|entity| chunks_query.get(entity).ok())
+        .map(|chunk| chunk.coords());
+    let mut snapshot = false;
+    let mut compare = false;
+    egui::Window::new(locale.text("terrain-diff-title"))
+        .id(egui::Id::new("config_diff"))
+        .show(egui_context.ctx(), |ui| {
+            ui.horizontal(|ui| {
+                snapshot = ui.button(locale.text("terrain-diff-snapshot")).clicked();
+                compare = ui
+                    .add(
+                        egui::Button::new(locale.text("terrain-diff-compare"))
+                            .enabled(diff.baseline.is_some() && target.is_some()),
+                    )
+                    .clicked();
+            });
+            if diff.baseline.is_none() {
+                ui.label(locale.text("terrain-diff-no-baseline"));
+            } else if target.is_none() {
+                ui.label(locale.text("terrain-heights-no-target"));
+            }
+
+            if let Some(result) = &diff.result {
+                ui.label(locale.format(
+                    "terrain-diff-summary",
+                    &[
+                        ("x", result.coords.x.into()),
+                        ("y", result.coords.y.into()),
+                        ("mean", format!("{:.2}", result.mean_delta).into()),
+                        ("max", format!("{:.2}", result.max_delta).into()),
+                    ],
+                ));
+                ui.label(locale.text("terrain-diff-legend"));
+                ui.image(egui::TextureId::User(DIFF_TEXTURE_ID), result.texture_size);
+            }
+        });
+
+    if snapshot {
+        diff.baseline = Some(config.clone());
+        diff.result = None;
+    }
+    let (baseline, coords) = match (&diff.baseline, target) {
+        (Some(baseline), Some(coords)) if compare => (baseline, coords),
+        _ => return,
+    };
+
+    // a single chunk is quick enough to generate twice while the button's pressed
+    let before = pipeline.run(baseline, coords);
+    let after = pipeline.run(&config, coords);
+    let (image, mean_delta, max_delta) = render_diff(&before, baseline, &after, &config);
+
+    let path = Path::new(DIFF_DIR).join(format!("chunk_{}_{}.png", coords.x, coords.y));
+    if let Err(error) = save_image(&image, &path) {
+        errors.report(format!("Failed to write {:?}: {}", path, error));
+    }
+
+    let texture_size = egui::vec2(image.width() as f32, image.height() as f32);
+    let texture = textures.add(Texture::new(
+        Extent3d::new(image.width(), image.height(), 1),
+        TextureDimension::D2,
+        image.into_raw(),
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+    egui_context.set_egui_texture(DIFF_TEXTURE_ID, texture.clone());
+    if let Some(previous) = diff.texture.replace(texture) {
+        textures.remove(previous);
+    }
+    diff.result = Some(DiffResult {
+        coords,
+        mean_delta,
+        max_delta,
+        texture_size,
+    });
+}
+
+// Lays out the baseline heights, the current heights and how far each moved, from blue where
+// the current config is lower through white to red where it's higher
+fn render_diff(
+    before: &HeightMap,
+    before_config: &Config,
+    after: &HeightMap,
+    after_config: &Config,
+) -> (RgbaImage, f32, f32) {
+    let size = before.size.min(after.size) as u32;
+    let world = |map: &HeightMap, config: &Config, x: u32, y: u32| {
+        map.data[y as usize][x as usize] * config.height_scale
+    };
+    let deltas: Vec<f32> = (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x, y)))
+        .map(|(x, y)| world(after, after_config, x, y) - world(before, before_config, x, y))
+        .collect();
+    let max_delta = deltas
+        .iter()
+        .fold(0.0f32, |largest, delta| largest.max(delta.abs()));
+    let mean_delta = deltas.iter().map(|delta| delta.abs()).sum::<f32>() / deltas.len() as f32;
+
+    let mut image = RgbaImage::from_pixel(size * 3 + GAP * 2, size, image::Rgba([0, 0, 0, 255]));
+    let gray = |height: f32| {
+        let value = (height.max(0.0).min(1.0) * 255.0) as u8;
+        image::Rgba([value, value, value, 255])
+    };
+    for y in 0..size {
+        for x in 0..size {
+            image.put_pixel(x, y, gray(before.data[y as usize][x as usize]));
+            image.put_pixel(size + GAP + x, y, gray(after.data[y as usize][x as usize]));
+
+            let amount = deltas[(y * size + x) as usize] / max_delta.max(f32::EPSILON);
+            let fade = (255.0 * (1.0 - amount.abs())) as u8;
+            let color = if amount >= 0.0 {
+                image::Rgba([255, fade, fade, 255])
+            } else {
+                image::Rgba([fade, fade, 255, 255])
+            };
+            image.put_pixel((size + GAP) * 2 + x, y, color);
+        }
+    }
+    (image, mean_delta, max_delta)
+}
+
+fn save_image(image: &RgbaImage, path: &Path) -> Result<(), Report> {
+    std::fs::create_dir_all(DIFF_DIR)?;
+    image.save(path)?;
+    Ok(())
+}
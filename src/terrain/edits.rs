@@ -0,0 +1,406 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bevy::prelude::*;
+use color_eyre::{eyre::eyre, Report};
+
+use super::{
+    endless::{ChunkCoords, CHUNK_SIZE},
+    height_map::HeightMap,
+    pipeline::GenerationPipeline,
+    Biome, Config, MAP_CHUNK_SIZE,
+};
+use crate::{bake::BakedArchive, error_log::ErrorLog};
+
+const EDITS_DIR: &str = "saves/terrain";
+// marks a file as terrain edits in this layout
+const MAGIC: &[u8; 8] = b"JWEDIT02";
+// the layout from before painting, with only the offsets
+const HEIGHTS_ONLY_MAGIC: &[u8; 8] = b"JWEDIT01";
+const CELLS: usize = (MAP_CHUNK_SIZE * MAP_CHUNK_SIZE) as usize;
+// each chunk's coordinates followed by the offset of every cell
+const CHUNK_ENTRY_SIZE: usize = 4 + 4 + CELLS * 4;
+// each chunk's coordinates followed by the terrain type painted on every cell
+const PAINT_ENTRY_SIZE: usize = 4 + 4 + CELLS;
+
+/// The terrain threshold painted over a cell of a chunk's paint layer, if any
+pub(super) fn painted_threshold(paint: &[u8], index: usize) -> Option<usize> {
+    paint[index].checked_sub(1).map(usize::from)
+}
+
+/// The changes made to the terrain by hand, as offsets from the heights the generation stages
+/// come up with and the terrain types painted over their colours. They're kept for each seed
+/// in `saves/terrain/seed-N.edits`.
+///
+/// Point queries in `query`, and so the props, only sample the noise and don't see them.
+#[derive(Default)]
+pub struct TerrainEdits {
+    // the normalized offset of every cell, row by row, of each chunk that's been edited
+    offsets: HashMap<ChunkCoords, Arc<Vec<f32>>>,
+    // the terrain type painted on every cell, row by row, of each chunk that's been painted
+    paint: HashMap<ChunkCoords, Arc<Vec<u8>>>,
+    // the heights generated for the chunks read while editing, so they're only generated once
+    generated: HashMap<ChunkCoords, HeightMap>,
+    // chunks edited since they were last sent to be regenerated
+    dirty: HashSet<ChunkCoords>,
+    // the new height in metres of every cell moved since the meshes were last patched, by
+    // its index in each chunk sharing it
+    moved: HashMap<ChunkCoords, HashMap<usize, f32>>,
+    // whether the patched chunks are held back from being regenerated
+    deferred: bool,
+    // chunks whose meshes have been patched in place since they were last regenerated
+    patched: HashSet<ChunkCoords>,
+    // whether anything's changed since the edits were last written out
+    unsaved: bool,
+    // the seed the edits were loaded for
+    seed: Option<u32>,
+}
+
+impl TerrainEdits {
+    /// The offsets made to a chunk's heights, if it's been edited
+    pub fn offsets(&self, coords: ChunkCoords) -> Option<Arc<Vec<f32>>> {
+        self.offsets.get(&coords).cloned()
+    }
+
+    /// The terrain types painted onto a chunk, if it's been painted
+    pub fn paint(&self, coords: ChunkCoords) -> Option<Arc<Vec<u8>>> {
+        self.paint.get(&coords).cloned()
+    }
+
+    /// Reads and writes the terrain's heights with these edits made to them, generating the
+    /// chunks that haven't been read yet the same way the workers do
+    pub fn editor<'a>(
+        &'a mut self,
+        config: &'a Config,
+        pipeline: &'a GenerationPipeline,
+        baked: Option<&'a BakedArchive>,
+    ) -> HeightEditor<'a> {
+        HeightEditor {
+            edits: self,
+            config,
+            pipeline,
+            baked,
+        }
+    }
+
+    /// Holds the chunks whose meshes have been patched in place back from being regenerated
+    /// while the ground's being shaped. Everything edited is regenerated, textures and
+    /// colliders along with it, once they're no longer held.
+    pub fn defer_regeneration(&mut self, deferred: bool) {
+        self.deferred = deferred;
+    }
+
+    /// Takes the chunks edited since the last call, to be regenerated, leaving those being
+    /// held back
+    pub(super) fn take_dirty(&mut self) -> HashSet<ChunkCoords> {
+        if self.deferred {
+            let patched = &self.patched;
+            let (held, ready) = mem::take(&mut self.dirty)
+                .into_iter()
+                .partition(|coords| patched.contains(coords));
+            self.dirty = held;
+            return ready;
+        }
+        self.patched.clear();
+        mem::take(&mut self.dirty)
+    }
+
+    /// Takes the heights of the cells moved since the last call, to patch the meshes with
+    pub(super) fn take_moved(&mut self) -> HashMap<ChunkCoords, HashMap<usize, f32>> {
+        mem::take(&mut self.moved)
+    }
+
+    /// Notes that a chunk's mesh shows its edits, so it can wait for the stroke to end
+    pub(super) fn mark_patched(&mut self, coords: ChunkCoords) {
+        self.patched.insert(coords);
+    }
+
+    /// Puts a chunk back to be regenerated on a later frame
+    pub(super) fn requeue(&mut self, coords: ChunkCoords) {
+        self.dirty.insert(coords);
+    }
+
+    /// Forgets the heights generated for editing, as the config they were generated for has
+    /// changed. The edits themselves are kept.
+    pub(super) fn forget_generated(&mut self) {
+        self.generated.clear();
+    }
+
+    /// Writes the edits out if they've changed since they were last written
+    pub fn save_if_changed(&mut self) -> Result<(), Report> {
+        let seed = match self.seed {
+            Some(seed) if self.unsaved => seed,
+            _ => return Ok(()),
+        };
+        let edited: Vec<_> = self
+            .offsets
+            .iter()
+            .filter(|(_, offsets)| offsets.iter().any(|&offset| offset != 0.0))
+            .collect();
+        let painted: Vec<_> = self
+            .paint
+            .iter()
+            .filter(|(_, paint)| paint.iter().any(|&terrain_type| terrain_type != 0))
+            .collect();
+
+        let mut bytes = Vec::with_capacity(
+            MAGIC.len()
+                + 4
+                + CHUNK_ENTRY_SIZE * edited.len()
+                + 4
+                + PAINT_ENTRY_SIZE * painted.len(),
+        );
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(edited.len() as u32).to_le_bytes());
+        for (coords, offsets) in edited {
+            bytes.extend_from_slice(&coords.x.to_le_bytes());
+            bytes.extend_from_slice(&coords.y.to_le_bytes());
+            for offset in offsets.iter() {
+                bytes.extend_from_slice(&offset.to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&(painted.len() as u32).to_le_bytes());
+        for (coords, paint) in painted {
+            bytes.extend_from_slice(&coords.x.to_le_bytes());
+            bytes.extend_from_slice(&coords.y.to_le_bytes());
+            bytes.extend_from_slice(paint);
+        }
+
+        let path = edits_path(seed);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, bytes)?;
+        self.unsaved = false;
+        Ok(())
+    }
+
+    fn load(path: &Path) -> Result<TerrainEdits, Report> {
+        let bytes = fs::read(path)?;
+        let magic = bytes.get(..MAGIC.len());
+        let has_paint = magic == Some(&MAGIC[..]);
+        if !has_paint && magic != Some(&HEIGHTS_ONLY_MAGIC[..]) {
+            return Err(eyre!(
+                "{:?} isn't terrain edits saved by this version",
+                path
+            ));
+        }
+        let truncated = || eyre!("{:?} is cut short", path);
+        let count = read_u32(&bytes, MAGIC.len()).ok_or_else(truncated)? as usize;
+        let start = MAGIC.len() + 4;
+        let end = start + count * CHUNK_ENTRY_SIZE;
+        let entries = bytes.get(start..end).ok_or_else(truncated)?;
+        let offsets = entries
+            .chunks_exact(CHUNK_ENTRY_SIZE)
+            .map(|entry| {
+                let offsets = entry[8..]
+                    .chunks_exact(4)
+                    .map(|cell| f32::from_le_bytes(cell.try_into().unwrap()))
+                    .collect();
+                (entry_coords(entry), Arc::new(offsets))
+            })
+            .collect();
+
+        let mut paint = HashMap::new();
+        if has_paint {
+            let count = read_u32(&bytes, end).ok_or_else(truncated)? as usize;
+            let entries = bytes
+                .get(end + 4..end + 4 + count * PAINT_ENTRY_SIZE)
+                .ok_or_else(truncated)?;
+            paint = entries
+                .chunks_exact(PAINT_ENTRY_SIZE)
+                .map(|entry| (entry_coords(entry), Arc::new(entry[8..].to_vec())))
+                .collect();
+        }
+
+        Ok(TerrainEdits {
+            offsets,
+            paint,
+            ..Default::default()
+        })
+    }
+}
+
+// The coordinates an entry of the file starts with
+fn entry_coords(entry: &[u8]) -> ChunkCoords {
+    ChunkCoords {
+        x: read_u32(entry, 0).unwrap() as i32,
+        y: read_u32(entry, 4).unwrap() as i32,
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Where the edits made to a seed's terrain are kept
+pub fn edits_path(seed: u32) -> PathBuf {
+    Path::new(EDITS_DIR).join(format!("seed-{}.edits", seed))
+}
+
+/// The terrain's heights with the edits made to them, in world space metres, and the terrain
+/// types painted onto it, a cell of the height map grid at a time. The cells are a metre
+/// apart, and those along a chunk's edges are shared with the chunks beside it, so are kept in
+/// step across all of them.
+pub struct HeightEditor<'a> {
+    edits: &'a mut TerrainEdits,
+    config: &'a Config,
+    pipeline: &'a GenerationPipeline,
+    baked: Option<&'a BakedArchive>,
+}
+
+impl HeightEditor<'_> {
+    pub fn height(&mut self, cell: IVec2) -> f32 {
+        let (coords, index) = chunks_sharing(cell)[0];
+        let generated = self.generated(coords, index);
+        let offset = self
+            .edits
+            .offsets
+            .get(&coords)
+            .map_or(0.0, |offsets| offsets[index]);
+        (generated + offset) * self.config.height_scale
+    }
+
+    pub fn set_height(&mut self, cell: IVec2, height: f32) {
+        let normalized = height / self.config.height_scale;
+        for &(coords, index) in chunks_sharing(cell).iter() {
+            let generated = self.generated(coords, index);
+            let offsets = self
+                .edits
+                .offsets
+                .entry(coords)
+                .or_insert_with(|| Arc::new(vec![0.0; CELLS]));
+            // copied if a worker's still meshing the chunk from the last edit
+            Arc::make_mut(offsets)[index] = normalized - generated;
+            self.edits.dirty.insert(coords);
+            self.edits
+                .moved
+                .entry(coords)
+                .or_default()
+                .insert(index, height);
+        }
+        self.edits.unsaved = true;
+    }
+
+    /// Paints the colour of a terrain threshold over the one a cell's height gives it, or with
+    /// `None` goes back to that colour
+    pub fn set_terrain_type(&mut self, cell: IVec2, terrain_type: Option<Biome>) {
+        // kept as one more than the threshold, 0 being left unpainted
+        let byte = terrain_type.map_or(0, |biome| biome as u8 + 1);
+        for &(coords, index) in chunks_sharing(cell).iter() {
+            let current = self
+                .edits
+                .paint
+                .get(&coords)
+                .map_or(0, |paint| paint[index]);
+            // held brushes paint the same cells every frame, which shouldn't recolour them
+            if current == byte {
+                continue;
+            }
+            let paint = self
+                .edits
+                .paint
+                .entry(coords)
+                .or_insert_with(|| Arc::new(vec![0; CELLS]));
+            Arc::make_mut(paint)[index] = byte;
+            self.edits.dirty.insert(coords);
+            self.edits.unsaved = true;
+        }
+    }
+
+    // The height the generation stages give a cell of a chunk
+    fn generated(&mut self, coords: ChunkCoords, index: usize) -> f32 {
+        let (config, pipeline, baked) = (self.config, self.pipeline, self.baked);
+        let generated = self
+            .edits
+            .generated
+            .entry(coords)
+            .or_insert_with(|| pipeline.chunk_height_map(config, coords, baked));
+        generated.data[index / generated.size][index % generated.size]
+    }
+}
+
+/// The cell of the height map grid nearest a point on the xz plane
+pub fn cell_at(position: Vec2) -> IVec2 {
+    // chunk meshes are centred on their chunk position, so shift the point into grid space
+    let grid_point = (position + Vec2::splat(CHUNK_SIZE as f32 / 2.0)).round();
+    IVec2::new(grid_point.x as i32, grid_point.y as i32)
+}
+
+/// Where a cell of the height map grid is on the xz plane
+pub fn cell_position(cell: IVec2) -> Vec2 {
+    Vec2::new(cell.x as f32, cell.y as f32) - Vec2::splat(CHUNK_SIZE as f32 / 2.0)
+}
+
+// Every chunk with the cell in its height map, along with where it is in the chunk's cells.
+// Cells on an edge are also the first or last of the chunk beside it.
+fn chunks_sharing(cell: IVec2) -> Vec<(ChunkCoords, usize)> {
+    fn along(value: i32) -> Vec<(i32, usize)> {
+        let size = CHUNK_SIZE as i32;
+        let (chunk, offset) = (value.div_euclid(size), value.rem_euclid(size) as usize);
+        if offset == 0 {
+            vec![(chunk, 0), (chunk - 1, CHUNK_SIZE as usize)]
+        } else {
+            vec![(chunk, offset)]
+        }
+    }
+
+    let mut chunks = Vec::with_capacity(4);
+    for (y, row) in along(cell.y) {
+        for &(x, column) in along(cell.x).iter() {
+            chunks.push((ChunkCoords { x, y }, row * MAP_CHUNK_SIZE as usize + column));
+        }
+    }
+    chunks
+}
+
+/// Adds the offsets made by hand to the heights generated for a chunk
+pub(super) fn apply_offsets(height_map: &mut HeightMap, offsets: &[f32]) {
+    for (row, row_offsets) in height_map
+        .data
+        .iter_mut()
+        .zip(offsets.chunks_exact(height_map.size))
+    {
+        for (height, offset) in row.iter_mut().zip(row_offsets) {
+            *height += offset;
+        }
+    }
+}
+
+// Saves the edits made to the last seed and loads those made to the new one, whenever the
+// terrain's seed changes
+pub fn load_for_seed(
+    config: Res<Config>,
+    mut edits: ResMut<TerrainEdits>,
+    mut errors: ResMut<ErrorLog>,
+) {
+    let seed = config.seed();
+    if edits.seed == Some(seed) {
+        return;
+    }
+    if let Err(error) = edits.save_if_changed() {
+        errors.report(format!("Failed to save the terrain edits: {}", error));
+    }
+
+    let path = edits_path(seed);
+    let loaded = if path.exists() {
+        TerrainEdits::load(&path).unwrap_or_else(|error| {
+            errors.report(format!(
+                "Failed to load the terrain edits {:?}: {}",
+                path, error
+            ));
+            TerrainEdits::default()
+        })
+    } else {
+        TerrainEdits::default()
+    };
+    *edits = TerrainEdits {
+        seed: Some(seed),
+        ..loaded
+    };
+}
@@ -1,194 +1,417 @@
-use crate::Player;
+use crate::{bake::BakedWorld, editor::AppMode, sky::Sun, Player};
 
-use super::{height_map::HeightMap, mesh, texture, Config, SimplificationLevel, MAP_CHUNK_SIZE};
+use super::{
+    debug::TerrainDebugConfig,
+    edits::TerrainEdits,
+    failure::{ChunkFailures, ChunkGenerationFailed, RetryGeneration},
+    height_map::HeightStats,
+    mesh,
+    pipeline::GenerationPipeline,
+    quadtree::{self, LodTree, Node, Selection},
+    shadows,
+    worker::{ChunkJob, ChunkWorkers},
+    Config, SimplificationLevel, MAP_CHUNK_SIZE,
+};
 use bevy::{
     math::{Vec3, Vec3Swizzles},
     prelude::*,
-    render::wireframe::Wireframe,
-    tasks::{AsyncComputeTaskPool, Task},
+    render::{
+        camera::{ActiveCameras, Camera},
+        render_graph::base::camera::CAMERA_3D,
+        wireframe::Wireframe,
+    },
+    tasks::AsyncComputeTaskPool,
 };
-use bevy_rapier3d::{physics::ColliderBundle, prelude::SharedShape};
+use bevy_rapier3d::{physics::ColliderBundle, prelude::RigidBodyVelocity};
 use derive_more::{Deref, DerefMut};
-use futures_lite::future;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-const CHUNK_SIZE: u32 = MAP_CHUNK_SIZE - 1;
+pub const CHUNK_SIZE: u32 = MAP_CHUNK_SIZE - 1;
 const CHUNK_UPDATE_MOVEMENT_THRESHOLD: f32 = CHUNK_SIZE as f32 * 0.1;
 
-pub fn setup(mut commands: Commands, mut events: EventWriter<StartChunkUpdateEvent>) {
+pub fn setup(
+    mut commands: Commands,
+    task_pool: Res<AsyncComputeTaskPool>,
+    mut events: EventWriter<StartChunkUpdateEvent>,
+) {
+    commands.insert_resource(ChunkWorkers::spawn(&task_pool.0));
     commands.insert_resource(SeenChunks::default());
+    commands.insert_resource(FarNodes::default());
+    commands.insert_resource(LodTree::default());
+    commands.insert_resource(TexturePool::default());
     commands.insert_resource(LastChunkUpdatePosition::default());
     events.send(StartChunkUpdateEvent);
 }
 
 // Ensures the chunks are updated only if the player has moved a set distance since the last update
 pub fn trigger_update(
+    app_mode: Res<State<AppMode>>,
     mut events: EventWriter<StartChunkUpdateEvent>,
     mut last_chunk_update_position: ResMut<LastChunkUpdatePosition>,
     player_query: Query<(&Player, &Transform)>,
 ) {
-    let viewer_position = player_query.iter().nth(0).unwrap().1.translation.xz();
+    // the levels are pinned while editing, so the chunks being shaped stay as they are
+    if *app_mode.current() != AppMode::Play {
+        return;
+    }
+    let viewer_position = match player_query.iter().next() {
+        Some((_, transform)) => transform.translation.xz(),
+        None => return,
+    };
     if viewer_position.distance(last_chunk_update_position.0) > CHUNK_UPDATE_MOVEMENT_THRESHOLD {
         last_chunk_update_position.0 = viewer_position;
         events.send(StartChunkUpdateEvent);
     }
 }
 
-// Creates / updates chunk entities with the correct simplification level and coordinates
+// Creates / updates chunk entities with the correct simplification level and coordinates,
+// along with the larger nodes covering the rest of the view
 pub fn initialize_chunks(
     mut commands: Commands,
     config: Res<Config>,
     mut seen_chunks: ResMut<SeenChunks>,
+    mut far_nodes: ResMut<FarNodes>,
+    mut lod_tree: ResMut<LodTree>,
+    mut failures: ResMut<ChunkFailures>,
     mut start_chunk_update_events: EventReader<StartChunkUpdateEvent>,
-    player_query: Query<(&Player, &Transform)>,
+    mut chunk_spawned_events: EventWriter<ChunkSpawnedEvent>,
+    player_query: Query<(&Transform, Option<&RigidBodyVelocity>), With<Player>>,
+    mut chunks_query: Query<&mut Chunk>,
 ) {
     if start_chunk_update_events.iter().next().is_none() {
         return;
     }
 
-    let viewer_position = player_query.iter().nth(0).unwrap().1.translation.xz();
-    let viewer_chunk_coords = ChunkCoords::from_position(&viewer_position);
-
-    let chunk_range = if config.endless {
-        let chunks_in_view_distance = config.max_view_distance / CHUNK_SIZE as f32;
-        (-(chunks_in_view_distance as i32))..chunks_in_view_distance as i32
+    let (viewer_position, travel) = match player_query.iter().next() {
+        Some((transform, velocity)) => (
+            transform.translation.xz(),
+            velocity.map_or(Vec2::ZERO, |velocity| Vec3::from(velocity.linvel).xz()),
+        ),
+        None => return,
+    };
+    let selection = if config.endless {
+        quadtree::select(&config, &mut lod_tree, viewer_position, travel)
     } else {
-        0..1
+        Selection {
+            chunks: vec![(ChunkCoords::default(), SimplificationLevel::min())],
+            nodes: Vec::new(),
+        }
     };
-    for y_offset in chunk_range.clone() {
-        for x_offset in chunk_range.clone() {
-            let chunk_coords = ChunkCoords {
-                x: viewer_chunk_coords.x + x_offset,
-                y: viewer_chunk_coords.y + y_offset,
-            };
-
-            let distance_from_viewer = chunk_coords.to_position().distance(viewer_position);
+    // chunks whose level changed, or which were just seen or dropped, along with the level
+    // they had
+    let mut changed = Vec::new();
 
-            let simplification_level = if distance_from_viewer
-                < config.low_simplification_threshold.max_distance
-            {
-                config.low_simplification_threshold.level
-            } else if distance_from_viewer < config.medium_simplification_threshold.max_distance {
-                config.medium_simplification_threshold.level
-            } else if distance_from_viewer < config.high_simplification_threshold.max_distance {
-                config.high_simplification_threshold.level
-            } else {
-                SimplificationLevel::max()
-            };
+    // Chunks no longer wanted stay on screen until whatever replaces them has a mesh
+    let wanted: HashSet<ChunkCoords> = selection.chunks.iter().map(|&(coords, _)| coords).collect();
+    let dropped: Vec<ChunkCoords> = seen_chunks
+        .keys()
+        .filter(|coords| !wanted.contains(coords))
+        .copied()
+        .collect();
+    for coords in dropped {
+        if let Some((level, entity)) = seen_chunks.remove(&coords) {
+            commands.entity(entity).insert(Retiring);
+            failures.0.remove(&coords);
+            changed.push((coords, Some(level)));
+        }
+    }
 
-            if let Some((existing_simplification_level, entity)) =
-                seen_chunks.get_mut(&chunk_coords)
-            {
-                if *existing_simplification_level != simplification_level {
-                    *existing_simplification_level = simplification_level;
-                    commands
-                        .entity(*entity)
-                        .insert(Processing)
-                        .insert(Chunk {
-                            coords: chunk_coords,
-                            simplification_level,
-                        })
-                        .remove_bundle::<ColliderBundle>();
+    for (chunk_coords, simplification_level) in selection.chunks {
+        if let Some((existing_simplification_level, entity)) = seen_chunks.get_mut(&chunk_coords) {
+            if *existing_simplification_level != simplification_level {
+                changed.push((chunk_coords, Some(*existing_simplification_level)));
+                *existing_simplification_level = simplification_level;
+                if let Ok(mut chunk) = chunks_query.get_mut(*entity) {
+                    if chunk.refine_to.is_some() {
+                        // still on its coarse first pass, so refine straight to the new level
+                        let coarse = chunk.simplification_level;
+                        chunk.refine_to =
+                            Some(simplification_level).filter(|&level| level != coarse);
+                        continue;
+                    }
+                    chunk.simplification_level = simplification_level;
                 }
-            } else {
-                let entity = commands
-                    .spawn()
-                    .insert(Chunk {
-                        coords: chunk_coords,
-                        simplification_level,
-                    })
+                commands
+                    .entity(*entity)
                     .insert(Processing)
-                    .id();
-                seen_chunks.insert(chunk_coords, (simplification_level, entity));
+                    .remove_bundle::<ColliderBundle>();
             }
+        } else {
+            // Get something on screen at the cheapest level first so fast travel never
+            // shows holes, then regenerate it at the level it should be
+            let coarse = SimplificationLevel::max();
+            let entity = commands
+                .spawn()
+                .insert(Chunk {
+                    coords: chunk_coords,
+                    simplification_level: coarse,
+                    refine_to: Some(simplification_level).filter(|&level| level != coarse),
+                    ..Default::default()
+                })
+                .insert(Processing)
+                .id();
+            seen_chunks.insert(chunk_coords, (simplification_level, entity));
+            changed.push((chunk_coords, None));
+            chunk_spawned_events.send(ChunkSpawnedEvent {
+                coords: chunk_coords,
+                entity,
+            });
         }
     }
+
+    // The nodes are only ever generated at one level, their size doing the simplifying
+    let wanted: HashSet<Node> = selection.nodes.iter().copied().collect();
+    far_nodes.retain(|node, entity| {
+        let keep = wanted.contains(node);
+        if !keep {
+            commands.entity(*entity).insert(Retiring);
+        }
+        keep
+    });
+    for node in selection.nodes {
+        far_nodes.entry(node).or_insert_with(|| {
+            commands
+                .spawn()
+                .insert(Chunk {
+                    coords: node.coords,
+                    depth: node.depth,
+                    simplification_level: SimplificationLevel::min(),
+                    ..Default::default()
+                })
+                .insert(Processing)
+                .id()
+        });
+    }
+
+    restitch_neighbours(&mut commands, &seen_chunks, &changed);
 }
 
-// Computes the chunk mesh and texture
+// Regenerates the chunks next to those that changed level whose shared edge has to bend
+// differently to meet them now. Unloaded chunks are left out, as they're beyond the view.
+fn restitch_neighbours(
+    commands: &mut Commands,
+    seen_chunks: &SeenChunks,
+    changed: &[(ChunkCoords, Option<SimplificationLevel>)],
+) {
+    let mut restitched = HashSet::new();
+    for (coords, previous) in changed {
+        let current = seen_chunks.get(coords).map(|&(level, _)| level);
+        for neighbour in coords.neighbours().iter() {
+            let (level, entity) = match seen_chunks.get(neighbour) {
+                Some(&seen) => seen,
+                None => continue,
+            };
+            let anchors = |other: Option<SimplificationLevel>| {
+                other.and_then(|other| mesh::edge_anchors(level, other))
+            };
+            if anchors(*previous) != anchors(current) && restitched.insert(entity) {
+                // taken off and put back on so a chunk already in flight is sent again. The
+                // collider stays to stand on until the regenerated one replaces it.
+                commands
+                    .entity(entity)
+                    .remove::<Processing>()
+                    .insert(Processing);
+            }
+        }
+    }
+}
+
+// Hands each chunk that needs generating to the workers
 pub fn process_chunks(
     newly_processing_chunks_query: Query<(Entity, &Chunk), Added<Processing>>,
     config: Res<Config>,
-    task_pool: ResMut<AsyncComputeTaskPool>,
-    mut commands: Commands,
+    seen_chunks: Res<SeenChunks>,
+    pipeline: Res<GenerationPipeline>,
+    workers: Res<ChunkWorkers>,
+    baked: Res<BakedWorld>,
+    edits: Res<TerrainEdits>,
+    sun: Res<Sun>,
+    player_query: Query<&Transform, With<Player>>,
 ) {
+    let viewer = match player_query.iter().next() {
+        Some(transform) => transform.translation.xz(),
+        None => return,
+    };
+
     for (entity, chunk) in newly_processing_chunks_query.iter() {
-        let config = config.clone();
-        let simplification_level = chunk.simplification_level.clone();
-        let entity = entity.clone();
-        let chunk_coords = chunk.coords.clone();
-
-        let task = task_pool.spawn(async move {
-            let height_map = HeightMap::generate(&config, &chunk_coords);
-            let texture = texture::generate(&height_map, &config);
-            let mut terrain_mesh_generator =
-                mesh::Generator::new(height_map, config.height_scale, simplification_level);
-            terrain_mesh_generator.generate();
-            let mesh = terrain_mesh_generator.graphics_mesh();
-            let collider_shape = terrain_mesh_generator.collider_shape();
-
-            (texture, mesh, collider_shape)
+        let baked_sun = shadows::sun_for_chunk(&config, &sun, chunk.centre(), viewer);
+        // stitched to the level each neighbour is headed for, past any coarse first pass. The
+        // nodes further out only meet each other along skirts.
+        let (neighbour_levels, height_offsets, paint) = if chunk.depth == 0 {
+            (
+                neighbour_levels(&seen_chunks, chunk.coords),
+                edits.offsets(chunk.coords),
+                edits.paint(chunk.coords),
+            )
+        } else {
+            ([None; 4], None, None)
+        };
+        workers.send(ChunkJob {
+            entity,
+            coords: chunk.coords,
+            depth: chunk.depth,
+            simplification_level: chunk.simplification_level,
+            neighbour_levels,
+            config: config.clone(),
+            pipeline: pipeline.clone(),
+            sun: baked_sun,
+            cache_generation: workers.cache.generation(),
+            baked: baked.0.clone(),
+            height_offsets,
+            paint,
         });
+    }
+}
 
-        commands.entity(entity).insert(task);
+fn neighbour_levels(
+    seen_chunks: &SeenChunks,
+    coords: ChunkCoords,
+) -> [Option<SimplificationLevel>; 4] {
+    let mut levels = [None; 4];
+    for (level, neighbour) in levels.iter_mut().zip(coords.neighbours().iter()) {
+        *level = seen_chunks.get(neighbour).map(|&(level, _)| level);
     }
+    levels
 }
 
-// This system polls the chunk generation tasks and when one is complete updates the entity with a mesh, texture, and physics collider
+// This system picks up the chunks the workers have finished and updates each entity with a mesh, texture, and physics collider
 pub fn insert_chunks(
     mut commands: Commands,
-    mut chunks_query: Query<(Entity, &Chunk, &mut ChunkTask)>,
+    workers: Res<ChunkWorkers>,
+    mut chunks_query: Query<(&mut Chunk, Option<&Handle<StandardMaterial>>)>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut textures: ResMut<Assets<Texture>>,
+    mut texture_pool: ResMut<TexturePool>,
+    mut failures: ResMut<ChunkFailures>,
+    mut failed_events: EventWriter<ChunkGenerationFailed>,
     config: Res<Config>,
 ) {
-    for (entity, chunk, mut task) in chunks_query.iter_mut() {
-        if let Some((texture, mesh, collider_shape)) =
-            future::block_on(future::poll_once(&mut *task))
+    for result in workers.finished() {
+        let entity = result.entity;
+        // the chunk may have been unloaded, or be waiting on a newer level, since it was sent
+        if let Some((mut chunk, previous_material)) = chunks_query
+            .get_mut(entity)
+            .ok()
+            .filter(|(chunk, _)| chunk.simplification_level == result.simplification_level)
         {
+            let (texture, splat, mesh, collider_shape, bounds, stats) = match result.generated {
+                Ok(generated) => generated,
+                Err(message) => {
+                    chunk.failed_attempts += 1;
+                    failed_events.send(ChunkGenerationFailed {
+                        coords: chunk.coords,
+                        entity,
+                        attempts: chunk.failed_attempts,
+                        message,
+                    });
+
+                    match RetryGeneration::after(chunk.failed_attempts) {
+                        Some(retry) => commands.entity(entity).insert(retry),
+                        None => commands.entity(entity).remove::<Processing>(),
+                    };
+                    continue;
+                }
+            };
+            chunk.failed_attempts = 0;
+            failures.0.remove(&chunk.coords);
+
+            let _upload =
+                info_span!("upload_chunk", x = chunk.coords.x, y = chunk.coords.y).entered();
+            chunk.bounds = Some(bounds);
+            chunk.stats = Some(stats);
+            chunk.splat = splat;
+
+            // nodes are meshed from as many cells as a chunk, stretched out over their span
             let position = chunk.coords.to_position();
+            let span = chunk.span() as f32;
             let transform = Transform {
                 translation: Vec3::new(
                     position.x - CHUNK_SIZE as f32 / 2.0,
                     0.0,
                     position.y - CHUNK_SIZE as f32 / 2.0,
                 ),
+                scale: Vec3::new(span, 1.0, span),
                 ..Default::default()
             };
 
-            let pbr = PbrBundle {
-                mesh: meshes.add(mesh),
-                material: materials.add(StandardMaterial {
-                    base_color_texture: Some(textures.add(texture)),
+            // A chunk changing its simplification level is coloured in exactly the same, so it
+            // keeps its material and texture along with anything painted onto them
+            let material = match previous_material {
+                Some(material) => {
+                    // except for distant chunks, whose shadows move round with the sun, and
+                    // chunks that have been edited
+                    if result.sun != chunk.baked_sun || chunk.recolour {
+                        let existing = materials
+                            .get(material)
+                            .and_then(|material| material.base_color_texture.as_ref())
+                            .and_then(|handle| textures.get_mut(handle));
+                        if let Some(existing) = existing {
+                            existing.data = texture.data;
+                            chunk.rebakes += 1;
+                        }
+                    }
+                    material.clone()
+                }
+                None => materials.add(StandardMaterial {
+                    base_color_texture: Some(texture_pool.recycle(&mut textures, texture)),
                     roughness: config.material_roughness,
                     reflectance: config.material_reflectance,
                     unlit: true,
                     ..Default::default()
                 }),
-                transform,
-                ..Default::default()
             };
 
-            let collider = ColliderBundle {
-                position: transform.translation.into(),
-                shape: collider_shape,
-                ..ColliderBundle::default()
+            let mesh = meshes.add(mesh);
+            // the old mesh was for the previous simplification level, so nothing else uses it
+            if let Some(previous) = chunk.mesh.replace(mesh.clone()) {
+                meshes.remove(previous);
+            }
+            chunk.material = Some(material.clone());
+            chunk.baked_sun = result.sun;
+            chunk.recolour = false;
+
+            let pbr = PbrBundle {
+                mesh,
+                material,
+                transform,
+                ..Default::default()
             };
 
-            commands
-                .entity(entity)
-                .insert_bundle(pbr)
-                .insert_bundle(collider);
+            commands.entity(entity).insert_bundle(pbr);
 
             if config.wireframe {
                 commands.entity(entity).insert(Wireframe);
             }
 
-            commands
-                .entity(entity)
-                .remove::<Processing>()
-                .remove::<ChunkTask>();
+            match chunk.refine_to.take() {
+                // the coarse mesh is only a stand in, so it goes without a collider and is
+                // generated again straight away
+                Some(level) => {
+                    chunk.simplification_level = level;
+                    commands
+                        .entity(entity)
+                        .remove::<Processing>()
+                        .insert(Processing);
+                }
+                // the nodes are too far away to walk on
+                None if chunk.depth > 0 => {
+                    commands.entity(entity).remove::<Processing>();
+                }
+                None => {
+                    // the heightfield is centred on the chunk rather than its first vertex.
+                    // An edited chunk's replaces the one it kept standing on till now.
+                    let collider = ColliderBundle {
+                        position: Vec3::new(position.x, 0.0, position.y).into(),
+                        shape: collider_shape,
+                        ..ColliderBundle::default()
+                    };
+                    commands
+                        .entity(entity)
+                        .insert_bundle(collider)
+                        .remove::<Processing>();
+                }
+            }
         }
     }
 }
@@ -197,46 +420,285 @@ pub fn insert_chunks(
 pub fn rebuild_on_change(
     mut commands: Commands,
     config: Res<Config>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
     chunk_query: Query<(Entity, &Chunk)>,
     mut seen_chunks: ResMut<SeenChunks>,
+    mut far_nodes: ResMut<FarNodes>,
+    mut lod_tree: ResMut<LodTree>,
+    mut texture_pool: ResMut<TexturePool>,
+    mut failures: ResMut<ChunkFailures>,
+    mut edits: ResMut<TerrainEdits>,
+    workers: Res<ChunkWorkers>,
     mut events: EventWriter<StartChunkUpdateEvent>,
 ) {
     if config.is_changed() {
-        // Destroy all the previous terrain entities
-        for (entity, _) in chunk_query.iter() {
-            commands.entity(entity).despawn_recursive()
+        // Destroy all the previous terrain entities along with their meshes and materials,
+        // keeping their textures to write the new terrain into
+        for (entity, chunk) in chunk_query.iter() {
+            unload_chunk(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut texture_pool,
+                entity,
+                chunk,
+            );
         }
 
         seen_chunks.clear();
+        far_nodes.clear();
+        // the cached height maps were generated for the old config
+        workers.cache.clear();
+        edits.forget_generated();
+        lod_tree.clear();
+        failures.0.clear();
         events.send(StartChunkUpdateEvent);
     }
 }
 
-// Computes if chunks should be visible based on the distance between the edge of the chunk and the player
+// Regenerates the chunks whose heights have been edited, keeping their colliders to stand on
+// until the new ones are in. Chunks already in flight are held back until they're done, so a
+// brush held down doesn't pile jobs up.
+pub fn regenerate_edited(
+    mut commands: Commands,
+    mut edits: ResMut<TerrainEdits>,
+    seen_chunks: Res<SeenChunks>,
+    mut chunks_query: Query<(&mut Chunk, Option<&Processing>)>,
+) {
+    for coords in edits.take_dirty() {
+        let entity = match seen_chunks.get(&coords) {
+            Some(&(_, entity)) => entity,
+            // picked up with the edits whenever it's loaded
+            None => continue,
+        };
+        if let Ok((mut chunk, processing)) = chunks_query.get_mut(entity) {
+            if processing.is_some() {
+                edits.requeue(coords);
+                continue;
+            }
+            chunk.recolour = true;
+            commands.entity(entity).insert(Processing);
+        }
+    }
+}
+
+// Moves the vertices of the chunks being shaped to their new heights in place, so a held
+// brush shows at once rather than waiting on the workers. The chunks patched are regenerated
+// for their textures and colliders, and the cells between their vertices, once the stroke's
+// done.
+pub fn patch_edited(
+    config: Res<Config>,
+    mut edits: ResMut<TerrainEdits>,
+    seen_chunks: Res<SeenChunks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    // those in flight will get a mesh from before these edits, so are regenerated again
+    mut chunks_query: Query<&mut Chunk, Without<Processing>>,
+) {
+    for (coords, heights) in edits.take_moved() {
+        // flat shading splits the vertices apart, so those chunks are regenerated as they go
+        if config.flat_shading {
+            continue;
+        }
+        let entity = match seen_chunks.get(&coords) {
+            Some(&(_, entity)) => entity,
+            None => continue,
+        };
+        let mut chunk = match chunks_query.get_mut(entity) {
+            Ok(chunk) => chunk,
+            Err(_) => continue,
+        };
+        let level = chunk.simplification_level;
+        let mut stitched = [false; 4];
+        for (side, neighbour) in coords.neighbours().iter().enumerate() {
+            stitched[side] = seen_chunks.get(neighbour).map_or(false, |&(other, _)| {
+                mesh::edge_anchors(level, other).is_some()
+            });
+        }
+        let increment = mesh::simplification_increment(level);
+        let patched = match chunk.mesh.as_ref().and_then(|mesh| meshes.get_mut(mesh)) {
+            Some(mesh) => {
+                mesh::patch_heights(mesh, MAP_CHUNK_SIZE as usize, increment, &heights, stitched)
+            }
+            None => false,
+        };
+        if !patched {
+            continue;
+        }
+        edits.mark_patched(coords);
+
+        // only touched when the ground's risen or sunk past them, as that marks the chunk
+        // changed
+        let bounds = match chunk.bounds {
+            Some(bounds) => bounds,
+            None => continue,
+        };
+        let widened = heights
+            .values()
+            .fold(bounds, |bounds, &height| HeightBounds {
+                min: bounds.min.min(height),
+                max: bounds.max.max(height),
+            });
+        if widened != bounds {
+            chunk.bounds = Some(widened);
+        }
+    }
+}
+
+// Unloads the chunks and nodes that were dropped once everything now covering the same
+// ground has a mesh, so splitting and joining nodes never leaves a hole. Anything dropped
+// for being out of view has nothing replacing it, so goes straight away.
+pub fn retire_replaced_chunks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut texture_pool: ResMut<TexturePool>,
+    seen_chunks: Res<SeenChunks>,
+    far_nodes: Res<FarNodes>,
+    retiring_query: Query<(Entity, &Chunk), With<Retiring>>,
+    chunks_query: Query<(&Chunk, Option<&Processing>), Without<Retiring>>,
+) {
+    // given up on chunks won't ever get a mesh, so aren't waited on
+    let ready = |entity: Entity| {
+        chunks_query
+            .get(entity)
+            .map_or(false, |(chunk, processing)| {
+                chunk.mesh.is_some() || processing.is_none()
+            })
+    };
+
+    for (entity, chunk) in retiring_query.iter() {
+        let node = chunk.node();
+        let chunks_ready = (0..node.span())
+            .flat_map(|y| (0..node.span()).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                seen_chunks.get(&ChunkCoords {
+                    x: node.coords.x + x,
+                    y: node.coords.y + y,
+                })
+            })
+            .all(|&(_, entity)| ready(entity));
+        let nodes_ready = far_nodes
+            .iter()
+            .filter(|(other, _)| other.overlaps(&node))
+            .all(|(_, &entity)| ready(entity));
+
+        if chunks_ready && nodes_ready {
+            unload_chunk(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut texture_pool,
+                entity,
+                chunk,
+            );
+        }
+    }
+}
+
+/// How many chunks out from the player's chunk can be loaded, the rest of the view being
+/// covered by larger nodes
+pub fn kept_chunk_radius(config: &Config) -> i32 {
+    quadtree::chunk_radius(config)
+}
+
+// Despawns a chunk along with its mesh and material, keeping its texture to write another
+// chunk into
+fn unload_chunk(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    texture_pool: &mut TexturePool,
+    entity: Entity,
+    chunk: &Chunk,
+) {
+    if let Some(mesh) = &chunk.mesh {
+        meshes.remove(mesh);
+    }
+    if let Some(material) = chunk
+        .material
+        .as_ref()
+        .and_then(|material| materials.remove(material))
+    {
+        texture_pool.0.extend(material.base_color_texture);
+    }
+    commands.entity(entity).despawn_recursive()
+}
+
+/// Hides the chunks too far from the player, or outside the view of the camera drawing the
+/// main pass, every frame as the camera turns
 pub fn compute_chunk_visibility(
     config: Res<Config>,
+    debug_config: Res<TerrainDebugConfig>,
+    active_cameras: Res<ActiveCameras>,
     mut chunks_query: Query<(&mut Visible, &Chunk)>,
     player_query: Query<(&Player, &Transform)>,
-    mut start_chunk_update_events: EventReader<StartChunkUpdateEvent>,
+    cameras_query: Query<(&Camera, &GlobalTransform)>,
 ) {
-    if start_chunk_update_events.iter().next().is_none() {
-        return;
-    }
-
-    let viewer_position = player_query.iter().nth(0).unwrap().1.translation.xz();
+    let viewer_position = match player_query.iter().next() {
+        Some((_, transform)) => transform.translation.xz(),
+        None => return,
+    };
+    // the player's eyes, or the editor's or overview's camera when they have the view
+    let frustum = active_cameras
+        .get(CAMERA_3D)
+        .and_then(|active_camera| active_camera.entity)
+        .and_then(|entity| cameras_query.get(entity).ok())
+        .filter(|_| debug_config.frustum_culling)
+        .map(|(camera, transform)| {
+            Frustum::new(camera.projection_matrix * transform.compute_matrix().inverse())
+        });
 
     for (mut visible, chunk) in chunks_query.iter_mut() {
-        let distance_from_viewer = chunk.coords.to_position().distance(viewer_position);
+        // the nodes are only ever picked while some of them is close enough, so only the
+        // chunks are held to the view distance
+        let in_range = chunk.depth > 0
+            || chunk.coords.to_position().distance(viewer_position)
+                <= config.max_view_distance as f32;
+        // chunks without a mesh yet have nothing to draw either way
+        let in_view = match (&frustum, chunk.aabb()) {
+            (Some(frustum), Some((min, max))) => frustum.intersects(min, max),
+            _ => true,
+        };
 
-        if distance_from_viewer > config.max_view_distance as f32 {
-            visible.is_visible = false;
-        } else {
-            visible.is_visible = true;
+        let shown = in_range && in_view;
+        // only touched when it changes, as the water and anything else following the chunk's
+        // visibility look for changes
+        if visible.is_visible != shown {
+            visible.is_visible = shown;
         }
     }
 }
 
-type ChunkTask = Task<(Texture, Mesh, SharedShape)>;
+// The planes around what a camera can see, each facing inwards
+struct Frustum([Vec4; 6]);
+
+impl Frustum {
+    // From a camera's view projection, with its depth running from 0 at the near plane to 1
+    // at the far one
+    fn new(view_projection: Mat4) -> Frustum {
+        let row = |index| view_projection.row(index);
+        Frustum([
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ])
+    }
+
+    // Whether any of a box might be in view, from the corner of the box furthest into each
+    // plane. Boxes just outside a corner of the view can still pass, which only costs drawing
+    // them.
+    fn intersects(&self, min: Vec3, max: Vec3) -> bool {
+        self.0.iter().all(|plane| {
+            let normal = plane.truncate();
+            let furthest = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            normal.dot(furthest) + plane.w >= 0.0
+        })
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct ChunkCoords {
@@ -252,6 +714,27 @@ impl ChunkCoords {
         }
     }
 
+    /// The chunk whose mesh covers a point, as chunk meshes are centred on their position
+    pub fn containing(point: Vec2) -> ChunkCoords {
+        let coords = (point / CHUNK_SIZE as f32).round();
+        ChunkCoords {
+            x: coords.x as i32,
+            y: coords.y as i32,
+        }
+    }
+
+    /// The chunks sharing an edge with this one, the ones before and after it along y then
+    /// along x, matching the edges of its mesh
+    pub fn neighbours(&self) -> [ChunkCoords; 4] {
+        let (x, y) = (self.x, self.y);
+        [
+            ChunkCoords { x, y: y - 1 },
+            ChunkCoords { x, y: y + 1 },
+            ChunkCoords { x: x - 1, y },
+            ChunkCoords { x: x + 1, y },
+        ]
+    }
+
     pub fn to_position(&self) -> Vec2 {
         Vec2::new(
             (self.x * CHUNK_SIZE as i32) as f32,
@@ -260,21 +743,508 @@ impl ChunkCoords {
     }
 }
 
+/// The lowest and highest points of a chunk's mesh, in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeightBounds {
+    pub min: f32,
+    pub max: f32,
+}
+
 #[derive(Debug, Default)]
 pub struct Chunk {
     coords: ChunkCoords,
+    // 0 for a chunk, or how many times a distant node halves before it's down to chunks
+    depth: u32,
     simplification_level: SimplificationLevel,
+    // unknown until the chunk's mesh has been generated
+    bounds: Option<HeightBounds>,
+    stats: Option<HeightStats>,
+    // the sun the chunk's texture has shadows baked in for
+    baked_sun: Option<Vec3>,
+    // the level to regenerate at once the quick coarse first pass is in
+    refine_to: Option<SimplificationLevel>,
+    // the heights have been edited, so the texture has to be coloured in again
+    recolour: bool,
+    // how many times the texture has been coloured in again under the same handle
+    rebakes: u32,
+    // how many times in a row generating this chunk has panicked
+    failed_attempts: u32,
+    // the assets made for this chunk alone, removed along with it
+    mesh: Option<Handle<Mesh>>,
+    material: Option<Handle<StandardMaterial>>,
+    // the splat map generated along with the texture, until it's handed to the splat material
+    splat: Option<Texture>,
+}
+
+impl Chunk {
+    pub fn coords(&self) -> ChunkCoords {
+        self.coords
+    }
+
+    /// How many times the node covering this entity halves before it's down to chunks, 0 for
+    /// an ordinary chunk
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn span(&self) -> i32 {
+        self.node().span()
+    }
+
+    /// The middle of the ground this covers, in the world
+    pub fn centre(&self) -> Vec2 {
+        self.node().centre()
+    }
+
+    pub(super) fn node(&self) -> Node {
+        Node {
+            coords: self.coords,
+            depth: self.depth,
+        }
+    }
+
+    pub fn bounds(&self) -> Option<HeightBounds> {
+        self.bounds
+    }
+
+    pub fn stats(&self) -> Option<&HeightStats> {
+        self.stats.as_ref()
+    }
+
+    pub fn baked_sun(&self) -> Option<Vec3> {
+        self.baked_sun
+    }
+
+    pub fn mesh(&self) -> Option<&Handle<Mesh>> {
+        self.mesh.as_ref()
+    }
+
+    /// Goes up each time the texture is written over in place, so anything holding a copy of
+    /// it can tell it's out of date
+    pub fn rebakes(&self) -> u32 {
+        self.rebakes
+    }
+
+    pub(super) fn take_splat(&mut self) -> Option<Texture> {
+        self.splat.take()
+    }
+
+    /// The world space box around the chunk's mesh, as its min and max corners
+    pub fn aabb(&self) -> Option<(Vec3, Vec3)> {
+        let bounds = self.bounds?;
+        let centre = self.centre();
+        let half = self.node().width() / 2.0;
+        Some((
+            Vec3::new(centre.x - half, bounds.min, centre.y - half),
+            Vec3::new(centre.x + half, bounds.max, centre.y + half),
+        ))
+    }
 }
 
 pub struct Processing;
 
+// Marks chunks and nodes that are no longer wanted, shown until what replaces them is in
+pub struct Retiring;
+
 // Acts as a cache for the chunks or were constantly looping through all chunks
 #[derive(Deref, DerefMut, Clone, Debug, Default)]
 pub struct SeenChunks(pub HashMap<ChunkCoords, (SimplificationLevel, Entity)>);
 
+/// The nodes covering the view past the chunks, by where they are
+#[derive(Deref, DerefMut, Debug, Default)]
+pub struct FarNodes(HashMap<Node, Entity>);
+
+/// Chunk textures that are no longer shown, written over by new chunks instead of allocating
+#[derive(Default)]
+pub struct TexturePool(Vec<Handle<Texture>>);
+
+impl TexturePool {
+    fn recycle(&mut self, textures: &mut Assets<Texture>, texture: Texture) -> Handle<Texture> {
+        while let Some(handle) = self.0.pop() {
+            if let Some(existing) = textures.get_mut(&handle) {
+                if existing.size == texture.size && existing.format == texture.format {
+                    existing.data = texture.data;
+                    return handle;
+                }
+            }
+        }
+        textures.add(texture)
+    }
+}
+
 // Track how far the player has moved since the last time we updated the chunks, indicating to the systems when they need to run again
 #[derive(Deref, DerefMut, Clone, Debug, Default)]
 pub struct LastChunkUpdatePosition(pub Vec2);
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct StartChunkUpdateEvent;
+
+// Sent when a chunk entity is created for coordinates that weren't loaded before
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkSpawnedEvent {
+    pub coords: ChunkCoords,
+    pub entity: Entity,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::{
+        app::Events,
+        asset::AssetPlugin,
+        render::{mesh::VertexAttributeValues, pipeline::PrimitiveTopology},
+    };
+
+    use super::*;
+    use crate::terrain::{
+        height_map::HeightPyramid,
+        mesh::{Generator, UvMapping},
+    };
+
+    const SEED: u32 = 7;
+    // frames run at each step before the workers are let finish, for what's dropped to be
+    // seen waiting on what replaces it
+    const HELD_FRAMES: usize = 2;
+    // enough for a chunk to have its coarse mesh, be refined and take over from what it
+    // replaces
+    const FINISHING_FRAMES: usize = 4;
+
+    // Whether the stand in workers finish what's processing
+    struct Finishing(bool);
+
+    // Stands in for the workers and `insert_chunks`, giving everything processing a mesh and
+    // material, and sending those on their coarse first pass round again at their real level
+    fn finish_processing(
+        mut commands: Commands,
+        finishing: Res<Finishing>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut processing_query: Query<(Entity, &mut Chunk), With<Processing>>,
+    ) {
+        if !finishing.0 {
+            return;
+        }
+        for (entity, mut chunk) in processing_query.iter_mut() {
+            let mesh = meshes.add(Mesh::new(PrimitiveTopology::TriangleList));
+            if let Some(previous) = chunk.mesh.replace(mesh) {
+                meshes.remove(previous);
+            }
+            if chunk.material.is_none() {
+                chunk.material = Some(materials.add(StandardMaterial::default()));
+            }
+            match chunk.refine_to.take() {
+                Some(level) => {
+                    chunk.simplification_level = level;
+                    commands
+                        .entity(entity)
+                        .remove::<Processing>()
+                        .insert(Processing);
+                }
+                None => {
+                    commands.entity(entity).remove::<Processing>();
+                }
+            }
+        }
+    }
+
+    fn walk_app() -> App {
+        let mut config = Config::default();
+        config.seed = SEED;
+        config.lod.split_distance = 1.0;
+        config.lod.hysteresis = 0.25;
+        config.lod.max_depth = 2;
+        config.max_view_distance = 1000.0;
+        config.unload_radius = 1200.0;
+
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .add_state(AppMode::Play)
+            .add_event::<StartChunkUpdateEvent>()
+            .add_event::<ChunkSpawnedEvent>()
+            .insert_resource(config)
+            .insert_resource(SeenChunks::default())
+            .insert_resource(FarNodes::default())
+            .insert_resource(LodTree::default())
+            .insert_resource(TexturePool::default())
+            .insert_resource(LastChunkUpdatePosition::default())
+            .insert_resource(ChunkFailures::default())
+            .insert_resource(Finishing(false))
+            .add_system(trigger_update.system().label("trigger_update"))
+            .add_system(
+                initialize_chunks
+                    .system()
+                    .label("initialize_chunks")
+                    .after("trigger_update"),
+            )
+            .add_system(
+                finish_processing
+                    .system()
+                    .label("finish_processing")
+                    .after("initialize_chunks"),
+            )
+            .add_system(retire_replaced_chunks.system().after("finish_processing"));
+        builder.app
+    }
+
+    fn coords(x: i32, y: i32) -> ChunkCoords {
+        ChunkCoords { x, y }
+    }
+
+    fn seen(app: &App) -> HashMap<ChunkCoords, SimplificationLevel> {
+        let seen_chunks = app.world.get_resource::<SeenChunks>().unwrap();
+        seen_chunks
+            .iter()
+            .map(|(&coords, &(level, _))| (coords, level))
+            .collect()
+    }
+
+    fn sorted(mut coords: Vec<ChunkCoords>) -> Vec<ChunkCoords> {
+        coords.sort_by_key(|coords| (coords.x, coords.y));
+        coords
+    }
+
+    // The ground drawn by anything with a mesh, and that covered by what's wanted now, in
+    // chunks
+    fn ground(app: &mut App) -> (HashSet<ChunkCoords>, HashSet<ChunkCoords>) {
+        let mut drawn = HashSet::new();
+        let mut wanted = HashSet::new();
+        let mut chunks_query = app.world.query::<(&Chunk, Option<&Retiring>)>();
+        for (chunk, retiring) in chunks_query.iter(&app.world) {
+            let node = chunk.node();
+            let covered: Vec<_> = (0..node.span())
+                .flat_map(|y| (0..node.span()).map(move |x| (x, y)))
+                .map(|(x, y)| coords(node.coords.x + x, node.coords.y + y))
+                .collect();
+            if chunk.mesh.is_some() {
+                drawn.extend(covered.iter().copied());
+            }
+            if retiring.is_none() {
+                wanted.extend(covered);
+            }
+        }
+        (drawn, wanted)
+    }
+
+    // Runs a frame, checking that none of the ground still wanted that was drawn before it
+    // has stopped being drawn
+    fn update_without_holes(app: &mut App, position: Vec2) {
+        let (drawn_before, _) = ground(app);
+        app.update();
+        let (drawn, wanted) = ground(app);
+        let holes: Vec<_> = drawn_before
+            .intersection(&wanted)
+            .filter(|coords| !drawn.contains(coords))
+            .collect();
+        assert!(holes.is_empty(), "holes at {:?} at {}", holes, position);
+    }
+
+    fn square(x: i32, y: i32) -> Vec<ChunkCoords> {
+        vec![
+            coords(x, y),
+            coords(x, y + 1),
+            coords(x + 1, y),
+            coords(x + 1, y + 1),
+        ]
+    }
+
+    // Walks east then north over the fixed seed, checking which chunks load and unload at
+    // every step, that whatever's dropped stays on screen until its replacement has a mesh,
+    // and that it goes once it has
+    #[test]
+    fn scripted_walk() {
+        let mut app = walk_app();
+        let player = app
+            .world
+            .spawn()
+            .insert(Player)
+            .insert(Transform::default())
+            .id();
+        // the first update is sent by the setup, as the player hasn't moved yet
+        app.world
+            .get_resource_mut::<Events<StartChunkUpdateEvent>>()
+            .unwrap()
+            .send(StartChunkUpdateEvent);
+
+        let steps: Vec<(Vec2, Vec<ChunkCoords>, Vec<ChunkCoords>)> = vec![
+            (
+                Vec2::new(0.0, 0.0),
+                [square(-2, 0), square(0, -2), square(0, 0)].concat(),
+                vec![],
+            ),
+            (Vec2::new(120.0, 0.0), vec![], vec![]),
+            (Vec2::new(240.0, 0.0), square(2, 0), square(-2, 0)),
+            (Vec2::new(360.0, 0.0), square(2, -2), vec![]),
+            (Vec2::new(480.0, 0.0), vec![], vec![]),
+            (Vec2::new(600.0, 0.0), vec![], square(0, -2)),
+            (Vec2::new(720.0, 0.0), square(4, 0), square(0, 0)),
+            (Vec2::new(840.0, 0.0), square(4, -2), vec![]),
+            (Vec2::new(960.0, 0.0), vec![], vec![]),
+            (Vec2::new(960.0, 120.0), vec![], square(2, -2)),
+            (Vec2::new(960.0, 240.0), square(4, 2), square(4, -2)),
+            (Vec2::new(960.0, 360.0), square(2, 2), vec![]),
+            (Vec2::new(960.0, 480.0), vec![], vec![]),
+        ];
+
+        let mut held_back = 0;
+        for (position, expected_loaded, expected_dropped) in steps {
+            let before = seen(&app);
+            app.world.get_mut::<Transform>(player).unwrap().translation =
+                Vec3::new(position.x, 0.0, position.y);
+            app.world.get_resource_mut::<Finishing>().unwrap().0 = false;
+            for _ in 0..HELD_FRAMES {
+                update_without_holes(&mut app, position);
+            }
+            held_back += app
+                .world
+                .query_filtered::<Entity, With<Retiring>>()
+                .iter(&app.world)
+                .count();
+            app.world.get_resource_mut::<Finishing>().unwrap().0 = true;
+            for _ in 0..FINISHING_FRAMES {
+                update_without_holes(&mut app, position);
+            }
+            let after = seen(&app);
+
+            let loaded = after
+                .keys()
+                .filter(|coords| !before.contains_key(coords))
+                .copied()
+                .collect();
+            let dropped = before
+                .keys()
+                .filter(|coords| !after.contains_key(coords))
+                .copied()
+                .collect();
+            assert_eq!(
+                sorted(loaded),
+                sorted(expected_loaded),
+                "loaded at {}",
+                position
+            );
+            assert_eq!(
+                sorted(dropped),
+                sorted(expected_dropped),
+                "dropped at {}",
+                position
+            );
+
+            let retiring = app
+                .world
+                .query_filtered::<Entity, With<Retiring>>()
+                .iter(&app.world)
+                .count();
+            assert_eq!(retiring, 0, "left retiring at {}", position);
+            let (drawn, wanted) = ground(&mut app);
+            assert!(wanted.is_subset(&drawn), "left undrawn at {}", position);
+        }
+        assert!(
+            held_back > 0,
+            "nothing dropped ever waited on its replacement"
+        );
+
+        let mut expected: HashMap<_, _> = (2..=5)
+            .flat_map(|x| (0..=3).map(move |y| (coords(x, y), SimplificationLevel(2))))
+            .collect();
+        expected.insert(coords(4, 2), SimplificationLevel::min());
+        assert_eq!(seen(&app), expected);
+    }
+
+    // Raises the ground with the brush held, which should move the chunk's vertex straight
+    // away and hold off regenerating it until the brush is let go
+    #[test]
+    fn held_stroke_patches_mesh() {
+        let config = Config::default();
+        let pipeline = GenerationPipeline::default();
+        let coords = ChunkCoords::default();
+        let level = SimplificationLevel::min();
+
+        let height_map = pipeline.chunk_height_map(&config, coords, None);
+        let mut generator = Generator::new(
+            Arc::new(HeightPyramid::build(height_map)),
+            config.height_scale,
+            level,
+            UvMapping::Chunk,
+        );
+        generator.generate();
+        let bounds = generator.height_bounds();
+        let mesh = generator.graphics_mesh();
+
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Mesh>()
+            .insert_resource(config.clone())
+            .insert_resource(TerrainEdits::default())
+            .add_system(patch_edited.system().label("patch_edited"))
+            .add_system(regenerate_edited.system().after("patch_edited"));
+        let mut app = builder.app;
+        let mesh = app
+            .world
+            .get_resource_mut::<Assets<Mesh>>()
+            .unwrap()
+            .add(mesh);
+        let entity = app
+            .world
+            .spawn()
+            .insert(Chunk {
+                coords,
+                simplification_level: level,
+                bounds: Some(bounds),
+                mesh: Some(mesh.clone()),
+                ..Default::default()
+            })
+            .id();
+        let mut seen_chunks = SeenChunks::default();
+        seen_chunks.insert(coords, (level, entity));
+        app.world.insert_resource(seen_chunks);
+
+        // the middle of the chunk, which has a vertex every other cell at this level
+        let cell = IVec2::new(120, 120);
+        let vertex = 60 * 121 + 60;
+        let position = |app: &App| {
+            let meshes = app.world.get_resource::<Assets<Mesh>>().unwrap();
+            match meshes
+                .get(&mesh)
+                .and_then(|mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION))
+            {
+                Some(VertexAttributeValues::Float3(positions)) => positions[vertex],
+                other => panic!("positions are {:?}", other),
+            }
+        };
+        let before = position(&app);
+
+        let raised = {
+            let mut edits = app.world.get_resource_mut::<TerrainEdits>().unwrap();
+            edits.defer_regeneration(true);
+            let mut editor = edits.editor(&config, &pipeline, None);
+            let raised = editor.height(cell) + 10.0;
+            editor.set_height(cell, raised);
+            raised
+        };
+        app.update();
+
+        let after = position(&app);
+        assert_eq!(after[1], raised);
+        assert_eq!([after[0], after[2]], [before[0], before[2]]);
+        assert!(
+            app.world.get::<Processing>(entity).is_none(),
+            "regenerated while the brush is held"
+        );
+
+        app.world
+            .get_resource_mut::<TerrainEdits>()
+            .unwrap()
+            .defer_regeneration(false);
+        app.update();
+        assert!(
+            app.world.get::<Processing>(entity).is_some(),
+            "not regenerated once the brush is let go"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use bevy::math::Vec2;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{endless::ChunkCoords, height_map::HeightMap, pipeline::GenerationStage, Config};
+
+/// How the water droplets running over each chunk carve and fill it. Rates and the capacity
+/// are per droplet step, in world-space height.
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErosionConfig {
+    pub(super) enabled: bool,
+    // droplets run over each chunk, which has 241 x 241 cells
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0, max = 500000))]
+    pub(super) droplets: usize,
+    // steps a droplet takes before it's dropped, if it hasn't left the chunk or stopped
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1, max = 200))]
+    pub(super) max_lifetime: usize,
+    // how much a droplet keeps going the way it was rather than turning downhill
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub(super) inertia: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub(super) sediment_capacity: f32,
+    // keeps droplets on flat ground carrying a little sediment
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub(super) min_slope: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub(super) erosion_rate: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub(super) deposition_rate: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub(super) evaporation: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub(super) gravity: f32,
+    // cells over which the erosion fades out towards the chunk's edges, which its
+    // neighbours share and have to agree on
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1, max = 120))]
+    pub(super) edge_margin: usize,
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            droplets: 40000,
+            max_lifetime: 40,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_slope: 0.01,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            evaporation: 0.02,
+            gravity: 4.0,
+            edge_margin: 24,
+        }
+    }
+}
+
+/// Runs water droplets down the normalized height map, each picking up sediment where it
+/// speeds downhill and dropping it where it slows, which carves valleys and leaves fans of
+/// sediment at their feet
+pub struct ErosionStage;
+
+impl GenerationStage for ErosionStage {
+    fn name(&self) -> &'static str {
+        "erosion"
+    }
+
+    fn apply(&self, config: &Config, coords: ChunkCoords, height_map: &mut HeightMap) {
+        if config.erosion.enabled && height_map.size >= 3 {
+            erode(config, coords, height_map);
+        }
+    }
+}
+
+fn erode(config: &Config, coords: ChunkCoords, height_map: &mut HeightMap) {
+    let params = &config.erosion;
+    let size = height_map.size;
+    // run in world-space heights, so the rates mean the same whatever the height scale
+    let original: Vec<f32> = height_map
+        .data
+        .iter()
+        .flatten()
+        .map(|&height| height * config.height_scale)
+        .collect();
+    let mut heights = original.clone();
+
+    // the same droplets every time the chunk is generated, at any simplification level
+    let mut rng = StdRng::seed_from_u64(
+        config.seed as u64
+            ^ (coords.x as i64 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (coords.y as i64 as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f),
+    );
+    let last = (size - 1) as f32;
+
+    for _ in 0..params.droplets {
+        let mut position = Vec2::new(rng.gen_range(0.0..last), rng.gen_range(0.0..last));
+        let mut direction = Vec2::ZERO;
+        let mut speed = 1.0;
+        let mut water = 1.0;
+        let mut sediment = 0.0;
+
+        for _ in 0..params.max_lifetime {
+            let (height, gradient) = sample(&heights, size, position);
+            direction = direction * params.inertia - gradient * (1.0 - params.inertia);
+            if direction.length_squared() < f32::EPSILON {
+                break;
+            }
+            direction = direction.normalize();
+
+            let next = position + direction;
+            if next.x < 0.0 || next.y < 0.0 || next.x >= last || next.y >= last {
+                break;
+            }
+            let (next_height, _) = sample(&heights, size, next);
+            let drop = height - next_height;
+
+            let capacity = drop.max(params.min_slope) * speed * water * params.sediment_capacity;
+            if drop < 0.0 || sediment > capacity {
+                // climbing out of a pit fills it in, otherwise only what's over capacity
+                // settles
+                let amount = if drop < 0.0 {
+                    (-drop).min(sediment)
+                } else {
+                    (sediment - capacity) * params.deposition_rate
+                };
+                sediment -= amount;
+                spread(&mut heights, size, position, amount);
+            } else {
+                // never dig deeper than the step down, or the droplet would leave a hole
+                let amount = ((capacity - sediment) * params.erosion_rate).min(drop);
+                spread(&mut heights, size, position, -amount);
+                sediment += amount;
+            }
+
+            speed = (speed * speed + drop * params.gravity).max(0.0).sqrt();
+            water *= 1.0 - params.evaporation;
+            position = next;
+        }
+    }
+
+    // fade the change out towards the edges, so neighbouring chunks still meet there
+    let margin = params.edge_margin.max(1) as f32;
+    for y in 0..size {
+        for x in 0..size {
+            let from_edge = x.min(y).min(size - 1 - x).min(size - 1 - y) as f32;
+            let fade = (from_edge / margin).min(1.0);
+            let index = y * size + x;
+            let height = original[index] + (heights[index] - original[index]) * fade;
+            height_map.data[y][x] = height / config.height_scale;
+        }
+    }
+}
+
+// The height at a point between the cells, and which way it rises, bilinearly interpolated
+fn sample(heights: &[f32], size: usize, position: Vec2) -> (f32, Vec2) {
+    let (x, y) = (position.x as usize, position.y as usize);
+    let (u, v) = (position.x - x as f32, position.y - y as f32);
+    let index = y * size + x;
+    let (top_left, top_right) = (heights[index], heights[index + 1]);
+    let (bottom_left, bottom_right) = (heights[index + size], heights[index + size + 1]);
+
+    let height = top_left * (1.0 - u) * (1.0 - v)
+        + top_right * u * (1.0 - v)
+        + bottom_left * (1.0 - u) * v
+        + bottom_right * u * v;
+    let gradient = Vec2::new(
+        (top_right - top_left) * (1.0 - v) + (bottom_right - bottom_left) * v,
+        (bottom_left - top_left) * (1.0 - u) + (bottom_right - top_right) * u,
+    );
+    (height, gradient)
+}
+
+// Adds height to the four cells around a point, most to the closest
+fn spread(heights: &mut [f32], size: usize, position: Vec2, amount: f32) {
+    let (x, y) = (position.x as usize, position.y as usize);
+    let (u, v) = (position.x - x as f32, position.y - y as f32);
+    let index = y * size + x;
+    heights[index] += amount * (1.0 - u) * (1.0 - v);
+    heights[index + 1] += amount * u * (1.0 - v);
+    heights[index + size] += amount * (1.0 - u) * v;
+    heights[index + size + 1] += amount * u * v;
+}
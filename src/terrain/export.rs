@@ -0,0 +1,134 @@
+use std::{env, fs, path::PathBuf};
+
+use bevy::{math::Vec2, tasks::TaskPool};
+use color_eyre::{eyre::eyre, Report};
+use image::{ImageBuffer, Luma};
+
+use super::{Config, HeightMap, CHUNK_SIZE};
+
+// rows sampled by each task, few enough that every core gets some of a small export
+const ROWS_PER_TASK: usize = 16;
+
+/// A heightmap to write for importing into other tools, asked for with
+/// `--export-heightmap <min x,min z,max x,max z> <width>x<height> <path>`, along with
+/// `--seed <seed>` to export another seed than the default
+pub struct HeightmapExport {
+    pub seed: u32,
+    // the world space corners of the rectangle, the pixels on its edges falling on them
+    pub min: Vec2,
+    pub max: Vec2,
+    pub width: usize,
+    pub height: usize,
+    // a 16 bit greyscale .png, or little endian .raw / .r16 as Unity and Unreal import them
+    pub path: PathBuf,
+}
+
+impl HeightmapExport {
+    pub fn requested() -> Option<HeightmapExport> {
+        let mut args = env::args().skip_while(|arg| arg != "--export-heightmap");
+        args.next()?;
+        let export = (|| {
+            let corners = args
+                .next()?
+                .split(',')
+                .map(|value| value.trim().parse::<f32>().ok())
+                .collect::<Option<Vec<_>>>()?;
+            let (width, height) = args.next()?.split_once('x').and_then(|(width, height)| {
+                Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+            })?;
+            let path = PathBuf::from(args.next()?);
+            match corners[..] {
+                [min_x, min_z, max_x, max_z]
+                    if min_x < max_x && min_z < max_z && width > 1 && height > 1 =>
+                {
+                    Some(HeightmapExport {
+                        seed: super::seed_argument(),
+                        min: Vec2::new(min_x, min_z),
+                        max: Vec2::new(max_x, max_z),
+                        width,
+                        height,
+                        path,
+                    })
+                }
+                _ => None,
+            }
+        })();
+        if export.is_none() {
+            eprintln!(
+                "--export-heightmap needs a rectangle, resolution and file, e.g. \
+                 --export-heightmap -2048,-2048,2048,2048 1025x1025 terrain.png"
+            );
+        }
+        export
+    }
+
+    /// Samples the noise over the rectangle on every core, the rows running from min z to
+    /// max z, then writes the heights out scaled to the whole 16 bit range
+    pub fn write(&self) -> Result<(), Report> {
+        let mut config = Config::saved_or_default();
+        config.set_seed(self.seed);
+        let sampler = HeightMap::sampler(&config);
+        let step =
+            (self.max - self.min) / Vec2::new((self.width - 1) as f32, (self.height - 1) as f32);
+
+        let mut heights = vec![0u16; self.width * self.height];
+        TaskPool::new().scope(|scope| {
+            for (task, rows) in heights.chunks_mut(self.width * ROWS_PER_TASK).enumerate() {
+                let sampler = &sampler;
+                let (width, min) = (self.width, self.min);
+                scope.spawn(async move {
+                    for (index, height) in rows.iter_mut().enumerate() {
+                        let row = task * ROWS_PER_TASK + index / width;
+                        let position = min + Vec2::new((index % width) as f32, row as f32) * step;
+                        // the same shift into grid space the queries make
+                        let grid_point = position + Vec2::splat(CHUNK_SIZE as f32 / 2.0);
+                        *height = (sampler(grid_point).clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+                    }
+                });
+            }
+        });
+
+        let extension = self
+            .path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("png") => {
+                ImageBuffer::<Luma<u16>, _>::from_raw(
+                    self.width as u32,
+                    self.height as u32,
+                    heights,
+                )
+                .ok_or_else(|| eyre!("The heightmap doesn't fill the image"))?
+                .save(&self.path)?;
+            }
+            Some("raw") | Some("r16") => {
+                let bytes: Vec<u8> = heights
+                    .iter()
+                    .flat_map(|height| height.to_le_bytes().to_vec())
+                    .collect();
+                fs::write(&self.path, bytes)?;
+            }
+            _ => {
+                return Err(eyre!(
+                    "Can't export a heightmap to {:?}, use a .png, .raw or .r16 file",
+                    self.path
+                ))
+            }
+        }
+
+        println!(
+            "Wrote the {}x{} heightmap of seed {} to {:?}, spanning {} by {} metres with \
+             white at {} metres high",
+            self.width,
+            self.height,
+            self.seed,
+            self.path,
+            self.max.x - self.min.x,
+            self.max.y - self.min.y,
+            config.height_scale
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::{any::Any, collections::HashMap};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use super::{endless::Processing, ChunkCoords};
+use crate::locale::Locale;
+
+// a failed chunk waits this long before its first retry, doubling every time after
+const FIRST_RETRY_DELAY: f32 = 0.5;
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Sent when generating a chunk panicked, before it's retried
+#[derive(Clone, Debug)]
+pub struct ChunkGenerationFailed {
+    pub coords: ChunkCoords,
+    pub entity: Entity,
+    // how many times in a row this chunk has failed
+    pub attempts: u32,
+    pub message: String,
+}
+
+/// Chunks whose generation has failed and not since succeeded, with the latest failure
+#[derive(Default)]
+pub struct ChunkFailures(pub HashMap<ChunkCoords, (u32, String)>);
+
+// Waits out the backoff before a failed chunk is generated again
+pub struct RetryGeneration(Timer);
+
+impl RetryGeneration {
+    /// The wait before trying a chunk again, or nothing once it has failed too many times
+    pub fn after(attempts: u32) -> Option<RetryGeneration> {
+        if attempts >= MAX_ATTEMPTS {
+            return None;
+        }
+        let delay = FIRST_RETRY_DELAY * 2.0f32.powi(attempts as i32 - 1);
+        Some(RetryGeneration(Timer::from_seconds(delay, false)))
+    }
+}
+
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+pub fn retry_failed_chunks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut retry_query: Query<(Entity, &mut RetryGeneration)>,
+) {
+    for (entity, mut retry) in retry_query.iter_mut() {
+        if retry.0.tick(time.delta()).just_finished() {
+            // adding Processing again is what queues the chunk up to be generated
+            commands
+                .entity(entity)
+                .remove::<RetryGeneration>()
+                .remove::<Processing>()
+                .insert(Processing);
+        }
+    }
+}
+
+pub fn record_failures(
+    mut failures: ResMut<ChunkFailures>,
+    mut events: EventReader<ChunkGenerationFailed>,
+) {
+    for event in events.iter() {
+        if event.attempts >= MAX_ATTEMPTS {
+            error!(
+                "Gave up generating chunk {:?} after {} attempts: {}",
+                event.coords, event.attempts, event.message
+            );
+        } else {
+            warn!(
+                "Generating chunk {:?} failed, retrying: {}",
+                event.coords, event.message
+            );
+        }
+        failures
+            .0
+            .insert(event.coords, (event.attempts, event.message.clone()));
+    }
+}
+
+pub fn failures_panel(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    mut failures: ResMut<ChunkFailures>,
+) {
+    if failures.0.is_empty() {
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new(locale.text("terrain-failures-title"))
+        .id(egui::Id::new("chunk_failures"))
+        .open(&mut open)
+        .show(egui_context.ctx(), |ui| {
+            for (coords, (attempts, message)) in failures.0.iter() {
+                let status = locale.text(if *attempts >= MAX_ATTEMPTS {
+                    "terrain-failure-gave-up"
+                } else {
+                    "terrain-failure-retrying"
+                });
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    locale.format(
+                        "terrain-failure",
+                        &[
+                            ("x", coords.x.into()),
+                            ("y", coords.y.into()),
+                            ("attempts", (*attempts).into()),
+                            ("status", status.into()),
+                        ],
+                    ),
+                );
+                ui.label(message);
+            }
+        });
+    if !open {
+        failures.0.clear();
+    }
+}
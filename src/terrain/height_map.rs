@@ -1,53 +1,98 @@
 use bevy::math::Vec2;
 use nalgebra_glm::smoothstep;
-use noise::{NoiseFn, Perlin};
+use noise::{NoiseFn, Perlin, Seedable};
 
-use super::{endless::ChunkCoords, Config, MAP_CHUNK_SIZE};
+use super::{biome::Climate, endless::ChunkCoords, Config, MAP_CHUNK_SIZE};
 
 // values to estimate the maximum possible height of the noise map before normalization (global)
 const AMPLITUDE_HEURISTIC: f32 = 0.9;
 const HEIGHT_HEURISTIC: f32 = 1.1;
+pub const HISTOGRAM_BINS: usize = 16;
 
+/// A summary of a height map's normalized heights, for checking the generation stages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeightStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    // how many heights fall into each even slice of 0 to 1
+    pub histogram: [u32; HISTOGRAM_BINS],
+}
+
+/// Where a height map's cells fall in the noise's grid space: the first cell's point, and how
+/// far apart the cells are. Chunks have a cell every unit, while the larger nodes further out
+/// spread the same number of cells over more ground.
+#[derive(Clone, Copy, Debug)]
+pub struct GridArea {
+    pub origin: Vec2,
+    pub spacing: f32,
+}
+
+impl GridArea {
+    pub fn chunk(coords: ChunkCoords) -> GridArea {
+        GridArea {
+            origin: coords.to_position(),
+            spacing: 1.0,
+        }
+    }
+
+    pub fn point(&self, x: usize, y: usize) -> Vec2 {
+        self.origin + Vec2::new(x as f32, y as f32) * self.spacing
+    }
+}
+
+#[derive(Clone)]
 pub struct HeightMap {
     pub data: Vec<Vec<f32>>,
     pub size: usize,
 }
 
 impl HeightMap {
-    pub fn generate(config: &Config, chunk_coords: &ChunkCoords) -> HeightMap {
-        let mut height_map = HeightMap::generate_noise(config, chunk_coords);
+    pub fn empty() -> HeightMap {
+        HeightMap {
+            data: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Samples the normalized height at a single point, in the same coordinate space
+    /// as the height map grid (chunk offset + cell)
+    pub fn sample(config: &Config, point: Vec2) -> f32 {
+        HeightMap::sampler(config)(point)
+    }
+
+    /// Samples normalized heights at any points in grid space, setting the noise up once for
+    /// all of them
+    pub fn sampler(config: &Config) -> impl Fn(Vec2) -> f32 + Sync + '_ {
+        let noise = Perlin::new().set_seed(config.seed);
+        let climate = Climate::new(config.seed);
+        let max_possible_height = max_possible_height(config);
+        move |point| {
+            let height = HeightMap::noise_at(config, &noise, &climate, point);
+            normalize_height(height, max_possible_height)
+        }
+    }
+
+    pub fn generate_noise(config: &Config, chunk_coords: &ChunkCoords) -> HeightMap {
+        HeightMap::generate_area(config, GridArea::chunk(*chunk_coords))
+    }
+
+    /// The noise over an area of any spacing, normalized. The later generation stages only
+    /// shape chunks, so the distant nodes covering more ground per cell go without them.
+    pub fn distant(config: &Config, area: GridArea) -> HeightMap {
+        let mut height_map = HeightMap::generate_area(config, area);
         height_map.normalize(config);
         height_map
     }
 
-    fn generate_noise(config: &Config, chunk_coords: &ChunkCoords) -> HeightMap {
-        let noise = Perlin::new();
-
-        // sanity check the scale
-        let scale = config.scale.max(f32::EPSILON);
+    fn generate_area(config: &Config, area: GridArea) -> HeightMap {
+        let noise = Perlin::new().set_seed(config.seed);
+        let climate = Climate::new(config.seed);
 
-        let chunk_offset = chunk_coords.to_position();
-        let map = (0..MAP_CHUNK_SIZE)
+        let map = (0..MAP_CHUNK_SIZE as usize)
             .map(|y| {
-                (0..MAP_CHUNK_SIZE)
-                    .map(|x| {
-                        let mut height = 0.0;
-                        let mut amplitude = 1.0;
-                        let mut frequency = 1.0;
-
-                        for _ in 0..config.octaves {
-                            let sample = (Vec2::new(x as f32, y as f32) + chunk_offset)
-                                / Vec2::new(MAP_CHUNK_SIZE as f32, MAP_CHUNK_SIZE as f32)
-                                / (scale * frequency);
-                            let perlin_point = [sample.x as f64, sample.y as f64];
-                            height += noise.get(perlin_point) as f32 * amplitude;
-
-                            amplitude *= config.persistence;
-                            frequency *= config.lacunarity;
-                        }
-
-                        height
-                    })
+                (0..MAP_CHUNK_SIZE as usize)
+                    .map(|x| HeightMap::noise_at(config, &noise, &climate, area.point(x, y)))
                     .collect()
             })
             .collect();
@@ -58,27 +103,168 @@ impl HeightMap {
         }
     }
 
-    fn normalize(&mut self, config: &Config) {
-        // determine an approximated maximum possible height difference
-        // between the min an max height for global normalization
-        let mut max_possible_height = 0.0;
+    pub fn stats(&self) -> HeightStats {
+        let mut stats = HeightStats {
+            min: f32::MAX,
+            max: f32::MIN,
+            mean: 0.0,
+            histogram: [0; HISTOGRAM_BINS],
+        };
+        let mut total = 0.0;
+        let mut count = 0;
+
+        for &height in self.data.iter().flatten() {
+            stats.min = stats.min.min(height);
+            stats.max = stats.max.max(height);
+            total += height as f64;
+            count += 1;
+
+            let bin = (height.max(0.0) * HISTOGRAM_BINS as f32) as usize;
+            stats.histogram[bin.min(HISTOGRAM_BINS - 1)] += 1;
+        }
+        if count > 0 {
+            stats.mean = (total / count as f64) as f32;
+        }
+        stats
+    }
+
+    fn noise_at(config: &Config, noise: &Perlin, climate: &Climate, point: Vec2) -> f32 {
+        // sanity check the scale
+        let scale = config.scale.max(f32::EPSILON);
+        let regions = if config.biomes.enabled {
+            Some(climate.weights(&config.biomes, point))
+        } else {
+            None
+        };
+
+        let mut height = 0.0;
         let mut amplitude = 1.0;
+        let mut frequency = 1.0;
 
-        for _ in 0..config.octaves {
-            max_possible_height += amplitude;
-            amplitude *= config.persistence * AMPLITUDE_HEURISTIC;
+        for octave in 0..config.octave_count() {
+            let sample = point
+                / Vec2::new(MAP_CHUNK_SIZE as f32, MAP_CHUNK_SIZE as f32)
+                / (scale * frequency);
+            let perlin_point = [sample.x as f64, sample.y as f64];
+            // regions with fewer octaves fade the finer ones out across their borders
+            let presence = regions.map_or(1.0, |regions| {
+                regions.blend(&config.biomes, |params| {
+                    if octave < params.octaves {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                })
+            });
+            height += noise.get(perlin_point) as f32 * amplitude * presence;
+
+            amplitude *= config.persistence;
+            frequency *= config.lacunarity;
         }
 
-        max_possible_height *= HEIGHT_HEURISTIC;
+        // the relief is scaled about the middle height, which normalizing keeps within 0 to 1
+        regions.map_or(height, |regions| {
+            height * regions.blend(&config.biomes, |params| params.height_scale)
+        })
+    }
 
-        // approximated spread around zero
-        let spread = max_possible_height / 2.0;
+    pub fn normalize(&mut self, config: &Config) {
+        let max_possible_height = max_possible_height(config);
 
         // normalize the map height between 0 and 1
         self.data.iter_mut().for_each(|row| {
             row.iter_mut().for_each(|height| {
-                *height = smoothstep(-spread, spread, *height / max_possible_height);
+                *height = normalize_height(*height, max_possible_height);
             })
         });
     }
 }
+
+/// A chunk's height map along with successively halved copies of it (full, 1/2, 1/4, ...),
+/// so every simplification level meshes from heights filtered for its own resolution
+pub struct HeightPyramid {
+    pub levels: Vec<HeightMap>,
+}
+
+impl HeightPyramid {
+    /// Halves the map for as long as its cells still line up with the full resolution ones
+    pub fn build(base: HeightMap) -> HeightPyramid {
+        let mut levels = vec![base];
+        loop {
+            let last = levels.last().unwrap();
+            if last.size < 3 || (last.size - 1) % 2 != 0 {
+                break;
+            }
+            let next = downsample(last);
+            levels.push(next);
+        }
+        HeightPyramid { levels }
+    }
+
+    pub fn base(&self) -> &HeightMap {
+        &self.levels[0]
+    }
+
+    /// The coarsest level whose cells still fall on every `increment`th full resolution cell,
+    /// along with its scale relative to the full resolution map as a power of two
+    pub fn level_for_increment(&self, increment: usize) -> (&HeightMap, usize) {
+        let level = (increment.trailing_zeros() as usize).min(self.levels.len() - 1);
+        (&self.levels[level], level)
+    }
+}
+
+// Halves the map with a tent filter. The edges are only filtered along themselves so that
+// neighbouring chunks, which share their edge cells, still agree at every level.
+fn downsample(map: &HeightMap) -> HeightMap {
+    let last = map.size - 1;
+    let size = last / 2 + 1;
+    let taps = |centre: usize| -> Vec<(usize, f32)> {
+        if centre == 0 || centre == last {
+            vec![(centre, 1.0)]
+        } else {
+            vec![(centre - 1, 0.25), (centre, 0.5), (centre + 1, 0.25)]
+        }
+    };
+
+    let data = (0..size)
+        .map(|y| {
+            let y_taps = taps(y * 2);
+            (0..size)
+                .map(|x| {
+                    let x_taps = taps(x * 2);
+                    y_taps
+                        .iter()
+                        .flat_map(|&(ty, wy)| {
+                            x_taps
+                                .iter()
+                                .map(move |&(tx, wx)| map.data[ty][tx] * wx * wy)
+                        })
+                        .sum()
+                })
+                .collect()
+        })
+        .collect();
+
+    HeightMap { data, size }
+}
+
+// determine an approximated maximum possible height difference
+// between the min an max height for global normalization
+fn max_possible_height(config: &Config) -> f32 {
+    let mut max_possible_height = 0.0;
+    let mut amplitude = 1.0;
+
+    for _ in 0..config.octave_count() {
+        max_possible_height += amplitude;
+        amplitude *= config.persistence * AMPLITUDE_HEURISTIC;
+    }
+
+    max_possible_height * HEIGHT_HEURISTIC
+}
+
+fn normalize_height(height: f32, max_possible_height: f32) -> f32 {
+    // approximated spread around zero
+    let spread = max_possible_height / 2.0;
+
+    smoothstep(-spread, spread, height / max_possible_height)
+}
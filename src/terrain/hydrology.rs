@@ -0,0 +1,59 @@
+use std::cmp::Ordering;
+
+use super::height_map::HeightMap;
+
+// the eight neighbours water can run off to, with how far away each one is
+const NEIGHBOURS: [(isize, isize, f32); 8] = [
+    (-1, -1, std::f32::consts::SQRT_2),
+    (0, -1, 1.0),
+    (1, -1, std::f32::consts::SQRT_2),
+    (-1, 0, 1.0),
+    (1, 0, 1.0),
+    (-1, 1, std::f32::consts::SQRT_2),
+    (0, 1, 1.0),
+    (1, 1, std::f32::consts::SQRT_2),
+];
+
+/// How many cells drain through each cell, row by row, with every cell passing all of its
+/// water on to whichever neighbour it drops towards most steeply (D8).
+///
+/// Only the chunk's own cells are counted, so water coming in over its edges is missed.
+pub fn flow_accumulation(height_map: &HeightMap) -> Vec<f32> {
+    let size = height_map.size;
+    let mut accumulation = vec![1.0; size * size];
+
+    // the highest cells hand on their water first, so every cell has everything from
+    // upstream by the time it passes its own along
+    let mut order: Vec<(usize, usize)> = (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x, y)))
+        .collect();
+    order.sort_by(|&(ax, ay), &(bx, by)| {
+        height_map.data[by][bx]
+            .partial_cmp(&height_map.data[ay][ax])
+            .unwrap_or(Ordering::Equal)
+    });
+
+    for (x, y) in order {
+        let height = height_map.data[y][x];
+        let steepest = NEIGHBOURS
+            .iter()
+            .filter_map(|&(dx, dy, distance)| {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if nx < 0 || ny < 0 || nx >= size as isize || ny >= size as isize {
+                    return None;
+                }
+                let (nx, ny) = (nx as usize, ny as usize);
+                let drop = (height - height_map.data[ny][nx]) / distance;
+                Some((nx, ny, drop))
+            })
+            .filter(|&(_, _, drop)| drop > 0.0)
+            .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(Ordering::Equal));
+
+        if let Some((nx, ny, _)) = steepest {
+            let flow = accumulation[y * size + x];
+            accumulation[ny * size + nx] += flow;
+        }
+    }
+
+    accumulation
+}
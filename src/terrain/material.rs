@@ -0,0 +1,298 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{PipelineDescriptor, RenderPipeline},
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::ShaderStages,
+        texture::{AddressMode, Extent3d, TextureDimension, TextureFormat},
+    },
+};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    endless::Chunk,
+    height_map::{GridArea, HeightMap},
+    texture::{gradient, smoothstep},
+    Biome, Config,
+};
+use crate::error_log::ErrorLog;
+
+pub const SPLAT_MATERIAL_NODE: &str = "splat_material";
+
+// normalized height either side of each band's top over which the layers fade into each
+// other, even when the colour map has hard bands
+const MIN_BLEND: f32 = 0.01;
+
+/// Draws the terrain with up to four textures tiled over it in place of the chunk colour
+/// maps, mixed for each cell by its height and how steep it is
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialConfig {
+    pub(super) enabled: bool,
+    // metres across each tile of the textures
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    pub(super) tile_size: f32,
+    // the textures under assets/, each left empty to draw its layer in the colour of its
+    // terrain threshold instead
+    pub(super) grass: String,
+    pub(super) rock: String,
+    pub(super) sand: String,
+    pub(super) snow: String,
+}
+
+impl Default for MaterialConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tile_size: 8.0,
+            grass: "textures/grass.jpg".to_string(),
+            rock: String::new(),
+            sand: String::new(),
+            snow: String::new(),
+        }
+    }
+}
+
+impl MaterialConfig {
+    // in the order of the splat map's channels
+    fn layers(&self) -> [(&str, Biome); 4] {
+        [
+            (self.grass.as_str(), Biome::Lowland),
+            (self.rock.as_str(), Biome::Rock),
+            (self.sand.as_str(), Biome::Sand),
+            (self.snow.as_str(), Biome::Snow),
+        ]
+    }
+}
+
+/// The layers a chunk is drawn with and how much of each covers every cell
+#[derive(RenderResources, TypeUuid)]
+#[uuid = "0c6f4e8a-92d1-4b7e-a5f3-6e1d8b2c7a94"]
+pub struct SplatMaterial {
+    // the light on the terrain, kept the same as the chunk's own material's colour
+    pub tint: Color,
+    pub tile_size: f32,
+    // the colours of the layers without a texture, white for those with one
+    pub grass_color: Color,
+    pub rock_color: Color,
+    pub sand_color: Color,
+    pub snow_color: Color,
+    // grass, rock, sand and snow in red, green, blue and alpha
+    pub splat: Handle<Texture>,
+    pub grass: Handle<Texture>,
+    pub rock: Handle<Texture>,
+    pub sand: Handle<Texture>,
+    pub snow: Handle<Texture>,
+}
+
+struct SplatAssets {
+    pipeline: Handle<PipelineDescriptor>,
+    // drawn for the layers without a texture, in their colour
+    blank: Handle<Texture>,
+    layers: [Handle<Texture>; 4],
+}
+
+pub fn add_splat_graph(world: &mut World) {
+    let mut graph = world.get_resource_mut::<RenderGraph>().unwrap();
+    graph.add_system_node(
+        SPLAT_MATERIAL_NODE,
+        AssetRenderResourcesNode::<SplatMaterial>::new(true),
+    );
+    graph
+        .add_node_edge(SPLAT_MATERIAL_NODE, base::node::MAIN_PASS)
+        .unwrap();
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    let descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: errors.load(&asset_server, "shaders/terrain.vert"),
+        fragment: Some(errors.load(&asset_server, "shaders/terrain.frag")),
+    });
+    let blank = textures.add(Texture::new(
+        Extent3d::new(1, 1, 1),
+        TextureDimension::D2,
+        vec![255; 4],
+        TextureFormat::Rgba8Unorm,
+    ));
+    commands.insert_resource(SplatAssets {
+        pipeline: pipelines.add(descriptor),
+        layers: [blank.clone(), blank.clone(), blank.clone(), blank.clone()],
+        blank,
+    });
+}
+
+/// How much of each layer covers every cell, from how high and steep it is: sand up to the
+/// top of the beach, grass up through the trees, then rock and snow, with rock on the slopes
+/// past the terrain's rock slope
+pub fn splat_map(height_map: &HeightMap, config: &Config, area: GridArea) -> Texture {
+    let thresholds = &config.terrain_thresholds;
+    let blend = config.color_blend.max(MIN_BLEND);
+    let (grass, rock, sand, snow) = (Vec4::X, Vec4::Y, Vec4::Z, Vec4::W);
+
+    let mut data = Vec::with_capacity(height_map.size * height_map.size * 4);
+    for y in 0..height_map.size {
+        for x in 0..height_map.size {
+            let height = height_map.data[y][x];
+            let past = |biome: Biome| {
+                let top = thresholds[biome as usize].max_height;
+                smoothstep((height - top + blend) / (blend * 2.0))
+            };
+            let mut weights = sand;
+            weights = weights.lerp(grass, past(Biome::Sand));
+            weights = weights.lerp(rock, past(Biome::Forest));
+            weights = weights.lerp(snow, past(Biome::Rock));
+            if config.rock_slope_degrees > 0.0 {
+                let slope = gradient(height_map, x, y, config, area.spacing);
+                let degrees = slope.length().atan().to_degrees();
+                let rockiness =
+                    smoothstep((degrees - config.rock_slope_degrees) / config.rock_blend_degrees);
+                weights = weights.lerp(rock, rockiness);
+            }
+            let weights: [f32; 4] = weights.into();
+            data.extend(weights.iter().map(|weight| (weight * 255.0).round() as u8));
+        }
+    }
+
+    Texture::new(
+        Extent3d::new(height_map.size as u32, height_map.size as u32, 1),
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8Unorm,
+    )
+}
+
+// Loads the layers' textures again whenever they might have changed
+pub fn load_layers(
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    mut assets: ResMut<SplatAssets>,
+    mut errors: ResMut<ErrorLog>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+    let assets = &mut *assets;
+    for (handle, (path, _)) in assets
+        .layers
+        .iter_mut()
+        .zip(config.material.layers().iter())
+    {
+        *handle = if path.is_empty() {
+            assets.blank.clone()
+        } else {
+            errors.load(&asset_server, path)
+        };
+    }
+}
+
+// The layers are tiled, so they repeat rather than stretching their edges
+pub fn repeat_layers(
+    mut events: EventReader<AssetEvent<Texture>>,
+    assets: Res<SplatAssets>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if !assets.layers.contains(handle) {
+            continue;
+        }
+        // changing the sampler sends another modified event, so only change it once
+        let repeats = textures.get(handle).map_or(true, |texture| {
+            texture.sampler.address_mode_u == AddressMode::Repeat
+        });
+        if repeats {
+            continue;
+        }
+        if let Some(texture) = textures.get_mut(handle) {
+            texture.sampler.address_mode_u = AddressMode::Repeat;
+            texture.sampler.address_mode_v = AddressMode::Repeat;
+        }
+    }
+}
+
+/// Draws the chunks that have just been generated with the splat material. Those generated
+/// without a splat map, as they are once it's turned off and every chunk is generated again,
+/// keep the material they were inserted with.
+pub fn attach(
+    mut commands: Commands,
+    config: Res<Config>,
+    assets: Res<SplatAssets>,
+    mut textures: ResMut<Assets<Texture>>,
+    mut splat_materials: ResMut<Assets<SplatMaterial>>,
+    mut chunks_query: Query<(Entity, &mut Chunk), Changed<Chunk>>,
+) {
+    for (entity, mut chunk) in chunks_query.iter_mut() {
+        let splat = match chunk.take_splat() {
+            Some(splat) => splat,
+            None => continue,
+        };
+        let thresholds = &config.terrain_thresholds;
+        let layers = config.material.layers();
+        let color = |index: usize| {
+            let (path, biome) = layers[index];
+            if path.is_empty() {
+                thresholds[biome as usize].color
+            } else {
+                Color::WHITE
+            }
+        };
+        let material = splat_materials.add(SplatMaterial {
+            // the night lighting catches it up from the chunk's own material
+            tint: Color::WHITE,
+            tile_size: config.material.tile_size,
+            grass_color: color(0),
+            rock_color: color(1),
+            sand_color: color(2),
+            snow_color: color(3),
+            splat: textures.add(splat),
+            grass: assets.layers[0].clone(),
+            rock: assets.layers[1].clone(),
+            sand: assets.layers[2].clone(),
+            snow: assets.layers[3].clone(),
+        });
+        // replaces the PBR pipeline the chunk was just inserted with
+        commands
+            .entity(entity)
+            .insert(material)
+            .insert(RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                assets.pipeline.clone(),
+            )]));
+    }
+}
+
+// Lights the splat material the same as the colour map it's drawn in place of
+pub fn follow_lighting(
+    materials: Res<Assets<StandardMaterial>>,
+    mut splat_materials: ResMut<Assets<SplatMaterial>>,
+    chunks_query: Query<(&Handle<StandardMaterial>, &Handle<SplatMaterial>), With<Chunk>>,
+) {
+    for (standard, splat) in chunks_query.iter() {
+        let tint = match materials.get(standard) {
+            Some(material) => material.base_color,
+            None => continue,
+        };
+        // only touched when it's changed, as that has it uploaded again
+        let stale = splat_materials
+            .get(splat)
+            .map_or(false, |material| material.tint != tint);
+        if stale {
+            if let Some(material) = splat_materials.get_mut(splat) {
+                material.tint = tint;
+            }
+        }
+    }
+}
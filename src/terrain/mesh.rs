@@ -1,19 +1,101 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    mem,
+    sync::Arc,
+};
+
 use bevy::{
-    math::Vec3,
+    math::{Vec2, Vec3},
     render::{
         mesh::{Indices, Mesh, VertexAttributeValues},
         pipeline::PrimitiveTopology,
     },
 };
-use bevy_rapier3d::{na::Point3, prelude::ColliderShape};
+use bevy_rapier3d::{
+    na::{DMatrix, Vector3},
+    prelude::ColliderShape,
+};
+
+use super::{
+    endless::HeightBounds,
+    height_map::{HeightMap, HeightPyramid},
+    SimplificationLevel,
+};
+
+thread_local! {
+    // the buffers each worker thread has finished with, to build its next chunk in
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+// Mesh buffers kept around between chunks so their allocations are reused. Whatever ends up
+// in a chunk's mesh goes with it, so only the leftovers are given back: the 32 bit indices of
+// meshes that fit in 16 bit ones, and the shared vertices of flat shaded meshes.
+#[derive(Default)]
+struct Scratch {
+    vertices: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    triangles: Vec<u32>,
+}
+
+impl Scratch {
+    // Empties this thread's pool
+    fn take() -> Scratch {
+        SCRATCH.with(|pool| mem::take(&mut *pool.borrow_mut()))
+    }
+
+    // Puts the buffers back in this thread's pool, keeping whichever of each is larger
+    fn recycle(self) {
+        SCRATCH.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            keep_larger(&mut pool.vertices, self.vertices);
+            keep_larger(&mut pool.normals, self.normals);
+            keep_larger(&mut pool.uvs, self.uvs);
+            keep_larger(&mut pool.triangles, self.triangles);
+        });
+    }
+}
+
+fn keep_larger<T>(kept: &mut Vec<T>, other: Vec<T>) {
+    if other.capacity() > kept.capacity() {
+        *kept = other;
+    }
+}
+
+// Refills a recycled buffer with len copies of value
+fn refill<T: Clone>(mut buffer: Vec<T>, len: usize, value: T) -> Vec<T> {
+    buffer.clear();
+    buffer.resize(len, value);
+    buffer
+}
 
-use super::{height_map::HeightMap, SimplificationLevel};
+/// How a chunk's mesh lays out its texture coordinates
+#[derive(Clone, Copy, Debug)]
+pub enum UvMapping {
+    // 0 to 1 across the chunk
+    Chunk,
+    // world position divided by the tile size, with the origin being where the chunk's
+    // first vertex sits in the world
+    World { origin: Vec2, tile_size: f32 },
+}
 
 pub struct Generator {
-    pub height_map: HeightMap,
+    pub pyramid: Arc<HeightPyramid>,
     pub height_scale: f32,
     pub simplification_level: SimplificationLevel,
     pub simplification_increment: usize,
+    pub uv_mapping: UvMapping,
+    // how far the strip hung down from the chunk's edges reaches, none when 0
+    pub skirt_depth: f32,
+    // gives every triangle its own vertices and a single normal and colour for a low poly look
+    pub flat_shading: bool,
+    // heights are snapped to multiples of this when flat shading, none when 0
+    pub height_step: f32,
+    // the levels the neighbouring chunks are generated at, in the order of
+    // `ChunkCoords::neighbours`, for stitching the edges shared with them. None where there's
+    // no chunk loaded.
+    pub neighbour_levels: [Option<SimplificationLevel>; 4],
     pub vertices_per_line: usize,
     pub vertices: Vec<[f32; 3]>,
     pub triangles: Vec<u32>,
@@ -25,24 +107,25 @@ pub struct Generator {
 
 impl Generator {
     pub fn new(
-        height_map: HeightMap,
+        pyramid: Arc<HeightPyramid>,
         height_scale: f32,
         simplification_level: SimplificationLevel,
+        uv_mapping: UvMapping,
     ) -> Generator {
-        let map_width = height_map.data.len();
-
-        let simplification_increment = if simplification_level == SimplificationLevel(0) {
-            1
-        } else {
-            (simplification_level.0 * 2) as usize
-        };
+        let map_width = pyramid.base().size;
+        let simplification_increment = simplification_increment(simplification_level);
         let vertices_per_line = (map_width - 1) / simplification_increment + 1;
 
         Generator {
-            height_map,
+            pyramid,
             height_scale,
             simplification_level,
             simplification_increment,
+            uv_mapping,
+            skirt_depth: 0.0,
+            flat_shading: false,
+            height_step: 0.0,
+            neighbour_levels: [None; 4],
             vertices_per_line,
             map_width,
             vertices: vec![],
@@ -54,28 +137,43 @@ impl Generator {
     }
 
     pub fn generate(&mut self) {
-        let map_size = self.map_width * self.map_width;
+        // Only every simplification_increment'th height map point becomes a vertex, so the
+        // buffers are sized and strided by the vertices actually kept
+        let vertex_count = self.vertices_per_line * self.vertices_per_line;
+        let quads_per_line = self.vertices_per_line - 1;
 
-        self.vertices = vec![[0., 0., 0.]; map_size];
-        self.normals = vec![[0., 0., 0.]; map_size];
-        self.uvs = vec![[0., 0.]; map_size];
-        self.triangles = vec![0; (self.map_width - 1) * (self.map_width - 1) * 6];
+        let scratch = Scratch::take();
+        self.vertices = refill(scratch.vertices, vertex_count, [0., 0., 0.]);
+        self.normals = refill(scratch.normals, vertex_count, [0., 0., 0.]);
+        self.uvs = refill(scratch.uvs, vertex_count, [0., 0.]);
+        self.triangles = refill(scratch.triangles, quads_per_line * quads_per_line * 6, 0);
         self.triangles_index = 0;
 
-        let mut vertex_index = 0;
-        let mut y = 0;
-        while y < self.map_width {
-            let mut x = 0;
-            while x < self.map_width {
-                let height = self.height_map.data[y][x] * self.height_scale;
+        // Sample the pyramid level matching the spacing of the vertices, so every level of
+        // detail follows the same smoothed silhouette instead of skipping over detail
+        let pyramid = self.pyramid.clone();
+        let (height_map, level) = pyramid.level_for_increment(self.simplification_increment);
 
+        for row in 0..self.vertices_per_line {
+            for column in 0..self.vertices_per_line {
+                let x = column * self.simplification_increment;
+                let y = row * self.simplification_increment;
+                let height = self.height(height_map, level, x, y);
+
+                let vertex_index = row * self.vertices_per_line + column;
                 self.vertices[vertex_index] = [x as f32, height as f32, y as f32];
-                self.uvs[vertex_index] = [
-                    x as f32 / self.map_width as f32,
-                    y as f32 / self.map_width as f32,
-                ];
+                self.uvs[vertex_index] = match self.uv_mapping {
+                    UvMapping::Chunk => [
+                        x as f32 / self.map_width as f32,
+                        y as f32 / self.map_width as f32,
+                    ],
+                    UvMapping::World { origin, tile_size } => [
+                        (origin.x + x as f32) / tile_size,
+                        (origin.y + y as f32) / tile_size,
+                    ],
+                };
 
-                if x < self.map_width - 1 && y < self.map_width - 1 {
+                if column < quads_per_line && row < quads_per_line {
                     let top_left = vertex_index;
                     let top_right = vertex_index + 1;
                     let bottom_left = vertex_index + self.vertices_per_line;
@@ -83,13 +181,158 @@ impl Generator {
                     self.add_triangle(bottom_right, top_left, bottom_left);
                     self.add_triangle(top_left, bottom_right, top_right);
                 }
-
-                vertex_index += 1;
-                x += self.simplification_increment;
             }
-            y += self.simplification_increment;
         }
+        self.stitch_edges();
         self.calculate_normals();
+        if self.skirt_depth > 0.0 {
+            self.add_skirt();
+        }
+    }
+
+    // A height map cell's height in the world, snapped when flat shading
+    fn height(&self, height_map: &HeightMap, level: usize, x: usize, y: usize) -> f32 {
+        let height = height_map.data[y >> level][x >> level] * self.height_scale;
+        if self.flat_shading && self.height_step > 0.0 {
+            (height / self.height_step).round() * self.height_step
+        } else {
+            height
+        }
+    }
+
+    // The vertex indices along each edge, in the order of `ChunkCoords::neighbours`: the first
+    // and last rows, then the first and last columns
+    fn edges(&self) -> [Vec<usize>; 4] {
+        let n = self.vertices_per_line;
+        [
+            (0..n).collect(),
+            (0..n).map(|column| (n - 1) * n + column).collect(),
+            (0..n).map(|row| row * n).collect(),
+            (0..n).map(|row| row * n + n - 1).collect(),
+        ]
+    }
+
+    // Bends each edge shared with a chunk at another level onto the line both chunks agree
+    // on, so there's no crack between them. The pyramid's edge cells are only filtered along
+    // the edge, so this chunk's pyramid holds the neighbour's edge heights too.
+    fn stitch_edges(&mut self) {
+        let pyramid = self.pyramid.clone();
+        let last = self.map_width - 1;
+        for (side, edge) in self.edges().iter().enumerate() {
+            let (spacing, anchor_increment) = match self.neighbour_levels[side]
+                .and_then(|neighbour| edge_anchors(self.simplification_level, neighbour))
+            {
+                Some(anchors) => anchors,
+                None => continue,
+            };
+            let (anchor_map, level) = pyramid.level_for_increment(anchor_increment);
+            // the cell at a distance along the edge
+            let cell = |along: usize| match side {
+                0 => (along, 0),
+                1 => (along, last),
+                2 => (0, along),
+                _ => (last, along),
+            };
+
+            for (step, &index) in edge.iter().enumerate() {
+                let along = step * self.simplification_increment;
+                let start = along / spacing * spacing;
+                let end = (start + spacing).min(last);
+                let (start_x, start_y) = cell(start);
+                let (end_x, end_y) = cell(end);
+                let from = self.height(anchor_map, level, start_x, start_y);
+                let to = self.height(anchor_map, level, end_x, end_y);
+                let t = (along - start) as f32 / spacing as f32;
+                self.vertices[index][1] = from + (to - from) * t;
+            }
+        }
+    }
+
+    // Hangs a strip down from every edge, so any gap left between neighbouring chunks shows
+    // more terrain rather than the sky through it
+    fn add_skirt(&mut self) {
+        let n = self.vertices_per_line;
+        let centre = Vec3::new(
+            self.vertices[n * n - 1][0] / 2.0,
+            0.0,
+            self.vertices[n * n - 1][2] / 2.0,
+        );
+
+        for edge in self.edges().iter() {
+            let first_skirt_vertex = self.vertices.len();
+            for &index in edge.iter() {
+                let [x, y, z] = self.vertices[index];
+                self.vertices.push([x, y - self.skirt_depth, z]);
+                self.normals.push(self.normals[index]);
+                self.uvs.push(self.uvs[index]);
+            }
+
+            for step in 0..edge.len() - 1 {
+                let (top_a, top_b) = (edge[step], edge[step + 1]);
+                let (bottom_a, bottom_b) =
+                    (first_skirt_vertex + step, first_skirt_vertex + step + 1);
+
+                // wind the wall so it faces away from the middle of the chunk
+                let a = Vec3::from(self.vertices[top_a]);
+                let b = Vec3::from(self.vertices[top_b]);
+                let c = Vec3::from(self.vertices[bottom_a]);
+                let outward = (a + b) / 2.0 - centre;
+                let faces_out = (b - a).cross(c - a).dot(outward) > 0.0;
+                let quad = if faces_out {
+                    [top_a, top_b, bottom_a, top_b, bottom_b, bottom_a]
+                } else {
+                    [top_a, bottom_a, top_b, top_b, bottom_a, bottom_b]
+                };
+                self.triangles
+                    .extend(quad.iter().map(|&index| index as u32));
+            }
+        }
+    }
+
+    // Gives each triangle its own three vertices, all with the face's normal and the uv of its
+    // middle so the whole face takes one colour from the chunk texture
+    fn split_faces(&mut self) {
+        let mut vertices = Vec::with_capacity(self.triangles.len());
+        let mut normals = Vec::with_capacity(self.triangles.len());
+        let mut uvs = Vec::with_capacity(self.triangles.len());
+
+        for triangle in self.triangles.chunks_exact(3) {
+            let corners = [
+                self.vertices[triangle[0] as usize],
+                self.vertices[triangle[1] as usize],
+                self.vertices[triangle[2] as usize],
+            ];
+            let normal: [f32; 3] = Vec3::from(face_normal(corners[0], corners[1], corners[2]))
+                .normalize_or_zero()
+                .into();
+            let middle = triangle
+                .iter()
+                .map(|&index| Vec2::from(self.uvs[index as usize]))
+                .fold(Vec2::ZERO, |sum, uv| sum + uv)
+                / 3.0;
+
+            vertices.extend_from_slice(&corners);
+            normals.extend_from_slice(&[normal; 3]);
+            uvs.extend_from_slice(&[<[f32; 2]>::from(middle); 3]);
+        }
+
+        let triangles = (0..vertices.len() as u32).collect();
+        Scratch {
+            vertices: mem::replace(&mut self.vertices, vertices),
+            normals: mem::replace(&mut self.normals, normals),
+            uvs: mem::replace(&mut self.uvs, uvs),
+            triangles: mem::replace(&mut self.triangles, triangles),
+        }
+        .recycle();
+    }
+
+    // The vertices and triangles of the surface itself, without the skirt
+    fn surface(&self) -> (&[[f32; 3]], &[u32]) {
+        let quads_per_line = self.vertices_per_line - 1;
+        (
+            &self.vertices[..self.vertices_per_line * self.vertices_per_line],
+            &self.triangles[..quads_per_line * quads_per_line * 6],
+        )
     }
 
     fn add_triangle(&mut self, a: usize, b: usize, c: usize) {
@@ -99,40 +342,64 @@ impl Generator {
         self.triangles_index += 3;
     }
 
-    pub fn graphics_mesh(&mut self) -> Mesh {
+    // Moves the generated buffers straight into the mesh, so build the collider first
+    pub fn graphics_mesh(mut self) -> Mesh {
+        if self.flat_shading {
+            self.split_faces();
+        }
+
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.set_indices(Some(Indices::U32(self.triangles.clone())));
+        // Most chunks are distant and simplified, so they fit in half size indices
+        let indices = if self.vertices.len() <= u16::MAX as usize + 1 {
+            let indices = self.triangles.iter().map(|&index| index as u16).collect();
+            Scratch {
+                triangles: self.triangles,
+                ..Scratch::default()
+            }
+            .recycle();
+            Indices::U16(indices)
+        } else {
+            Indices::U32(self.triangles)
+        };
+        mesh.set_indices(Some(indices));
         mesh.set_attribute(
             Mesh::ATTRIBUTE_POSITION,
-            VertexAttributeValues::Float3(self.vertices.clone()),
+            VertexAttributeValues::Float3(self.vertices),
         );
         mesh.set_attribute(
             Mesh::ATTRIBUTE_UV_0,
-            VertexAttributeValues::Float2(self.uvs.clone()),
+            VertexAttributeValues::Float2(self.uvs),
         );
-        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals.clone());
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
 
-        return mesh;
+        mesh
     }
 
-    pub fn collider_shape(&self) -> ColliderShape {
-        let vertices = self
-            .vertices
-            .iter()
-            .map(|&[x, y, z]| Point3::new(x, y, z))
-            .collect();
+    pub fn height_bounds(&self) -> HeightBounds {
+        let (vertices, _) = self.surface();
+        vertices.iter().fold(
+            HeightBounds {
+                min: f32::MAX,
+                max: f32::MIN,
+            },
+            |bounds, &[_, y, _]| HeightBounds {
+                min: bounds.min.min(y),
+                max: bounds.max.max(y),
+            },
+        )
+    }
 
-        let indices = (0..self.triangles.len() / 3)
-            .map(|i| {
-                let j = i * 3;
-                [
-                    self.triangles[j],
-                    self.triangles[j + 1],
-                    self.triangles[j + 2],
-                ]
-            })
-            .collect();
-        ColliderShape::trimesh(vertices, indices)
+    /// A heightfield of the surface at the mesh's simplification level, which is lighter to
+    /// build and query than a triangle mesh. It's centred on the middle of the chunk.
+    pub fn collider_shape(&self) -> ColliderShape {
+        // the skirt is hidden under the neighbouring chunks, so there's nothing to stand on
+        let (vertices, _) = self.surface();
+        let n = self.vertices_per_line;
+        // rows run along z and columns along x like the vertices, and the heightfield splits
+        // each cell along the same diagonal as the mesh's triangles
+        let heights = DMatrix::from_fn(n, n, |row, column| vertices[row * n + column][1]);
+        let extent = vertices[n * n - 1][0];
+        ColliderShape::heightfield(heights, Vector3::new(extent, 1.0, extent))
     }
 
     // Right now this is not a perfect way of handling the normals.
@@ -141,7 +408,7 @@ impl Generator {
     // This will not give us the most realistic pbr lighting.
     fn calculate_normals(&mut self) {
         for triangle_indexes in self.triangles.chunks_exact(3) {
-            let normal = self.face_normal(
+            let normal = face_normal(
                 self.vertices[triangle_indexes[0] as usize],
                 self.vertices[triangle_indexes[1] as usize],
                 self.vertices[triangle_indexes[2] as usize],
@@ -152,9 +419,327 @@ impl Generator {
             self.normals[triangle_indexes[2] as usize] = normal;
         }
     }
+}
+
+fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    let (a, b, c) = (Vec3::from(a), Vec3::from(b), Vec3::from(c));
+    (b - a).cross(c - a).into()
+}
+
+/// How many height map cells apart a level's vertices are
+pub fn simplification_increment(level: SimplificationLevel) -> usize {
+    if level == SimplificationLevel(0) {
+        1
+    } else {
+        (level.0 * 2) as usize
+    }
+}
+
+/// How a chunk at one level has to bend the edge it shares with a chunk at another for them to
+/// meet: onto straight lines between anchors every `spacing` cells, with the anchors' heights
+/// taken from the pyramid level for `anchor_increment`. Both chunks work out the same anchors
+/// from the pair of levels. None when the neighbour's vertices fall on every one of the
+/// chunk's own, which then already are the anchors.
+pub fn edge_anchors(
+    own: SimplificationLevel,
+    neighbour: SimplificationLevel,
+) -> Option<(usize, usize)> {
+    let own = simplification_increment(own);
+    let neighbour = simplification_increment(neighbour);
+    // the cells both chunks have a vertex on
+    let spacing = own / gcd(own, neighbour) * neighbour;
+    if spacing == own {
+        return None;
+    }
+    Some((spacing, own.max(neighbour)))
+}
+
+/// Moves vertices of a smooth shaded mesh to new heights in place, along with the skirt hung
+/// under them, and works the normals around them out again the same way `Generator::generate`
+/// does. The heights are by cell of a height map `map_width` cells a side, the mesh having a
+/// vertex every `increment` cells; those between its vertices are left out. The vertices on
+/// the `stitched` edges, in the order of `ChunkCoords::neighbours`, are left on the line they
+/// were bent onto. False if the mesh isn't laid out that way.
+pub fn patch_heights(
+    mesh: &mut Mesh,
+    map_width: usize,
+    increment: usize,
+    heights: &HashMap<usize, f32>,
+    stitched: [bool; 4],
+) -> bool {
+    let n = (map_width - 1) / increment + 1;
+    let positions = match mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float3(positions)) => positions,
+        _ => return false,
+    };
+    let skirted = positions.len() == n * n + 4 * n;
+    if positions.len() != n * n && !skirted {
+        return false;
+    }
+    // the skirt vertex hung under each edge a vertex is on, in the order `add_skirt` adds them
+    let skirt = |index: usize| {
+        let (row, column) = (index / n, index % n);
+        let sides = [row == 0, row == n - 1, column == 0, column == n - 1];
+        let along = [column, column, row, row];
+        (0..4)
+            .filter(move |&side| sides[side])
+            .map(move |side| (side, n * n + side * n + along[side]))
+    };
+
+    let mut affected = HashSet::new();
+    for (&cell, &height) in heights {
+        let (row, column) = (cell / map_width, cell % map_width);
+        if row % increment != 0 || column % increment != 0 {
+            continue;
+        }
+        let index = row / increment * n + column / increment;
+        if skirt(index).any(|(side, _)| stitched[side]) {
+            continue;
+        }
+        let rise = height - positions[index][1];
+        positions[index][1] = height;
+        if skirted {
+            for (_, below) in skirt(index) {
+                positions[below][1] += rise;
+            }
+        }
+        // every normal taken from a triangle with this vertex in it
+        let (row, column) = (index / n, index % n);
+        for y in row.saturating_sub(1)..=(row + 1).min(n - 1) {
+            for x in column.saturating_sub(1)..=(column + 1).min(n - 1) {
+                affected.insert(y * n + x);
+            }
+        }
+    }
+
+    let normals: Vec<_> = affected
+        .iter()
+        .map(|&index| {
+            let [a, b, c] = normal_triangle(n, index);
+            (index, face_normal(positions[a], positions[b], positions[c]))
+        })
+        .collect();
+    match mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float3(mesh_normals)) => {
+            for (index, normal) in normals {
+                mesh_normals[index] = normal;
+                if skirted {
+                    for (_, below) in skirt(index) {
+                        mesh_normals[below] = normal;
+                    }
+                }
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+// The corners of the triangle a grid vertex takes its normal from, the last one it's in of
+// those `generate` adds, as `calculate_normals` leaves each vertex with that one's normal
+fn normal_triangle(n: usize, index: usize) -> [usize; 3] {
+    let (row, column) = (index / n, index % n);
+    let last = n - 1;
+    // each quad is split into (bottom right, top left, bottom left) then (top left,
+    // bottom right, top right)
+    let quad = |row: usize, column: usize| row * n + column;
+    let (tl, first) = match (row < last, column < last) {
+        (true, true) => (quad(row, column), false),
+        (true, false) => (quad(row, column - 1), false),
+        (false, true) => (quad(row - 1, column), true),
+        (false, false) => (quad(row - 1, column - 1), false),
+    };
+    let (tr, bl, br) = (tl + 1, tl + n, tl + n + 1);
+    if first {
+        [br, tl, bl]
+    } else {
+        [tl, br, tr]
+    }
+}
 
-    fn face_normal(&self, a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
-        let (a, b, c) = (Vec3::from(a), Vec3::from(b), Vec3::from(c));
-        (b - a).cross(c - a).into()
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::terrain::MAP_CHUNK_SIZE;
+
+    // Rough heights from a seed, much steeper than any real terrain, so every triangle gets
+    // tried against more than smooth slopes
+    fn height_map(seed: u64) -> HeightMap {
+        let size = MAP_CHUNK_SIZE as usize;
+        let mut state = seed | 1;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 1000) as f32 / 1000.0
+        };
+        let data = (0..size)
+            .map(|_| (0..size).map(|_| next()).collect())
+            .collect();
+        HeightMap { data, size }
+    }
+
+    fn level() -> impl Strategy<Value = SimplificationLevel> {
+        (0..=SimplificationLevel::max().0).prop_map(SimplificationLevel)
+    }
+
+    fn assert_triangles(vertices: &[[f32; 3]], triangles: &[u32]) -> Result<(), TestCaseError> {
+        prop_assert_eq!(triangles.len() % 3, 0);
+        for triangle in triangles.chunks_exact(3) {
+            for &index in triangle {
+                prop_assert!(
+                    (index as usize) < vertices.len(),
+                    "{:?} is out of bounds",
+                    triangle
+                );
+            }
+            let [a, b, c] = [
+                vertices[triangle[0] as usize],
+                vertices[triangle[1] as usize],
+                vertices[triangle[2] as usize],
+            ];
+            let area = Vec3::from(face_normal(a, b, c)).length();
+            prop_assert!(
+                area > 1e-4,
+                "{:?} at {:?} is degenerate",
+                triangle,
+                [a, b, c]
+            );
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn meshes_are_well_formed(
+            seed in any::<u64>(),
+            level in level(),
+            neighbour_levels in proptest::array::uniform4(proptest::option::of(level())),
+            skirt_depth in prop_oneof![Just(0.0f32), 1.0f32..20.0],
+            flat_shading in any::<bool>(),
+            height_scale in 1.0f32..200.0,
+        ) {
+            let pyramid = Arc::new(HeightPyramid::build(height_map(seed)));
+            let mut generator = Generator::new(pyramid, height_scale, level, UvMapping::Chunk);
+            generator.skirt_depth = skirt_depth;
+            generator.flat_shading = flat_shading;
+            generator.neighbour_levels = neighbour_levels;
+            generator.generate();
+
+            let n = generator.vertices_per_line;
+            let skirt = if skirt_depth > 0.0 { 4 * n } else { 0 };
+            prop_assert_eq!(generator.vertices.len(), n * n + skirt);
+            prop_assert_eq!(generator.normals.len(), generator.vertices.len());
+            prop_assert_eq!(generator.uvs.len(), generator.vertices.len());
+            assert_triangles(&generator.vertices, &generator.triangles)?;
+
+            // and again once flat shading has split the faces apart
+            let mesh = generator.graphics_mesh();
+            let vertices = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+                Some(VertexAttributeValues::Float3(vertices)) => vertices.clone(),
+                other => return Err(TestCaseError::fail(format!("positions are {:?}", other))),
+            };
+            let normals = mesh.attribute(Mesh::ATTRIBUTE_NORMAL).map(VertexAttributeValues::len);
+            let uvs = mesh.attribute(Mesh::ATTRIBUTE_UV_0).map(VertexAttributeValues::len);
+            prop_assert_eq!(normals, Some(vertices.len()));
+            prop_assert_eq!(uvs, Some(vertices.len()));
+            let triangles: Vec<u32> = match mesh.indices() {
+                Some(Indices::U16(indices)) => indices.iter().map(|&index| index as u32).collect(),
+                Some(Indices::U32(indices)) => indices.clone(),
+                None => return Err(TestCaseError::fail("the mesh has no indices")),
+            };
+            assert_triangles(&vertices, &triangles)?;
+        }
+
+        #[test]
+        fn stitched_edges_meet(
+            seed in any::<u64>(),
+            neighbour_seed in any::<u64>(),
+            own in level(),
+            neighbour in level(),
+            side in 0..4usize,
+        ) {
+            // the neighbour is its own terrain, sharing only the cells along the edge, which
+            // is its opposite side
+            let opposite = side ^ 1;
+            let ours = height_map(seed);
+            let mut theirs = height_map(neighbour_seed);
+            for along in 0..ours.size {
+                let (x, y) = edge_cell(side, along);
+                let (neighbour_x, neighbour_y) = edge_cell(opposite, along);
+                theirs.data[neighbour_y][neighbour_x] = ours.data[y][x];
+            }
+
+            let ours = edge_heights(ours, own, neighbour, side);
+            let theirs = edge_heights(theirs, neighbour, own, opposite);
+            // every vertex on the finer side has to lie on the coarser side's edge, or there's
+            // a crack where the finer one bends away from it
+            let (fine, coarse) = if ours.len() >= theirs.len() {
+                (ours, theirs)
+            } else {
+                (theirs, ours)
+            };
+            let spacing = coarse[1].0;
+            for &(along, height) in fine.iter() {
+                let (start, from) = coarse[along / spacing];
+                let (end, to) = coarse[(along / spacing + 1).min(coarse.len() - 1)];
+                let t = if end > start {
+                    (along - start) as f32 / (end - start) as f32
+                } else {
+                    0.0
+                };
+                let expected = from + (to - from) * t;
+                prop_assert!(
+                    (height - expected).abs() < 1e-3,
+                    "the finer edge is at {} {} cells along, off the coarser one at {}",
+                    height,
+                    along,
+                    expected
+                );
+            }
+        }
+    }
+
+    // The cell a distance along one of a map's edges, in the order of `ChunkCoords::neighbours`
+    fn edge_cell(side: usize, along: usize) -> (usize, usize) {
+        let last = MAP_CHUNK_SIZE as usize - 1;
+        match side {
+            0 => (along, 0),
+            1 => (along, last),
+            2 => (0, along),
+            _ => (last, along),
+        }
+    }
+
+    // How far along each vertex on one edge of the mesh is, in cells, and its height, with the
+    // chunk on the other side of that edge at another level
+    fn edge_heights(
+        height_map: HeightMap,
+        level: SimplificationLevel,
+        neighbour: SimplificationLevel,
+        side: usize,
+    ) -> Vec<(usize, f32)> {
+        let pyramid = Arc::new(HeightPyramid::build(height_map));
+        let mut generator = Generator::new(pyramid, 50.0, level, UvMapping::Chunk);
+        generator.neighbour_levels[side] = Some(neighbour);
+        generator.generate();
+        let increment = generator.simplification_increment;
+        generator.edges()[side]
+            .iter()
+            .enumerate()
+            .map(|(step, &index)| (step * increment, generator.vertices[index][1]))
+            .collect()
     }
 }
@@ -1,69 +1,183 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    env,
+    hash::{Hash, Hasher},
+};
+
 use bevy::{self, prelude::*};
-use bevy_inspector_egui::{Inspectable, InspectorPlugin};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
 use derive_more::{Add, Deref, From, Into, Mul};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{AddSettings, SettingsTab};
 
+use self::{
+    biome::{BiomeConfig, Climate},
+    erosion::ErosionConfig,
+    material::MaterialConfig,
+    quadtree::LodConfig,
+};
+
+pub mod analysis;
+mod biome;
+mod cache;
+mod config_file;
+mod debug;
+mod diff;
+pub mod edits;
 mod endless;
+mod erosion;
+pub mod export;
+mod failure;
 mod height_map;
+mod hydrology;
+mod material;
 mod mesh;
+mod overview;
+mod pipeline;
+mod quadtree;
+pub mod query;
+pub mod scatter;
+mod shadows;
 mod texture;
+mod validate;
+pub mod water;
+mod worker;
+
+pub use edits::TerrainEdits;
+pub use endless::{
+    kept_chunk_radius, Chunk, ChunkCoords, ChunkSpawnedEvent, HeightBounds, SeenChunks, CHUNK_SIZE,
+};
+pub use failure::ChunkGenerationFailed;
+pub use height_map::HeightMap;
+pub use pipeline::{GenerationPipeline, GenerationStage};
 
 const MAP_CHUNK_SIZE: u32 = 241;
 
-#[derive(Inspectable, Clone, Debug)]
+/// Everything that shapes and draws the terrain, kept in `assets/terrain.ron` between runs.
+/// Settings missing from the file keep their defaults.
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
-    #[inspectable(min = 1)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1))]
     seed: u32,
-    #[inspectable(min = 0.0001)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0001))]
     lacunarity: f32, // increase for more hills closer together
-    #[inspectable(min = 0.0001)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0001))]
     persistence: f32,
-    #[inspectable(min = 1)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1))]
     octaves: usize,
-    #[inspectable(min = 1.0)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
     height_scale: f32,
-    #[inspectable(min = 0.0001)]
+    // world-space height of the water surface, everything below it is coloured as the first
+    // terrain threshold and flooded by the water planes
+    sea_level: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0001))]
     scale: f32,
     wireframe: bool,
-    #[inspectable(min = MAP_CHUNK_SIZE as f32)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = MAP_CHUNK_SIZE as f32))]
     max_view_distance: f32,
-    low_simplification_threshold: SimplificationThreshold,
-    medium_simplification_threshold: SimplificationThreshold,
-    high_simplification_threshold: SimplificationThreshold,
-    #[inspectable(min = 0.0, max = 1.0)]
+    // how far out what's loaded is kept before it's unloaded, past the view distance so the
+    // nodes on its edge don't load and unload over and over
+    #[cfg_attr(feature = "dev-tools", inspectable(min = MAP_CHUNK_SIZE as f32))]
+    unload_radius: f32,
+    // how many of the height maps generated last are kept, at about a quarter of a megabyte
+    // each, so coming back to an area doesn't run the generation stages over again
+    height_map_cache_size: usize,
+    // how the chunks up close give way to ever larger, coarser nodes further out
+    lod: LodConfig,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
     material_roughness: f32,
-    #[inspectable(min = 0.0, max = 1.0)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
     material_reflectance: f32,
+    // UVs from world position / uv_tile_size so tiling textures run on across chunk borders.
+    // The chunk colour maps are drawn for 0 to 1 UVs, so only for materials that tile.
+    world_uvs: bool,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    uv_tile_size: f32,
+    // depth of the strip hung from each chunk's edges to hide seams, 0 to leave it off
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    skirt_depth: f32,
+    // world-space height between the contour lines drawn over the terrain, 0 to hide them
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    contour_interval: f32,
+    // shade distant chunks' textures for the sun, since bevy draws no shadows of its own
+    bake_shadows: bool,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    shadow_bake_distance: f32,
+    // how far the sun moves, in degrees, before the distant chunks are shaded again
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    shadow_rebake_degrees: f32,
+    // tint the cells that lots of water drains through blue
+    flow_overlay: bool,
+    // how many cells have to drain through a cell before it's tinted
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    flow_threshold: f32,
+    // what the chunk textures show, cycled through with F7
+    color_mode: ColorMode,
+    // normalized height either side of each threshold over which its colour fades into the
+    // next one's, 0 for hard bands
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 0.2))]
+    color_blend: f32,
+    // slopes steeper than this, in degrees, start turning the colour of rock, 0 to leave
+    // them the colour of their height
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 90.0))]
+    rock_slope_degrees: f32,
+    // how many degrees steeper than that the slopes are all rock
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1, max = 45.0))]
+    rock_blend_degrees: f32,
+    // faceted low poly look instead of smooth shading
+    flat_shading: bool,
+    // world-space height the faceted terrain is stepped by, 0 to leave it smooth
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    flat_height_step: f32,
     endless: bool,
     terrain_thresholds: [TerrainThreshold; 6],
+    // deserts, plains and mountains, each with their own octaves and thresholds
+    biomes: BiomeConfig,
+    // water droplets run over each chunk's height map to carve valleys, off by default
+    erosion: ErosionConfig,
+    // tiling textures mixed by height and slope in place of the colour maps, off by default
+    material: MaterialConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             height_scale: 100.0,
+            sea_level: 35.0,
             seed: 2,
             octaves: 6,
             lacunarity: 0.6,
             persistence: 0.5,
             scale: 1.0,
             wireframe: false,
-            low_simplification_threshold: SimplificationThreshold {
-                max_distance: 700.,
-                level: SimplificationLevel(1),
-            },
-            medium_simplification_threshold: SimplificationThreshold {
-                max_distance: 1000.,
-                level: SimplificationLevel(2),
-            },
-            high_simplification_threshold: SimplificationThreshold {
-                max_distance: 1300.,
-                level: SimplificationLevel(4),
-            },
-            max_view_distance: 1500.,
+            lod: LodConfig::default(),
+            max_view_distance: 6000.,
+            unload_radius: 6600.,
+            height_map_cache_size: 256,
             material_roughness: 0.98,
             material_reflectance: 0.1,
+            world_uvs: false,
+            uv_tile_size: 16.0,
+            skirt_depth: 0.0,
+            contour_interval: 0.0,
+            bake_shadows: true,
+            shadow_bake_distance: 700.0,
+            shadow_rebake_degrees: 5.0,
+            flow_overlay: false,
+            flow_threshold: 100.0,
+            color_mode: ColorMode::Terrain,
+            color_blend: 0.0,
+            rock_slope_degrees: 0.0,
+            rock_blend_degrees: 15.0,
+            flat_shading: false,
+            flat_height_step: 0.5,
             endless: true,
             terrain_thresholds: [
+                // the water, which covers everything below sea_level whatever its max_height
                 TerrainThreshold {
                     max_height: 0.35,
                     color: Color::rgb(0.0, 0.1, 0.8),
@@ -89,27 +203,192 @@ impl Default for Config {
                     color: Color::rgb(1.0, 1.0, 1.0),
                 },
             ],
+            biomes: BiomeConfig::default(),
+            erosion: ErosionConfig::default(),
+            material: MaterialConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// World-space height of the water surface
+    pub fn sea_level(&self) -> f32 {
+        self.sea_level
+    }
+
+    /// Which terrain threshold a normalized height falls into at a point in the height map
+    /// grid, going by the thresholds of the region there
+    pub fn biome(&self, height: f32, point: Vec2) -> Biome {
+        let climate = Climate::new(self.seed);
+        self.biome_in(height, self.thresholds_at(&climate, point))
+    }
+
+    fn biome_in(&self, height: f32, thresholds: &[TerrainThreshold; 6]) -> Biome {
+        if height * self.height_scale < self.sea_level {
+            return Biome::Water;
+        }
+        let index = thresholds
+            .iter()
+            .skip(1)
+            .position(|terrain| height < terrain.max_height)
+            .map_or(thresholds.len() - 1, |index| index + 1);
+        Biome::ALL[index]
+    }
+
+    // The thresholds of whichever region mostly shapes a point in the height map grid
+    fn thresholds_at(&self, climate: &Climate, point: Vec2) -> &[TerrainThreshold; 6] {
+        if !self.biomes.enabled {
+            return &self.terrain_thresholds;
+        }
+        let region = climate.weights(&self.biomes, point).dominant();
+        &self.biomes.params(region).terrain_thresholds
+    }
+
+    // How many octaves of noise are layered up, the most of any region's when they're on
+    fn octave_count(&self) -> usize {
+        if self.biomes.enabled {
+            self.biomes.max_octaves()
+        } else {
+            self.octaves
         }
     }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+    }
+
+    /// Identifies the settings that shape the ground and what grows on it, so anything baked
+    /// from them can tell whether it still matches
+    pub fn generation_fingerprint(&self) -> u64 {
+        let shaping = (
+            self.seed,
+            self.lacunarity,
+            self.persistence,
+            self.octaves,
+            self.height_scale,
+            self.sea_level,
+            self.scale,
+            &self.terrain_thresholds,
+            &self.biomes,
+            &self.erosion,
+        );
+        let mut hasher = DefaultHasher::new();
+        format!("{:?}", shaping).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Sets how far out the terrain is drawn, simplified and shaded, for the quality presets
+    pub fn set_quality(
+        &mut self,
+        view_distance: f32,
+        lod_split_distance: f32,
+        bake_shadows: bool,
+        shadow_bake_distance: f32,
+    ) {
+        // the unload radius keeps the same margin past the view distance
+        self.unload_radius += view_distance - self.max_view_distance;
+        self.max_view_distance = view_distance;
+        self.lod.split_distance = lod_split_distance;
+        self.bake_shadows = bake_shadows;
+        self.shadow_bake_distance = shadow_bake_distance;
+    }
+}
+
+/// The seed given with `--seed <seed>` to the tools run from the command line, or the
+/// default one
+pub fn seed_argument() -> u32 {
+    let mut args = env::args().skip_while(|arg| arg != "--seed");
+    args.next();
+    args.next()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| Config::default().seed)
 }
 
-#[derive(Inspectable, Clone, Copy, Debug)]
+/// Reads a comma separated list of seeds, where each entry is a seed or an inclusive range
+/// like `1-8`
+pub fn parse_seeds(list: &str) -> Option<Vec<u32>> {
+    let mut seeds = Vec::new();
+    for entry in list.split(',') {
+        match entry.split_once('-') {
+            Some((first, last)) => {
+                seeds.extend(first.trim().parse::<u32>().ok()?..=last.trim().parse().ok()?)
+            }
+            None => seeds.push(entry.trim().parse().ok()?),
+        }
+    }
+    if seeds.is_empty() {
+        None
+    } else {
+        Some(seeds)
+    }
+}
+
+/// Colours the terrain can be drawn in, the false colour ones being for checking generation
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ColorMode {
+    Terrain,
+    // steepness, from green on the flat to red on cliffs
+    Slope,
+    // which way the ground faces downhill, as a hue around the colour wheel
+    Aspect,
+    // tenths of the normalized height, each in its own colour
+    Altitude,
+    // which terrain threshold, or biome, each cell falls into
+    Biome,
+}
+
+impl ColorMode {
+    pub fn next(self) -> ColorMode {
+        match self {
+            ColorMode::Terrain => ColorMode::Slope,
+            ColorMode::Slope => ColorMode::Aspect,
+            ColorMode::Aspect => ColorMode::Altitude,
+            ColorMode::Altitude => ColorMode::Biome,
+            ColorMode::Biome => ColorMode::Terrain,
+        }
+    }
+}
+
+/// The kind of ground each of the terrain thresholds stands for, from the lowest up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Water,
+    Sand,
+    Lowland,
+    Forest,
+    Rock,
+    Snow,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 6] = [
+        Biome::Water,
+        Biome::Sand,
+        Biome::Lowland,
+        Biome::Forest,
+        Biome::Rock,
+        Biome::Snow,
+    ];
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 struct TerrainThreshold {
-    #[inspectable(min = 0.0, max = 1.1)]
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.1))]
     max_height: f32,
     color: Color,
 }
 
-#[derive(Inspectable, Clone, Copy, Debug)]
-struct SimplificationThreshold {
-    max_distance: f32,
-    level: SimplificationLevel,
-}
-
-#[derive(
-    Inspectable, PartialEq, From, Add, Mul, Into, Deref, Clone, Copy, Debug, Eq, Hash, Default,
-)]
-pub struct SimplificationLevel(#[inspectable(min = 1, max = 6)] u32);
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(PartialEq, From, Add, Mul, Into, Deref, Clone, Copy, Debug, Eq, Hash, Default)]
+pub struct SimplificationLevel(
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1, max = 6))] u32,
+);
 
 impl SimplificationLevel {
     pub fn min() -> Self {
@@ -125,9 +404,56 @@ pub struct Terrain;
 
 impl Plugin for Terrain {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_plugin(InspectorPlugin::<Config>::new())
+        app.add_settings::<Config>(SettingsTab::World, "Terrain")
+            .add_settings::<debug::TerrainDebugConfig>(SettingsTab::Debug, "Terrain debug")
+            .add_settings::<water::WaterConfig>(SettingsTab::World, "Water")
+            .init_resource::<GenerationPipeline>()
+            .init_resource::<debug::TargetedChunk>()
+            .init_resource::<diff::ConfigDiff>()
+            .init_resource::<overview::Overview>()
+            .init_resource::<validate::ConfigProblems>()
+            .init_resource::<TerrainEdits>()
+            .add_system_to_stage(CoreStage::PreUpdate, validate::validate_config.system())
+            .add_system_to_stage(CoreStage::PreUpdate, edits::load_for_seed.system())
+            .add_system(validate::problems_panel.system())
+            .add_system(texture::cycle_color_mode.system())
+            .add_system(shadows::rebake_distant_chunks.system().after("sky::sun"))
             .add_event::<endless::StartChunkUpdateEvent>()
+            .add_event::<endless::ChunkSpawnedEvent>()
+            .add_event::<ChunkGenerationFailed>()
+            .init_resource::<failure::ChunkFailures>()
+            .add_system(failure::retry_failed_chunks.system())
+            .add_system(failure::record_failures.system())
+            .add_system(failure::failures_panel.system())
+            .add_startup_system(config_file::load_on_startup.system())
+            .add_system(config_file::save_on_key.system())
             .add_startup_system(endless::setup.system())
+            .add_startup_system(debug::setup.system())
+            .add_startup_system(overview::setup.system())
+            .add_startup_system(water::setup.system())
+            .add_system(water::spawn_planes.system())
+            .add_system(water::update_planes.system())
+            .add_system(water::buoyancy.system())
+            .add_asset::<material::SplatMaterial>()
+            .add_startup_system(material::setup.system())
+            .add_system(material::load_layers.system())
+            .add_system(material::repeat_layers.system())
+            .add_system(material::attach.system().after("endless::insert_chunks"))
+            .add_system(material::follow_lighting.system())
+            .add_system(overview::toggle.system().label("overview::toggle"))
+            .add_system(overview::follow_player.system().after("overview::toggle"))
+            .add_system(debug::target_chunk.system().label("debug::target_chunk"))
+            .add_system(debug::draw_lines.system().after("debug::target_chunk"))
+            .add_system(
+                debug::height_stats_panel
+                    .system()
+                    .after("debug::target_chunk"),
+            )
+            .add_system(
+                diff::config_diff_panel
+                    .system()
+                    .after("debug::target_chunk"),
+            )
             .add_system(
                 endless::trigger_update
                     .system()
@@ -136,6 +462,7 @@ impl Plugin for Terrain {
             .add_system(
                 endless::initialize_chunks
                     .system()
+                    .label("endless::initialize_chunks")
                     .before("endless::compute_chunk_visibility")
                     .after("endless::trigger_update"),
             )
@@ -144,9 +471,27 @@ impl Plugin for Terrain {
                     .system()
                     .before("endless::compute_chunk_visibility"),
             )
+            .add_system(
+                endless::patch_edited
+                    .system()
+                    .label("endless::patch_edited")
+                    .before("endless::compute_chunk_visibility"),
+            )
+            .add_system(
+                endless::regenerate_edited
+                    .system()
+                    .after("endless::patch_edited")
+                    .before("endless::compute_chunk_visibility"),
+            )
+            .add_system(
+                endless::retire_replaced_chunks
+                    .system()
+                    .after("endless::initialize_chunks"),
+            )
             .add_system(
                 endless::insert_chunks
                     .system()
+                    .label("endless::insert_chunks")
                     .before("endless::compute_chunk_visibility"),
             )
             .add_system(
@@ -160,5 +505,8 @@ impl Plugin for Terrain {
                     .system()
                     .after("endless::compute_chunk_visibility"),
             );
+
+        water::add_water_graph(app.world_mut());
+        material::add_splat_graph(app.world_mut());
     }
 }
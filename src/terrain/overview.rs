@@ -0,0 +1,182 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{
+    log::info,
+    math::Vec3Swizzles,
+    prelude::*,
+    render::{
+        camera::{ActiveCameras, Camera, CameraProjection, OrthographicProjection},
+        mesh::Indices,
+        pipeline::PrimitiveTopology,
+        render_graph::base::camera::CAMERA_3D,
+    },
+};
+
+use super::{kept_chunk_radius, ChunkCoords, Config, CHUNK_SIZE};
+use crate::{first_person::PlayerEyes, Player};
+
+const OVERVIEW_KEY: KeyCode = KeyCode::F8;
+// high enough above the player to be over any mountain, with the far plane past the sea floor
+const CAMERA_HEIGHT: f32 = 2000.0;
+const FAR: f32 = 4000.0;
+// the grid floats just under the camera, so the terrain never covers it
+const GRID_DEPTH: f32 = 10.0;
+
+/// Whether the main view is looking straight down on the chunks around the player rather
+/// than out of the player's eyes
+#[derive(Default)]
+pub struct Overview {
+    pub active: bool,
+    camera: Option<Entity>,
+    grid: Option<Entity>,
+    // the chunk the grid was last laid out around, and how far out it reaches
+    drawn: Option<(ChunkCoords, i32)>,
+}
+
+// Marks the lines drawn along the chunk borders
+struct OverviewGrid;
+
+pub fn setup(
+    mut commands: Commands,
+    mut overview: ResMut<Overview>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // facing straight down with north at the top of the window, sized by the camera system
+    let mut camera = OrthographicCameraBundle::new_3d();
+    camera.camera.name = None;
+    camera.orthographic_projection.far = FAR;
+    camera.transform.rotation = Quat::from_rotation_x(-FRAC_PI_2);
+    overview.camera = Some(commands.spawn_bundle(camera).id());
+
+    overview.grid = Some(
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(Mesh::new(PrimitiveTopology::LineList)),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::rgb(1.0, 0.9, 0.2),
+                    unlit: true,
+                    ..Default::default()
+                }),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: false,
+                },
+                ..Default::default()
+            })
+            .insert(OverviewGrid)
+            .id(),
+    );
+}
+
+// Hands the main pass between the player's eyes and the overview camera
+pub fn toggle(
+    keys: Res<Input<KeyCode>>,
+    mut overview: ResMut<Overview>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    mut grid_query: Query<&mut Visible, With<OverviewGrid>>,
+    eyes_query: Query<Entity, With<PlayerEyes>>,
+) {
+    if !keys.just_pressed(OVERVIEW_KEY) {
+        return;
+    }
+    overview.active = !overview.active;
+    overview.drawn = None;
+    info!(
+        "Terrain overview: {}",
+        if overview.active { "on" } else { "off" }
+    );
+
+    let camera = if overview.active {
+        overview.camera
+    } else {
+        eyes_query.iter().next()
+    };
+    if let Some(active_camera) = active_cameras.get_mut(CAMERA_3D) {
+        active_camera.entity = camera;
+    }
+    for mut visible in grid_query.iter_mut() {
+        visible.is_visible = overview.active;
+    }
+}
+
+// Keeps the overview centred over the player, zoomed out to fit every chunk kept loaded, and
+// lays the grid out again whenever the player crosses into another chunk
+pub fn follow_player(
+    config: Res<Config>,
+    mut overview: ResMut<Overview>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut camera_query: Query<
+        (&mut Transform, &mut Camera, &mut OrthographicProjection),
+        Without<Player>,
+    >,
+    mut grid_query: Query<
+        (&Handle<Mesh>, &mut Transform),
+        (With<OverviewGrid>, Without<Camera>, Without<Player>),
+    >,
+) {
+    if !overview.active {
+        return;
+    }
+    let player = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+    let radius = kept_chunk_radius(&config);
+    let camera_height = player.y + CAMERA_HEIGHT;
+
+    if let Some(Ok((mut transform, mut camera, mut projection))) =
+        overview.camera.map(|entity| camera_query.get_mut(entity))
+    {
+        transform.translation = Vec3::new(player.x, camera_height, player.z);
+        // half the window's height covers the kept chunks on one side, plus the player's own
+        let extent = (radius as f32 + 0.5) * CHUNK_SIZE as f32;
+        if (projection.scale - extent).abs() > f32::EPSILON {
+            projection.scale = extent;
+            // the camera only rebuilds its projection when the window changes, so do it here
+            camera.projection_matrix = projection.get_projection_matrix();
+        }
+    }
+
+    let centre = ChunkCoords::containing(player.xz());
+    let (mesh, mut transform) = match overview.grid.map(|entity| grid_query.get_mut(entity)) {
+        Some(Ok(grid)) => grid,
+        _ => return,
+    };
+    transform.translation.y = camera_height - GRID_DEPTH;
+    if overview.drawn == Some((centre, radius)) {
+        return;
+    }
+    overview.drawn = Some((centre, radius));
+    if let Some(mesh) = meshes.get_mut(mesh) {
+        let positions = grid_positions(centre, radius);
+        let indices = (0..positions.len() as u32).collect();
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            vec![[0.0, 1.0, 0.0]; positions.len()],
+        );
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_indices(Some(Indices::U32(indices)));
+    }
+}
+
+// A line along each chunk border, running both ways across the kept chunks. Chunk meshes are
+// centred on their position, so the borders fall halfway between chunks.
+fn grid_positions(centre: ChunkCoords, radius: i32) -> Vec<[f32; 3]> {
+    let size = CHUNK_SIZE as f32;
+    let origin = centre.to_position();
+    let reach = (radius as f32 + 0.5) * size;
+    (-radius..=radius + 1)
+        .flat_map(|i| {
+            let offset = (i as f32 - 0.5) * size;
+            vec![
+                [origin.x + offset, 0.0, origin.y - reach],
+                [origin.x + offset, 0.0, origin.y + reach],
+                [origin.x - reach, 0.0, origin.y + offset],
+                [origin.x + reach, 0.0, origin.y + offset],
+            ]
+        })
+        .collect()
+}
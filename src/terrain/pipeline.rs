@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use bevy::log::{info_span, warn};
+
+use super::{endless::ChunkCoords, erosion::ErosionStage, height_map::HeightMap, Config};
+use crate::bake::BakedArchive;
+
+/// One step in building a chunk's height map, such as laying down noise or eroding it.
+///
+/// Point queries in `query` only sample the noise, so stages that reshape the terrain
+/// afterwards won't be seen by them.
+pub trait GenerationStage: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    fn apply(&self, config: &Config, coords: ChunkCoords, height_map: &mut HeightMap);
+}
+
+/// The stages each chunk's height map passes through, in order, before it's meshed and
+/// coloured in. The distant quadtree nodes only take the noise, normalized.
+#[derive(Clone)]
+pub struct GenerationPipeline {
+    stages: Vec<Arc<dyn GenerationStage>>,
+}
+
+impl Default for GenerationPipeline {
+    fn default() -> Self {
+        let mut pipeline = GenerationPipeline { stages: Vec::new() };
+        pipeline
+            .add_stage(NoiseStage)
+            .add_stage(NormalizeStage)
+            .add_stage(ErosionStage);
+        pipeline
+    }
+}
+
+impl GenerationPipeline {
+    pub fn add_stage(&mut self, stage: impl GenerationStage) -> &mut Self {
+        self.stages.push(Arc::new(stage));
+        self
+    }
+
+    /// Slots a stage in before the named one, or at the end if there's no stage by that name
+    pub fn add_stage_before(&mut self, before: &str, stage: impl GenerationStage) -> &mut Self {
+        match self.stages.iter().position(|stage| stage.name() == before) {
+            Some(index) => self.stages.insert(index, Arc::new(stage)),
+            None => {
+                warn!("No terrain generation stage named {}", before);
+                self.stages.push(Arc::new(stage));
+            }
+        }
+        self
+    }
+
+    pub fn run(&self, config: &Config, coords: ChunkCoords) -> HeightMap {
+        let mut height_map = HeightMap::empty();
+        for stage in self.stages.iter() {
+            info_span!("generation_stage", stage = stage.name())
+                .in_scope(|| stage.apply(config, coords, &mut height_map));
+        }
+        height_map
+    }
+
+    /// A chunk's height map as the stages shape it, read from the archive instead if it was
+    /// baked there for the same settings
+    pub fn chunk_height_map(
+        &self,
+        config: &Config,
+        coords: ChunkCoords,
+        baked: Option<&BakedArchive>,
+    ) -> HeightMap {
+        baked
+            .and_then(|archive| archive.height_map(config, coords))
+            .unwrap_or_else(|| self.run(config, coords))
+    }
+}
+
+/// Fills the height map with layered perlin noise
+pub struct NoiseStage;
+
+impl GenerationStage for NoiseStage {
+    fn name(&self) -> &'static str {
+        "noise"
+    }
+
+    fn apply(&self, config: &Config, coords: ChunkCoords, height_map: &mut HeightMap) {
+        *height_map = HeightMap::generate_noise(config, &coords);
+    }
+}
+
+/// Squashes the raw noise heights into 0 to 1, the same way across every chunk
+pub struct NormalizeStage;
+
+impl GenerationStage for NormalizeStage {
+    fn name(&self) -> &'static str {
+        "normalize"
+    }
+
+    fn apply(&self, config: &Config, _coords: ChunkCoords, height_map: &mut HeightMap) {
+        height_map.normalize(config);
+    }
+}
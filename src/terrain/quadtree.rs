@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+
+use bevy::math::Vec2;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+use super::{ChunkCoords, Config, SimplificationLevel, CHUNK_SIZE};
+
+// above this speed the nodes ahead of the player start splitting early
+const PRELOAD_MIN_SPEED: f32 = 30.0;
+// how closely a node has to line up with the direction of travel to split early
+const PRELOAD_CONE: f32 = 0.7;
+
+/// How the terrain's level of detail falls away from the player. The world is split into
+/// large square nodes, each halving into four smaller ones as the player gets closer, down to
+/// single chunks.
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LodConfig {
+    // a node splits into four once the player's closer to its middle than this many of its
+    // own widths, so higher keeps more detail further out
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0, max = 4.0))]
+    pub(super) split_distance: f32,
+    // and only joins back up once the player's this much further out again, so nodes on the
+    // line don't split and join over and over
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub(super) hysteresis: f32,
+    // how many times the largest nodes halve before they're single chunks
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1, max = 8))]
+    pub(super) max_depth: u32,
+}
+
+impl Default for LodConfig {
+    fn default() -> Self {
+        Self {
+            split_distance: 2.0,
+            hysteresis: 0.25,
+            max_depth: 5,
+        }
+    }
+}
+
+/// A square of the world covering `span` by `span` chunks, starting from the chunk at its
+/// coordinates. Nodes at depth 0 are single chunks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub coords: ChunkCoords,
+    pub depth: u32,
+}
+
+impl Node {
+    pub fn span(&self) -> i32 {
+        1 << self.depth
+    }
+
+    pub fn width(&self) -> f32 {
+        (self.span() * CHUNK_SIZE as i32) as f32
+    }
+
+    /// The middle of the node in the world, chunks being centred on their position
+    pub fn centre(&self) -> Vec2 {
+        self.coords.to_position() + Vec2::splat((self.span() - 1) as f32 * CHUNK_SIZE as f32 / 2.0)
+    }
+
+    /// Whether any chunk falls inside both nodes
+    pub fn overlaps(&self, other: &Node) -> bool {
+        let overlap = |a: i32, a_span: i32, b: i32, b_span: i32| a < b + b_span && b < a + a_span;
+        overlap(self.coords.x, self.span(), other.coords.x, other.span())
+            && overlap(self.coords.y, self.span(), other.coords.y, other.span())
+    }
+
+    fn children(&self) -> [Node; 4] {
+        let half = self.span() / 2;
+        let child = |x: i32, y: i32| Node {
+            coords: ChunkCoords {
+                x: self.coords.x + x,
+                y: self.coords.y + y,
+            },
+            depth: self.depth - 1,
+        };
+        [
+            child(0, 0),
+            child(half, 0),
+            child(0, half),
+            child(half, half),
+        ]
+    }
+
+    // How far the player is from the nearest point of the node
+    fn gap(&self, viewer: Vec2) -> f32 {
+        let half = Vec2::splat(self.width() / 2.0);
+        ((viewer - self.centre()).abs() - half)
+            .max(Vec2::ZERO)
+            .length()
+    }
+}
+
+/// The nodes split on the last update, which need the player to move further away before
+/// they join back up, and those picked, which stay until they're past the unload radius
+#[derive(Default)]
+pub struct LodTree {
+    split: HashSet<Node>,
+    selected: HashSet<Node>,
+}
+
+impl LodTree {
+    pub fn clear(&mut self) {
+        self.split.clear();
+        self.selected.clear();
+    }
+}
+
+/// The chunks and larger nodes that cover the view around the player
+#[derive(Default)]
+pub struct Selection {
+    // each chunk along with the level it's meshed at
+    pub chunks: Vec<(ChunkCoords, SimplificationLevel)>,
+    // the nodes still too far away to split down to chunks
+    pub nodes: Vec<Node>,
+}
+
+/// Walks down from the largest nodes within the view distance, splitting every node the
+/// player is close enough to. Anything already loaded is kept out to the unload radius.
+/// While the player's travelling fast, the nodes ahead split a chunk further out so their
+/// detail is in by the time the player gets there.
+pub fn select(config: &Config, tree: &mut LodTree, viewer: Vec2, travel: Vec2) -> Selection {
+    let lod = &config.lod;
+    let root_span = 1 << lod.max_depth;
+    let view = config.max_view_distance;
+    let unload = config.unload_radius.max(view);
+    // every root any part of that falls into, the roots lining up on multiples of their span
+    let first = ChunkCoords::containing(viewer - Vec2::splat(unload));
+    let last = ChunkCoords::containing(viewer + Vec2::splat(unload));
+
+    let mut selection = Selection::default();
+    let mut split = HashSet::new();
+    let mut selected = HashSet::new();
+    let mut pending = Vec::new();
+    for y in first.y.div_euclid(root_span)..=last.y.div_euclid(root_span) {
+        for x in first.x.div_euclid(root_span)..=last.x.div_euclid(root_span) {
+            pending.push(Node {
+                coords: ChunkCoords {
+                    x: x * root_span,
+                    y: y * root_span,
+                },
+                depth: lod.max_depth,
+            });
+        }
+    }
+
+    while let Some(node) = pending.pop() {
+        let loaded = tree.split.contains(&node) || tree.selected.contains(&node);
+        let keep_within = if loaded { unload } else { view };
+        if node.gap(viewer) > keep_within {
+            continue;
+        }
+        let mut reach = node.width() * lod.split_distance;
+        if tree.split.contains(&node) {
+            reach *= 1.0 + lod.hysteresis;
+        }
+        if is_ahead(&node, viewer, travel) {
+            reach += CHUNK_SIZE as f32;
+        }
+        let near = node.centre().distance(viewer) < reach;
+
+        if node.depth > 0 && near {
+            split.insert(node);
+            pending.extend(node.children().iter());
+        } else if node.depth > 0 {
+            selected.insert(node);
+            selection.nodes.push(node);
+        } else {
+            selected.insert(node);
+            selection.chunks.push((
+                node.coords,
+                chunk_level(lod, node.centre().distance(viewer)),
+            ));
+        }
+    }
+
+    tree.split = split;
+    tree.selected = selected;
+    selection
+}
+
+fn is_ahead(node: &Node, viewer: Vec2, travel: Vec2) -> bool {
+    if travel.length() < PRELOAD_MIN_SPEED {
+        return false;
+    }
+    let direction = (node.centre() - viewer).normalize_or_zero();
+    direction.dot(travel.normalize()) > PRELOAD_CONE
+}
+
+// Chunks carry the halving on in their own vertices: the closest have every other cell, and
+// those out where their parents would have stayed whole have every fourth, as spaced out as
+// the smallest nodes' vertices
+fn chunk_level(lod: &LodConfig, distance: f32) -> SimplificationLevel {
+    if distance < CHUNK_SIZE as f32 * lod.split_distance {
+        SimplificationLevel::min()
+    } else {
+        SimplificationLevel(2)
+    }
+}
+
+/// How many chunks out from the player's chunk there can be, with the hysteresis holding
+/// splits open and one more ahead of fast travel
+pub fn chunk_radius(config: &Config) -> i32 {
+    let lod = &config.lod;
+    let reach = 2.0 * lod.split_distance * (1.0 + lod.hysteresis) + 1.0;
+    reach.ceil() as i32 + 1
+}
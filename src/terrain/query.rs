@@ -0,0 +1,76 @@
+use bevy::{
+    math::{Quat, Vec2, Vec3},
+    transform::components::Transform,
+};
+
+use super::{endless::CHUNK_SIZE, height_map::HeightMap, Biome, Config};
+
+// distance between the samples used to estimate the surface normal
+const NORMAL_SAMPLE_OFFSET: f32 = 1.0;
+
+/// World-space height of the terrain surface at a point on the xz plane
+pub fn height_at(config: &Config, position: Vec2) -> f32 {
+    // chunk meshes are centred on their chunk position, so shift the point back
+    // into the height map's grid space
+    let grid_point = position + Vec2::splat(CHUNK_SIZE as f32 / 2.0);
+    HeightMap::sample(config, grid_point) * config.height_scale
+}
+
+/// The biome of the terrain at a point on the xz plane
+pub fn biome_at(config: &Config, position: Vec2) -> Biome {
+    let grid_point = position + Vec2::splat(CHUNK_SIZE as f32 / 2.0);
+    config.biome(HeightMap::sample(config, grid_point), grid_point)
+}
+
+/// Approximate surface normal of the terrain at a point on the xz plane
+pub fn normal_at(config: &Config, position: Vec2) -> Vec3 {
+    let offset = NORMAL_SAMPLE_OFFSET;
+    let left = height_at(config, position - Vec2::new(offset, 0.0));
+    let right = height_at(config, position + Vec2::new(offset, 0.0));
+    let back = height_at(config, position - Vec2::new(0.0, offset));
+    let front = height_at(config, position + Vec2::new(0.0, offset));
+
+    Vec3::new(left - right, 2.0 * offset, back - front).normalize()
+}
+
+/// Stands something on the terrain at a point on the xz plane, tilted to match the ground
+/// averaged across a footprint of the given radius
+pub fn place_on_surface(config: &Config, position: Vec2, footprint: f32) -> Transform {
+    let samples = [Vec2::ZERO, Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y];
+    let normal = samples
+        .iter()
+        .map(|&offset| normal_at(config, position + offset * footprint))
+        .fold(Vec3::ZERO, |sum, normal| sum + normal)
+        .normalize();
+
+    Transform {
+        translation: Vec3::new(position.x, height_at(config, position), position.y),
+        rotation: Quat::from_rotation_arc(Vec3::Y, normal),
+        ..Default::default()
+    }
+}
+
+/// Searches outwards in rings from a point for the closest spot that sits above the water
+pub fn nearest_land(config: &Config, from: Vec2, max_radius: f32) -> Option<Vec2> {
+    const RING_STEP: f32 = 8.0;
+    const DIRECTIONS: usize = 16;
+
+    let water_height = config.sea_level();
+    if height_at(config, from) > water_height {
+        return Some(from);
+    }
+
+    let mut radius = RING_STEP;
+    while radius <= max_radius {
+        for i in 0..DIRECTIONS {
+            let angle = i as f32 / DIRECTIONS as f32 * std::f32::consts::PI * 2.0;
+            let point = from + Vec2::new(angle.cos(), angle.sin()) * radius;
+            if height_at(config, point) > water_height {
+                return Some(point);
+            }
+        }
+        radius += RING_STEP;
+    }
+
+    None
+}
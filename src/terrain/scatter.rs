@@ -0,0 +1,40 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use bevy::math::{Vec2, Vec3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    endless::{ChunkCoords, CHUNK_SIZE},
+    query, Config,
+};
+
+/// Deterministically picks `count` points on the terrain surface within a chunk.
+///
+/// The same seed, chunk and `salt` always give the same points, so each kind of
+/// scattered object should use its own salt to avoid landing on top of the others.
+pub fn scatter_points(config: &Config, coords: ChunkCoords, salt: u64, count: usize) -> Vec<Vec3> {
+    let mut rng = chunk_rng(config, coords, salt);
+    let centre = coords.to_position();
+    let half_size = CHUNK_SIZE as f32 / 2.0;
+
+    (0..count)
+        .map(|_| {
+            let point = centre
+                + Vec2::new(
+                    rng.gen_range(-half_size..half_size),
+                    rng.gen_range(-half_size..half_size),
+                );
+            Vec3::new(point.x, query::height_at(config, point), point.y)
+        })
+        .collect()
+}
+
+/// A random number generator seeded from the world seed and chunk coordinates
+pub fn chunk_rng(config: &Config, coords: ChunkCoords, salt: u64) -> StdRng {
+    let mut hasher = DefaultHasher::new();
+    (config.seed, coords.x, coords.y, salt).hash(&mut hasher);
+    StdRng::seed_from_u64(hasher.finish())
+}
@@ -0,0 +1,126 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_rapier3d::physics::ColliderBundle;
+
+use super::{
+    endless::{Chunk, Processing},
+    height_map::HeightMap,
+    Config,
+};
+use crate::{sky::Sun, Player};
+
+// how far across the chunk, in cells, to look for anything standing between a cell and the sun
+const MAX_SHADOW_STEPS: usize = 96;
+// light still reaching the ground in shadow and on slopes facing away from the sun
+const SHADOW_AMBIENT: f32 = 0.45;
+
+/// The sun to bake into a chunk's texture, if it's far enough away to need it
+pub fn sun_for_chunk(
+    config: &Config,
+    sun: &Sun,
+    chunk_position: Vec2,
+    viewer: Vec2,
+) -> Option<Vec3> {
+    let far = chunk_position.distance(viewer) >= config.shadow_bake_distance;
+    (config.bake_shadows && far && sun.elevation() > 0.0).then(|| sun.direction)
+}
+
+/// Darkens every cell by how much it faces away from the sun, and further for anything in
+/// the chunk between it and the sun. The cells are `spacing` apart in the world.
+pub fn bake(
+    colors: &mut [Color],
+    height_map: &HeightMap,
+    config: &Config,
+    sun: Vec3,
+    spacing: f32,
+) {
+    let size = height_map.size;
+    let height = |x: usize, y: usize| height_map.data[y][x] * config.height_scale;
+    let across = sun.xz().normalize_or_zero();
+    // how much the ray towards the sun climbs for every cell it crosses
+    let rise = sun.y / sun.xz().length().max(f32::EPSILON) * spacing;
+
+    for y in 0..size {
+        for x in 0..size {
+            let start = height(x, y);
+            let shadowed = across != Vec2::ZERO
+                && (1..MAX_SHADOW_STEPS).any(|step| {
+                    let point = Vec2::new(x as f32, y as f32) + across * step as f32;
+                    if point.x < 0.0
+                        || point.y < 0.0
+                        || point.x > (size - 1) as f32
+                        || point.y > (size - 1) as f32
+                    {
+                        return false;
+                    }
+                    height(point.x.round() as usize, point.y.round() as usize)
+                        > start + rise * step as f32
+                });
+
+            let normal = surface_normal(height_map, x, y, config, spacing);
+            let lit = if shadowed {
+                0.0
+            } else {
+                normal.dot(sun).max(0.0)
+            };
+            let light = SHADOW_AMBIENT + (1.0 - SHADOW_AMBIENT) * lit;
+
+            let color = &mut colors[y * size + x];
+            *color = Color::rgb(color.r() * light, color.g() * light, color.b() * light);
+        }
+    }
+}
+
+fn surface_normal(
+    height_map: &HeightMap,
+    x: usize,
+    y: usize,
+    config: &Config,
+    spacing: f32,
+) -> Vec3 {
+    let last = height_map.size - 1;
+    let height = |x: usize, y: usize| height_map.data[y][x] * config.height_scale;
+    let (left, right) = (x.saturating_sub(1), (x + 1).min(last));
+    let (up, down) = (y.saturating_sub(1), (y + 1).min(last));
+    let dx = (height(right, y) - height(left, y)) / ((right - left) as f32 * spacing);
+    let dz = (height(x, down) - height(x, up)) / ((down - up) as f32 * spacing);
+
+    Vec3::new(-dx, 1.0, -dz).normalize()
+}
+
+// Once the sun has moved far enough, sends the distant chunks back to have their shadows baked
+// again. The chunks up close aren't shaded at all, so are left alone.
+pub fn rebake_distant_chunks(
+    mut commands: Commands,
+    config: Res<Config>,
+    sun: Res<Sun>,
+    mut baked_for: Local<Option<Vec3>>,
+    player_query: Query<&Transform, With<Player>>,
+    chunks_query: Query<(Entity, &Chunk), Without<Processing>>,
+) {
+    let viewer = match player_query.iter().next() {
+        Some(transform) => transform.translation.xz(),
+        None => return,
+    };
+
+    let current = (config.bake_shadows && sun.elevation() > 0.0).then(|| sun.direction);
+    let moved = match (*baked_for, current) {
+        (Some(baked), Some(current)) => {
+            baked.angle_between(current).to_degrees() > config.shadow_rebake_degrees
+        }
+        (baked, current) => baked.is_some() != current.is_some(),
+    };
+    if !moved {
+        return;
+    }
+    *baked_for = current;
+
+    for (entity, chunk) in chunks_query.iter() {
+        let position = chunk.coords().to_position();
+        if chunk.baked_sun() != sun_for_chunk(&config, &sun, position, viewer) {
+            commands
+                .entity(entity)
+                .remove_bundle::<ColliderBundle>()
+                .insert(Processing);
+        }
+    }
+}
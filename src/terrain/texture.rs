@@ -3,30 +3,261 @@ use bevy::{
     render::texture::{Extent3d, TextureDimension, TextureFormat},
 };
 
-use super::{height_map::HeightMap, Config};
+use super::{
+    biome::{Climate, Region},
+    edits,
+    height_map::{GridArea, HeightMap},
+    hydrology, shadows, Biome, ColorMode, Config, TerrainThreshold,
+};
+
+const COLOR_MODE_KEY: KeyCode = KeyCode::F7;
+// slopes this steep or more are drawn fully red
+const MAX_SLOPE_DEGREES: f32 = 60.0;
+const ALTITUDE_BANDS: f32 = 10.0;
+
+// Changing the config has every chunk coloured in again
+pub fn cycle_color_mode(keys: Res<Input<KeyCode>>, mut config: ResMut<Config>) {
+    if keys.just_pressed(COLOR_MODE_KEY) {
+        config.color_mode = config.color_mode.next();
+        info!("Terrain colour mode: {:?}", config.color_mode);
+    }
+}
 
-pub fn generate(height_map: &HeightMap, config: &Config) -> Texture {
-    let color_map = generate_color_map(height_map, config);
+/// Colours in a chunk or distant node, shaded for the sun when it's given, with the terrain
+/// types painted onto a chunk in place of the colours its heights give
+pub fn generate(
+    height_map: &HeightMap,
+    config: &Config,
+    area: GridArea,
+    sun: Option<Vec3>,
+    paint: Option<&[u8]>,
+) -> Texture {
+    let mut color_map = generate_color_map(height_map, config, area, paint);
+    if config.contour_interval > 0.0 {
+        draw_contours(&mut color_map, height_map, config);
+    }
+    if config.flow_overlay {
+        draw_flow(&mut color_map, height_map, config);
+    }
+    if let Some(sun) = sun {
+        shadows::bake(&mut color_map.colors, height_map, config, sun, area.spacing);
+    }
     return generate_texture(&color_map);
 }
 
-fn generate_color_map(height_map: &HeightMap, config: &Config) -> ColorMap {
+fn generate_color_map(
+    height_map: &HeightMap,
+    config: &Config,
+    area: GridArea,
+    paint: Option<&[u8]>,
+) -> ColorMap {
+    let climate = Climate::new(config.seed);
     let mut color_map = ColorMap::new((height_map.size, height_map.size));
     for y in 0..height_map.size {
         for x in 0..height_map.size {
             let height = height_map.data[y][x];
+            let point = area.point(x, y);
+            if config.color_mode != ColorMode::Terrain {
+                color_map
+                    .colors
+                    .push(false_color(height_map, x, y, config, &climate, area));
+                continue;
+            }
 
-            for terrain in config.terrain_thresholds.iter() {
-                if height < terrain.max_height {
-                    color_map.colors.push(terrain.color);
-                    break;
-                }
+            let painted =
+                paint.and_then(|paint| edits::painted_threshold(paint, y * height_map.size + x));
+            let rockiness = if config.rock_slope_degrees > 0.0 && painted.is_none() {
+                let slope = gradient(height_map, x, y, config, area.spacing);
+                let degrees = slope.length().atan().to_degrees();
+                smoothstep((degrees - config.rock_slope_degrees) / config.rock_blend_degrees)
+            } else {
+                0.0
+            };
+            if !config.biomes.enabled {
+                color_map.colors.push(threshold_color(
+                    height,
+                    &config.terrain_thresholds,
+                    config,
+                    painted,
+                    rockiness,
+                ));
+                continue;
+            }
+            // each region's colours are mixed in by how much it shapes the cell, so the
+            // regions fade into each other rather than meeting at a line
+            let regions = climate.weights(&config.biomes, point);
+            let mut color = Vec4::ZERO;
+            for (&region, &weight) in Region::ALL.iter().zip(regions.0.iter()) {
+                let thresholds = &config.biomes.params(region).terrain_thresholds;
+                let region_color = threshold_color(height, thresholds, config, painted, rockiness);
+                color += Vec4::from(region_color) * weight;
             }
+            color_map.colors.push(color.into());
         }
     }
     return color_map;
 }
 
+// The colour of the threshold a height falls into, faded into the ones either side of it by
+// the colour blend and towards rock by how steep the ground is, or of the one painted over it
+fn threshold_color(
+    height: f32,
+    thresholds: &[TerrainThreshold; 6],
+    config: &Config,
+    painted: Option<usize>,
+    rockiness: f32,
+) -> Color {
+    if let Some(painted) = painted {
+        return thresholds[painted.min(thresholds.len() - 1)].color;
+    }
+    // how far past a threshold's top the height is, from 0 below the blend to 1 above it
+    let past = |top: f32| {
+        if config.color_blend > 0.0 {
+            smoothstep((height - top + config.color_blend) / (config.color_blend * 2.0))
+        } else if height < top {
+            0.0
+        } else {
+            1.0
+        }
+    };
+
+    // each threshold's colour takes over from the one below as the height climbs past it
+    let mut land = Vec4::from(thresholds[1].color);
+    for pair in thresholds[1..].windows(2) {
+        land = land.lerp(Vec4::from(pair[1].color), past(pair[0].max_height));
+    }
+    let rock = Vec4::from(thresholds[Biome::Rock as usize].color);
+    land = land.lerp(rock, rockiness);
+
+    // the first threshold is the water, which always reaches up to the sea level
+    let water = Vec4::from(thresholds[0].color);
+    water
+        .lerp(land, past(config.sea_level / config.height_scale))
+        .into()
+}
+
+pub(super) fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn false_color(
+    height_map: &HeightMap,
+    x: usize,
+    y: usize,
+    config: &Config,
+    climate: &Climate,
+    area: GridArea,
+) -> Color {
+    let height = height_map.data[y][x];
+    match config.color_mode {
+        ColorMode::Terrain => unreachable!(),
+        ColorMode::Slope => {
+            let gradient = gradient(height_map, x, y, config, area.spacing);
+            let degrees = gradient.length().atan().to_degrees();
+            let steepness = (degrees / MAX_SLOPE_DEGREES).min(1.0);
+            Color::hsl(120.0 * (1.0 - steepness), 0.8, 0.5)
+        }
+        ColorMode::Aspect => {
+            let downhill = -gradient(height_map, x, y, config, area.spacing);
+            if downhill.length() < 0.01 {
+                return Color::GRAY;
+            }
+            let degrees = downhill.y.atan2(downhill.x).to_degrees().rem_euclid(360.0);
+            Color::hsl(degrees, 0.8, 0.5)
+        }
+        ColorMode::Altitude => {
+            let band = (height * ALTITUDE_BANDS).floor().min(ALTITUDE_BANDS - 1.0);
+            // alternate the lightness so neighbouring bands stand apart
+            let lightness = if band as i32 % 2 == 0 { 0.45 } else { 0.6 };
+            Color::hsl(
+                240.0 * (1.0 - band / (ALTITUDE_BANDS - 1.0)),
+                0.8,
+                lightness,
+            )
+        }
+        ColorMode::Biome => {
+            let thresholds = config.thresholds_at(climate, area.point(x, y));
+            let index = config.biome_in(height, thresholds) as usize;
+            let hue = 360.0 * index as f32 / config.terrain_thresholds.len() as f32;
+            Color::hsl(hue, 0.6, 0.5)
+        }
+    }
+}
+
+// How much the world-space height rises per unit along x and y, from the cells either side
+pub(super) fn gradient(
+    height_map: &HeightMap,
+    x: usize,
+    y: usize,
+    config: &Config,
+    spacing: f32,
+) -> Vec2 {
+    let last = height_map.size - 1;
+    let height = |x: usize, y: usize| height_map.data[y][x] * config.height_scale;
+    let (left, right) = (x.saturating_sub(1), (x + 1).min(last));
+    let (up, down) = (y.saturating_sub(1), (y + 1).min(last));
+
+    Vec2::new(
+        (height(right, y) - height(left, y)) / (right - left) as f32,
+        (height(x, down) - height(x, up)) / (down - up) as f32,
+    ) / spacing
+}
+
+// Darkens the cells on the low side of every contour line, leaving the water alone
+fn draw_contours(color_map: &mut ColorMap, height_map: &HeightMap, config: &Config) {
+    let band = |x: usize, y: usize| {
+        (height_map.data[y][x] * config.height_scale / config.contour_interval).floor()
+    };
+    let size = height_map.size;
+
+    for y in 0..size {
+        for x in 0..size {
+            if height_map.data[y][x] * config.height_scale < config.sea_level {
+                continue;
+            }
+            let own_band = band(x, y);
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            let on_line = neighbours
+                .iter()
+                .filter(|&&(nx, ny)| nx < size && ny < size)
+                .any(|&(nx, ny)| band(nx, ny) > own_band);
+
+            if on_line {
+                let color = &mut color_map.colors[y * size + x];
+                *color = Color::rgb(color.r() * 0.5, color.g() * 0.5, color.b() * 0.5);
+            }
+        }
+    }
+}
+
+// Tints the cells a lot of water drains through blue, more strongly the more drains through
+fn draw_flow(color_map: &mut ColorMap, height_map: &HeightMap, config: &Config) {
+    let accumulation = hydrology::flow_accumulation(height_map);
+    let threshold = config.flow_threshold.max(1.0);
+    let most = (height_map.size * height_map.size) as f32;
+    let river = Color::rgb(0.1, 0.3, 1.0);
+
+    for (color, &flow) in color_map.colors.iter_mut().zip(accumulation.iter()) {
+        if flow < threshold {
+            continue;
+        }
+        // flow grows quickly downstream, so blend on a log scale
+        let amount = 0.4 + 0.6 * (flow / threshold).ln() / (most / threshold).ln().max(1.0);
+        let amount = amount.min(1.0);
+        *color = Color::rgb(
+            color.r() + (river.r() - color.r()) * amount,
+            color.g() + (river.g() - color.g()) * amount,
+            color.b() + (river.b() - color.b()) * amount,
+        );
+    }
+}
+
 fn generate_texture(color_map: &ColorMap) -> Texture {
     let mut image_buffer: Vec<u8> = vec![];
 
@@ -59,3 +290,141 @@ impl ColorMap {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf};
+
+    use super::*;
+    use crate::terrain::MAP_CHUNK_SIZE;
+
+    // how far any channel can drift from the reference, for float differences between builds
+    const TOLERANCE: u8 = 2;
+    // cells between the random heights the hills are eased between
+    const CELL: usize = 24;
+
+    // Rolling hills from a seed, from below the sea up past the snow line, with slopes from flat
+    // to cliffs. They're made here rather than with the noise so the references only change
+    // with the colouring.
+    fn height_map(seed: u32) -> HeightMap {
+        let size = MAP_CHUNK_SIZE as usize;
+        let corner = |x: usize, y: usize| {
+            let mut hash =
+                seed ^ (x as u32).wrapping_mul(0x27d4_eb2d) ^ (y as u32).wrapping_mul(0x1656_67b1);
+            hash ^= hash >> 15;
+            hash = hash.wrapping_mul(0x2c1b_3c6d);
+            hash ^= hash >> 12;
+            (hash % 1000) as f32 / 1000.0 * 1.1
+        };
+        let eased = |value: usize| smoothstep((value % CELL) as f32 / CELL as f32);
+        let data = (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| {
+                        let (column, row) = (x / CELL, y / CELL);
+                        let along = |row: usize| {
+                            let (a, b) = (corner(column, row), corner(column + 1, row));
+                            a + (b - a) * eased(x)
+                        };
+                        let (top, bottom) = (along(row), along(row + 1));
+                        top + (bottom - top) * eased(y)
+                    })
+                    .collect()
+            })
+            .collect();
+        HeightMap { data, size }
+    }
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        // the regions are laid out by the noise
+        config.biomes.enabled = false;
+        config
+    }
+
+    // Compares a colour map with the reference of the same name in tests/golden, or writes the
+    // reference with UPDATE_GOLDEN set, once a change to the colours is meant
+    fn assert_matches_golden(name: &str, texture: &Texture) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("color-map-{}.png", name));
+        let (width, height) = (texture.size.width, texture.size.height);
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            image::save_buffer(&path, &texture.data, width, height, image::ColorType::Rgba8)
+                .unwrap();
+            return;
+        }
+
+        let golden = image::open(&path)
+            .unwrap_or_else(|error| panic!("failed to open {:?}: {}", path, error))
+            .to_rgba8();
+        assert_eq!(
+            golden.dimensions(),
+            (width, height),
+            "{} is a different size",
+            name
+        );
+        let mismatched = golden
+            .as_raw()
+            .chunks_exact(4)
+            .zip(texture.data.chunks_exact(4))
+            .enumerate()
+            .filter(|(_, (expected, actual))| {
+                expected
+                    .iter()
+                    .zip(actual.iter())
+                    .any(|(&expected, &actual)| {
+                        expected.max(actual) - expected.min(actual) > TOLERANCE
+                    })
+            })
+            .map(|(index, _)| (index as u32 % width, index as u32 / width))
+            .collect::<Vec<_>>();
+        assert!(
+            mismatched.is_empty(),
+            "{} differs from {:?} at {} cells, the first at {:?}",
+            name,
+            path,
+            mismatched.len(),
+            mismatched.first()
+        );
+    }
+
+    fn color_map(seed: u32, config: &Config, paint: Option<&[u8]>) -> Texture {
+        let area = GridArea {
+            origin: Vec2::ZERO,
+            spacing: 1.0,
+        };
+        generate(&height_map(seed), config, area, None, paint)
+    }
+
+    #[test]
+    fn hard_bands() {
+        assert_matches_golden("hard-bands", &color_map(1, &config(), None));
+    }
+
+    #[test]
+    fn blended() {
+        let mut config = config();
+        config.color_blend = 0.05;
+        assert_matches_golden("blended", &color_map(2, &config, None));
+    }
+
+    #[test]
+    fn rocky_and_painted() {
+        let mut config = config();
+        config.color_blend = 0.02;
+        config.rock_slope_degrees = 55.0;
+        // a band of snow painted down the middle
+        let size = MAP_CHUNK_SIZE as usize;
+        let paint: Vec<u8> = (0..size * size)
+            .map(|index| {
+                if (100..140).contains(&(index % size)) {
+                    Biome::Snow as u8 + 1
+                } else {
+                    0
+                }
+            })
+            .collect();
+        assert_matches_golden("rocky-painted", &color_map(3, &config, Some(&paint)));
+    }
+}
@@ -0,0 +1,164 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext};
+
+use super::{Config, TerrainThreshold};
+use crate::locale::Locale;
+
+// normalized heights never reach above 1, so the last threshold has to clear it
+const TOP_THRESHOLD: f32 = 1.1;
+// the largest nodes are 61km across at this depth
+const MAX_LOD_DEPTH: u32 = 8;
+
+/// What was wrong with the terrain config the last time it was corrected
+#[derive(Default)]
+pub struct ConfigProblems(pub Vec<String>);
+
+impl Config {
+    /// Corrects any settings that would produce broken terrain, describing each fix
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.octaves == 0 {
+            self.octaves = 1;
+            problems.push("Octaves must be at least 1".to_string());
+        }
+        for (name, value) in [
+            ("Scale", &mut self.scale),
+            ("Lacunarity", &mut self.lacunarity),
+            ("Persistence", &mut self.persistence),
+            ("Height scale", &mut self.height_scale),
+        ]
+        .iter_mut()
+        {
+            if value.is_nan() || **value <= 0.0 {
+                **value = 0.0001;
+                problems.push(format!("{} must be above 0", name));
+            }
+        }
+
+        fix_thresholds(&mut self.terrain_thresholds, "Terrain", &mut problems);
+
+        if self.biomes.climate_scale.is_nan() || self.biomes.climate_scale < 1.0 {
+            self.biomes.climate_scale = 1.0;
+            problems.push("Biome climate scale must be at least 1".to_string());
+        }
+        for (name, params) in ["Desert", "Plains", "Mountains"]
+            .iter()
+            .zip(self.biomes.params_mut().iter_mut())
+        {
+            if params.octaves == 0 {
+                params.octaves = 1;
+                problems.push(format!("{} octaves must be at least 1", name));
+            }
+            if params.height_scale.is_nan() || params.height_scale < 0.0 {
+                params.height_scale = 0.0;
+                problems.push(format!("{} height scale can't be below 0", name));
+            }
+            fix_thresholds(&mut params.terrain_thresholds, name, &mut problems);
+        }
+
+        if self.unload_radius.is_nan() || self.unload_radius < self.max_view_distance {
+            self.unload_radius = self.max_view_distance;
+            problems.push("Unload radius can't be below the view distance".to_string());
+        }
+
+        // any less and the node the player's standing in might not split down to chunks,
+        // which are the only ground with colliders
+        if self.lod.split_distance.is_nan() || self.lod.split_distance < 1.0 {
+            self.lod.split_distance = 1.0;
+            problems.push("LOD split distance must be at least 1".to_string());
+        }
+        if self.lod.hysteresis.is_nan() || self.lod.hysteresis < 0.0 {
+            self.lod.hysteresis = 0.0;
+            problems.push("LOD hysteresis can't be below 0".to_string());
+        }
+        if self.lod.max_depth == 0 || self.lod.max_depth > MAX_LOD_DEPTH {
+            self.lod.max_depth = self.lod.max_depth.max(1).min(MAX_LOD_DEPTH);
+            problems.push(format!(
+                "LOD max depth must be between 1 and {}",
+                MAX_LOD_DEPTH
+            ));
+        }
+
+        problems
+    }
+}
+
+// Sorts a set of thresholds and stretches the last over every height, naming the set they
+// belong to in the problems
+fn fix_thresholds(thresholds: &mut [TerrainThreshold; 6], name: &str, problems: &mut Vec<String>) {
+    if thresholds
+        .windows(2)
+        .any(|pair| pair[0].max_height > pair[1].max_height)
+    {
+        thresholds.sort_by(|a, b| {
+            a.max_height
+                .partial_cmp(&b.max_height)
+                .unwrap_or(Ordering::Equal)
+        });
+        problems.push(format!(
+            "{} thresholds were out of order, so were sorted",
+            name
+        ));
+    }
+    let top = thresholds.len() - 1;
+    if thresholds[top].max_height <= 1.0 {
+        thresholds[top].max_height = TOP_THRESHOLD;
+        problems.push(format!(
+            "The last {} threshold must cover heights up to 1",
+            name.to_lowercase()
+        ));
+    }
+}
+
+// Fixes the config whenever it changes, keeping hold of what was wrong to show the player
+pub fn validate_config(
+    mut config: ResMut<Config>,
+    mut problems: ResMut<ConfigProblems>,
+    mut just_fixed: Local<bool>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+    // our own fix from last frame shows up as a change, so don't let it clear the problems
+    if *just_fixed {
+        *just_fixed = false;
+        return;
+    }
+
+    let mut fixed = config.clone();
+    let found = fixed.validate();
+    if !found.is_empty() {
+        for problem in found.iter() {
+            warn!("Terrain config: {}", problem);
+        }
+        *config = fixed;
+        *just_fixed = true;
+    }
+    problems.0 = found;
+}
+
+pub fn problems_panel(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    mut problems: ResMut<ConfigProblems>,
+) {
+    if problems.0.is_empty() {
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new(locale.text("terrain-problems-title"))
+        .id(egui::Id::new("terrain_config_problems"))
+        .open(&mut open)
+        .show(egui_context.ctx(), |ui| {
+            for problem in problems.0.iter() {
+                ui.colored_label(egui::Color32::YELLOW, problem);
+            }
+        });
+    if !open {
+        problems.0.clear();
+    }
+}
@@ -0,0 +1,200 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{CullMode, PipelineDescriptor, RenderPipeline},
+        render_graph::{base, RenderGraph, RenderResourcesNode},
+        renderer::RenderResources,
+        shader::ShaderStages,
+    },
+};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::{
+    physics::RapierConfiguration,
+    prelude::{RigidBodyForces, RigidBodyPosition, RigidBodyVelocity},
+};
+
+use crate::{error_log::ErrorLog, first_person::BODY_HALF_HEIGHT, Player, TimeUniform};
+
+use super::{endless::Chunk, Config, CHUNK_SIZE};
+
+pub const WATER_MATERIAL_NODE: &str = "water_material";
+
+/// How the sea is drawn and how it holds the player up
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct WaterConfig {
+    pub visible: bool,
+    // how hard the water pushes up on the player once they're all the way under, against
+    // gravity. Past 1 they float back up to the surface.
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 3.0))]
+    pub buoyancy: f32,
+    // how much of their speed the water takes off the player every second they're all the
+    // way under
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 10.0))]
+    pub drag: f32,
+}
+
+impl Default for WaterConfig {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            buoyancy: 1.2,
+            drag: 2.0,
+        }
+    }
+}
+
+/// The colour of the water, that of the first terrain threshold
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "5b9e2d71-c3a4-4f08-9e6b-2d8f1a7c4e35"]
+pub struct WaterMaterial {
+    pub color: Color,
+}
+
+// The sea over a chunk, hung from the chunk so it's unloaded along with it
+struct WaterPlane;
+
+// Marks the chunks that have been given their water
+struct Watered;
+
+struct WaterAssets {
+    pipeline: Handle<PipelineDescriptor>,
+    mesh: Handle<Mesh>,
+}
+
+pub fn add_water_graph(world: &mut World) {
+    let mut graph = world.get_resource_mut::<RenderGraph>().unwrap();
+    graph.add_system_node(
+        WATER_MATERIAL_NODE,
+        RenderResourcesNode::<WaterMaterial>::new(true),
+    );
+    graph
+        .add_node_edge(WATER_MATERIAL_NODE, base::node::MAIN_PASS)
+        .unwrap();
+    // the time uniform's node is added along with the aurora's
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: errors.load(&asset_server, "shaders/mvp.vert"),
+        fragment: Some(errors.load(&asset_server, "shaders/water.frag")),
+    });
+    // seen from below while swimming, and see-through to the sea floor from above
+    descriptor.primitive.cull_mode = CullMode::None;
+    descriptor
+        .depth_stencil
+        .as_mut()
+        .unwrap()
+        .depth_write_enabled = false;
+
+    commands.insert_resource(WaterAssets {
+        pipeline: pipelines.add(descriptor),
+        mesh: meshes.add(Mesh::from(shape::Plane {
+            size: CHUNK_SIZE as f32,
+        })),
+    });
+}
+
+// Floods the chunks and nodes that dip below the sea level once their heights are known.
+// Chunks that are later raised out of it keep their water, hidden under the ground.
+pub fn spawn_planes(
+    mut commands: Commands,
+    config: Res<Config>,
+    assets: Res<WaterAssets>,
+    chunks_query: Query<(Entity, &Chunk), Without<Watered>>,
+) {
+    for (entity, chunk) in chunks_query.iter() {
+        match chunk.bounds() {
+            Some(bounds) if bounds.min < config.sea_level() => {}
+            _ => continue,
+        }
+        let plane = commands
+            .spawn_bundle(MeshBundle {
+                mesh: assets.mesh.clone(),
+                render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                    assets.pipeline.clone(),
+                )]),
+                transform: plane_transform(&config),
+                visible: Visible {
+                    is_visible: false,
+                    is_transparent: true,
+                },
+                ..Default::default()
+            })
+            .insert(WaterMaterial {
+                color: config.terrain_thresholds[0].color,
+            })
+            .insert(TimeUniform::default())
+            .insert(WaterPlane)
+            .id();
+        commands
+            .entity(entity)
+            .insert(Watered)
+            .push_children(&[plane]);
+    }
+}
+
+// Shows the water along with the chunk it's over, and keeps it at the sea level in the first
+// threshold's colour
+pub fn update_planes(
+    config: Res<Config>,
+    water_config: Res<WaterConfig>,
+    chunks_query: Query<&Visible, With<Chunk>>,
+    mut planes_query: Query<
+        (&Parent, &mut Visible, &mut Transform, &mut WaterMaterial),
+        (With<WaterPlane>, Without<Chunk>),
+    >,
+) {
+    for (parent, mut visible, mut transform, mut material) in planes_query.iter_mut() {
+        let chunk_visible = chunks_query
+            .get(parent.0)
+            .map_or(false, |chunk| chunk.is_visible);
+        visible.is_visible = water_config.visible && chunk_visible;
+        if config.is_changed() {
+            *transform = plane_transform(&config);
+            material.color = config.terrain_thresholds[0].color;
+        }
+    }
+}
+
+// Chunks are positioned at their first corner and stretched over their span, so the plane
+// sits in the middle of the cells and is stretched along with them
+fn plane_transform(config: &Config) -> Transform {
+    let half = CHUNK_SIZE as f32 / 2.0;
+    Transform::from_xyz(half, config.sea_level(), half)
+}
+
+/// Holds the player up and slows them down by however much of them is under the sea
+pub fn buoyancy(
+    time: Res<Time>,
+    config: Res<Config>,
+    water_config: Res<WaterConfig>,
+    rapier_config: Res<RapierConfiguration>,
+    mut player_query: Query<
+        (&RigidBodyPosition, &RigidBodyForces, &mut RigidBodyVelocity),
+        With<Player>,
+    >,
+) {
+    let delta_seconds = time.delta_seconds();
+    for (position, forces, mut velocity) in player_query.iter_mut() {
+        let feet = position.position.translation.vector.y - BODY_HALF_HEIGHT;
+        let submerged = ((config.sea_level() - feet) / (BODY_HALF_HEIGHT * 2.0)).clamp(0.0, 1.0);
+        if submerged <= 0.0 {
+            continue;
+        }
+
+        let mut linvel: Vec3 = velocity.linvel.into();
+        // no gravity while flying, so nothing to push back against
+        let gravity = rapier_config.gravity.y * forces.gravity_scale;
+        linvel.y -= gravity * water_config.buoyancy * submerged * delta_seconds;
+        linvel *= (1.0 - water_config.drag * submerged * delta_seconds).max(0.0);
+        velocity.linvel = linvel.into();
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+};
+
+use bevy::{prelude::*, tasks::TaskPool};
+use bevy_rapier3d::prelude::SharedShape;
+use crossbeam_channel::{Receiver, Sender};
+use futures_lite::future;
+
+use super::{
+    cache::HeightMapCache,
+    edits,
+    endless::{ChunkCoords, HeightBounds, CHUNK_SIZE},
+    failure,
+    height_map::{GridArea, HeightMap, HeightPyramid, HeightStats},
+    material,
+    mesh::{self, UvMapping},
+    pipeline::GenerationPipeline,
+    quadtree::Node,
+    texture, Config, SimplificationLevel,
+};
+use crate::bake::BakedArchive;
+
+// the splat map is only made when the terrain is drawn with the splat material
+pub type GeneratedChunk = (
+    Texture,
+    Option<Texture>,
+    Mesh,
+    SharedShape,
+    HeightBounds,
+    HeightStats,
+);
+
+// How deep the skirts hang for every chunk a node spans, or along a chunk's edge with a node.
+// Neither is stitched to what's beside it, so the skirt has to cover the whole gap.
+const LOD_SKIRT_DEPTH: f32 = 20.0;
+
+pub struct ChunkJob {
+    pub entity: Entity,
+    pub coords: ChunkCoords,
+    // 0 for a chunk, or the depth of the distant node starting at the coordinates
+    pub depth: u32,
+    pub simplification_level: SimplificationLevel,
+    // the levels of the chunks along each edge, in the order of `ChunkCoords::neighbours`
+    pub neighbour_levels: [Option<SimplificationLevel>; 4],
+    pub config: Config,
+    pub pipeline: GenerationPipeline,
+    // the sun to bake shadows into the texture for, if the chunk is far away
+    pub sun: Option<Vec3>,
+    // the cache's generation when the job was sent, see `HeightMapCache::generation`
+    pub cache_generation: u64,
+    // the archive to read the chunk's height map from, if it was baked ahead of time
+    pub baked: Option<Arc<BakedArchive>>,
+    // the changes made to the chunk's heights by hand, if there are any
+    pub height_offsets: Option<Arc<Vec<f32>>>,
+    // the terrain types painted onto the chunk by hand, if there are any
+    pub paint: Option<Arc<Vec<u8>>>,
+}
+
+pub struct ChunkResult {
+    pub entity: Entity,
+    // the level the chunk was generated at, which is stale if it's changed since
+    pub simplification_level: SimplificationLevel,
+    pub sun: Option<Vec3>,
+    pub generated: Result<GeneratedChunk, String>,
+}
+
+/// Threads that live as long as the app and generate whichever chunks are sent to them,
+/// so finished chunks can be picked up without polling a task for every chunk in flight
+pub struct ChunkWorkers {
+    jobs: Sender<ChunkJob>,
+    results: Receiver<ChunkResult>,
+    pub cache: HeightMapCache,
+}
+
+impl ChunkWorkers {
+    /// Starts a worker per thread in the task pool, which still colours in the textures
+    pub fn spawn(task_pool: &TaskPool) -> ChunkWorkers {
+        let (jobs, job_receiver) = crossbeam_channel::unbounded::<ChunkJob>();
+        let (result_sender, results) = crossbeam_channel::unbounded();
+        let cache = HeightMapCache::default();
+
+        for index in 0..task_pool.thread_num().max(1) {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let texture_pool = task_pool.clone();
+            let cache = cache.clone();
+            thread::Builder::new()
+                .name(format!("Chunk worker ({})", index))
+                .spawn(move || {
+                    // ends once the app drops its end of the channel
+                    for job in job_receiver.iter() {
+                        let result = ChunkResult {
+                            entity: job.entity,
+                            simplification_level: job.simplification_level,
+                            sun: job.sun,
+                            generated: generate(job, &texture_pool, &cache),
+                        };
+                        if result_sender.send(result).is_err() {
+                            break;
+                        }
+                    }
+                })
+                .expect("Failed to start chunk worker thread");
+        }
+
+        ChunkWorkers {
+            jobs,
+            results,
+            cache,
+        }
+    }
+
+    pub fn send(&self, job: ChunkJob) {
+        // the workers only stop once this resource is dropped
+        let _ = self.jobs.send(job);
+    }
+
+    /// Every chunk finished since the last call, without waiting on any
+    pub fn finished(&self) -> impl Iterator<Item = ChunkResult> + '_ {
+        self.results.try_iter()
+    }
+}
+
+// Computes the chunk mesh and texture
+fn generate(
+    job: ChunkJob,
+    texture_pool: &TaskPool,
+    cache: &HeightMapCache,
+) -> Result<GeneratedChunk, String> {
+    let ChunkJob {
+        coords,
+        depth,
+        simplification_level,
+        neighbour_levels,
+        config,
+        pipeline,
+        sun,
+        cache_generation,
+        baked,
+        height_offsets,
+        paint,
+        ..
+    } = job;
+
+    // a panicking stage would otherwise leave the chunk processing forever, so catch it
+    // and hand back what went wrong to be retried
+    let generated = panic::catch_unwind(AssertUnwindSafe(|| {
+        let span = (1 << depth) as f32;
+        let area = GridArea {
+            origin: coords.to_position(),
+            spacing: span,
+        };
+        let node = Node { coords, depth };
+        let mut height_map = match cache.get(node, cache_generation) {
+            Some(height_map) => height_map,
+            None => {
+                let height_map = if depth > 0 {
+                    HeightMap::distant(&config, area)
+                } else {
+                    pipeline.chunk_height_map(&config, coords, baked.as_deref())
+                };
+                cache.insert(
+                    node,
+                    cache_generation,
+                    height_map.clone(),
+                    config.height_map_cache_size,
+                );
+                height_map
+            }
+        };
+        // the cache keeps the heights as generated, so editing them doesn't invalidate it
+        if let Some(offsets) = &height_offsets {
+            edits::apply_offsets(&mut height_map, offsets);
+        }
+        let stats = info_span!("height_stats").in_scope(|| height_map.stats());
+        let pyramid =
+            Arc::new(info_span!("height_pyramid").in_scope(|| HeightPyramid::build(height_map)));
+
+        // the texture and mesh only read the height map, so colour it in alongside meshing
+        let texture_task = {
+            let pyramid = pyramid.clone();
+            let config = config.clone();
+            texture_pool.spawn(async move {
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    let texture = info_span!("chunk_texture").in_scope(|| {
+                        texture::generate(
+                            pyramid.base(),
+                            &config,
+                            area,
+                            sun,
+                            paint.as_deref().map(Vec::as_slice),
+                        )
+                    });
+                    let splat = if config.material.enabled {
+                        Some(
+                            info_span!("chunk_splat_map")
+                                .in_scope(|| material::splat_map(pyramid.base(), &config, area)),
+                        )
+                    } else {
+                        None
+                    };
+                    (texture, splat)
+                }))
+            })
+        };
+
+        // the mesh is stretched over the node's span, so the tiles shrink to match
+        let uv_mapping = if config.world_uvs {
+            UvMapping::World {
+                origin: (coords.to_position() - Vec2::splat(CHUNK_SIZE as f32 / 2.0)) / span,
+                tile_size: config.uv_tile_size / span,
+            }
+        } else {
+            UvMapping::Chunk
+        };
+        let (mesh, collider_shape, bounds) = info_span!("chunk_mesh").in_scope(|| {
+            let mut terrain_mesh_generator = mesh::Generator::new(
+                pyramid,
+                config.height_scale,
+                simplification_level,
+                uv_mapping,
+            );
+            terrain_mesh_generator.skirt_depth =
+                if depth > 0 || neighbour_levels.iter().any(Option::is_none) {
+                    config.skirt_depth.max(LOD_SKIRT_DEPTH * span)
+                } else {
+                    config.skirt_depth
+                };
+            terrain_mesh_generator.flat_shading = config.flat_shading;
+            terrain_mesh_generator.height_step = config.flat_height_step;
+            terrain_mesh_generator.neighbour_levels = neighbour_levels;
+            terrain_mesh_generator.generate();
+            let bounds = terrain_mesh_generator.height_bounds();
+            let collider_shape = terrain_mesh_generator.collider_shape();
+            (
+                terrain_mesh_generator.graphics_mesh(),
+                collider_shape,
+                bounds,
+            )
+        });
+        (texture_task, mesh, collider_shape, bounds, stats)
+    }));
+    let (texture_task, mesh, collider_shape, bounds, stats) =
+        generated.map_err(failure::panic_message)?;
+    let (texture, splat) = future::block_on(texture_task).map_err(failure::panic_message)?;
+
+    Ok((texture, splat, mesh, collider_shape, bounds, stats))
+}
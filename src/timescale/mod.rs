@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::rapier::dynamics::IntegrationParameters;
+
+use crate::settings::{AddSettings, SettingsTab};
+
+// the physics step at normal speed
+const PHYSICS_DT: f32 = 1.0 / 60.0;
+const MIN_SCALE: f32 = 1.0 / 16.0;
+const MAX_SCALE: f32 = 8.0;
+
+pub struct TimescalePlugin;
+
+impl Plugin for TimescalePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_settings::<Timescale>(SettingsTab::Debug, "Timescale")
+            .add_system(change_scale.system().label("timescale::change"))
+            .add_system(scale_physics.system().after("timescale::change"))
+            .add_system(
+                advance_clock
+                    .system()
+                    .label("timescale::clock")
+                    .after("timescale::change"),
+            );
+    }
+}
+
+/// How fast the world runs compared to real time, for slow motion and fast forwarding
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct Timescale {
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0625, max = 8.0))]
+    pub scale: f32,
+    // held still by the editor, whatever the scale
+    #[cfg_attr(feature = "dev-tools", inspectable(ignore))]
+    pub paused: bool,
+    // seconds the world has run for at its own speed
+    #[cfg_attr(feature = "dev-tools", inspectable(ignore))]
+    elapsed: f64,
+    #[cfg_attr(feature = "dev-tools", inspectable(ignore))]
+    delta: f32,
+}
+
+impl Default for Timescale {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            paused: false,
+            elapsed: 0.0,
+            delta: 0.0,
+        }
+    }
+}
+
+impl Timescale {
+    /// Seconds of world time that passed this frame
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta
+    }
+
+    /// Seconds of world time since startup, running slower or faster with the scale
+    pub fn seconds_since_startup(&self) -> f64 {
+        self.elapsed
+    }
+}
+
+// [ halves the speed, ] doubles it and \ puts it back to normal
+fn change_scale(keys: Res<Input<KeyCode>>, mut timescale: ResMut<Timescale>) {
+    if keys.just_pressed(KeyCode::LBracket) {
+        timescale.scale = (timescale.scale * 0.5).max(MIN_SCALE);
+        info!("Timescale: {}", timescale.scale);
+    }
+    if keys.just_pressed(KeyCode::RBracket) {
+        timescale.scale = (timescale.scale * 2.0).min(MAX_SCALE);
+        info!("Timescale: {}", timescale.scale);
+    }
+    if keys.just_pressed(KeyCode::Backslash) {
+        timescale.scale = 1.0;
+        info!("Timescale: {}", timescale.scale);
+    }
+}
+
+fn advance_clock(time: Res<Time>, mut timescale: ResMut<Timescale>) {
+    timescale.delta = if timescale.paused {
+        0.0
+    } else {
+        time.delta_seconds() * timescale.scale
+    };
+    timescale.elapsed += timescale.delta as f64;
+}
+
+// The physics takes one step a frame, so stretching or shrinking the step slows or speeds it
+fn scale_physics(timescale: Res<Timescale>, mut integration: ResMut<IntegrationParameters>) {
+    integration.dt = PHYSICS_DT * timescale.scale;
+}
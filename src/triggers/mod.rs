@@ -0,0 +1,177 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_egui::{egui, EguiContext};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error_log::ErrorLog,
+    locale::Locale,
+    npc::SpawnWanderersEvent,
+    save::WorldSave,
+    terrain::{ChunkCoords, ChunkSpawnedEvent, SeenChunks},
+    Player,
+};
+
+// seconds a trigger's message stays on screen
+const MESSAGE_DURATION: f32 = 5.0;
+
+pub struct TriggersPlugin;
+
+impl Plugin for TriggersPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<TriggerFiredEvent>()
+            .init_resource::<Messages>()
+            .add_system(load_in_new_chunks.system())
+            .add_system(despawn_unloaded.system())
+            .add_system(detect.system().label("triggers::detect"))
+            .add_system(run_actions.system().after("triggers::detect"))
+            .add_system(show_messages.system());
+    }
+}
+
+/// What happens when the player walks into a trigger
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum TriggerAction {
+    Message(String),
+    // an audio asset to play, such as "sounds/theme.ogg"
+    Music(String),
+    SpawnNpcs(usize),
+}
+
+/// A sphere that fires its action as the player enters it, as written in RON in the world
+/// save or a prefab
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TriggerVolume {
+    pub position: [f32; 3],
+    pub radius: f32,
+    // only fire the first time the player enters, until the chunk is loaded again
+    #[serde(default)]
+    pub once: bool,
+    pub action: TriggerAction,
+}
+
+/// Sent when the player enters a trigger volume
+#[derive(Clone, Debug)]
+pub struct TriggerFiredEvent {
+    pub action: TriggerAction,
+    pub position: Vec3,
+}
+
+pub struct Trigger {
+    pub chunk: ChunkCoords,
+    pub volume: TriggerVolume,
+    inside: bool,
+    fired: bool,
+}
+
+// Messages from triggers still on screen, with how long each has left
+#[derive(Default)]
+struct Messages(Vec<(String, f32)>);
+
+pub fn spawn_trigger(commands: &mut Commands, chunk: ChunkCoords, volume: TriggerVolume) {
+    commands.spawn().insert(Trigger {
+        chunk,
+        volume,
+        inside: false,
+        fired: false,
+    });
+}
+
+// Sets up the triggers of each chunk as it loads
+fn load_in_new_chunks(
+    mut commands: Commands,
+    save: Res<WorldSave>,
+    mut events: EventReader<ChunkSpawnedEvent>,
+) {
+    for event in events.iter() {
+        if let Some(volumes) = save.triggers.get(&(event.coords.x, event.coords.y)) {
+            for volume in volumes {
+                spawn_trigger(&mut commands, event.coords, volume.clone());
+            }
+        }
+    }
+}
+
+fn despawn_unloaded(
+    mut commands: Commands,
+    seen_chunks: Res<SeenChunks>,
+    triggers_query: Query<(Entity, &Trigger)>,
+) {
+    for (entity, trigger) in triggers_query.iter() {
+        if !seen_chunks.contains_key(&trigger.chunk) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn detect(
+    mut events: EventWriter<TriggerFiredEvent>,
+    player_query: Query<&Transform, With<Player>>,
+    mut triggers_query: Query<&mut Trigger>,
+) {
+    let player = match player_query.iter().next() {
+        Some(transform) => transform.translation,
+        None => return,
+    };
+
+    for mut trigger in triggers_query.iter_mut() {
+        let position = Vec3::from(trigger.volume.position);
+        let inside = position.distance(player) <= trigger.volume.radius;
+        let entered = inside && !trigger.inside;
+        trigger.inside = inside;
+        if !entered || (trigger.volume.once && trigger.fired) {
+            continue;
+        }
+
+        trigger.fired = true;
+        events.send(TriggerFiredEvent {
+            action: trigger.volume.action.clone(),
+            position,
+        });
+    }
+}
+
+fn run_actions(
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    audio: Res<Audio>,
+    mut messages: ResMut<Messages>,
+    mut events: EventReader<TriggerFiredEvent>,
+    mut spawn_events: EventWriter<SpawnWanderersEvent>,
+) {
+    for event in events.iter() {
+        match &event.action {
+            TriggerAction::Message(message) => {
+                messages.0.push((message.clone(), MESSAGE_DURATION));
+            }
+            TriggerAction::Music(path) => audio.play(errors.load(&asset_server, path)),
+            TriggerAction::SpawnNpcs(count) => spawn_events.send(SpawnWanderersEvent {
+                position: event.position.xz(),
+                count: *count,
+            }),
+        }
+    }
+}
+
+fn show_messages(
+    egui_context: Res<EguiContext>,
+    locale: Res<Locale>,
+    time: Res<Time>,
+    mut messages: ResMut<Messages>,
+) {
+    for (_, remaining) in messages.0.iter_mut() {
+        *remaining -= time.delta_seconds();
+    }
+    messages.0.retain(|(_, remaining)| *remaining > 0.0);
+    if messages.0.is_empty() {
+        return;
+    }
+
+    egui::Area::new("trigger_messages")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 80.0])
+        .show(egui_context.ctx(), |ui| {
+            for (message, _) in messages.0.iter() {
+                // a message can name a string in assets/locales to have it translated
+                ui.heading(locale.get(message, None).unwrap_or_else(|| message.clone()));
+            }
+        });
+}
@@ -0,0 +1,322 @@
+use bevy::{
+    math::Vec3Swizzles,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use futures_lite::future;
+
+use crate::{
+    first_person::PlayerEyes,
+    terrain::{self, query, Chunk, CHUNK_SIZE},
+};
+
+use super::{
+    wet::{Wetness, WetnessConfig},
+    Precipitation,
+};
+
+// chunk textures have one texel per height map point
+const TEXTURE_SIZE: usize = CHUNK_SIZE as usize + 1;
+// spacing of the texels whose slope is measured, the ones between are blended from them
+const SURVEY_STEP: usize = 4;
+// how much the snow or wetness has to change before the chunk's texture is repainted
+const REPAINT_THRESHOLD: f32 = 0.02;
+// width of the soft edge between snow and bare ground, in cover amount
+const SNOW_EDGE: f32 = 0.15;
+// most hollows kept on a chunk for puddles to form in
+const MAX_HOLLOWS: usize = 12;
+// flatness a hollow needs for a puddle to lie in it
+const HOLLOW_FLATNESS: f32 = 0.6;
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct SnowConfig {
+    // only chunks this close to the player gather snow and rain
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub radius: f32,
+    // cover gained each second in the heaviest snow, where 1 buries all the flat ground
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub accumulation_rate: f32,
+    // cover lost each second once it stops snowing
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub melt_rate: f32,
+    // slopes steeper than this, in degrees, are too steep for snow or water to settle
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0, max = 90.0))]
+    pub max_slope: f32,
+    pub color: Color,
+    // chunks that start surveying where snow and water can settle each frame
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1))]
+    pub chunks_per_frame: usize,
+}
+
+impl Default for SnowConfig {
+    fn default() -> Self {
+        Self {
+            radius: 400.0,
+            accumulation_rate: 0.02,
+            melt_rate: 0.01,
+            max_slope: 40.0,
+            color: Color::rgb(0.95, 0.97, 1.0),
+            chunks_per_frame: 1,
+        }
+    }
+}
+
+/// The snow and wetness on a chunk, painted straight onto its texture
+pub struct GroundCover {
+    texture: Handle<Texture>,
+    // the texture and material as they were generated, before any weather was painted over
+    base: Vec<u8>,
+    // the chunk's rebakes when the copy was taken
+    rebakes: u32,
+    base_roughness: f32,
+    base_reflectance: f32,
+    // for each texel, from 0 where nothing settles up to 1 on flat ground above the water
+    exposure: Vec<f32>,
+    snow: f32,
+    painted_snow: f32,
+    painted_wetness: f32,
+    /// Low spots on the chunk where the rain collects, in world space
+    pub hollows: Vec<Vec3>,
+}
+
+struct Survey {
+    exposure: Vec<f32>,
+    hollows: Vec<Vec3>,
+}
+
+pub struct SurveyTask(Task<Survey>);
+
+// Starts working out where snow and water can settle on the nearby chunks once the
+// weather turns
+pub fn survey(
+    mut commands: Commands,
+    config: Res<SnowConfig>,
+    terrain_config: Res<terrain::Config>,
+    precipitation: Res<Precipitation>,
+    task_pool: Res<AsyncComputeTaskPool>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    chunks_query: Query<
+        (Entity, &Chunk),
+        (
+            With<Handle<StandardMaterial>>,
+            Without<GroundCover>,
+            Without<SurveyTask>,
+        ),
+    >,
+) {
+    if precipitation.intensity <= 0.0 {
+        return;
+    }
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation.xz(),
+        None => return,
+    };
+
+    let nearby = chunks_query
+        .iter()
+        .filter(|(_, chunk)| {
+            chunk.depth() == 0 && chunk.coords().to_position().distance(eyes) < config.radius
+        })
+        .take(config.chunks_per_frame);
+    for (entity, chunk) in nearby {
+        let terrain_config = terrain_config.clone();
+        let origin = chunk.coords().to_position();
+        let max_slope = config.max_slope;
+        let task = task_pool.spawn(async move { survey_chunk(&terrain_config, origin, max_slope) });
+        commands.entity(entity).insert(SurveyTask(task));
+    }
+}
+
+// Keeps a copy of each surveyed chunk's bare texture to paint the weather over
+pub fn finish_survey(
+    mut commands: Commands,
+    materials: Res<Assets<StandardMaterial>>,
+    textures: Res<Assets<Texture>>,
+    mut tasks_query: Query<(Entity, &Chunk, &Handle<StandardMaterial>, &mut SurveyTask)>,
+) {
+    for (entity, chunk, handle, mut task) in tasks_query.iter_mut() {
+        let survey = match future::block_on(future::poll_once(&mut task.0)) {
+            Some(survey) => survey,
+            None => continue,
+        };
+        commands.entity(entity).remove::<SurveyTask>();
+
+        let material = match materials.get(handle) {
+            Some(material) => material,
+            None => continue,
+        };
+        let texture = match &material.base_color_texture {
+            Some(texture) => texture.clone(),
+            None => continue,
+        };
+        let base = match textures.get(&texture) {
+            Some(texture) => texture.data.clone(),
+            None => continue,
+        };
+
+        commands.entity(entity).insert(GroundCover {
+            texture,
+            base,
+            rebakes: chunk.rebakes(),
+            base_roughness: material.roughness,
+            base_reflectance: material.reflectance,
+            exposure: survey.exposure,
+            snow: 0.0,
+            painted_snow: 0.0,
+            painted_wetness: 0.0,
+            hollows: survey.hollows,
+        });
+    }
+}
+
+// Builds up snow on the nearby chunks while it's snowing and melts it everywhere otherwise,
+// repainting the chunk textures in place as the snow and wetness change
+pub fn accumulate(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<SnowConfig>,
+    wet_config: Res<WetnessConfig>,
+    precipitation: Res<Precipitation>,
+    wetness: Res<Wetness>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+    mut chunks_query: Query<(Entity, &Chunk, &Handle<StandardMaterial>, &mut GroundCover)>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation.xz(),
+        None => return,
+    };
+
+    for (entity, chunk, handle, mut cover) in chunks_query.iter_mut() {
+        // the chunk has been regenerated with a new texture, or its texture coloured in again,
+        // so survey it again
+        let texture = materials
+            .get(handle)
+            .and_then(|material| material.base_color_texture.as_ref());
+        if texture != Some(&cover.texture) || chunk.rebakes() != cover.rebakes {
+            commands.entity(entity).remove::<GroundCover>();
+            continue;
+        }
+
+        let nearby = chunk.coords().to_position().distance(eyes) < config.radius;
+        let rate = if precipitation.is_snowing() && nearby {
+            config.accumulation_rate * precipitation.intensity
+        } else {
+            -config.melt_rate
+        };
+        cover.snow = (cover.snow + rate * time.delta_seconds()).clamp(0.0, 1.0);
+
+        let dry = cover.snow == 0.0 && wetness.0 == 0.0;
+        let unchanged = (cover.snow - cover.painted_snow).abs() < REPAINT_THRESHOLD
+            && (wetness.0 - cover.painted_wetness).abs() < REPAINT_THRESHOLD;
+        let needs_clearing = dry && (cover.painted_snow > 0.0 || cover.painted_wetness > 0.0);
+        if unchanged && !needs_clearing {
+            // nothing left to show, so stop holding on to the copy of the texture
+            if dry {
+                commands.entity(entity).remove::<GroundCover>();
+            }
+            continue;
+        }
+
+        if let Some(texture) = textures.get_mut(&cover.texture) {
+            paint(
+                &mut texture.data,
+                &cover,
+                config.color,
+                wetness.0 * wet_config.darkening,
+            );
+            cover.painted_snow = cover.snow;
+            cover.painted_wetness = wetness.0;
+        }
+        // wet ground is smoother and shinier, for when the terrain is lit
+        if let Some(material) = materials.get_mut(handle) {
+            material.roughness = cover.base_roughness
+                + (wet_config.wet_roughness - cover.base_roughness) * wetness.0;
+            material.reflectance = cover.base_reflectance
+                + (wet_config.wet_reflectance - cover.base_reflectance) * wetness.0;
+        }
+    }
+}
+
+// Darkens the flat ground where the rain soaks in, then settles snow on the most exposed
+// texels first, spreading onto steeper ground as it deepens
+fn paint(data: &mut [u8], cover: &GroundCover, snow_color: Color, darkening: f32) {
+    let snow = [
+        snow_color.r() * 255.0,
+        snow_color.g() * 255.0,
+        snow_color.b() * 255.0,
+    ];
+
+    for (i, exposure) in cover.exposure.iter().enumerate() {
+        let damp = 1.0 - darkening * exposure;
+        let t = ((cover.snow + exposure - 1.0) / SNOW_EDGE).clamp(0.0, 1.0);
+        for channel in 0..3 {
+            let index = i * 4 + channel;
+            let base = cover.base[index] as f32 * damp;
+            data[index] = (base + (snow[channel] - base) * t) as u8;
+        }
+    }
+}
+
+// Measures the height and slope on a coarse grid over the chunk, blending the slope out to
+// every texel and picking out the flat bottoms of hollows
+fn survey_chunk(config: &terrain::Config, origin: Vec2, max_slope: f32) -> Survey {
+    let corner = origin - Vec2::splat(CHUNK_SIZE as f32 / 2.0);
+    let coarse_size = (TEXTURE_SIZE - 1) / SURVEY_STEP + 1;
+    let flat_enough = max_slope.to_radians().cos();
+    let water_height = config.sea_level();
+
+    let points: Vec<Vec3> = (0..coarse_size * coarse_size)
+        .map(|i| {
+            let grid = Vec2::new((i % coarse_size) as f32, (i / coarse_size) as f32);
+            let point = corner + grid * SURVEY_STEP as f32;
+            Vec3::new(point.x, query::height_at(config, point), point.y)
+        })
+        .collect();
+    let coarse: Vec<f32> = points
+        .iter()
+        .map(|point| {
+            if point.y < water_height {
+                return 0.0;
+            }
+            let normal = query::normal_at(config, point.xz());
+            ((normal.y - flat_enough) / (1.0 - flat_enough)).clamp(0.0, 1.0)
+        })
+        .collect();
+
+    let at =
+        |x: usize, y: usize| coarse[y.min(coarse_size - 1) * coarse_size + x.min(coarse_size - 1)];
+    let exposure = (0..TEXTURE_SIZE * TEXTURE_SIZE)
+        .map(|i| {
+            let (x, y) = (i % TEXTURE_SIZE, i / TEXTURE_SIZE);
+            let (cell_x, cell_y) = (x / SURVEY_STEP, y / SURVEY_STEP);
+            let fx = (x % SURVEY_STEP) as f32 / SURVEY_STEP as f32;
+            let fy = (y % SURVEY_STEP) as f32 / SURVEY_STEP as f32;
+
+            let top = at(cell_x, cell_y) + (at(cell_x + 1, cell_y) - at(cell_x, cell_y)) * fx;
+            let bottom =
+                at(cell_x, cell_y + 1) + (at(cell_x + 1, cell_y + 1) - at(cell_x, cell_y + 1)) * fx;
+            top + (bottom - top) * fy
+        })
+        .collect();
+
+    // a hollow is a flat point lower than everything around it
+    let hollows = (1..coarse_size - 1)
+        .flat_map(|y| (1..coarse_size - 1).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let height = points[y * coarse_size + x].y;
+            at(x, y) > HOLLOW_FLATNESS
+                && (y - 1..=y + 1)
+                    .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                    .filter(|&neighbour| neighbour != (x, y))
+                    .all(|(nx, ny)| points[ny * coarse_size + nx].y > height)
+        })
+        .map(|(x, y)| points[y * coarse_size + x])
+        .take(MAX_HOLLOWS)
+        .collect();
+
+    Survey { exposure, hollows }
+}
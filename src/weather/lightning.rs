@@ -0,0 +1,299 @@
+use bevy::{math::Vec3Swizzles, prelude::*};
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use rand::Rng;
+
+use crate::{
+    decals::SpawnDecalEvent,
+    error_log::ErrorLog,
+    first_person::PlayerEyes,
+    sky::PrimaryLight,
+    terrain::{self, query},
+};
+
+use super::Precipitation;
+
+// how high above the strike the bolt comes down from
+const BOLT_HEIGHT: f32 = 400.0;
+const BOLT_SEGMENTS: usize = 12;
+// how far each bend in the bolt can wander sideways
+const BOLT_JITTER: f32 = 20.0;
+const BOLT_WIDTH: f32 = 1.2;
+// strikes land within this angle either side of where the player is looking, in radians
+const VIEW_SPREAD: f32 = 0.6;
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct LightningConfig {
+    // rain intensity above which the rain turns into a thunderstorm
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub storm_threshold: f32,
+    // strikes each minute at the height of a storm
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub strikes_per_minute: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub min_distance: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub max_distance: f32,
+    // seconds the bolt stays on screen
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.01))]
+    pub bolt_duration: f32,
+    // seconds the flash takes to fade
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.01))]
+    pub flash_duration: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub flash_brightness: f32,
+    // world units the thunder travels each second
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub speed_of_sound: f32,
+    pub scorch_marks: bool,
+    // seconds a scorch mark stays on the ground
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub scorch_lifetime: f32,
+}
+
+impl Default for LightningConfig {
+    fn default() -> Self {
+        Self {
+            storm_threshold: 0.6,
+            strikes_per_minute: 6.0,
+            min_distance: 80.0,
+            max_distance: 600.0,
+            bolt_duration: 0.25,
+            flash_duration: 0.4,
+            flash_brightness: 1.5,
+            speed_of_sound: 343.0,
+            scorch_marks: true,
+            scorch_lifetime: 60.0,
+        }
+    }
+}
+
+/// Sent when lightning hits the ground
+#[derive(Clone, Copy, Debug)]
+pub struct LightningStrikeEvent {
+    pub position: Vec3,
+    // how far the strike was from the player
+    pub distance: f32,
+}
+
+pub struct Bolt {
+    age: f32,
+}
+
+pub struct LightningAssets {
+    segment_mesh: Handle<Mesh>,
+    bolt_material: Handle<StandardMaterial>,
+    thunder: Handle<AudioSource>,
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut errors: ResMut<ErrorLog>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(LightningAssets {
+        segment_mesh: meshes.add(Mesh::from(shape::Box::new(1.0, 1.0, 1.0))),
+        bolt_material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.9, 0.92, 1.0),
+            unlit: true,
+            ..Default::default()
+        }),
+        thunder: errors.load(&asset_server, "sounds/thunder.wav"),
+    });
+}
+
+// Strikes a random point in view every so often while a storm is raging
+pub fn strike(
+    time: Res<Time>,
+    config: Res<LightningConfig>,
+    terrain_config: Res<terrain::Config>,
+    precipitation: Res<Precipitation>,
+    mut strikes: EventWriter<LightningStrikeEvent>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    if !precipitation.is_raining() || precipitation.intensity < config.storm_threshold {
+        return;
+    }
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes,
+        None => return,
+    };
+
+    let storm = (precipitation.intensity - config.storm_threshold)
+        / (1.0 - config.storm_threshold).max(f32::EPSILON);
+    let chance = config.strikes_per_minute / 60.0 * storm * time.delta_seconds();
+    let mut rng = rand::thread_rng();
+    if rng.gen::<f32>() >= chance {
+        return;
+    }
+
+    let forward = (eyes.rotation * -Vec3::Z).xz().normalize_or_zero();
+    let angle = rng.gen_range(-VIEW_SPREAD..VIEW_SPREAD);
+    let (sin, cos) = angle.sin_cos();
+    let direction = Vec2::new(
+        forward.x * cos - forward.y * sin,
+        forward.x * sin + forward.y * cos,
+    );
+    let distance =
+        rng.gen_range(config.min_distance..config.max_distance.max(config.min_distance + 1.0));
+    let point = eyes.translation.xz() + direction * distance;
+    let position = Vec3::new(point.x, query::height_at(&terrain_config, point), point.y);
+
+    strikes.send(LightningStrikeEvent {
+        position,
+        distance: position.distance(eyes.translation),
+    });
+}
+
+// Draws a jagged bolt down from the clouds to each strike
+pub fn spawn_bolts(
+    mut commands: Commands,
+    assets: Res<LightningAssets>,
+    mut strikes: EventReader<LightningStrikeEvent>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for strike in strikes.iter() {
+        let top = strike.position
+            + Vec3::new(
+                rng.gen_range(-BOLT_JITTER..BOLT_JITTER),
+                BOLT_HEIGHT,
+                rng.gen_range(-BOLT_JITTER..BOLT_JITTER),
+            );
+        let points: Vec<Vec3> = (0..=BOLT_SEGMENTS)
+            .map(|i| {
+                let t = i as f32 / BOLT_SEGMENTS as f32;
+                let point = top + (strike.position - top) * t;
+                // the ends stay put so the bolt meets the ground where it struck
+                if i == 0 || i == BOLT_SEGMENTS {
+                    return point;
+                }
+                point
+                    + Vec3::new(
+                        rng.gen_range(-BOLT_JITTER..BOLT_JITTER),
+                        0.0,
+                        rng.gen_range(-BOLT_JITTER..BOLT_JITTER),
+                    )
+            })
+            .collect();
+
+        commands
+            .spawn_bundle((Transform::default(), GlobalTransform::default()))
+            .insert(Bolt { age: 0.0 })
+            .with_children(|parent| {
+                for pair in points.windows(2) {
+                    let span = pair[1] - pair[0];
+                    parent.spawn_bundle(PbrBundle {
+                        mesh: assets.segment_mesh.clone(),
+                        material: assets.bolt_material.clone(),
+                        transform: Transform {
+                            translation: (pair[0] + pair[1]) * 0.5,
+                            rotation: Quat::from_rotation_arc(Vec3::Y, span.normalize()),
+                            scale: Vec3::new(BOLT_WIDTH, span.length(), BOLT_WIDTH),
+                        },
+                        ..Default::default()
+                    });
+                }
+            });
+    }
+}
+
+pub fn fade_bolts(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<LightningConfig>,
+    mut bolts_query: Query<(Entity, &mut Bolt)>,
+) {
+    for (entity, mut bolt) in bolts_query.iter_mut() {
+        bolt.age += time.delta_seconds();
+        if bolt.age > config.bolt_duration {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Lights the world up from the direction of the last strike, flickering as it fades
+pub fn flash(
+    time: Res<Time>,
+    config: Res<LightningConfig>,
+    mut strikes: EventReader<LightningStrikeEvent>,
+    mut light: ResMut<PrimaryLight>,
+    mut clear_color: ResMut<ClearColor>,
+    mut remaining: Local<f32>,
+    mut direction: Local<Vec3>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    for strike in strikes.iter() {
+        *remaining = config.flash_duration;
+        if let Some(eyes) = eyes_query.iter().next() {
+            *direction =
+                (strike.position + Vec3::Y * BOLT_HEIGHT - eyes.translation).normalize_or_zero();
+        }
+    }
+    if *remaining <= 0.0 {
+        return;
+    }
+    *remaining -= time.delta_seconds();
+
+    let fade = (*remaining / config.flash_duration).max(0.0);
+    let flicker = if (time.seconds_since_startup() * 30.0) as u32 % 3 == 0 {
+        0.4
+    } else {
+        1.0
+    };
+    let brightness = config.flash_brightness * fade * flicker;
+
+    let flash = Vec4::new(brightness, brightness, brightness, 0.0);
+    light.color = (Vec4::from(light.color) + flash).into();
+    light.direction = *direction;
+    clear_color.0 = (Vec4::from(clear_color.0) + flash * 0.5)
+        .min(Vec4::ONE)
+        .into();
+}
+
+// Rumbles the thunder once the sound has had time to travel from each strike
+pub fn thunder(
+    time: Res<Time>,
+    config: Res<LightningConfig>,
+    assets: Res<LightningAssets>,
+    audio: Res<Audio>,
+    mut strikes: EventReader<LightningStrikeEvent>,
+    mut pending: Local<Vec<f32>>,
+) {
+    for strike in strikes.iter() {
+        pending.push(strike.distance / config.speed_of_sound);
+    }
+
+    for delay in pending.iter_mut() {
+        *delay -= time.delta_seconds();
+        if *delay <= 0.0 {
+            audio.play(assets.thunder.clone());
+        }
+    }
+    pending.retain(|delay| *delay > 0.0);
+}
+
+// Leaves a burnt patch of ground where each strike landed
+pub fn scorch(
+    config: Res<LightningConfig>,
+    mut strikes: EventReader<LightningStrikeEvent>,
+    mut decals: EventWriter<SpawnDecalEvent>,
+) {
+    for strike in strikes.iter() {
+        if !config.scorch_marks {
+            continue;
+        }
+        decals.send(SpawnDecalEvent {
+            transform: Transform {
+                translation: strike.position,
+                scale: Vec3::new(3.0, 0.05, 3.0),
+                ..Default::default()
+            },
+            color: Color::rgb(0.08, 0.07, 0.06),
+            lifetime: config.scorch_lifetime,
+            fade: 0.0,
+        });
+    }
+}
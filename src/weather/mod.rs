@@ -0,0 +1,282 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+use noise::{NoiseFn, Perlin};
+use rand::Rng;
+
+use crate::{
+    first_person::PlayerEyes,
+    particles::{GroundContact, ParticleBurstEvent},
+    settings::{AddSettings, SettingsTab},
+};
+
+use self::{
+    ground::SnowConfig,
+    lightning::{LightningConfig, LightningStrikeEvent},
+    wet::{Wetness, WetnessConfig},
+};
+
+mod ground;
+mod lightning;
+mod wet;
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Wind>()
+            .init_resource::<Precipitation>()
+            .init_resource::<Wetness>()
+            .add_event::<LightningStrikeEvent>()
+            .add_settings::<WindConfig>(SettingsTab::World, "Wind")
+            .add_settings::<PrecipitationConfig>(SettingsTab::World, "Precipitation")
+            .add_settings::<SnowConfig>(SettingsTab::World, "Snow")
+            .add_settings::<WetnessConfig>(SettingsTab::World, "Wetness")
+            .add_settings::<LightningConfig>(SettingsTab::World, "Lightning")
+            .add_startup_system(wet::setup.system())
+            .add_startup_system(lightning::setup.system())
+            .add_system(update_wind.system())
+            .add_system(
+                update_precipitation
+                    .system()
+                    .label("weather::precipitation"),
+            )
+            .add_system(fall.system().after("weather::precipitation"))
+            .add_system(
+                wet::soak
+                    .system()
+                    .label("weather::soak")
+                    .after("weather::precipitation"),
+            )
+            .add_system(ground::survey.system())
+            .add_system(ground::finish_survey.system())
+            .add_system(ground::accumulate.system().after("weather::soak"))
+            .add_system(wet::fill_puddles.system().after("weather::soak"))
+            .add_system(wet::size_puddles.system().after("weather::soak"))
+            .add_system(
+                lightning::strike
+                    .system()
+                    .label("weather::strike")
+                    .after("weather::precipitation"),
+            )
+            .add_system(lightning::spawn_bolts.system().after("weather::strike"))
+            .add_system(lightning::fade_bolts.system())
+            .add_system(
+                lightning::flash
+                    .system()
+                    .label("weather::flash")
+                    .after("weather::strike")
+                    .after("sky::light")
+                    .after("sky::color"),
+            )
+            .add_system(lightning::thunder.system().after("weather::strike"))
+            .add_system(lightning::scorch.system().after("weather::strike"));
+    }
+}
+
+/// The current wind blowing across the world, in world units per second
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Wind {
+    pub velocity: Vec3,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug)]
+pub struct WindConfig {
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub base_speed: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub gust_strength: f32,
+    // how quickly the wind direction and gusts change over time
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0001))]
+    pub variability: f32,
+}
+
+impl Default for WindConfig {
+    fn default() -> Self {
+        WindConfig {
+            base_speed: 6.0,
+            gust_strength: 4.0,
+            variability: 0.02,
+        }
+    }
+}
+
+// Slowly wanders the wind direction and strength using low frequency noise over time
+fn update_wind(
+    time: Res<Time>,
+    config: Res<WindConfig>,
+    noise: Local<Perlin>,
+    mut wind: ResMut<Wind>,
+) {
+    let t = time.seconds_since_startup() * config.variability as f64;
+
+    let angle = noise.get([t, 0.5]) as f32 * std::f32::consts::PI * 2.0;
+    let gust = noise.get([t * 4.0, 10.5]) as f32;
+    let speed = (config.base_speed + gust * config.gust_strength).max(0.0);
+
+    wind.velocity = Vec3::new(angle.cos(), 0.0, angle.sin()) * speed;
+}
+
+/// What's falling from the sky, if anything
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PrecipitationKind {
+    Rain,
+    Snow,
+}
+
+/// The rain or snow currently falling, with an intensity of 0 when the sky is clear
+#[derive(Clone, Copy, Debug)]
+pub struct Precipitation {
+    pub kind: PrecipitationKind,
+    pub intensity: f32,
+}
+
+impl Default for Precipitation {
+    fn default() -> Self {
+        Self {
+            kind: PrecipitationKind::Rain,
+            intensity: 0.0,
+        }
+    }
+}
+
+impl Precipitation {
+    pub fn is_raining(&self) -> bool {
+        self.kind == PrecipitationKind::Rain && self.intensity > 0.0
+    }
+
+    pub fn is_snowing(&self) -> bool {
+        self.kind == PrecipitationKind::Snow && self.intensity > 0.0
+    }
+}
+
+/// Lets the weather be pinned while testing instead of following the forecast
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ForcedWeather {
+    Forecast,
+    Clear,
+    Rain,
+    Snow,
+}
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+#[derive(Clone, Debug)]
+pub struct PrecipitationConfig {
+    pub forced: ForcedWeather,
+    // how quickly storms come and go
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0001))]
+    pub variability: f32,
+    // fraction of the time the sky is clear
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub clear_skies: f32,
+    // fraction of the storms that fall as snow rather than rain
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub snow_chance: f32,
+    // particles dropped around the player each second at full intensity
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub particle_rate: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 1.0))]
+    pub particle_radius: f32,
+}
+
+impl Default for PrecipitationConfig {
+    fn default() -> Self {
+        PrecipitationConfig {
+            forced: ForcedWeather::Forecast,
+            variability: 0.005,
+            clear_skies: 0.6,
+            snow_chance: 0.4,
+            particle_rate: 150.0,
+            particle_radius: 25.0,
+        }
+    }
+}
+
+// Drifts between clear skies and storms using low frequency noise, in the same way as the wind
+fn update_precipitation(
+    time: Res<Time>,
+    config: Res<PrecipitationConfig>,
+    noise: Local<Perlin>,
+    mut precipitation: ResMut<Precipitation>,
+) {
+    *precipitation = match config.forced {
+        ForcedWeather::Clear => Precipitation::default(),
+        ForcedWeather::Rain => Precipitation {
+            kind: PrecipitationKind::Rain,
+            intensity: 1.0,
+        },
+        ForcedWeather::Snow => Precipitation {
+            kind: PrecipitationKind::Snow,
+            intensity: 1.0,
+        },
+        ForcedWeather::Forecast => {
+            let t = time.seconds_since_startup() * config.variability as f64;
+            // perlin noise sits roughly within -1 to 1, so map it into 0 to 1
+            let storm = (noise.get([t, 20.5]) as f32 * 0.5 + 0.5).clamp(0.0, 1.0);
+            let cold = (noise.get([t * 0.5, 30.5]) as f32 * 0.5 + 0.5).clamp(0.0, 1.0);
+
+            let intensity = ((storm - config.clear_skies)
+                / (1.0 - config.clear_skies).max(f32::EPSILON))
+            .clamp(0.0, 1.0);
+            let kind = if cold < config.snow_chance {
+                PrecipitationKind::Snow
+            } else {
+                PrecipitationKind::Rain
+            };
+            Precipitation { kind, intensity }
+        }
+    };
+}
+
+// Drops rain or snow particles in the air around the player
+fn fall(
+    time: Res<Time>,
+    config: Res<PrecipitationConfig>,
+    precipitation: Res<Precipitation>,
+    mut owed: Local<f32>,
+    mut bursts: EventWriter<ParticleBurstEvent>,
+    eyes_query: Query<&GlobalTransform, With<PlayerEyes>>,
+) {
+    let eyes = match eyes_query.iter().next() {
+        Some(eyes) => eyes.translation,
+        None => return,
+    };
+
+    *owed += config.particle_rate * precipitation.intensity * time.delta_seconds();
+    let count = *owed as usize;
+    *owed -= count as f32;
+
+    let (color, size, lifetime, gravity, ground) = match precipitation.kind {
+        PrecipitationKind::Rain => (
+            Color::rgba(0.6, 0.7, 0.9, 0.6),
+            0.05,
+            1.0,
+            30.0,
+            GroundContact::Vanish,
+        ),
+        PrecipitationKind::Snow => (Color::WHITE, 0.12, 5.0, 1.5, GroundContact::Settle),
+    };
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..count {
+        let offset = Vec3::new(
+            rng.gen_range(-config.particle_radius..config.particle_radius),
+            rng.gen_range(5.0..15.0),
+            rng.gen_range(-config.particle_radius..config.particle_radius),
+        );
+        bursts.send(ParticleBurstEvent {
+            position: eyes + offset,
+            color,
+            count: 1,
+            speed: 0.5,
+            size,
+            lifetime,
+            gravity,
+            ground,
+            wind: 0.0,
+        });
+    }
+}
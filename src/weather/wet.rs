@@ -0,0 +1,154 @@
+use bevy::prelude::*;
+#[cfg(feature = "dev-tools")]
+use bevy_inspector_egui::Inspectable;
+
+use super::{ground::GroundCover, Precipitation};
+
+/// How soaked the ground is, from 0 when dry to 1 after a long downpour
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Wetness(pub f32);
+
+#[cfg_attr(feature = "dev-tools", derive(Inspectable))]
+pub struct WetnessConfig {
+    // wetness gained each second in the heaviest rain
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub soak_rate: f32,
+    // wetness lost each second once the rain stops
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0))]
+    pub dry_rate: f32,
+    // how much darker the flat ground gets when soaked
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub darkening: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub wet_roughness: f32,
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.0, max = 1.0))]
+    pub wet_reflectance: f32,
+    // radius of a puddle once the ground is soaked
+    #[cfg_attr(feature = "dev-tools", inspectable(min = 0.1))]
+    pub puddle_size: f32,
+    pub puddle_color: Color,
+}
+
+impl Default for WetnessConfig {
+    fn default() -> Self {
+        Self {
+            soak_rate: 0.05,
+            dry_rate: 0.01,
+            darkening: 0.35,
+            wet_roughness: 0.1,
+            wet_reflectance: 0.9,
+            puddle_size: 2.5,
+            puddle_color: Color::rgb(0.35, 0.42, 0.5),
+        }
+    }
+}
+
+/// The puddles lying in a chunk's hollows
+pub struct Puddles(Vec<Entity>);
+
+pub struct Puddle;
+
+pub struct PuddleAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+pub fn setup(
+    mut commands: Commands,
+    config: Res<WetnessConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(PuddleAssets {
+        // squashed flat into a disc when spawned
+        mesh: meshes.add(Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 2,
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: config.puddle_color,
+            roughness: 0.05,
+            reflectance: 1.0,
+            unlit: true,
+            ..Default::default()
+        }),
+    });
+}
+
+// Soaks the ground while it rains and slowly dries it out afterwards
+pub fn soak(
+    time: Res<Time>,
+    config: Res<WetnessConfig>,
+    precipitation: Res<Precipitation>,
+    mut wetness: ResMut<Wetness>,
+) {
+    let rate = if precipitation.is_raining() {
+        config.soak_rate * precipitation.intensity
+    } else {
+        -config.dry_rate
+    };
+    wetness.0 = (wetness.0 + rate * time.delta_seconds()).clamp(0.0, 1.0);
+}
+
+// Fills the hollows of the surveyed chunks with puddles while the ground is wet
+pub fn fill_puddles(
+    mut commands: Commands,
+    wetness: Res<Wetness>,
+    assets: Res<PuddleAssets>,
+    chunks_query: Query<(Entity, &GroundCover), Without<Puddles>>,
+) {
+    if wetness.0 <= 0.0 {
+        return;
+    }
+
+    for (entity, cover) in chunks_query.iter() {
+        let puddles = cover
+            .hollows
+            .iter()
+            .map(|&hollow| {
+                commands
+                    .spawn_bundle(PbrBundle {
+                        mesh: assets.mesh.clone(),
+                        material: assets.material.clone(),
+                        transform: Transform {
+                            translation: hollow,
+                            scale: Vec3::ZERO,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(Puddle)
+                    .id()
+            })
+            .collect();
+        commands.entity(entity).insert(Puddles(puddles));
+    }
+}
+
+// Grows the puddles as the ground soaks and shrinks them away as it dries, clearing them
+// out once it's dry or their chunk stops being covered
+pub fn size_puddles(
+    mut commands: Commands,
+    config: Res<WetnessConfig>,
+    wetness: Res<Wetness>,
+    chunks_query: Query<(Entity, &Puddles, Option<&GroundCover>)>,
+    mut puddles_query: Query<&mut Transform, With<Puddle>>,
+) {
+    let radius = config.puddle_size * wetness.0;
+
+    for (entity, puddles, cover) in chunks_query.iter() {
+        if wetness.0 <= 0.0 || cover.is_none() {
+            for &puddle in puddles.0.iter() {
+                commands.entity(puddle).despawn();
+            }
+            commands.entity(entity).remove::<Puddles>();
+            continue;
+        }
+
+        for &puddle in puddles.0.iter() {
+            if let Ok(mut transform) = puddles_query.get_mut(puddle) {
+                transform.scale = Vec3::new(radius, 0.02, radius);
+            }
+        }
+    }
+}